@@ -0,0 +1,107 @@
+use crate::output::{heavy_rule, Glyph};
+use crate::storage::{agent_stats, normalize_agent_id, LoreStorage};
+use colored::Colorize;
+use std::io::Write;
+
+pub struct AgentsOptions {
+    pub json: bool,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: AgentsOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let agents = agent_stats(&storage.get_entry_metas()?);
+
+    if options.json {
+        let json = serde_json::to_string_pretty(&agents)?;
+        writeln!(out, "{}", json)?;
+        return Ok(());
+    }
+
+    if agents.is_empty() {
+        writeln!(out, "{} No entries recorded yet.", "Info:".blue())?;
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(70).dimmed())?;
+    writeln!(out, "{} ({} total)", "Agents".bold(), agents.len())?;
+    writeln!(out, "{}", heavy_rule(70).dimmed())?;
+
+    for agent in &agents {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{} ({} {})",
+            agent.agent_id.yellow().bold(),
+            agent.entry_count,
+            if agent.entry_count == 1 {
+                "entry"
+            } else {
+                "entries"
+            }
+        )?;
+        writeln!(
+            out,
+            "  {} {} {} {}",
+            "Active:".bold(),
+            agent.first_activity.format("%Y-%m-%d"),
+            Glyph::Arrow.as_str().dimmed(),
+            agent.last_activity.format("%Y-%m-%d")
+        )?;
+        writeln!(
+            out,
+            "  {} {}",
+            "Files:".bold(),
+            agent.file_count.to_string().cyan()
+        )?;
+        if !agent.tags.is_empty() {
+            let tags: Vec<String> = agent.tags.iter().map(|t| format!("#{}", t)).collect();
+            writeln!(out, "  {} {}", "Tags:".bold(), tags.join(", ").magenta())?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(70).dimmed())?;
+
+    Ok(())
+}
+
+pub fn rename(
+    storage: &LoreStorage,
+    old: String,
+    new: String,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let new = if storage.normalize_agent_ids()? {
+        normalize_agent_id(&new)
+    } else {
+        new
+    };
+
+    let renamed = storage.rename_agent(&old, &new)?;
+
+    if renamed == 0 {
+        writeln!(
+            out,
+            "{} No entries found for agent {}",
+            "Info:".blue(),
+            old.yellow()
+        )?;
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "{} Renamed {} to {} ({} {})",
+        Glyph::Check.as_str().green(),
+        old.yellow(),
+        new.yellow(),
+        renamed,
+        if renamed == 1 { "entry" } else { "entries" }
+    )?;
+
+    Ok(())
+}
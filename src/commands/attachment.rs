@@ -0,0 +1,70 @@
+use crate::commands::CommandError;
+use crate::output::{short_id, Glyph};
+use crate::storage::{hash_bytes, LoreStorage};
+use colored::Colorize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub struct AttachmentGetOptions {
+    /// Entry ID or unique prefix
+    pub id: String,
+    pub filename: String,
+    /// Where to write the attachment; `-` for stdout
+    pub output: Option<PathBuf>,
+}
+
+/// Copy an attachment out of the store. Without `--output` it is written under
+/// its own name in the current directory, which must not exist yet.
+pub fn get(
+    storage: &LoreStorage,
+    options: AttachmentGetOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let id = storage.resolve_entry_id(&options.id)?;
+    let entry = storage.load_entry(&id)?;
+    let content = storage.load_attachment(&entry, &options.filename)?;
+
+    let changed = entry
+        .attachments
+        .iter()
+        .any(|a| a.filename == options.filename && a.sha256 != hash_bytes(&content));
+    if changed {
+        eprintln!(
+            "{} {} no longer matches the hash recorded with entry {}",
+            "Warning:".yellow(),
+            options.filename,
+            short_id(&id)
+        );
+    }
+
+    let output = match options.output {
+        Some(path) if path == Path::new("-") => {
+            out.write_all(&content)?;
+            return Ok(());
+        }
+        Some(path) => path,
+        None => {
+            let path = PathBuf::from(&options.filename);
+            if path.exists() {
+                return Err(CommandError::InvalidInput(format!(
+                    "{} already exists; choose another path with --output",
+                    path.display()
+                ))
+                .into());
+            }
+            path
+        }
+    };
+    std::fs::write(&output, &content)
+        .map_err(|e| CommandError::Io(format!("Cannot write {}: {}", output.display(), e)))?;
+
+    writeln!(
+        out,
+        "{} Wrote {} ({} bytes) to {}",
+        Glyph::Check.as_str().green(),
+        options.filename.cyan(),
+        content.len(),
+        output.display().to_string().cyan()
+    )?;
+    Ok(())
+}
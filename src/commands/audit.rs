@@ -0,0 +1,80 @@
+use crate::models::{AuditAction, AuditEvent};
+use crate::storage::LoreStorage;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use std::io::Write;
+
+pub struct AuditOptions {
+    pub action: Option<AuditAction>,
+    pub entry: Option<String>,
+    pub agent: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    pub json: bool,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: AuditOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut events = storage.load_audit_log()?;
+    retain_matching(&mut events, &options);
+
+    // Keep the most recent events
+    if let Some(limit) = options.limit {
+        let skip = events.len().saturating_sub(limit);
+        events.drain(..skip);
+    }
+
+    if options.json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&events)?)?;
+        return Ok(());
+    }
+
+    if events.is_empty() {
+        writeln!(out, "{} No audit events found.", "Info:".blue())?;
+        return Ok(());
+    }
+
+    for event in &events {
+        let action = match event.action {
+            AuditAction::Record => event.action.to_string().green(),
+            AuditAction::Import => event.action.to_string().blue(),
+            AuditAction::Edit => event.action.to_string().yellow(),
+            AuditAction::Prune => event.action.to_string().red(),
+        };
+        writeln!(
+            out,
+            "{}  {:<6}  {}  {}  {}",
+            event
+                .timestamp
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+                .dimmed(),
+            action,
+            event.entry_id.get(..8).unwrap_or(&event.entry_id),
+            event.agent_id.yellow(),
+            event.target_file.cyan()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Drop events that don't match the action, entry ID prefix, agent substring
+/// and time filters
+fn retain_matching(events: &mut Vec<AuditEvent>, options: &AuditOptions) {
+    events.retain(|event| {
+        options.action.is_none_or(|action| event.action == action)
+            && options
+                .entry
+                .as_deref()
+                .is_none_or(|id| event.entry_id.starts_with(id))
+            && options
+                .agent
+                .as_deref()
+                .is_none_or(|agent| event.agent_id.contains(agent))
+            && options.since.is_none_or(|since| event.timestamp >= since)
+    });
+}
@@ -0,0 +1,202 @@
+use crate::commands::CommandError;
+use crate::models::ThoughtObject;
+use crate::output::Glyph;
+use crate::storage::{normalize_path, LoreStorage};
+use colored::Colorize;
+use serde::Serialize;
+use std::io::Write;
+
+pub struct BlameOptions {
+    pub file: String,
+    pub json: bool,
+}
+
+/// The entry attributed to a single line
+#[derive(Debug)]
+pub struct LineAttribution<'a> {
+    pub line: usize,
+    pub entry: Option<&'a ThoughtObject>,
+    /// Other entries that also cover this line
+    pub others: usize,
+}
+
+/// A line record emitted by `blame --json`
+#[derive(Serialize)]
+struct BlameRecord<'a> {
+    line: usize,
+    entry_id: Option<&'a str>,
+    intent: Option<&'a str>,
+    agent: Option<&'a str>,
+}
+
+const INTENT_WIDTH: usize = 24;
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: BlameOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = storage.root();
+    let normalized = normalize_path(&options.file);
+
+    let content = std::fs::read_to_string(root.join(&normalized))
+        .map_err(|e| CommandError::cannot_read(&normalized, e))?;
+    let lines: Vec<&str> = content.lines().collect();
+
+    let (mut ranged, whole_file) = storage.get_partitioned_entries_for_file(&normalized)?;
+    // Anchored ranges are attributed where their anchor is now
+    for entry in &mut ranged {
+        if let Some(range) = entry.anchored_range(&content) {
+            entry.line_range = Some(range);
+        }
+    }
+    let attributions = attribute_lines(lines.len(), &ranged, &whole_file);
+
+    if options.json {
+        let records: Vec<BlameRecord> = attributions
+            .iter()
+            .map(|a| BlameRecord {
+                line: a.line,
+                entry_id: a.entry.map(|e| e.id.as_str()),
+                intent: a.entry.map(|e| e.intent.as_str()),
+                agent: a.entry.map(|e| e.agent_id.as_str()),
+            })
+            .collect();
+        let json = serde_json::to_string_pretty(&records)?;
+        writeln!(out, "{}", json)?;
+        return Ok(());
+    }
+
+    let number_width = lines.len().to_string().len();
+    for (attribution, text) in attributions.iter().zip(&lines) {
+        let gutter = match attribution.entry {
+            Some(entry) => {
+                let more = if attribution.others > 0 {
+                    format!("+{} more", attribution.others)
+                } else {
+                    String::new()
+                };
+                format!(
+                    "{:<12} {} {:<width$} {:>7}",
+                    truncate(&entry.agent_id, 12).yellow(),
+                    entry.timestamp.format("%Y-%m-%d").to_string().dimmed(),
+                    truncate(&entry.intent, INTENT_WIDTH),
+                    more.dimmed(),
+                    width = INTENT_WIDTH
+                )
+            }
+            None => " ".repeat(12 + 1 + 10 + 1 + INTENT_WIDTH + 1 + 7),
+        };
+
+        writeln!(
+            out,
+            "{} {} {:>width$} {}",
+            gutter,
+            Glyph::Bar.as_str().dimmed(),
+            attribution.line.to_string().dimmed(),
+            text,
+            width = number_width
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Attribute each of `total_lines` lines to the most recent ranged entry
+/// covering it, falling back to the most recent whole-file entry. Both entry
+/// lists must be sorted newest first.
+pub fn attribute_lines<'a>(
+    total_lines: usize,
+    ranged: &'a [ThoughtObject],
+    whole_file: &'a [ThoughtObject],
+) -> Vec<LineAttribution<'a>> {
+    (1..=total_lines)
+        .map(|line| {
+            let covering: Vec<&ThoughtObject> = ranged
+                .iter()
+                .filter(|e| {
+                    e.line_range
+                        .is_some_and(|(start, end)| start <= line && line <= end)
+                })
+                .collect();
+
+            if let Some(newest) = covering.first() {
+                LineAttribution {
+                    line,
+                    entry: Some(newest),
+                    others: covering.len() - 1,
+                }
+            } else {
+                LineAttribution {
+                    line,
+                    entry: whole_file.first(),
+                    others: whole_file.len().saturating_sub(1),
+                }
+            }
+        })
+        .collect()
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    let first_line = text.lines().next().unwrap_or("");
+    if first_line.chars().count() > max_chars {
+        let ellipsis = Glyph::Ellipsis.as_str();
+        let keep = max_chars.saturating_sub(ellipsis.chars().count());
+        let cut: String = first_line.chars().take(keep).collect();
+        format!("{}{}", cut, ellipsis)
+    } else {
+        first_line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(intent: &str, range: Option<(usize, usize)>) -> ThoughtObject {
+        let entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            intent.to_string(),
+            "Reasoning".to_string(),
+        );
+        match range {
+            Some((start, end)) => entry.with_line_range(start, end),
+            None => entry,
+        }
+    }
+
+    #[test]
+    fn test_attribute_lines_prefers_newest_ranged_entry() {
+        // Newest first
+        let ranged = vec![entry("newer", Some((3, 4))), entry("older", Some((1, 4)))];
+
+        let attributions = attribute_lines(5, &ranged, &[]);
+
+        assert_eq!(attributions[0].entry.unwrap().intent, "older");
+        assert_eq!(attributions[0].others, 0);
+        assert_eq!(attributions[2].entry.unwrap().intent, "newer");
+        assert_eq!(attributions[2].others, 1);
+        assert!(attributions[4].entry.is_none());
+    }
+
+    #[test]
+    fn test_attribute_lines_falls_back_to_whole_file_entry() {
+        let ranged = vec![entry("ranged", Some((2, 2)))];
+        let whole = vec![entry("whole", None)];
+
+        let attributions = attribute_lines(3, &ranged, &whole);
+
+        assert_eq!(attributions[0].entry.unwrap().intent, "whole");
+        assert_eq!(attributions[1].entry.unwrap().intent, "ranged");
+        assert_eq!(attributions[2].entry.unwrap().intent, "whole");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a much longer intent", 8), "a much …");
+        assert_eq!(truncate("first\nsecond", 10), "first");
+    }
+}
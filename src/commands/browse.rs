@@ -0,0 +1,387 @@
+use crate::commands::CommandError;
+use crate::models::ThoughtObject;
+use crate::output::Glyph;
+use crate::storage::LoreStorage;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::{IsTerminal, Write};
+
+/// Rows moved by PageUp/PageDown in the entry list
+const PAGE_ROWS: usize = 10;
+
+pub fn execute(
+    storage: &LoreStorage,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !std::io::stdout().is_terminal() {
+        return Err(CommandError::InvalidInput(
+            "lore browse needs an interactive terminal; use 'lore list' instead".to_string(),
+        )
+        .into());
+    }
+
+    let entries = storage.get_all_entries()?;
+    if entries.is_empty() {
+        writeln!(
+            out,
+            "{} No entries recorded yet.",
+            colored::Colorize::blue("Info:")
+        )?;
+        return Ok(());
+    }
+
+    // Restores the terminal on panic too
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, Browser::new(entries));
+    ratatui::restore();
+    result.map_err(Into::into)
+}
+
+fn run(terminal: &mut DefaultTerminal, mut browser: Browser) -> std::io::Result<()> {
+    loop {
+        terminal.draw(|frame| browser.render(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !browser.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// State of the two-pane browser: every entry in memory, the ones matching
+/// the filter, and the selection among those
+struct Browser {
+    entries: Vec<ThoughtObject>,
+    /// Indexes into `entries` of those matching `filter`, newest first
+    visible: Vec<usize>,
+    list: ListState,
+    filter: String,
+    /// Whether keys are being typed into the filter
+    editing: bool,
+    /// Lines scrolled in the detail pane
+    detail_scroll: u16,
+}
+
+impl Browser {
+    fn new(entries: Vec<ThoughtObject>) -> Self {
+        let mut browser = Self {
+            visible: (0..entries.len()).collect(),
+            entries,
+            list: ListState::default(),
+            filter: String::new(),
+            editing: false,
+            detail_scroll: 0,
+        };
+        browser.list.select(Some(0));
+        browser
+    }
+
+    fn selected(&self) -> Option<&ThoughtObject> {
+        let index = *self.visible.get(self.list.selected()?)?;
+        self.entries.get(index)
+    }
+
+    /// Recompute the visible entries after the filter changed, keeping the
+    /// selection at the top
+    fn apply_filter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.visible = (0..self.entries.len())
+            .filter(|&i| matches_filter(&self.entries[i], &filter))
+            .collect();
+        self.list.select((!self.visible.is_empty()).then_some(0));
+        self.detail_scroll = 0;
+    }
+
+    /// Move the selection by `delta` rows, stopping at either end
+    fn move_by(&mut self, delta: isize) {
+        let Some(last) = self.visible.len().checked_sub(1) else {
+            return;
+        };
+        let current = self.list.selected().unwrap_or(0);
+        let next = current.saturating_add_signed(delta).min(last);
+        self.list.select(Some(next));
+        self.detail_scroll = 0;
+    }
+
+    /// Apply a key press. Returns false when the browser should close.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+
+        if self.editing {
+            match key.code {
+                KeyCode::Enter => self.editing = false,
+                KeyCode::Esc => {
+                    self.editing = false;
+                    self.filter.clear();
+                    self.apply_filter();
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.apply_filter();
+                }
+                KeyCode::Char(c) => {
+                    self.filter.push(c);
+                    self.apply_filter();
+                }
+                KeyCode::Up => self.move_by(-1),
+                KeyCode::Down => self.move_by(1),
+                _ => {}
+            }
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Esc if self.filter.is_empty() => return false,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.apply_filter();
+            }
+            KeyCode::Char('/') => self.editing = true,
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::PageUp => self.move_by(-(PAGE_ROWS as isize)),
+            KeyCode::PageDown => self.move_by(PAGE_ROWS as isize),
+            KeyCode::Home | KeyCode::Char('g') => self.move_by(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => self.move_by(isize::MAX),
+            KeyCode::Char('J') => self.detail_scroll = self.detail_scroll.saturating_add(1),
+            KeyCode::Char('K') => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+            _ => {}
+        }
+        true
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let items: Vec<ListItem> = self
+            .visible
+            .iter()
+            .map(|&i| list_item(&self.entries[i]))
+            .collect();
+        let title = format!(" Entries ({}/{}) ", self.visible.len(), self.entries.len());
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, left, &mut self.list);
+
+        let detail = self.selected().map(detail_text).unwrap_or_default();
+        let detail = Paragraph::new(detail)
+            .block(Block::default().borders(Borders::ALL).title(" Detail "))
+            .wrap(Wrap { trim: false })
+            .scroll((self.detail_scroll, 0));
+        frame.render_widget(detail, right);
+
+        let status_line = if self.editing || !self.filter.is_empty() {
+            Line::from(vec![
+                Span::raw("/").bold(),
+                Span::raw(self.filter.as_str()),
+                Span::raw(if self.editing { "_" } else { "" }),
+                Span::raw("  Enter: keep  Esc: clear").dark_gray(),
+            ])
+        } else {
+            Line::from(
+                "Up/Down j/k: move  PgUp/PgDn  g/G: first/last  J/K: scroll detail  /: filter  q: quit"
+                    .dark_gray(),
+            )
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+/// Whether `entry` contains `filter` (already lowercase) in its file, agent,
+/// intent, reasoning, tags or rejected alternatives
+fn matches_filter(entry: &ThoughtObject, filter: &str) -> bool {
+    if filter.is_empty() {
+        return true;
+    }
+    let contains = |text: &str| text.to_lowercase().contains(filter);
+    contains(&entry.target_file)
+        || contains(&entry.agent_id)
+        || contains(&entry.intent)
+        || contains(&entry.reasoning_trace)
+        || entry.tags.iter().any(|tag| contains(tag))
+        || entry
+            .rejected_alternatives
+            .iter()
+            .any(|alt| contains(&alt.name) || alt.reason.as_deref().is_some_and(contains))
+}
+
+/// One row of the entry list: date, file, agent and intent
+fn list_item(entry: &ThoughtObject) -> ListItem<'static> {
+    ListItem::new(Line::from(vec![
+        Span::raw(entry.timestamp.format("%Y-%m-%d ").to_string()).dark_gray(),
+        Span::raw(entry.target_file.clone()).cyan(),
+        Span::raw(format!(" {} ", entry.agent_id)).yellow(),
+        Span::raw(entry.intent.lines().next().unwrap_or_default().to_string()),
+    ]))
+}
+
+/// Everything recorded for an entry, for the detail pane
+fn detail_text(entry: &ThoughtObject) -> Text<'static> {
+    let heading = |text: &str| Line::from(text.to_string().bold().underlined());
+    let field = |name: &str, value: String| {
+        Line::from(vec![
+            Span::raw(format!("{}: ", name)).bold(),
+            Span::raw(value),
+        ])
+    };
+
+    let mut file = entry.target_file.clone();
+    if let Some((start, end)) = entry.line_range {
+        file.push_str(&format!(":{}-{}", start, end));
+    }
+
+    let mut lines = vec![
+        Line::from(entry.intent.clone().bold()),
+        Line::default(),
+        field("File", file),
+        field("Agent", entry.agent_id.clone()),
+        field(
+            "Date",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        ),
+        field("ID", entry.id.clone()),
+    ];
+    if let Some(commit) = &entry.commit_hash {
+        lines.push(field("Commit", commit[..8.min(commit.len())].to_string()));
+    }
+    if let Some(confidence) = entry.confidence {
+        lines.push(field("Confidence", format!("{:.0}%", confidence * 100.0)));
+    }
+    if !entry.tags.is_empty() {
+        let tags: Vec<String> = entry.tags.iter().map(|t| format!("#{}", t)).collect();
+        lines.push(field("Tags", tags.join(" ")));
+    }
+    if let Some(supersedes) = &entry.supersedes {
+        lines.push(field(
+            "Supersedes",
+            supersedes[..8.min(supersedes.len())].to_string(),
+        ));
+    }
+
+    lines.push(Line::default());
+    lines.push(heading("Reasoning"));
+    lines.extend(
+        entry
+            .reasoning_trace
+            .lines()
+            .map(|line| Line::from(line.to_string())),
+    );
+
+    if !entry.rejected_alternatives.is_empty() {
+        lines.push(Line::default());
+        lines.push(heading("Rejected Alternatives"));
+        for alt in &entry.rejected_alternatives {
+            let mut spans = vec![
+                Span::raw(format!("{} ", Glyph::Cross)).fg(Color::Red),
+                Span::raw(alt.name.clone()),
+            ];
+            if let Some(reason) = &alt.reason {
+                spans.push(Span::raw(format!(" - {}", reason)).dark_gray());
+            }
+            lines.push(Line::from(spans));
+        }
+    }
+
+    if !entry.references.is_empty() {
+        lines.push(Line::default());
+        lines.push(heading("References"));
+        for reference in &entry.references {
+            lines.push(Line::from(format!("  {}", reference)));
+        }
+    }
+
+    Text::from(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, intent: &str) -> ThoughtObject {
+        ThoughtObject::new(
+            file.to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            intent.to_string(),
+            "Reasoning".to_string(),
+        )
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_filter_narrows_and_navigation_stays_in_bounds() {
+        let mut browser = Browser::new(vec![
+            entry("src/auth.rs", "Use JWT"),
+            entry("src/db.rs", "Pool connections"),
+            entry("src/auth_cache.rs", "Cache tokens"),
+        ]);
+
+        browser.move_by(-1);
+        assert_eq!(browser.list.selected(), Some(0));
+        browser.move_by(isize::MAX);
+        assert_eq!(browser.list.selected(), Some(2));
+
+        browser.handle_key(key(KeyCode::Char('/')));
+        for c in "AUTH".chars() {
+            browser.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(browser.visible, vec![0, 2]);
+        assert_eq!(browser.selected().unwrap().intent, "Use JWT");
+
+        // `q` is part of the filter while typing, and quits afterwards
+        assert!(browser.handle_key(key(KeyCode::Char('q'))));
+        assert!(browser.visible.is_empty());
+        assert!(browser.selected().is_none());
+        browser.handle_key(key(KeyCode::Backspace));
+        browser.handle_key(key(KeyCode::Enter));
+        assert_eq!(browser.visible, vec![0, 2]);
+
+        // Esc clears the filter before it quits
+        assert!(browser.handle_key(key(KeyCode::Esc)));
+        assert_eq!(browser.visible.len(), 3);
+        assert!(!browser.handle_key(key(KeyCode::Char('q'))));
+    }
+
+    #[test]
+    fn test_matches_filter_searches_reasoning_and_tags() {
+        let entry = entry("a.rs", "Intent").with_tags(vec!["Perf".to_string()]);
+        assert!(matches_filter(&entry, ""));
+        assert!(matches_filter(&entry, "perf"));
+        assert!(matches_filter(&entry, "reason"));
+        assert!(!matches_filter(&entry, "missing"));
+    }
+
+    #[test]
+    fn test_render_shows_list_and_detail() {
+        let mut browser = Browser::new(vec![entry("src/auth.rs", "Use JWT")]);
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| browser.render(frame)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Entries (1/1)"));
+        assert!(screen.contains("src/auth.rs"));
+        assert!(screen.contains("Reasoning"));
+    }
+}
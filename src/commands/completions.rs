@@ -0,0 +1,138 @@
+use crate::storage::LoreStorage;
+use clap_complete::Shell;
+use std::io::Write;
+
+/// Subcommands whose first positional argument is a file with recorded lore
+const FILE_COMMANDS: &[&str] = &["explain", "blame", "coverage", "mv"];
+
+pub struct CompletionsOptions {
+    pub shell: Shell,
+}
+
+/// Print a completion script for `shell`. On top of clap's static script,
+/// bash, zsh and fish complete file arguments from `lore __complete-files`.
+pub fn execute(
+    cmd: &mut clap::Command,
+    options: CompletionsOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut script = Vec::new();
+    clap_complete::generate(options.shell, cmd, "lore", &mut script);
+    let script = String::from_utf8(script)?;
+
+    let script = match options.shell {
+        Shell::Bash => format!("{}{}", script, bash_files()),
+        Shell::Zsh => zsh_with_files(&script),
+        Shell::Fish => format!("{}{}", script, fish_files()),
+        _ => script,
+    };
+
+    out.write_all(script.as_bytes())?;
+    Ok(())
+}
+
+/// Print every file with recorded lore, one per line. Backs the dynamic part
+/// of the completion scripts, so it prints nothing outside a lore project.
+pub fn complete_files(
+    storage: &LoreStorage,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for file in storage.load_index()?.files.keys() {
+        writeln!(out, "{}", file)?;
+    }
+    Ok(())
+}
+
+/// Wrap clap's `_lore` so the file argument completes recorded files first,
+/// falling back to plain filenames when there are none
+fn bash_files() -> String {
+    format!(
+        r#"
+_lore_with_files() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    case "${{COMP_WORDS[1]}}" in
+        {})
+            if [[ ${{COMP_CWORD}} -ge 2 && ${{cur}} != -* ]]; then
+                COMPREPLY=( $(compgen -W "$(lore __complete-files 2>/dev/null)" -- "${{cur}}") )
+                return 0
+            fi
+            ;;
+    esac
+    _lore "$@"
+}}
+
+if [[ "${{BASH_VERSINFO[0]}}" -eq 4 && "${{BASH_VERSINFO[1]}}" -ge 4 || "${{BASH_VERSINFO[0]}}" -gt 4 ]]; then
+    complete -F _lore_with_files -o nosort -o bashdefault -o default lore
+else
+    complete -F _lore_with_files -o bashdefault -o default lore
+fi
+"#,
+        FILE_COMMANDS.join("|")
+    )
+}
+
+/// Same wrapper for zsh. The script's tail either calls `_lore` (autoloaded
+/// from `fpath`) or registers it with `compdef`; both have to go through the
+/// wrapper instead, and the autoload path re-registers it so later completions
+/// don't go straight to the static `_lore`.
+fn zsh_with_files(script: &str) -> String {
+    const TAIL: &str = "if [ \"$funcstack[1]\" = \"_lore\" ]; then";
+    let script = match script.rfind(TAIL) {
+        Some(tail) => &script[..tail],
+        None => script,
+    };
+    format!(
+        r#"{}_lore_with_files() {{
+    if (( CURRENT > 2 )) && [[ ${{words[2]}} == ({}) && $PREFIX != -* ]]; then
+        local -a files
+        files=(${{(f)"$(lore __complete-files 2>/dev/null)"}})
+        if (( ${{#files}} )); then
+            compadd -a files && return 0
+        fi
+    fi
+    _lore "$@"
+}}
+
+if [ "$funcstack[1]" = "_lore" ]; then
+    compdef _lore_with_files lore
+    _lore_with_files "$@"
+else
+    compdef _lore_with_files lore
+fi
+"#,
+        script,
+        FILE_COMMANDS.join("|")
+    )
+}
+
+fn fish_files() -> String {
+    format!(
+        "complete -c lore -n \"__fish_seen_subcommand_from {}\" -f -a \"(lore __complete-files 2>/dev/null)\"\n",
+        FILE_COMMANDS.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zsh_script_registers_wrapper_once() {
+        let mut cmd = clap::Command::new("lore")
+            .subcommand(clap::Command::new("explain").arg(clap::Arg::new("file").required(true)));
+        let mut script = Vec::new();
+        clap_complete::generate(Shell::Zsh, &mut cmd, "lore", &mut script);
+        let script = zsh_with_files(&String::from_utf8(script).unwrap());
+
+        assert!(!script.contains("compdef _lore lore"));
+        assert_eq!(script.matches("if [ \"$funcstack[1]\"").count(), 1);
+        assert!(script.contains("_lore_with_files \"$@\""));
+        assert!(script.contains("(explain|blame|coverage|mv)"));
+    }
+
+    #[test]
+    fn test_bash_wrapper_replaces_registration() {
+        assert!(bash_files().contains("explain|blame|coverage|mv)"));
+        assert!(bash_files().contains("complete -F _lore_with_files"));
+    }
+}
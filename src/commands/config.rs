@@ -0,0 +1,105 @@
+use crate::global_config::{GlobalConfig, GLOBAL_CONFIG_KEYS};
+use crate::models::CONFIG_KEYS;
+use crate::output::Glyph;
+use crate::storage::{find_lore_root, LoreStorage, StorageError};
+use colored::Colorize;
+use std::io::Write;
+
+/// Print a setting's value. Unset optional settings print nothing.
+pub fn get(
+    key: &str,
+    global: bool,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let value = if global {
+        GlobalConfig::load()?.get(key)?
+    } else {
+        open_storage()?.load_config()?.get(key)?
+    };
+
+    if let Some(value) = value {
+        writeln!(out, "{}", value)?;
+    }
+
+    Ok(())
+}
+
+/// Change a setting in `.lore/config.json`, or in the per-user config with `global`
+pub fn set(
+    key: &str,
+    value: &str,
+    global: bool,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let value = if global {
+        let mut config = GlobalConfig::load()?;
+        config.set(key, value)?;
+        config.save()?;
+        config.get(key)?
+    } else {
+        let storage = open_storage()?;
+        let mut config = storage.load_config()?;
+        config.set(key, value)?;
+        storage.save_config(&config)?;
+        config.get(key)?
+    };
+
+    match value {
+        Some(value) => writeln!(
+            out,
+            "{} {} = {}",
+            Glyph::Check.as_str().green(),
+            key.cyan(),
+            value
+        )?,
+        None => writeln!(
+            out,
+            "{} Unset {}",
+            Glyph::Check.as_str().green(),
+            key.cyan()
+        )?,
+    }
+
+    Ok(())
+}
+
+/// Print every setting, marking unset ones
+pub fn list(global: bool, out: &mut impl Write) -> Result<(), Box<dyn std::error::Error>> {
+    let values: Vec<(&str, Option<String>)> = if global {
+        let config = GlobalConfig::load()?;
+        GLOBAL_CONFIG_KEYS
+            .iter()
+            .map(|key| Ok((*key, config.get(key)?)))
+            .collect::<Result<_, String>>()?
+    } else {
+        let config = open_storage()?.load_config()?;
+        CONFIG_KEYS
+            .iter()
+            .map(|key| Ok((*key, config.get(key)?)))
+            .collect::<Result<_, String>>()?
+    };
+
+    if global {
+        if let Some(path) = GlobalConfig::path() {
+            writeln!(out, "{}", path.display().to_string().dimmed())?;
+        }
+    }
+
+    let width = values.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    for (key, value) in values {
+        match value {
+            Some(value) => writeln!(out, "{:<width$}  {}", key.cyan(), value)?,
+            None => writeln!(out, "{:<width$}  {}", key.cyan(), "(unset)".dimmed())?,
+        }
+    }
+
+    Ok(())
+}
+
+fn open_storage() -> Result<LoreStorage, Box<dyn std::error::Error>> {
+    // Find lore root
+    let current_dir = std::env::current_dir()?;
+    let root = find_lore_root(&current_dir).ok_or(StorageError::NotInitialized)?;
+
+    Ok(LoreStorage::new(root))
+}
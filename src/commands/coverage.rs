@@ -0,0 +1,193 @@
+use crate::commands::CommandError;
+use crate::output::Glyph;
+use crate::storage::{normalize_path, LoreStorage};
+use colored::Colorize;
+use serde::Serialize;
+use std::io::Write;
+
+pub struct CoverageOptions {
+    pub file: String,
+    pub json: bool,
+}
+
+/// Line coverage of a file by recorded reasoning
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Coverage {
+    pub total_lines: usize,
+    pub covered_lines: usize,
+    pub percent: f64,
+    /// Inclusive, 1-based line ranges not covered by any entry
+    pub uncovered: Vec<(usize, usize)>,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: CoverageOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = storage.root();
+    let normalized = normalize_path(&options.file);
+
+    let content = std::fs::read_to_string(root.join(&normalized))
+        .map_err(|e| CommandError::cannot_read(&normalized, e))?;
+    let total_lines = content.lines().count();
+
+    let entries = storage.get_entries_for_file(&normalized)?;
+    let ranges: Vec<Option<(usize, usize)>> = entries.iter().map(|e| e.line_range).collect();
+    let coverage = compute_coverage(&ranges, total_lines);
+
+    if options.json {
+        let json = serde_json::to_string_pretty(&coverage)?;
+        writeln!(out, "{}", json)?;
+        return Ok(());
+    }
+
+    let percent = format!("{:.1}%", coverage.percent);
+    let percent = if coverage.percent >= 80.0 {
+        percent.green()
+    } else if coverage.percent >= 40.0 {
+        percent.yellow()
+    } else {
+        percent.red()
+    };
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{} {} {} ({} of {} lines)",
+        "Coverage for".bold(),
+        normalized.cyan().bold(),
+        percent,
+        coverage.covered_lines,
+        coverage.total_lines
+    )?;
+
+    if !coverage.uncovered.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Uncovered lines:".bold())?;
+        for (start, end) in &coverage.uncovered {
+            if start == end {
+                writeln!(out, "  {} {}", Glyph::Arrow.as_str().yellow(), start)?;
+            } else {
+                writeln!(
+                    out,
+                    "  {} {}-{}",
+                    Glyph::Arrow.as_str().yellow(),
+                    start,
+                    end
+                )?;
+            }
+        }
+    }
+    writeln!(out)?;
+
+    Ok(())
+}
+
+/// Merge inclusive line ranges into a sorted, non-overlapping list
+pub fn merge_ranges(ranges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut sorted: Vec<(usize, usize)> =
+        ranges.iter().map(|&(a, b)| (a.min(b), a.max(b))).collect();
+    sorted.sort();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in sorted {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Compute coverage of a file with `total_lines` lines by the given entry
+/// ranges. A `None` range applies to the whole file.
+pub fn compute_coverage(ranges: &[Option<(usize, usize)>], total_lines: usize) -> Coverage {
+    let ranges: Vec<(usize, usize)> = if ranges.iter().any(Option::is_none) {
+        vec![(1, total_lines)]
+    } else {
+        ranges.iter().flatten().copied().collect()
+    };
+
+    // Clamp to the file and drop ranges that fall outside it
+    let clamped: Vec<(usize, usize)> = merge_ranges(&ranges)
+        .into_iter()
+        .map(|(start, end)| (start.max(1), end.min(total_lines)))
+        .filter(|(start, end)| start <= end)
+        .collect();
+
+    let covered_lines = clamped.iter().map(|(start, end)| end - start + 1).sum();
+
+    let mut uncovered = Vec::new();
+    let mut next = 1;
+    for (start, end) in &clamped {
+        if *start > next {
+            uncovered.push((next, start - 1));
+        }
+        next = end + 1;
+    }
+    if next <= total_lines {
+        uncovered.push((next, total_lines));
+    }
+
+    let percent = if total_lines == 0 {
+        0.0
+    } else {
+        covered_lines as f64 * 100.0 / total_lines as f64
+    };
+
+    Coverage {
+        total_lines,
+        covered_lines,
+        percent,
+        uncovered,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_ranges_overlapping_and_adjacent() {
+        assert_eq!(
+            merge_ranges(&[(10, 20), (1, 5), (15, 30), (6, 8)]),
+            vec![(1, 8), (10, 30)]
+        );
+    }
+
+    #[test]
+    fn test_compute_coverage_overlapping_ranges() {
+        let coverage = compute_coverage(&[Some((1, 10)), Some((5, 20)), Some((41, 50))], 100);
+
+        assert_eq!(coverage.covered_lines, 30);
+        assert_eq!(coverage.percent, 30.0);
+        assert_eq!(coverage.uncovered, vec![(21, 40), (51, 100)]);
+    }
+
+    #[test]
+    fn test_compute_coverage_whole_file_entry() {
+        let coverage = compute_coverage(&[Some((1, 10)), None], 50);
+
+        assert_eq!(coverage.covered_lines, 50);
+        assert_eq!(coverage.percent, 100.0);
+        assert!(coverage.uncovered.is_empty());
+    }
+
+    #[test]
+    fn test_compute_coverage_clamps_ranges_past_end_of_file() {
+        let coverage = compute_coverage(&[Some((8, 40)), Some((60, 70))], 10);
+
+        assert_eq!(coverage.covered_lines, 3);
+        assert_eq!(coverage.uncovered, vec![(1, 7)]);
+    }
+
+    #[test]
+    fn test_compute_coverage_no_entries() {
+        let coverage = compute_coverage(&[], 4);
+
+        assert_eq!(coverage.covered_lines, 0);
+        assert_eq!(coverage.percent, 0.0);
+        assert_eq!(coverage.uncovered, vec![(1, 4)]);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::models::{RejectedAlternative, ThoughtObject};
+use crate::output::{heavy_rule, rule, short_id, Glyph};
+use crate::storage::LoreStorage;
+use colored::Colorize;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::io::{self, Write};
+
+/// Lines of unchanged reasoning shown around each change
+const CONTEXT_LINES: usize = 3;
+
+pub struct DiffOptions {
+    pub old: String,
+    pub new: String,
+    pub json: bool,
+}
+
+/// A field whose value differs between the two entries
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+/// Items present in only one of the two entries
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SetChange<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+}
+
+/// A line of the reasoning trace diff
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LineChange {
+    /// `equal`, `insert` or `delete`
+    pub op: &'static str,
+    pub line: String,
+}
+
+/// How a second entry differs from the first, as emitted by `diff --json`.
+/// Unchanged fields are left out.
+#[derive(Debug, Serialize)]
+pub struct EntryDiff {
+    pub old_id: String,
+    pub new_id: String,
+    /// Whether the new entry supersedes the old one
+    pub supersedes: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_file: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intent: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reasoning_trace: Vec<LineChange>,
+    #[serde(skip_serializing_if = "SetChange::is_empty")]
+    pub tags: SetChange<String>,
+    #[serde(skip_serializing_if = "SetChange::is_empty")]
+    pub rejected_alternatives: SetChange<RejectedAlternative>,
+}
+
+impl<T> SetChange<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl EntryDiff {
+    /// Whether the entries say the same thing
+    pub fn is_empty(&self) -> bool {
+        self.target_file.is_none()
+            && self.intent.is_none()
+            && self.reasoning_trace.is_empty()
+            && self.tags.is_empty()
+            && self.rejected_alternatives.is_empty()
+    }
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: DiffOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let old = storage.load_entry(&storage.resolve_entry_id(&options.old)?)?;
+    let new = storage.load_entry(&storage.resolve_entry_id(&options.new)?)?;
+    let diff = diff_entries(&old, &new);
+
+    if options.json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&diff)?)?;
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    writeln!(
+        out,
+        "{} {} {} {}{}",
+        "Comparing".bold(),
+        short_id(&old.id).yellow(),
+        Glyph::Arrow.as_str(),
+        short_id(&new.id).yellow(),
+        if diff.supersedes {
+            " (supersedes)".dimmed().to_string()
+        } else {
+            String::new()
+        }
+    )?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+
+    if diff.is_empty() {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{} The entries have the same reasoning",
+            "Info:".blue()
+        )?;
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    match &diff.target_file {
+        Some(change) => writeln!(
+            out,
+            "{} {} {} {}",
+            "File:".bold(),
+            change.old.cyan(),
+            Glyph::Arrow.as_str(),
+            change.new.cyan()
+        )?,
+        None => writeln!(out, "{} {}", "File:".bold(), new.target_file.cyan())?,
+    }
+
+    if let Some(change) = &diff.intent {
+        writeln!(out)?;
+        writeln!(out, "{}", "Intent:".bold().underline())?;
+        writeln!(out, "{}", format!("- {}", change.old).red())?;
+        writeln!(out, "{}", format!("+ {}", change.new).green())?;
+    }
+
+    if !diff.reasoning_trace.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Reasoning:".bold().underline())?;
+        print_reasoning_diff(out, &old.reasoning_trace, &new.reasoning_trace)?;
+    }
+
+    if !diff.tags.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Tags:".bold().underline())?;
+        for tag in &diff.tags.added {
+            writeln!(out, "{}", format!("+ #{}", tag).green())?;
+        }
+        for tag in &diff.tags.removed {
+            writeln!(out, "{}", format!("- #{}", tag).red())?;
+        }
+    }
+
+    if !diff.rejected_alternatives.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Rejected Alternatives:".bold().underline())?;
+        for alt in &diff.rejected_alternatives.added {
+            writeln!(
+                out,
+                "{}",
+                format!("+ {}", describe_alternative(alt)).green()
+            )?;
+        }
+        for alt in &diff.rejected_alternatives.removed {
+            writeln!(out, "{}", format!("- {}", describe_alternative(alt)).red())?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", rule(60).dimmed())?;
+
+    Ok(())
+}
+
+/// Compare two entries field by field
+pub fn diff_entries(old: &ThoughtObject, new: &ThoughtObject) -> EntryDiff {
+    let changed = |a: &String, b: &String| {
+        (a != b).then(|| Change {
+            old: a.clone(),
+            new: b.clone(),
+        })
+    };
+
+    let reasoning_trace = if old.reasoning_trace == new.reasoning_trace {
+        Vec::new()
+    } else {
+        TextDiff::from_lines(&old.reasoning_trace, &new.reasoning_trace)
+            .iter_all_changes()
+            .map(|change| LineChange {
+                op: match change.tag() {
+                    ChangeTag::Equal => "equal",
+                    ChangeTag::Insert => "insert",
+                    ChangeTag::Delete => "delete",
+                },
+                line: change.value().trim_end_matches('\n').to_string(),
+            })
+            .collect()
+    };
+
+    EntryDiff {
+        old_id: old.id.clone(),
+        new_id: new.id.clone(),
+        supersedes: new.supersedes.as_deref() == Some(old.id.as_str()),
+        target_file: changed(&old.target_file, &new.target_file),
+        intent: changed(&old.intent, &new.intent),
+        reasoning_trace,
+        tags: set_change(&old.tags, &new.tags),
+        rejected_alternatives: set_change(&old.rejected_alternatives, &new.rejected_alternatives),
+    }
+}
+
+/// Items of `new` not in `old`, and of `old` not in `new`, in their original order
+fn set_change<T: Clone + PartialEq>(old: &[T], new: &[T]) -> SetChange<T> {
+    SetChange {
+        added: new.iter().filter(|t| !old.contains(t)).cloned().collect(),
+        removed: old.iter().filter(|t| !new.contains(t)).cloned().collect(),
+    }
+}
+
+/// Print the reasoning as a unified diff, with a few lines of context around
+/// each change
+fn print_reasoning_diff(out: &mut impl Write, old: &str, new: &str) -> io::Result<()> {
+    let diff = TextDiff::from_lines(old, new);
+    for (i, group) in diff.grouped_ops(CONTEXT_LINES).iter().enumerate() {
+        if i > 0 {
+            writeln!(out, "{}", Glyph::Ellipsis.as_str().dimmed())?;
+        }
+        for op in group {
+            for change in diff.iter_changes(op) {
+                let line = change.value().trim_end_matches('\n');
+                match change.tag() {
+                    ChangeTag::Equal => writeln!(out, "  {}", line)?,
+                    ChangeTag::Insert => writeln!(out, "{}", format!("+ {}", line).green())?,
+                    ChangeTag::Delete => writeln!(out, "{}", format!("- {}", line).red())?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe_alternative(alt: &RejectedAlternative) -> String {
+    match &alt.reason {
+        Some(reason) => format!("{} - {}", alt.name, reason),
+        None => alt.name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(intent: &str, reasoning: &str) -> ThoughtObject {
+        ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            intent.to_string(),
+            reasoning.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_diff_entries_reports_changed_fields() {
+        let mut old = entry("Use a cache", "Reads are slow.\nTry an LRU.\n");
+        old.tags = vec!["perf".to_string(), "cache".to_string()];
+        old.rejected_alternatives = vec![RejectedAlternative {
+            name: "Redis".to_string(),
+            reason: None,
+        }];
+
+        let mut new = entry("Use a cache", "Reads are slow.\nTry a TTL map.\n")
+            .with_supersedes(old.id.clone());
+        new.tags = vec!["perf".to_string(), "memory".to_string()];
+
+        let diff = diff_entries(&old, &new);
+        assert!(diff.supersedes);
+        assert!(diff.intent.is_none());
+        assert!(diff.target_file.is_none());
+        assert_eq!(
+            diff.reasoning_trace
+                .iter()
+                .map(|c| (c.op, c.line.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("equal", "Reads are slow."),
+                ("delete", "Try an LRU."),
+                ("insert", "Try a TTL map."),
+            ]
+        );
+        assert_eq!(diff.tags.added, vec!["memory"]);
+        assert_eq!(diff.tags.removed, vec!["cache"]);
+        assert!(diff.rejected_alternatives.added.is_empty());
+        assert_eq!(diff.rejected_alternatives.removed[0].name, "Redis");
+    }
+
+    #[test]
+    fn test_diff_entries_identical_reasoning_is_empty() {
+        let old = entry("Intent", "Reasoning");
+        let new = entry("Intent", "Reasoning");
+
+        let diff = diff_entries(&old, &new);
+        assert!(diff.is_empty());
+        assert!(!diff.supersedes);
+
+        let json = serde_json::to_value(&diff).unwrap();
+        assert!(json.get("reasoning_trace").is_none());
+        assert!(json.get("tags").is_none());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::commands::diff_stores::{print_section, summary};
+use crate::commands::CommandError;
+use crate::git::{ChangeType, GitContext};
+use crate::models::{EntryMeta, ThoughtObject};
+use crate::output::{heavy_rule, rule};
+use crate::storage::{LoreStorage, StoreDiff};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+pub struct DiffLoreOptions {
+    pub base: String,
+    pub head: String,
+    pub json: bool,
+}
+
+/// Entries committed to `.lore` between two revisions, as emitted by `--json`
+#[derive(Debug, Default, Serialize)]
+pub struct LoreChanges {
+    pub added: Vec<ThoughtObject>,
+    pub removed: Vec<ThoughtObject>,
+    /// Entries changed in place (their version at the later revision)
+    pub modified: Vec<ThoughtObject>,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: DiffLoreOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let git = GitContext::open(storage.root())?;
+    let resolve = |rev: &str| {
+        git.resolve(rev)
+            .map_err(|e| CommandError::Git(format!("Cannot resolve '{}': {}", rev, e)))
+    };
+    let base = resolve(&options.base)?;
+    let head = resolve(&options.head)?;
+
+    // Like `git diff base...head`: only what the head side added since they forked
+    let from = git.merge_base(&base, &head).map_err(|e| {
+        CommandError::Git(format!(
+            "No common ancestor of '{}' and '{}': {}",
+            options.base, options.head, e
+        ))
+    })?;
+    let changes = lore_changes(&git, &from, &head, &entries_dir(storage, &git)?)?;
+
+    if options.json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&changes)?)?;
+        return Ok(());
+    }
+
+    let range = format!("{}...{}", options.base, options.head);
+    let diff = StoreDiff {
+        added: changes.added.iter().map(EntryMeta::from).collect(),
+        removed: changes.removed.iter().map(EntryMeta::from).collect(),
+        modified: changes.modified.iter().map(EntryMeta::from).collect(),
+    };
+    if diff.is_empty() {
+        writeln!(
+            out,
+            "{} No lore changes in {}",
+            "Info:".blue(),
+            range.cyan()
+        )?;
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    writeln!(out, "{} {}", "Lore changes in".bold(), range.cyan())?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+
+    print_section(out, "Added", "+".green(), &diff.added)?;
+    print_section(out, "Removed", "-".red(), &diff.removed)?;
+    print_section(out, "Modified", "~".yellow(), &diff.modified)?;
+
+    writeln!(out)?;
+    writeln!(out, "{}", rule(60).dimmed())?;
+    writeln!(out, "{}", summary(&diff))?;
+    let agents = agent_counts(diff.added.iter().chain(&diff.modified));
+    if !agents.is_empty() {
+        writeln!(
+            out,
+            "{} {}",
+            "By:".dimmed(),
+            agents
+                .iter()
+                .map(|(agent, count)| format!("{} ({})", agent, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Where `.lore/entries` is relative to the repository root, since the store
+/// can live in a subdirectory of the repository
+fn entries_dir(storage: &LoreStorage, git: &GitContext) -> Result<String, CommandError> {
+    let not_in_repo = || {
+        CommandError::Git(format!(
+            "{} is not inside the git repository's working tree",
+            storage.root().display()
+        ))
+    };
+    let workdir = git
+        .workdir()
+        .and_then(|dir| dir.canonicalize().ok())
+        .ok_or_else(not_in_repo)?;
+    let root = storage.root().canonicalize().map_err(|_| not_in_repo())?;
+    let prefix = root.strip_prefix(&workdir).map_err(|_| not_in_repo())?;
+
+    Ok(prefix
+        .join(".lore")
+        .join("entries")
+        .to_string_lossy()
+        .replace('\\', "/"))
+}
+
+/// Entries added, removed and modified under `dir` between two commits, read
+/// from the git object database. An entry whose file only moved (e.g. by
+/// `lore migrate`) isn't reported; one that moved and changed is modified.
+pub fn lore_changes(
+    git: &GitContext,
+    from: &str,
+    to: &str,
+    dir: &str,
+) -> Result<LoreChanges, Box<dyn std::error::Error>> {
+    let entry_at = |rev: &str, path: &str| -> Result<Option<ThoughtObject>, CommandError> {
+        let content = git
+            .file_at_commit(rev, path)
+            .map_err(|e| CommandError::Git(format!("Cannot read {} at {}: {}", path, rev, e)))?;
+        Ok(content.and_then(|content| {
+            serde_json::from_slice(&content)
+                .ok()
+                .and_then(|value| LoreStorage::migrate(value).ok())
+        }))
+    };
+
+    let mut added: HashMap<String, ThoughtObject> = HashMap::new();
+    let mut removed: HashMap<String, ThoughtObject> = HashMap::new();
+    let mut changes = LoreChanges::default();
+    for file in git.files_changed_between(from, to, dir)? {
+        if !file.path.ends_with(".json") {
+            continue;
+        }
+        let entry = match file.change_type {
+            ChangeType::Deleted => entry_at(from, &file.path)?,
+            _ => entry_at(to, &file.path)?,
+        };
+        let Some(entry) = entry else {
+            continue;
+        };
+        match file.change_type {
+            ChangeType::Added => {
+                added.insert(entry.id.clone(), entry);
+            }
+            ChangeType::Deleted => {
+                removed.insert(entry.id.clone(), entry);
+            }
+            ChangeType::Modified | ChangeType::Renamed => changes.modified.push(entry),
+        }
+    }
+
+    for (id, entry) in added {
+        match removed.remove(&id) {
+            None => changes.added.push(entry),
+            Some(old) if serde_json::to_value(&old)? != serde_json::to_value(&entry)? => {
+                changes.modified.push(entry)
+            }
+            Some(_) => {}
+        }
+    }
+    changes.removed = removed.into_values().collect();
+
+    for entries in [
+        &mut changes.added,
+        &mut changes.removed,
+        &mut changes.modified,
+    ] {
+        entries.sort_by(|a, b| (b.timestamp, &a.id).cmp(&(a.timestamp, &b.id)));
+    }
+    Ok(changes)
+}
+
+/// How many entries each agent added or changed, most first
+fn agent_counts<'a>(metas: impl Iterator<Item = &'a EntryMeta>) -> Vec<(&'a str, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for meta in metas {
+        *counts.entry(&meta.agent_id).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(intent: &str) -> ThoughtObject {
+        ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            intent.to_string(),
+            "Reasoning".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_lore_changes_between_commits() {
+        let temp_dir = TempDir::new().unwrap();
+        let git_in = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .expect("Failed to run git");
+        };
+        git_in(&["init"]);
+        git_in(&["config", "user.email", "test@test.com"]);
+        git_in(&["config", "user.name", "Test User"]);
+
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+        let kept = entry("Kept");
+        let dropped = entry("Dropped");
+        let mut edited = entry("Before");
+        for e in [&kept, &dropped, &edited] {
+            storage.save_entry(e).unwrap();
+        }
+        git_in(&["add", "."]);
+        git_in(&["commit", "-m", "Base"]);
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let base = git.head_commit().unwrap();
+
+        let new = entry("New");
+        storage.save_entry(&new).unwrap();
+        storage.prune(std::slice::from_ref(&dropped), None).unwrap();
+        edited.intent = "After".to_string();
+        storage.update_entry(&edited).unwrap();
+        // Moved to the flat layout without changing
+        std::fs::rename(
+            temp_dir
+                .path()
+                .join(".lore/entries")
+                .join(&kept.id[..2])
+                .join(format!("{}.json", kept.id)),
+            temp_dir
+                .path()
+                .join(".lore/entries")
+                .join(format!("{}.json", kept.id)),
+        )
+        .unwrap();
+        git_in(&["add", "-A"]);
+        git_in(&["commit", "-m", "Branch"]);
+
+        let changes = lore_changes(&git, &base, "HEAD", ".lore/entries").unwrap();
+        let ids = |entries: &[ThoughtObject]| -> Vec<String> {
+            entries.iter().map(|e| e.id.clone()).collect()
+        };
+        assert_eq!(ids(&changes.added), vec![new.id]);
+        assert_eq!(ids(&changes.removed), vec![dropped.id]);
+        assert_eq!(changes.modified.len(), 1);
+        assert_eq!(changes.modified[0].intent, "After");
+    }
+}
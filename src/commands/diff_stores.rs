@@ -0,0 +1,113 @@
+use crate::commands::CommandError;
+use crate::models::EntryMeta;
+use crate::output::{heavy_rule, rule, short_id};
+use crate::storage::{LoreStorage, StoreDiff};
+use colored::{ColoredString, Colorize};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub struct DiffStoresOptions {
+    pub other: PathBuf,
+    pub stat: bool,
+    pub json: bool,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: DiffStoresOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let base = LoreStorage::new(other_root(&options.other)?);
+    let diff = storage.diff_against(&base)?;
+
+    if options.json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&diff)?)?;
+        return Ok(());
+    }
+
+    if options.stat {
+        writeln!(out, "{}", summary(&diff))?;
+        return Ok(());
+    }
+
+    if diff.is_empty() {
+        writeln!(
+            out,
+            "{} No differences from {}",
+            "Info:".blue(),
+            options.other.display()
+        )?;
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    writeln!(
+        out,
+        "{} {}",
+        "Lore compared with".bold(),
+        options.other.display().to_string().cyan()
+    )?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+
+    print_section(out, "Added", "+".green(), &diff.added)?;
+    print_section(out, "Removed", "-".red(), &diff.removed)?;
+    print_section(out, "Modified", "~".yellow(), &diff.modified)?;
+
+    writeln!(out)?;
+    writeln!(out, "{}", rule(60).dimmed())?;
+    writeln!(out, "{}", summary(&diff))?;
+
+    Ok(())
+}
+
+/// The project root of the other store. Accepts either the directory holding
+/// `.lore` or the `.lore` directory itself.
+fn other_root(path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if path.join(".lore").is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    match path.parent() {
+        Some(parent) if path.file_name().is_some_and(|name| name == ".lore") && path.is_dir() => {
+            Ok(parent.to_path_buf())
+        }
+        _ => {
+            Err(CommandError::NotFound(format!("No lore store found at {}", path.display())).into())
+        }
+    }
+}
+
+/// One-line summary, e.g. `3 added, 1 removed, 2 modified`
+pub(crate) fn summary(diff: &StoreDiff) -> String {
+    format!(
+        "{} added, {} removed, {} modified",
+        diff.added.len().to_string().green(),
+        diff.removed.len().to_string().red(),
+        diff.modified.len().to_string().yellow()
+    )
+}
+
+pub(crate) fn print_section(
+    out: &mut impl Write,
+    title: &str,
+    marker: ColoredString,
+    metas: &[EntryMeta],
+) -> io::Result<()> {
+    if metas.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{} ({})", title.bold(), metas.len())?;
+    for meta in metas {
+        writeln!(
+            out,
+            "  {} {} {} {}",
+            marker,
+            short_id(&meta.id).dimmed(),
+            meta.target_file.cyan(),
+            meta.intent
+        )?;
+    }
+    Ok(())
+}
@@ -1,145 +1,1319 @@
-use crate::models::ThoughtObject;
-use crate::storage::{find_lore_root, normalize_path, LoreStorage};
+use crate::commands::CommandError;
+use crate::diagnostics::{Diagnostics, WarningCode};
+use crate::filter::{overlap_len, page};
+use crate::git::GitContext;
+use crate::models::{Freshness, RangeLocation, ThoughtObject, MAX_SNAPSHOT_LINES};
+use crate::output::{self, heavy_rule, rule, short_id, Format, Glyph};
+use crate::storage::{hash_bytes, hash_file, supersessions, LoreStorage};
 use colored::Colorize;
+use globset::GlobBuilder;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::path::Path;
 
 pub struct ExplainOptions {
-    pub file: String,
+    /// Files, directories or glob patterns; anything but a single file is
+    /// explained file by file
+    pub files: Vec<String>,
     pub all: bool,
-    pub json: bool,
+    pub format: Format,
     pub limit: Option<usize>,
+    /// Newest entries to skip before `limit` applies
+    pub offset: usize,
+    pub current_only: bool,
+    pub show_diff: bool,
+    pub reverse: bool,
+    pub raw_reasoning: bool,
+    /// Only entries overlapping these lines, plus file-level ones unless `strict`
+    pub line_range: Option<(usize, usize)>,
+    pub strict: bool,
+    /// Only entries recorded at or before this commit, compared against the
+    /// file as of that commit
+    pub at: Option<String>,
+    /// Match the recorded path regardless of case
+    pub ignore_case: bool,
 }
 
-pub fn execute(options: ExplainOptions) -> Result<(), Box<dyn std::error::Error>> {
-    // Find lore root
-    let current_dir = std::env::current_dir()?;
-    let root =
-        find_lore_root(&current_dir).ok_or("Lore not initialized. Run 'lore init' first.")?;
+/// An entry as emitted by `explain --format json` (or yaml/toml), with computed fields
+#[derive(Serialize)]
+struct ExplainedEntry<'a> {
+    #[serde(flatten)]
+    entry: &'a ThoughtObject,
+    stale: bool,
+    /// IDs of the entries that replace this one
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    superseded_by: &'a [String],
+}
+
+/// Which entries replace which, keyed by the superseded entry's ID
+type Supersessions = HashMap<String, Vec<String>>;
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: ExplainOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = storage.root();
+    let file = match options.files.as_slice() {
+        [file] if !is_glob(file) => file,
+        _ => return explain_many(out, storage, root, &options),
+    };
+    let normalized = storage.relative_path(file)?;
+
+    if file.ends_with('/') || root.join(&normalized).is_dir() {
+        return explain_many(out, storage, root, &options);
+    }
+
+    // The path as recorded, which may differ in case from the one typed
+    let normalized = storage.resolve_file_path(&normalized, options.ignore_case)?;
+    let mut entries = storage.get_entries_for_file(&normalized)?;
+
+    // With --at, only what was known at that commit
+    let at = match &options.at {
+        Some(rev) => {
+            let git = GitContext::open(root)?;
+            let commit = git
+                .resolve(rev)
+                .map_err(|e| CommandError::Git(format!("Cannot resolve '{}': {}", rev, e)))?;
+            retain_known_at(&mut entries, &git, &commit);
+            Some((git, commit))
+        }
+        None => None,
+    };
+
+    // Read the file once; every entry for it is compared against this
+    let current_file = match &at {
+        Some((git, commit)) => git.file_at_commit(commit, &normalized)?,
+        None => std::fs::read(root.join(&normalized)).ok(),
+    };
+    let current_hash = current_file.as_deref().map(hash_bytes);
+
+    let mut diagnostics = Diagnostics::new(options.format.is_document());
+
+    // Point at the new path if the entries followed a rename
+    let moved_to = match (entries.is_empty(), &at) {
+        (true, None) => storage.moved_to(&normalized)?,
+        _ => None,
+    };
+
+    if entries.is_empty() && options.format.is_structured() {
+        if let Some(new_path) = &moved_to {
+            diagnostics.warn(
+                WarningCode::FileMoved,
+                format!("This file's lore moved to {}", new_path),
+                Some(&normalized),
+            );
+        }
+        return print_structured(
+            out,
+            options.format,
+            diagnostics,
+            &[],
+            None,
+            &Supersessions::new(),
+        );
+    }
 
-    let storage = LoreStorage::new(root);
-    let normalized = normalize_path(&options.file);
+    if let (true, Some((_, commit))) = (entries.is_empty(), &at) {
+        writeln!(
+            out,
+            "{} No reasoning found for {} as of {}",
+            "Info:".blue(),
+            normalized.cyan(),
+            commit[..8].cyan()
+        )?;
+        return Ok(());
+    }
 
-    let entries = storage.get_entries_for_file(&normalized)?;
+    if let Some(new_path) = moved_to {
+        writeln!(
+            out,
+            "{} This file's lore moved to {}",
+            "Info:".blue(),
+            new_path.cyan()
+        )?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "See it with: {}",
+            format!("lore explain {}", new_path).cyan()
+        )?;
+        return Ok(());
+    }
 
     if entries.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{} No reasoning found for {}",
             "Info:".blue(),
             normalized.cyan()
-        );
-        println!();
-        println!(
+        )?;
+        writeln!(out)?;
+        writeln!(
+            out,
             "Record reasoning with: {}",
-            format!("lore record --file {} -m \"your message\"", options.file).cyan()
-        );
+            format!("lore record --file {} -m \"your message\"", file).cyan()
+        )?;
         return Ok(());
     }
 
-    // Limit entries if requested
-    let entries: Vec<_> = if let Some(limit) = options.limit {
-        entries.into_iter().take(limit).collect()
-    } else if !options.all {
-        // Default: show only the most recent entry
-        entries.into_iter().take(1).collect()
-    } else {
-        entries
+    // As of --at, only entries that existed then can supersede others
+    let superseded_by = match at {
+        Some(_) => supersessions(&entries),
+        None => storage.superseded_by()?,
+    };
+
+    // Entries replaced by a newer one are history; only --all shows them
+    if !options.all {
+        entries.retain(|e| !superseded_by.contains_key(&e.id));
+        if entries.is_empty() && options.format.is_structured() {
+            return print_structured(out, options.format, diagnostics, &[], None, &superseded_by);
+        }
+        if entries.is_empty() {
+            writeln!(
+                out,
+                "{} All reasoning for {} has been superseded. Use --all to see it.",
+                "Info:".blue(),
+                normalized.cyan()
+            )?;
+            return Ok(());
+        }
+    }
+
+    if options.current_only {
+        entries.retain(|e| e.freshness(current_hash.as_deref()) == Freshness::Current);
+        if entries.is_empty() && options.format.is_structured() {
+            diagnostics.warn(
+                WarningCode::Stale,
+                format!("All reasoning for {} is stale", normalized),
+                Some(&normalized),
+            );
+            return print_structured(out, options.format, diagnostics, &[], None, &superseded_by);
+        }
+        if entries.is_empty() {
+            writeln!(
+                out,
+                "{} All reasoning for {} is stale (the file has changed since it was recorded)",
+                "Info:".blue(),
+                normalized.cyan()
+            )?;
+            return Ok(());
+        }
+    }
+
+    let entries = match options.line_range {
+        Some(range) => {
+            let mut entries = order_by_overlap(entries, range, options.strict);
+            page(&mut entries, options.offset, options.limit);
+            if entries.is_empty() && options.format.is_structured() {
+                return print_structured(
+                    out,
+                    options.format,
+                    diagnostics,
+                    &[],
+                    None,
+                    &superseded_by,
+                );
+            }
+            if entries.is_empty() {
+                writeln!(
+                    out,
+                    "{} No reasoning found for lines {}-{} of {}",
+                    "Info:".blue(),
+                    range.0,
+                    range.1,
+                    normalized.cyan()
+                )?;
+                return Ok(());
+            }
+            entries
+        }
+        None => select_entries(
+            entries,
+            options.all,
+            options.offset,
+            options.limit,
+            options.reverse,
+        ),
     };
 
-    if options.json {
-        // Output as JSON
-        let json = serde_json::to_string_pretty(&entries)?;
-        println!("{}", json);
+    if options.format.is_structured() {
+        freshness_warnings(
+            &mut diagnostics,
+            &normalized,
+            &entries,
+            current_hash.as_deref(),
+        );
+        print_structured(
+            out,
+            options.format,
+            diagnostics,
+            &entries,
+            current_hash.as_deref(),
+            &superseded_by,
+        )?;
     } else {
         // Pretty print
-        print_entries(&normalized, &entries);
+        let current_content = current_file
+            .as_deref()
+            .and_then(|content| std::str::from_utf8(content).ok());
+        print_entries(
+            out,
+            &normalized,
+            &entries,
+            current_hash.as_deref(),
+            current_content,
+            &PrintStyle {
+                show_diff: options.show_diff,
+                raw_reasoning: options.raw_reasoning,
+                wrap_width: terminal_width(),
+                by_lines: options.line_range.is_some(),
+                superseded_by: &superseded_by,
+            },
+        )?;
+        if entries.len() == 1 && options.line_range.is_none() {
+            writeln!(out, "{}", "Tip: Use --all to see complete history".dimmed())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `path` is a glob pattern rather than a plain path
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '[', '{'])
+}
+
+/// Explain every indexed file matched by the given files, directories and glob
+/// patterns, grouped by file. `--limit` (or the default of the latest entry)
+/// applies to each file separately. Targets matching nothing are warned about.
+fn explain_many(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    root: &Path,
+    options: &ExplainOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if options.line_range.is_some() || options.at.is_some() {
+        return Err(CommandError::InvalidInput(
+            "--lines and --at need a single file, not a directory or pattern".to_string(),
+        )
+        .into());
+    }
+
+    let mut diagnostics = Diagnostics::new(options.format.is_document());
+    let ignore_case = options.ignore_case || storage.is_case_insensitive();
+    let indexed = storage.files_under(".")?;
+
+    let mut files = BTreeSet::new();
+    let mut unmatched = 0;
+    for target in &options.files {
+        let matched = matching_files(storage, root, target, &indexed, ignore_case)?;
+        if matched.is_empty() {
+            unmatched += 1;
+            diagnostics.warn(
+                WarningCode::NoMatch,
+                format!("No reasoning found for {}", target),
+                Some(target),
+            );
+        }
+        files.extend(matched);
+    }
+
+    let superseded_by = storage.superseded_by()?;
+
+    // (file, entries to show, current hash of the file)
+    let mut groups = Vec::new();
+    for file in files {
+        let mut entries = storage.get_entries_for_file(&file)?;
+        if !options.all {
+            entries.retain(|e| !superseded_by.contains_key(&e.id));
+        }
+
+        let current_hash = hash_file(&root.join(&file)).ok();
+        if options.current_only {
+            entries.retain(|e| e.freshness(current_hash.as_deref()) == Freshness::Current);
+        }
+
+        let entries = select_entries(
+            entries,
+            options.all,
+            options.offset,
+            options.limit,
+            options.reverse,
+        );
+        if !entries.is_empty() {
+            groups.push((file, entries, current_hash));
+        }
+    }
+
+    if options.format.is_structured() {
+        if options.format == Format::Ndjson {
+            // File paths are in the entries themselves, so there's no need to group them
+            let entries: Vec<ExplainedEntry> = groups
+                .iter()
+                .flat_map(|(file, entries, current_hash)| {
+                    freshness_warnings(&mut diagnostics, file, entries, current_hash.as_deref());
+                    explained(entries, current_hash.as_deref(), &superseded_by)
+                })
+                .collect();
+            return output::print(out, &entries, options.format);
+        }
+
+        let mut by_file = BTreeMap::new();
+        for (file, entries, current_hash) in &groups {
+            freshness_warnings(&mut diagnostics, file, entries, current_hash.as_deref());
+            by_file.insert(
+                file,
+                explained(entries, current_hash.as_deref(), &superseded_by),
+            );
+        }
+        output::print(out, &diagnostics.into_report(by_file), options.format)?;
+        return Ok(());
+    }
+
+    let label = match options.files.as_slice() {
+        [target] if !is_glob(target) => match storage.relative_path(target)?.as_str() {
+            "" | "." => "./".to_string(),
+            dir => format!("{}/", dir),
+        },
+        targets => targets.join(" "),
+    };
+    if groups.is_empty() {
+        // Already warned about when no target matched anything
+        if unmatched < options.files.len() {
+            writeln!(
+                out,
+                "{} No reasoning found for {}",
+                "Info:".blue(),
+                label.cyan()
+            )?;
+        }
+        return Ok(());
+    }
+
+    let entry_count: usize = groups.iter().map(|(_, entries, _)| entries.len()).sum();
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    writeln!(
+        out,
+        "{} {} {}",
+        "Lore for:".bold(),
+        label.cyan().bold(),
+        format!(
+            "({} {}, {} {})",
+            groups.len(),
+            if groups.len() == 1 { "file" } else { "files" },
+            entry_count,
+            if entry_count == 1 { "entry" } else { "entries" }
+        )
+        .dimmed()
+    )?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+
+    for (file, entries, current_hash) in &groups {
+        let current_content = std::fs::read_to_string(root.join(file)).ok();
+        print_entries(
+            out,
+            file,
+            entries,
+            current_hash.as_deref(),
+            current_content.as_deref(),
+            &PrintStyle {
+                show_diff: options.show_diff,
+                raw_reasoning: options.raw_reasoning,
+                wrap_width: terminal_width(),
+                by_lines: false,
+                superseded_by: &superseded_by,
+            },
+        )?;
     }
 
     Ok(())
 }
 
-fn print_entries(file_path: &str, entries: &[ThoughtObject]) {
-    println!();
-    println!("{}", "═".repeat(60).dimmed());
-    println!("{} {}", "Lore for:".bold(), file_path.cyan().bold());
-    println!("{}", "═".repeat(60).dimmed());
+/// Indexed files `target` refers to: those matching a glob pattern, those under
+/// a directory, or the file itself if it has reasoning
+fn matching_files(
+    storage: &LoreStorage,
+    root: &Path,
+    target: &str,
+    indexed: &[String],
+    ignore_case: bool,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let normalized = storage.relative_path(target)?;
+
+    if is_glob(target) {
+        // `*` stays within a directory; `**` crosses them
+        let matcher = GlobBuilder::new(&normalized)
+            .literal_separator(true)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| {
+                CommandError::InvalidInput(format!("Invalid pattern '{}': {}", target, e))
+            })?
+            .compile_matcher();
+        return Ok(indexed
+            .iter()
+            .filter(|file| matcher.is_match(file))
+            .cloned()
+            .collect());
+    }
+
+    if target.ends_with('/') || root.join(&normalized).is_dir() {
+        return Ok(storage.files_under(&normalized)?);
+    }
+
+    let file = storage.resolve_file_path(&normalized, ignore_case)?;
+    Ok(indexed.iter().filter(|f| **f == file).cloned().collect())
+}
+
+/// Print entries and warnings in the `--json` envelope, serialized as `format`
+/// (or one entry per line for NDJSON)
+fn print_structured(
+    out: &mut impl Write,
+    format: Format,
+    diagnostics: Diagnostics,
+    entries: &[ThoughtObject],
+    current_hash: Option<&str>,
+    superseded_by: &Supersessions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    output::print_report(
+        out,
+        diagnostics,
+        explained(entries, current_hash, superseded_by),
+        format,
+    )
+}
+
+/// Entries with their computed fields for structured output
+fn explained<'a>(
+    entries: &'a [ThoughtObject],
+    current_hash: Option<&str>,
+    superseded_by: &'a Supersessions,
+) -> Vec<ExplainedEntry<'a>> {
+    entries
+        .iter()
+        .map(|entry| ExplainedEntry {
+            entry,
+            stale: entry.freshness(current_hash).is_stale(),
+            superseded_by: superseded_by.get(&entry.id).map_or(&[], Vec::as_slice),
+        })
+        .collect()
+}
+
+/// Warn once per file when it is missing or some shown entries are stale
+fn freshness_warnings(
+    diagnostics: &mut Diagnostics,
+    file: &str,
+    entries: &[ThoughtObject],
+    current_hash: Option<&str>,
+) {
+    if current_hash.is_none() {
+        diagnostics.warn(
+            WarningCode::FileMissing,
+            format!("{} no longer exists", file),
+            Some(file),
+        );
+        return;
+    }
+
+    let stale = entries
+        .iter()
+        .filter(|e| e.freshness(current_hash).is_stale())
+        .count();
+    if stale > 0 {
+        diagnostics.warn(
+            WarningCode::Stale,
+            format!(
+                "{} of {} entries were recorded against an older version of {}",
+                stale,
+                entries.len(),
+                file
+            ),
+            Some(file),
+        );
+    }
+}
+
+/// How `print_entries` renders each entry
+struct PrintStyle<'a> {
+    show_diff: bool,
+    raw_reasoning: bool,
+    /// Terminal width to wrap the reasoning trace to, or `None` to print it
+    /// unwrapped
+    wrap_width: Option<usize>,
+    /// Entries are ordered by overlap with `--lines`
+    by_lines: bool,
+    superseded_by: &'a Supersessions,
+}
+
+fn print_entries(
+    out: &mut impl Write,
+    file_path: &str,
+    entries: &[ThoughtObject],
+    current_hash: Option<&str>,
+    current_content: Option<&str>,
+    style: &PrintStyle,
+) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    writeln!(out, "{} {}", "Lore for:".bold(), file_path.cyan().bold())?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
 
     for (i, entry) in entries.iter().enumerate() {
-        if i > 0 {
-            println!("{}", "─".repeat(60).dimmed());
+        // With --lines, file-level entries follow the ranged ones under their own divider
+        let first_file_level = style.by_lines
+            && entry.line_range.is_none()
+            && (i == 0 || entries[i - 1].line_range.is_some());
+        if first_file_level {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}",
+                format!("{} file-level reasoning {}", rule(3), rule(35)).dimmed()
+            )?;
+        } else if i > 0 {
+            writeln!(out, "{}", rule(60).dimmed())?;
         }
 
         // Header
-        println!();
-        println!(
+        writeln!(out)?;
+        writeln!(
+            out,
             "{} {} {} {}",
             "Agent:".bold(),
             entry.agent_id.yellow(),
-            "│".dimmed(),
+            Glyph::Bar.as_str().dimmed(),
             entry
                 .timestamp
                 .format("%Y-%m-%d %H:%M:%S UTC")
                 .to_string()
                 .dimmed()
-        );
+        )?;
+        if let Some(updated_at) = entry.updated_at {
+            writeln!(
+                out,
+                "{}",
+                format!("amended {}", updated_at.format("%Y-%m-%d %H:%M:%S UTC")).dimmed()
+            )?;
+        }
 
         if let Some(commit) = &entry.commit_hash {
-            println!(
-                "{} {}",
-                "Commit:".bold(),
-                commit[..8.min(commit.len())].cyan()
-            );
+            match &entry.commit_author {
+                Some(author) => writeln!(
+                    out,
+                    "{} {} {}",
+                    "Commit:".bold(),
+                    commit[..8.min(commit.len())].cyan(),
+                    format!("by {}", author).dimmed()
+                )?,
+                None => writeln!(
+                    out,
+                    "{} {}",
+                    "Commit:".bold(),
+                    commit[..8.min(commit.len())].cyan()
+                )?,
+            }
+        }
+
+        if let Some(project) = &entry.project {
+            writeln!(out, "{} {}", "Project:".bold(), project.cyan())?;
+        }
+
+        if !entry.metadata.is_empty() {
+            writeln!(out, "{}", "Metadata:".bold())?;
+            for (key, value) in &entry.metadata {
+                writeln!(out, "  {} {}", format!("{}:", key).dimmed(), value)?;
+            }
+        }
+
+        if let Some(id) = &entry.supersedes {
+            writeln!(out, "{}", format!("supersedes {}", short_id(id)).dimmed())?;
+        }
+
+        if let Some(newer) = style.superseded_by.get(&entry.id) {
+            let newer: Vec<&str> = newer.iter().map(|id| short_id(id)).collect();
+            writeln!(
+                out,
+                "{}",
+                format!("(superseded by {})", newer.join(", ")).yellow()
+            )?;
+        }
+
+        if !entry.related_entries.is_empty() {
+            let related: Vec<&str> = entry
+                .related_entries
+                .iter()
+                .map(|id| short_id(id))
+                .collect();
+            writeln!(
+                out,
+                "{}",
+                format!("related to {}", related.join(", ")).dimmed()
+            )?;
+        }
+
+        if !entry.previous_paths.is_empty() {
+            writeln!(
+                out,
+                "{}",
+                format!("moved from {}", entry.previous_paths.join(", ")).dimmed()
+            )?;
+        }
+
+        if let (Some(anchor), Some(content)) = (&entry.anchor, current_content) {
+            match entry.anchor_line(content) {
+                Some(line) => writeln!(
+                    out,
+                    "{} {}, currently at line {}",
+                    "Anchor:".bold(),
+                    format!("`{}`", anchor).cyan(),
+                    line
+                )?,
+                None => writeln!(
+                    out,
+                    "{} {} {}",
+                    "Anchor:".bold(),
+                    format!("`{}`", anchor).cyan(),
+                    "(anchor missing)".red()
+                )?,
+            }
         }
 
         if let Some((start, end)) = entry.line_range {
-            println!("{} Lines {}-{}", "Range:".bold(), start, end);
+            // The anchor text wins over the boundary lines, which edits inside
+            // the range can change
+            let location = match current_content {
+                Some(content) => match (entry.anchored_range(content), &entry.line_anchor) {
+                    (Some((new_start, _)), _) if new_start == start => RangeLocation::Unchanged,
+                    (Some((new_start, new_end)), _) => RangeLocation::Moved(new_start, new_end),
+                    (None, Some(anchor)) => anchor.resolve(content, (start, end)),
+                    (None, None) => RangeLocation::Unchanged,
+                },
+                None => RangeLocation::Unchanged,
+            };
+            match location {
+                RangeLocation::Unchanged => {
+                    writeln!(out, "{} Lines {}-{}", "Range:".bold(), start, end)?
+                }
+                RangeLocation::Moved(new_start, new_end) => writeln!(
+                    out,
+                    "{} Lines {}-{} {}",
+                    "Range:".bold(),
+                    new_start,
+                    new_end,
+                    format!("(range moved, recorded as {}-{})", start, end).yellow()
+                )?,
+                RangeLocation::Lost => writeln!(
+                    out,
+                    "{} Lines {}-{} {}",
+                    "Range:".bold(),
+                    start,
+                    end,
+                    "(range lost)".red()
+                )?,
+            }
+
+            if let Some(snapshot) = &entry.code_snapshot {
+                let width = end.to_string().len();
+                for (offset, line) in snapshot.lines().enumerate() {
+                    if offset >= MAX_SNAPSHOT_LINES {
+                        // The "... (N more lines)" marker
+                        writeln!(out, "    {}", line.dimmed())?;
+                    } else {
+                        let number = format!("{:>width$}", start + offset);
+                        writeln!(
+                            out,
+                            "    {} {} {}",
+                            number.dimmed(),
+                            Glyph::Bar.as_str().dimmed(),
+                            line
+                        )?;
+                    }
+                }
+            }
+        }
+
+        let freshness = entry.freshness(current_hash);
+        let label = freshness_label(freshness, entry);
+        let status = match freshness {
+            Freshness::Current => label.green(),
+            Freshness::Stale => label.yellow(),
+            Freshness::FileMissing => label.red(),
+        };
+        writeln!(out, "{} {}", "Status:".bold(), status)?;
+
+        if let Some(confidence) = entry.confidence {
+            writeln!(
+                out,
+                "{} {}",
+                "Confidence:".bold(),
+                format_confidence(confidence)
+            )?;
         }
 
         // Intent
-        println!();
-        println!("{}", "Intent:".bold().underline());
-        println!("{}", entry.intent);
+        writeln!(out)?;
+        writeln!(out, "{}", "Intent:".bold().underline())?;
+        writeln!(out, "{}", entry.intent)?;
 
         // Reasoning trace
-        println!();
-        println!("{}", "Reasoning:".bold().underline());
+        writeln!(out)?;
+        writeln!(out, "{}", "Reasoning:".bold().underline())?;
 
-        // Format reasoning trace with word wrap
-        let lines: Vec<&str> = entry.reasoning_trace.lines().collect();
-        for line in lines {
-            println!("  {}", line);
+        let reasoning = format_reasoning(
+            &entry.reasoning_trace,
+            style.raw_reasoning,
+            style.wrap_width,
+        );
+        write!(out, "{}", reasoning)?;
+        if !reasoning.is_empty() && !reasoning.ends_with('\n') {
+            writeln!(out)?;
         }
 
         // Rejected alternatives
         if !entry.rejected_alternatives.is_empty() {
-            println!();
-            println!("{}", "Rejected Alternatives:".bold().underline());
+            writeln!(out)?;
+            writeln!(out, "{}", "Rejected Alternatives:".bold().underline())?;
             for alt in &entry.rejected_alternatives {
-                print!("  {} {}", "✗".red(), alt.name);
+                write!(out, "  {} {}", Glyph::Cross.as_str().red(), alt.name)?;
                 if let Some(reason) = &alt.reason {
-                    print!(" - {}", reason.dimmed());
+                    write!(out, " - {}", reason.dimmed())?;
                 }
-                println!();
+                writeln!(out)?;
+            }
+        }
+
+        // References
+        if !entry.references.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "{}", "References:".bold().underline())?;
+            for reference in &entry.references {
+                writeln!(
+                    out,
+                    "  {} {}",
+                    Glyph::Arrow.as_str().dimmed(),
+                    format_reference(reference)
+                )?;
+            }
+        }
+
+        // Attachments
+        if !entry.attachments.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "{}", "Attachments:".bold().underline())?;
+            for attachment in &entry.attachments {
+                writeln!(
+                    out,
+                    "  {} {} {}",
+                    Glyph::Arrow.as_str().dimmed(),
+                    attachment.filename.cyan(),
+                    format!("({})", format_size(attachment.size)).dimmed()
+                )?;
             }
         }
 
         // Tags
         if !entry.tags.is_empty() {
-            println!();
-            print!("{} ", "Tags:".bold());
+            writeln!(out)?;
+            write!(out, "{} ", "Tags:".bold())?;
             for (i, tag) in entry.tags.iter().enumerate() {
                 if i > 0 {
-                    print!(", ");
+                    write!(out, ", ")?;
+                }
+                write!(out, "{}", format!("#{}", tag).magenta())?;
+            }
+            writeln!(out)?;
+        }
+
+        // Diff
+        if style.show_diff {
+            if let Some(diff) = &entry.diff {
+                writeln!(out)?;
+                writeln!(out, "{}", "Diff:".bold().underline())?;
+                for line in diff.lines() {
+                    if line.starts_with('+') {
+                        writeln!(out, "  {}", line.green())?;
+                    } else if line.starts_with('-') {
+                        writeln!(out, "  {}", line.red())?;
+                    } else {
+                        writeln!(out, "  {}", line.dimmed())?;
+                    }
                 }
-                print!("{}", format!("#{}", tag).magenta());
             }
-            println!();
         }
 
-        println!();
+        writeln!(out)?;
+    }
+
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    Ok(())
+}
+
+/// The reasoning trace as printed: indented (and wrapped to `width`, if any)
+/// for the pretty view, or exactly as stored when `raw` so code blocks can be
+/// copied intact
+fn format_reasoning(trace: &str, raw: bool, width: Option<usize>) -> String {
+    if raw {
+        return trace.to_string();
+    }
+    let width = width.map(|w| w.saturating_sub(REASONING_INDENT.len()));
+    let mut formatted = String::new();
+    for line in trace.lines() {
+        let wrapped = match width {
+            Some(width) => wrap_line(line, width),
+            None => vec![line.to_string()],
+        };
+        for part in wrapped {
+            formatted.push_str(REASONING_INDENT);
+            formatted.push_str(&part);
+            formatted.push('\n');
+        }
+    }
+    formatted
+}
+
+const REASONING_INDENT: &str = "  ";
+
+/// Width to wrap the reasoning trace to: `$COLUMNS` if set, else the
+/// terminal's width, else 80. `None` when stdout isn't a terminal, so piped
+/// output stays verbatim.
+fn terminal_width() -> Option<usize> {
+    use std::io::IsTerminal;
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let columns = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.trim().parse().ok())
+        .filter(|&c: &usize| c > 0);
+    let size = || {
+        ratatui::crossterm::terminal::size()
+            .ok()
+            .map(|(cols, _)| usize::from(cols))
+            .filter(|&c| c > 0)
+    };
+    Some(columns.or_else(size).unwrap_or(80))
+}
+
+/// Split `line` into pieces of at most `width` characters, breaking at spaces
+/// where possible and splitting words longer than `width`. Continuation lines
+/// keep the line's leading indentation.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let line = line.trim_end();
+    if line.chars().count() <= width {
+        return vec![line.to_string()];
+    }
+    let body = line.trim_start();
+    let indent = &line[..line.len() - body.len()];
+    // Deeply indented lines (e.g. code) are left alone rather than wrapped
+    // into a sliver
+    let indent_len = indent.chars().count();
+    if indent_len * 2 >= width {
+        return vec![line.to_string()];
+    }
+    let room = width - indent_len;
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+    for word in body.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        if current_len > 0 && current_len + 1 + word.len() <= room {
+            current.push(' ');
+            current.push_str(&word.iter().collect::<String>());
+            current_len += 1 + word.len();
+            continue;
+        }
+        if current_len > 0 {
+            lines.push(format!("{}{}", indent, current));
+            current.clear();
+        }
+        while word.len() > room {
+            let rest = word.split_off(room);
+            lines.push(format!("{}{}", indent, word.iter().collect::<String>()));
+            word = rest;
+        }
+        current = word.iter().collect();
+        current_len = word.len();
+    }
+    if current_len > 0 {
+        lines.push(format!("{}{}", indent, current));
+    }
+    lines
+}
+
+/// A reference as shown: links underlined like a hyperlink, issue keys as-is
+fn format_reference(reference: &str) -> String {
+    if reference.starts_with("http://") || reference.starts_with("https://") {
+        reference.blue().underline().to_string()
+    } else {
+        reference.cyan().to_string()
+    }
+}
+
+/// How an entry's status line describes whether it still matches the file
+fn freshness_label(freshness: Freshness, entry: &ThoughtObject) -> String {
+    match freshness {
+        Freshness::Current => "current".to_string(),
+        Freshness::Stale => format!(
+            "file changed since this was recorded ({})",
+            entry.timestamp.format("%Y-%m-%d")
+        ),
+        Freshness::FileMissing => "file deleted".to_string(),
+    }
+}
+
+/// A byte count in the largest unit that keeps it at least 1, e.g. "2.5 KB"
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// A confidence as a percentage: red below 50%, yellow below 80%, green above
+fn format_confidence(confidence: f32) -> String {
+    let percent = format!("{:.0}%", confidence * 100.0);
+    if confidence < 0.5 {
+        percent.red().to_string()
+    } else if confidence < 0.8 {
+        percent.yellow().to_string()
+    } else {
+        percent.green().to_string()
+    }
+}
+
+/// Order newest-first `entries` for `--lines`: those overlapping `range`, most
+/// overlapping lines first (newest first among ties), then file-level entries
+/// unless `strict`. Entries for other lines are dropped.
+fn order_by_overlap(
+    entries: Vec<ThoughtObject>,
+    range: (usize, usize),
+    strict: bool,
+) -> Vec<ThoughtObject> {
+    let (mut ranged, file_level): (Vec<_>, Vec<_>) =
+        entries.into_iter().partition(|e| e.line_range.is_some());
+
+    ranged.retain(|e| e.line_range.is_some_and(|r| overlap_len(r, range) > 0));
+    // Stable, so ties stay newest first
+    ranged.sort_by_key(|e| std::cmp::Reverse(e.line_range.map_or(0, |r| overlap_len(r, range))));
+
+    if !strict {
+        ranged.extend(file_level);
     }
+    ranged
+}
+
+/// Keep entries recorded against `commit` or one of its ancestors; entries
+/// without a commit can't be placed in history and are dropped
+fn retain_known_at(entries: &mut Vec<ThoughtObject>, git: &GitContext, commit: &str) {
+    entries.retain(|e| {
+        e.commit_hash
+            .as_deref()
+            .is_some_and(|hash| git.is_ancestor_or_equal(hash, commit))
+    });
+}
+
+/// Pick which of the newest-first `entries` to show. `offset` skips the newest
+/// ones and the limit keeps the newest of the rest; `reverse` then flips them
+/// to oldest-first for display.
+fn select_entries(
+    mut entries: Vec<ThoughtObject>,
+    all: bool,
+    offset: usize,
+    limit: Option<usize>,
+    reverse: bool,
+) -> Vec<ThoughtObject> {
+    // Default: show only the most recent entry
+    let limit = limit.or(if all { None } else { Some(1) });
+    page(&mut entries, offset, limit);
+
+    if reverse {
+        entries.reverse();
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LoreStorage;
+    use chrono::{TimeZone, Utc};
+    use tempfile::TempDir;
+
+    /// Three entries for one file, returned newest-first like `explain` sees them
+    fn entries_for_file() -> (TempDir, Vec<ThoughtObject>) {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+
+        for day in 1..=3 {
+            let mut entry = ThoughtObject::new(
+                "a.rs".to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                format!("Day {}", day),
+                String::new(),
+            );
+            entry.timestamp = Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap();
+            storage.save_entry(&entry).unwrap();
+        }
+
+        let entries = storage.get_entries_for_file("a.rs").unwrap();
+        (temp_dir, entries)
+    }
+
+    fn intents(entries: &[ThoughtObject]) -> Vec<&str> {
+        entries.iter().map(|e| e.intent.as_str()).collect()
+    }
+
+    #[test]
+    fn test_matching_files_globs_directories_and_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+        for file in ["src/auth/jwt.rs", "src/auth/oauth/google.rs", "src/main.rs"] {
+            let entry = ThoughtObject::new(
+                file.to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                "Intent".to_string(),
+                String::new(),
+            );
+            storage.save_entry(&entry).unwrap();
+        }
+
+        let root = storage.root();
+        let indexed = storage.files_under(".").unwrap();
+        let matching = |target: &str, ignore_case: bool| {
+            let mut files = matching_files(&storage, root, target, &indexed, ignore_case).unwrap();
+            files.sort();
+            files
+        };
+
+        // `*` doesn't cross directories, `**` does
+        assert_eq!(matching("src/auth/*.rs", false), vec!["src/auth/jwt.rs"]);
+        assert_eq!(
+            matching("src/**/*.rs", false),
+            vec!["src/auth/jwt.rs", "src/auth/oauth/google.rs", "src/main.rs"]
+        );
+        assert_eq!(matching("SRC/*.RS", true), vec!["src/main.rs"]);
+        assert!(matching("SRC/*.RS", false).is_empty());
+
+        assert_eq!(
+            matching("src/auth/", false),
+            vec!["src/auth/jwt.rs", "src/auth/oauth/google.rs"]
+        );
+        assert_eq!(matching("./src/main.rs", false), vec!["src/main.rs"]);
+        assert!(matching("src/missing.rs", false).is_empty());
+
+        assert!(matching_files(&storage, root, "src/[", &indexed, false).is_err());
+    }
+
+    #[test]
+    fn test_select_entries_reverse_all() {
+        let (_temp_dir, entries) = entries_for_file();
+        let selected = select_entries(entries, true, 0, None, true);
+        assert_eq!(intents(&selected), vec!["Day 1", "Day 2", "Day 3"]);
+    }
+
+    #[test]
+    fn test_select_entries_limit_keeps_newest_before_reversing() {
+        let (_temp_dir, entries) = entries_for_file();
+        let selected = select_entries(entries, false, 0, Some(2), true);
+        assert_eq!(intents(&selected), vec!["Day 2", "Day 3"]);
+    }
+
+    #[test]
+    fn test_select_entries_offset_pages_from_newest() {
+        let (_temp_dir, entries) = entries_for_file();
+        let selected = select_entries(entries.clone(), true, 1, Some(1), false);
+        assert_eq!(intents(&selected), vec!["Day 2"]);
+
+        // Without --limit or --all, still one entry
+        let selected = select_entries(entries, false, 2, None, false);
+        assert_eq!(intents(&selected), vec!["Day 1"]);
+    }
+
+    #[test]
+    fn test_freshness_label() {
+        let mut entry = ranged("Intent", None);
+        entry.timestamp = Utc.with_ymd_and_hms(2024, 2, 14, 10, 0, 0).unwrap();
+        assert_eq!(freshness_label(Freshness::Current, &entry), "current");
+        assert_eq!(
+            freshness_label(Freshness::Stale, &entry),
+            "file changed since this was recorded (2024-02-14)"
+        );
+        assert_eq!(
+            freshness_label(Freshness::FileMissing, &entry),
+            "file deleted"
+        );
+    }
+
+    #[test]
+    fn test_freshness_warnings() {
+        let (_temp_dir, entries) = entries_for_file();
+
+        let mut diagnostics = Diagnostics::new(true);
+        freshness_warnings(&mut diagnostics, "a.rs", &entries, Some("hash"));
+        assert!(diagnostics.warnings().is_empty());
+
+        freshness_warnings(&mut diagnostics, "a.rs", &entries, Some("other"));
+        assert_eq!(diagnostics.warnings()[0].code, WarningCode::Stale);
+
+        freshness_warnings(&mut diagnostics, "a.rs", &entries, None);
+        assert_eq!(diagnostics.warnings()[1].code, WarningCode::FileMissing);
+        assert_eq!(diagnostics.warnings()[1].file.as_deref(), Some("a.rs"));
+    }
+
+    #[test]
+    fn test_format_reasoning_raw_is_unchanged() {
+        let trace = "Tried this:\n\n```rust\nfn main() {\n    run();\n}\n```\n\tand it worked\r\n";
+        assert_eq!(
+            format_reasoning(trace, true, Some(20)).as_bytes(),
+            trace.as_bytes()
+        );
+        assert!(format_reasoning(trace, false, None).starts_with("  Tried this:\n"));
+    }
+
+    #[test]
+    fn test_format_reasoning_wraps_to_width() {
+        let trace = "First paragraph is long enough to wrap\n\nShort\nline";
+        assert_eq!(
+            format_reasoning(trace, false, Some(20)),
+            "  First paragraph is\n  long enough to\n  wrap\n  \n  Short\n  line\n"
+        );
+        assert_eq!(
+            format_reasoning(trace, false, None),
+            "  First paragraph is long enough to wrap\n  \n  Short\n  line\n"
+        );
+    }
+
+    #[test]
+    fn test_wrap_line_splits_long_words_and_keeps_indent() {
+        assert_eq!(wrap_line("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(
+            wrap_line("  - one two three", 10),
+            vec!["  - one", "  two", "  three"]
+        );
+        assert_eq!(wrap_line("fits", 10), vec!["fits"]);
+    }
+
+    fn ranged(intent: &str, line_range: Option<(usize, usize)>) -> ThoughtObject {
+        let entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            intent.to_string(),
+            String::new(),
+        );
+        match line_range {
+            Some((start, end)) => entry.with_line_range(start, end),
+            None => entry,
+        }
+    }
+
+    #[test]
+    fn test_order_by_overlap() {
+        // Newest first, as loaded
+        let entries = vec![
+            ranged("whole file", None),
+            ranged("touches 80", Some((80, 120))),
+            ranged("elsewhere", Some((1, 10))),
+            ranged("inside", Some((50, 70))),
+            ranged("older whole file", None),
+            ranged("older inside", Some((45, 65))),
+        ];
+
+        let ordered = order_by_overlap(entries.clone(), (40, 80), false);
+        assert_eq!(
+            intents(&ordered),
+            vec![
+                "inside",
+                "older inside",
+                "touches 80",
+                "whole file",
+                "older whole file"
+            ]
+        );
+
+        let strict = order_by_overlap(entries, (40, 80), true);
+        assert_eq!(
+            intents(&strict),
+            vec!["inside", "older inside", "touches 80"]
+        );
+    }
+
+    #[test]
+    fn test_select_entries_default_is_newest_only() {
+        let (_temp_dir, entries) = entries_for_file();
+        let selected = select_entries(entries, false, 0, None, false);
+        assert_eq!(intents(&selected), vec!["Day 3"]);
+    }
+
+    #[test]
+    fn test_retain_known_at_keeps_entries_from_ancestors() {
+        let temp_dir = TempDir::new().unwrap();
+        let git_in = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .expect("Failed to run git");
+        };
+        git_in(&["init"]);
+        git_in(&["config", "user.email", "test@test.com"]);
+        git_in(&["config", "user.name", "Test User"]);
+
+        let mut commits = Vec::new();
+        for content in ["one", "two"] {
+            std::fs::write(temp_dir.path().join("a.rs"), content).unwrap();
+            git_in(&["add", "a.rs"]);
+            git_in(&["commit", "-m", content]);
+            commits.push(
+                GitContext::open(temp_dir.path())
+                    .unwrap()
+                    .head_commit()
+                    .unwrap(),
+            );
+        }
+        let git = GitContext::open(temp_dir.path()).unwrap();
+
+        let entry = |intent: &str, commit: Option<&str>| {
+            let entry = ThoughtObject::new(
+                "a.rs".to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                intent.to_string(),
+                String::new(),
+            );
+            match commit {
+                Some(commit) => entry.with_commit(commit.to_string()),
+                None => entry,
+            }
+        };
+        let entries = vec![
+            entry("Second", Some(&commits[1])),
+            entry("First", Some(&commits[0])),
+            entry("No commit", None),
+            entry("Unknown commit", Some(&"f".repeat(40))),
+        ];
 
-    println!("{}", "═".repeat(60).dimmed());
+        let mut at_first = entries.clone();
+        retain_known_at(&mut at_first, &git, &commits[0]);
+        assert_eq!(intents(&at_first), vec!["First"]);
 
-    if entries.len() == 1 {
-        println!("{}", "Tip: Use --all to see complete history".dimmed());
+        let mut at_second = entries;
+        retain_known_at(&mut at_second, &git, &commits[1]);
+        assert_eq!(intents(&at_second), vec!["Second", "First"]);
     }
 }
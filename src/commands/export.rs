@@ -0,0 +1,432 @@
+use crate::filter::retain_in_range;
+use crate::models::ThoughtObject;
+use crate::output::Glyph;
+use crate::storage::LoreStorage;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Output format for `lore export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// Versioned JSON bundle that `lore import` can read back
+    Json,
+    /// Human-readable Markdown document grouped by file
+    Markdown,
+}
+
+pub struct ExportOptions {
+    pub output: Option<PathBuf>,
+    pub format: ExportFormat,
+    pub file_filter: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: ExportOptions,
+    out: &mut impl io::Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = storage.get_all_entries()?;
+
+    // Apply filters
+    if let Some(file_filter) = &options.file_filter {
+        entries.retain(|e| e.target_file.contains(file_filter));
+    }
+    retain_in_range(&mut entries, options.since, None);
+    sort_for_export(&mut entries);
+
+    let count = entries.len();
+
+    let dir_output = options
+        .output
+        .as_deref()
+        .filter(|p| options.format == ExportFormat::Markdown && is_directory_target(p));
+    if let Some(dir) = dir_output {
+        let written = write_markdown_dir(dir, &entries)?;
+        eprintln!(
+            "{} Exported {} entries across {} files to {}",
+            Glyph::Check.as_str().green(),
+            count.to_string().green(),
+            written,
+            dir.display().to_string().cyan()
+        );
+        return Ok(());
+    }
+
+    let content = match options.format {
+        ExportFormat::Json => serde_json::to_string_pretty(&storage.export(entries)?)?,
+        ExportFormat::Markdown => render_markdown(&entries),
+    };
+
+    match &options.output {
+        Some(path) => {
+            std::fs::write(path, content)?;
+            eprintln!(
+                "{} Exported {} entries to {}",
+                Glyph::Check.as_str().green(),
+                count.to_string().green(),
+                path.display().to_string().cyan()
+            );
+        }
+        None => writeln!(out, "{}", content)?,
+    }
+
+    Ok(())
+}
+
+/// Order entries by file, then timestamp, then ID, so exporting the same data
+/// always produces the same output regardless of how the filesystem lists it
+fn sort_for_export(entries: &mut [ThoughtObject]) {
+    entries.sort_by(|a, b| {
+        (&a.target_file, a.timestamp, &a.id).cmp(&(&b.target_file, b.timestamp, &b.id))
+    });
+}
+
+/// An output path is treated as a directory if it already is one or has no extension
+fn is_directory_target(path: &Path) -> bool {
+    path.is_dir() || path.extension().is_none()
+}
+
+/// Group entries by target file, each group newest first
+fn group_by_file(entries: &[ThoughtObject]) -> BTreeMap<&str, Vec<&ThoughtObject>> {
+    let mut by_file: BTreeMap<&str, Vec<&ThoughtObject>> = BTreeMap::new();
+    for entry in entries {
+        by_file.entry(&entry.target_file).or_default().push(entry);
+    }
+    for file_entries in by_file.values_mut() {
+        file_entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.id.cmp(&b.id)));
+    }
+    by_file
+}
+
+/// Render entries as a Markdown document: one heading per file, entries newest first
+fn render_markdown(entries: &[ThoughtObject]) -> String {
+    let mut out = String::from("# Lore Reasoning\n");
+    for (file, file_entries) in group_by_file(entries) {
+        render_file(&mut out, file, &file_entries);
+    }
+    out
+}
+
+fn render_file(out: &mut String, file: &str, entries: &[&ThoughtObject]) {
+    let _ = write!(out, "\n## {}\n", inline_code(file));
+    for entry in entries {
+        render_entry(out, entry);
+    }
+}
+
+/// Name of the document linking the per-file documents
+const INDEX_DOC: &str = "index.md";
+
+/// Write one Markdown document per tracked file plus an `index.md` linking them.
+/// Returns the number of per-file documents written.
+fn write_markdown_dir(dir: &Path, entries: &[ThoughtObject]) -> std::io::Result<usize> {
+    let by_file = group_by_file(entries);
+    let mut index = String::from("# Lore Reasoning\n\n");
+
+    // A root-level file named `index` would get `index.md` too, so its
+    // document takes the first free `index~N.md` instead
+    let mut taken: HashSet<PathBuf> = by_file.keys().map(|file| doc_path(file)).collect();
+    taken.insert(PathBuf::from(INDEX_DOC));
+
+    for (file, file_entries) in &by_file {
+        let mut doc = doc_path(file);
+        if doc == Path::new(INDEX_DOC) {
+            doc = (1..)
+                .map(|n| PathBuf::from(format!("index~{}.md", n)))
+                .find(|candidate| !taken.contains(candidate))
+                .expect("some index~N.md is free");
+            taken.insert(doc.clone());
+        }
+        let depth = doc.components().count() - 1;
+
+        let mut out = format!("[← Index]({}{})\n", "../".repeat(depth), INDEX_DOC);
+        render_file(&mut out, file, file_entries);
+
+        let path = dir.join(&doc);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, out)?;
+
+        let _ = writeln!(
+            index,
+            "- [{}](<{}>) ({} {})",
+            inline_code(file),
+            doc.to_string_lossy().replace('\\', "/"),
+            file_entries.len(),
+            if file_entries.len() == 1 {
+                "entry"
+            } else {
+                "entries"
+            }
+        );
+    }
+
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(INDEX_DOC), index)?;
+
+    Ok(by_file.len())
+}
+
+/// Relative path of the Markdown document for `file`, mirroring the source tree.
+/// Components that could escape the output directory are dropped.
+fn doc_path(file: &str) -> PathBuf {
+    let mut path: PathBuf = Path::new(file)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect();
+    let name = match path.file_name() {
+        Some(name) => format!("{}.md", name.to_string_lossy()),
+        None => "_.md".to_string(),
+    };
+    path.set_file_name(name);
+    path
+}
+
+fn render_entry(out: &mut String, entry: &ThoughtObject) {
+    let _ = write!(out, "\n### {}\n\n", single_line(&entry.intent));
+
+    let mut meta = vec![
+        entry.timestamp.format("%Y-%m-%d %H:%M UTC").to_string(),
+        format!("agent {}", inline_code(&entry.agent_id)),
+    ];
+    if let Some(commit) = &entry.commit_hash {
+        meta.push(format!(
+            "commit {}",
+            inline_code(&commit[..commit.len().min(8)])
+        ));
+    }
+    if let Some((start, end)) = entry.line_range {
+        meta.push(format!("lines {}-{}", start, end));
+    }
+    let _ = writeln!(out, "*{}*", meta.join(" · "));
+
+    if !entry.reasoning_trace.is_empty() {
+        let fence = fence_for(&entry.reasoning_trace);
+        let _ = write!(
+            out,
+            "\n{fence}text\n{}\n{fence}\n",
+            entry.reasoning_trace.trim_end()
+        );
+    }
+
+    if !entry.rejected_alternatives.is_empty() {
+        out.push_str("\n**Rejected alternatives:**\n\n");
+        for alt in &entry.rejected_alternatives {
+            match &alt.reason {
+                Some(reason) => {
+                    let _ = writeln!(out, "- {}: {}", single_line(&alt.name), single_line(reason));
+                }
+                None => {
+                    let _ = writeln!(out, "- {}", single_line(&alt.name));
+                }
+            }
+        }
+    }
+
+    if !entry.tags.is_empty() {
+        let tags: Vec<_> = entry.tags.iter().map(|t| inline_code(t)).collect();
+        let _ = write!(out, "\n**Tags:** {}\n", tags.join(" "));
+    }
+}
+
+/// Collapse a value onto one line so it cannot break headings or list items
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A backtick fence longer than any backtick run inside `text`
+fn fence_for(text: &str) -> String {
+    "`".repeat(longest_backtick_run(text).max(2) + 1)
+}
+
+/// Wrap `text` in inline code, padding the delimiter past any backticks it contains
+fn inline_code(text: &str) -> String {
+    let text = single_line(text);
+    let ticks = "`".repeat(longest_backtick_run(&text) + 1);
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{ticks} {text} {ticks}")
+    } else {
+        format!("{ticks}{text}{ticks}")
+    }
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RejectedAlternative;
+    use chrono::TimeZone;
+
+    fn entry(file: &str, intent: &str, trace: &str, day: u32) -> ThoughtObject {
+        let mut entry = ThoughtObject::new(
+            file.to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            intent.to_string(),
+            trace.to_string(),
+        );
+        entry.timestamp = Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap();
+        entry
+    }
+
+    #[test]
+    fn test_markdown_groups_by_file_newest_first() {
+        let entries = vec![
+            entry("b.rs", "Older", "", 1),
+            entry("a.rs", "Only", "", 1),
+            entry("b.rs", "Newer", "", 2),
+        ];
+
+        let md = render_markdown(&entries);
+
+        let a = md.find("## `a.rs`").unwrap();
+        let b = md.find("## `b.rs`").unwrap();
+        assert!(a < b);
+        assert!(md.find("### Newer").unwrap() < md.find("### Older").unwrap());
+        assert!(md.find("### Newer").unwrap() > b);
+    }
+
+    #[test]
+    fn test_sort_for_export() {
+        let mut entries = vec![
+            entry("b.rs", "B", "", 1),
+            entry("a.rs", "Later", "", 2),
+            entry("a.rs", "Tie 2", "", 1),
+            entry("a.rs", "Tie 1", "", 1),
+        ];
+        entries[2].id = "2".to_string();
+        entries[3].id = "1".to_string();
+
+        sort_for_export(&mut entries);
+
+        let intents: Vec<&str> = entries.iter().map(|e| e.intent.as_str()).collect();
+        assert_eq!(intents, vec!["Tie 1", "Tie 2", "Later", "B"]);
+    }
+
+    #[test]
+    fn test_export_is_reproducible() {
+        let mut entries: Vec<ThoughtObject> = (1..=6)
+            .map(|day| {
+                entry(
+                    ["a.rs", "b.rs"][day as usize % 2],
+                    "Intent",
+                    "Trace",
+                    day % 3 + 1,
+                )
+            })
+            .collect();
+
+        sort_for_export(&mut entries);
+        let first = render_markdown(&entries);
+
+        // Same data, listed in a different order
+        entries.reverse();
+        sort_for_export(&mut entries);
+        assert_eq!(render_markdown(&entries).as_bytes(), first.as_bytes());
+    }
+
+    #[test]
+    fn test_markdown_renders_rejected_and_tags() {
+        let e = entry("a.rs", "Intent", "Because", 1)
+            .with_rejected(vec![RejectedAlternative {
+                name: "Mutex".to_string(),
+                reason: Some("contention".to_string()),
+            }])
+            .with_tags(vec!["perf".to_string()]);
+
+        let md = render_markdown(&[e]);
+
+        assert!(md.contains("- Mutex: contention"));
+        assert!(md.contains("**Tags:** `perf`"));
+        assert!(md.contains("```text\nBecause\n```"));
+    }
+
+    #[test]
+    fn test_markdown_fences_embedded_code_blocks() {
+        let trace = "See:\n```rust\nfn main() {}\n```\n# not a heading";
+        let md = render_markdown(&[entry("a.rs", "Intent", trace, 1)]);
+
+        assert!(md.contains("````text\nSee:"));
+        assert!(md.contains("# not a heading\n````"));
+    }
+
+    #[test]
+    fn test_doc_path_mirrors_tree_and_stays_inside() {
+        assert_eq!(doc_path("src/auth.rs"), PathBuf::from("src/auth.rs.md"));
+        assert_eq!(doc_path("../../etc/passwd"), PathBuf::from("etc/passwd.md"));
+        assert_eq!(doc_path("/abs/file.py"), PathBuf::from("abs/file.py.md"));
+    }
+
+    #[test]
+    fn test_markdown_dir_writes_file_docs_and_index() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let out = temp_dir.path().join("docs");
+        let entries = vec![
+            entry("src/a.rs", "First", "", 1),
+            entry("src/a.rs", "Second", "", 2),
+            entry("b.rs", "Other", "", 1),
+        ];
+
+        let written = write_markdown_dir(&out, &entries).unwrap();
+        assert_eq!(written, 2);
+
+        let index = std::fs::read_to_string(out.join("index.md")).unwrap();
+        assert!(index.contains("- [`src/a.rs`](<src/a.rs.md>) (2 entries)"));
+        assert!(index.contains("- [`b.rs`](<b.rs.md>) (1 entry)"));
+
+        let a = std::fs::read_to_string(out.join("src/a.rs.md")).unwrap();
+        assert!(a.starts_with("[← Index](../index.md)"));
+        assert!(a.find("### Second").unwrap() < a.find("### First").unwrap());
+        assert!(!a.contains("Other"));
+    }
+
+    #[test]
+    fn test_markdown_dir_keeps_doc_of_file_named_index() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let out = temp_dir.path().join("docs");
+        let entries = vec![
+            entry("index", "Root index", "", 1),
+            entry("index~1", "Taken name", "", 1),
+            entry("web/index", "Nested index", "", 1),
+        ];
+
+        assert_eq!(write_markdown_dir(&out, &entries).unwrap(), 3);
+
+        let index = std::fs::read_to_string(out.join("index.md")).unwrap();
+        assert!(index.starts_with("# Lore Reasoning"));
+        assert!(index.contains("- [`index`](<index~2.md>) (1 entry)"));
+        assert!(index.contains("- [`index~1`](<index~1.md>) (1 entry)"));
+        assert!(index.contains("- [`web/index`](<web/index.md>) (1 entry)"));
+
+        let doc = std::fs::read_to_string(out.join("index~2.md")).unwrap();
+        assert!(doc.starts_with("[← Index](index.md)"));
+        assert!(doc.contains("### Root index"));
+    }
+
+    #[test]
+    fn test_directory_target_detection() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert!(is_directory_target(temp_dir.path()));
+        assert!(is_directory_target(&temp_dir.path().join("docs")));
+        assert!(!is_directory_target(&temp_dir.path().join("out.md")));
+    }
+
+    #[test]
+    fn test_inline_code_escapes_backticks() {
+        assert_eq!(inline_code("plain"), "`plain`");
+        assert_eq!(inline_code("a`b"), "``a`b``");
+        assert_eq!(inline_code("`x"), "`` `x ``");
+    }
+}
@@ -0,0 +1,199 @@
+use crate::commands::Outcome;
+use crate::output::Glyph;
+use crate::storage::{EntryRef, FsckReport, LoreStorage};
+use colored::Colorize;
+use serde::Serialize;
+use std::io::{self, Write};
+
+pub struct FsckOptions {
+    pub check_targets: bool,
+    pub fix: bool,
+    pub json: bool,
+}
+
+/// Report emitted by `fsck --json`
+#[derive(Serialize)]
+struct FsckOutput<'a> {
+    /// Problems repaired by `--fix`
+    fixed: usize,
+
+    /// Problems still present
+    #[serde(flatten)]
+    report: &'a FsckReport,
+}
+
+/// Check the store's integrity, reporting `ProblemsFound` if anything is
+/// wrong. With `fix`, the index is rebuilt from the entry files first; entry files
+/// themselves are never deleted, so unparseable ones and duplicate IDs stay
+/// for the user to resolve.
+pub fn execute(
+    storage: &LoreStorage,
+    options: FsckOptions,
+    out: &mut impl Write,
+) -> Result<Outcome, Box<dyn std::error::Error>> {
+    let mut report = storage.fsck(options.check_targets)?;
+    let mut fixed = 0;
+
+    if options.fix && !report.is_clean() {
+        let found = report.problem_count();
+        storage.rebuild_index()?;
+        report = storage.fsck(options.check_targets)?;
+        fixed = found.saturating_sub(report.problem_count());
+    }
+
+    if options.json {
+        let output = FsckOutput {
+            fixed,
+            report: &report,
+        };
+        writeln!(out, "{}", serde_json::to_string_pretty(&output)?)?;
+    } else {
+        print_report(out, &report, options.fix, fixed)?;
+    }
+
+    Ok(if report.is_clean() {
+        Outcome::Success
+    } else {
+        Outcome::ProblemsFound
+    })
+}
+
+fn print_report(
+    out: &mut impl Write,
+    report: &FsckReport,
+    fix: bool,
+    fixed: usize,
+) -> io::Result<()> {
+    if fix && fixed > 0 {
+        writeln!(
+            out,
+            "{} Rebuilt the index, fixing {} {}",
+            Glyph::Check.as_str().green(),
+            fixed.to_string().green(),
+            plural(fixed, "problem", "problems")
+        )?;
+    }
+
+    if report.is_clean() {
+        writeln!(out, "{} Store is consistent", Glyph::Check.as_str().green())?;
+        return Ok(());
+    }
+
+    if let Some(reason) = &report.unreadable_index {
+        writeln!(out)?;
+        writeln!(out, "{}", "Unreadable index".bold())?;
+        writeln!(out, "  {}", reason)?;
+    }
+    print_refs(out, "Dangling index references", &report.dangling)?;
+    print_names(out, "Unparseable entry files", &report.unparseable)?;
+    print_refs(out, "Entries missing from the index", &report.unindexed)?;
+    print_names(out, "Duplicate entry IDs", &report.duplicates)?;
+    if let Some(count) = &report.entry_count {
+        writeln!(out)?;
+        writeln!(out, "{}", "Wrong entry count".bold())?;
+        writeln!(
+            out,
+            "  index records {}, found {}",
+            count.recorded, count.actual
+        )?;
+    }
+    print_refs(
+        out,
+        "Entries for files that no longer exist",
+        &report.missing_targets,
+    )?;
+
+    let problems = report.problem_count();
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{} {} {} found",
+        "Warning:".yellow(),
+        problems,
+        plural(problems, "problem", "problems")
+    )?;
+    let fixable = report.unreadable_index.is_some()
+        || !report.dangling.is_empty()
+        || !report.unindexed.is_empty()
+        || report.entry_count.is_some();
+    if !fix && fixable {
+        writeln!(
+            out,
+            "  Run {} to rebuild the index",
+            "lore fsck --fix".cyan()
+        )?;
+    }
+    Ok(())
+}
+
+fn print_refs(out: &mut impl Write, title: &str, refs: &[EntryRef]) -> io::Result<()> {
+    if refs.is_empty() {
+        return Ok(());
+    }
+    writeln!(out)?;
+    writeln!(out, "{} ({})", title.bold(), refs.len())?;
+    for entry in refs {
+        writeln!(out, "  {} {}", entry.id.dimmed(), entry.target_file.cyan())?;
+    }
+    Ok(())
+}
+
+fn print_names(out: &mut impl Write, title: &str, names: &[String]) -> io::Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    writeln!(out)?;
+    writeln!(out, "{} ({})", title.bold(), names.len())?;
+    for name in names {
+        writeln!(out, "  {}", name)?;
+    }
+    Ok(())
+}
+
+fn plural<'a>(n: usize, one: &'a str, many: &'a str) -> &'a str {
+    if n == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_damaged_index_is_reported_and_fixed() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+        let options = |fix| FsckOptions {
+            check_targets: false,
+            fix,
+            json: false,
+        };
+
+        let mut out = Vec::new();
+        assert_eq!(
+            execute(&storage, options(false), &mut out).unwrap(),
+            Outcome::Success
+        );
+        assert!(String::from_utf8_lossy(&out).contains("Store is consistent"));
+
+        std::fs::write(temp_dir.path().join(".lore/index.json"), "{").unwrap();
+        let mut out = Vec::new();
+        assert_eq!(
+            execute(&storage, options(false), &mut out).unwrap(),
+            Outcome::ProblemsFound
+        );
+        assert!(String::from_utf8_lossy(&out).contains("Unreadable index"));
+
+        let mut out = Vec::new();
+        assert_eq!(
+            execute(&storage, options(true), &mut out).unwrap(),
+            Outcome::Success
+        );
+        assert!(String::from_utf8_lossy(&out).contains("Rebuilt the index"));
+    }
+}
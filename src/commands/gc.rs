@@ -0,0 +1,125 @@
+use crate::commands::prune::{plural, print_entries};
+use crate::commands::CommandError;
+use crate::git::GitContext;
+use crate::models::ThoughtObject;
+use crate::output::Glyph;
+use crate::storage::LoreStorage;
+use colored::Colorize;
+use std::collections::HashSet;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+
+pub struct GcOptions {
+    /// Remove the orphaned entries instead of only reporting them
+    pub delete: bool,
+    /// Don't ask before deleting
+    pub yes: bool,
+    pub dry_run: bool,
+}
+
+/// Find entries whose target file is gone, and remove them with `delete`
+pub fn execute(
+    storage: &LoreStorage,
+    options: GcOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = storage.root();
+    let renamed = pending_renames(root);
+    let mut orphans: Vec<ThoughtObject> = storage
+        .get_all_entries()?
+        .into_iter()
+        .filter(|entry| is_orphan(entry, root, &renamed))
+        .collect();
+    orphans.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+
+    if orphans.is_empty() {
+        writeln!(
+            out,
+            "{} No entries point at deleted files",
+            Glyph::Check.as_str().green()
+        )?;
+        return Ok(());
+    }
+
+    print_entries(out, &orphans)?;
+    let files: HashSet<&str> = orphans.iter().map(|e| e.target_file.as_str()).collect();
+    let summary = format!(
+        "{} {} for {} deleted {}",
+        orphans.len(),
+        plural(orphans.len(), "entry", "entries"),
+        files.len(),
+        plural(files.len(), "file", "files")
+    );
+
+    if options.dry_run || !options.delete {
+        writeln!(out, "{} {}", "Orphaned:".yellow(), summary)?;
+        writeln!(
+            out,
+            "  Kept. Run {} to remove them",
+            "lore gc --delete".cyan()
+        )?;
+        return Ok(());
+    }
+
+    if !options.yes {
+        if !io::stdin().is_terminal() {
+            return Err(CommandError::InvalidInput(
+                "gc --delete asks for confirmation; pass --yes when not at a terminal".to_string(),
+            )
+            .into());
+        }
+        write!(out, "Delete {}? [y/N] ", summary)?;
+        out.flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            writeln!(out, "{} Nothing deleted", "Info:".blue())?;
+            return Ok(());
+        }
+    }
+
+    storage.prune(&orphans, None)?;
+    writeln!(out, "{} Removed {}", Glyph::Check.as_str().green(), summary)?;
+    Ok(())
+}
+
+/// Old paths of files git sees as renamed but whose entries haven't moved yet
+/// (the next `record` or `lore mv` moves them)
+fn pending_renames(root: &Path) -> HashSet<String> {
+    GitContext::open(root)
+        .and_then(|git| git.changed_files())
+        .map(|changes| changes.into_iter().filter_map(|c| c.old_path).collect())
+        .unwrap_or_default()
+}
+
+/// Whether `entry`'s target file no longer exists and wasn't renamed
+fn is_orphan(entry: &ThoughtObject, root: &Path, renamed: &HashSet<String>) -> bool {
+    !root.join(&entry.target_file).exists() && !renamed.contains(&entry.target_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(file: &str) -> ThoughtObject {
+        ThoughtObject::new(
+            file.to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_is_orphan_skips_existing_and_renamed_files() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("kept.rs"), "").unwrap();
+        let renamed = HashSet::from(["moved.rs".to_string()]);
+
+        assert!(is_orphan(&entry("gone.rs"), dir.path(), &renamed));
+        assert!(!is_orphan(&entry("kept.rs"), dir.path(), &renamed));
+        assert!(!is_orphan(&entry("moved.rs"), dir.path(), &renamed));
+    }
+}
@@ -0,0 +1,495 @@
+use crate::commands::record::{self, RecordOptions};
+use crate::commands::CommandError;
+use crate::git::{ChangeType, GitContext};
+use crate::output::Glyph;
+use crate::storage::LoreStorage;
+use colored::Colorize;
+use std::io::Write;
+use std::path::Path;
+
+/// Marks the start of the lines lore adds to a hook script
+const BLOCK_START: &str = "# >>> lore hook >>>";
+
+/// Marks the end of the lines lore adds to a hook script
+const BLOCK_END: &str = "# <<< lore hook <<<";
+
+/// Name of lore's merge driver in git config and `.gitattributes`
+const MERGE_DRIVER: &str = "lore-index";
+
+/// Command git runs to merge the indexes: ours, theirs, then the common ancestor
+const MERGE_DRIVER_COMMAND: &str = "lore merge-index %A %B %O";
+
+/// Git hooks lore knows how to install
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HookKind {
+    /// Record reasoning for each commit, using its message as the intent
+    PostCommit,
+    /// Warn when staged files have no reasoning recorded
+    PreCommit,
+    /// List staged files without reasoning as comments in the commit message
+    PrepareCommitMsg,
+}
+
+impl HookKind {
+    /// File name of the hook inside the hooks directory
+    fn file_name(self) -> &'static str {
+        match self {
+            HookKind::PostCommit => "post-commit",
+            HookKind::PreCommit => "pre-commit",
+            HookKind::PrepareCommitMsg => "prepare-commit-msg",
+        }
+    }
+
+    /// Command the hook runs. Failures never block the git operation.
+    fn command(self) -> &'static str {
+        match self {
+            HookKind::PostCommit => "lore record --from-commit HEAD || true",
+            HookKind::PreCommit => "lore hook run pre-commit || true",
+            HookKind::PrepareCommitMsg => "lore hook run prepare-commit-msg \"$@\" || true",
+        }
+    }
+}
+
+/// Install the lore shim for `kind`. An existing hook that lore didn't write is
+/// left alone unless `force` is set, in which case the shim is appended to it.
+pub fn install(
+    storage: &LoreStorage,
+    kind: HookKind,
+    force: bool,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let git = open_git(storage)?;
+    let hooks_dir = git.hooks_dir()?;
+    let path = hooks_dir.join(kind.file_name());
+
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    if existing.as_deref().is_some_and(has_block) {
+        writeln!(
+            out,
+            "{} lore {} hook is already installed at {}",
+            "Info:".blue(),
+            kind.file_name(),
+            path.display()
+        )?;
+        return Ok(());
+    }
+
+    if existing.is_some() && !force {
+        return Err(CommandError::InvalidInput(format!(
+            "A {} hook already exists at {}. Use --force to add lore to it.",
+            kind.file_name(),
+            path.display()
+        ))
+        .into());
+    }
+
+    std::fs::create_dir_all(&hooks_dir)?;
+    std::fs::write(&path, add_block(existing.as_deref(), kind))?;
+    make_executable(&path)?;
+
+    writeln!(
+        out,
+        "{} Installed {} hook at {}",
+        Glyph::Check.as_str().green(),
+        kind.file_name(),
+        path.display().to_string().cyan()
+    )?;
+
+    Ok(())
+}
+
+/// Remove the lines lore added to the hook, deleting the file if nothing else remains
+pub fn uninstall(
+    storage: &LoreStorage,
+    kind: HookKind,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let git = open_git(storage)?;
+    let path = git.hooks_dir()?.join(kind.file_name());
+
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) if has_block(&content) => content,
+        Ok(_) | Err(_) => {
+            writeln!(
+                out,
+                "{} No lore {} hook installed",
+                "Info:".blue(),
+                kind.file_name()
+            )?;
+            return Ok(());
+        }
+    };
+
+    match remove_block(&content) {
+        Some(remaining) => std::fs::write(&path, remaining)?,
+        None => std::fs::remove_file(&path)?,
+    }
+
+    writeln!(
+        out,
+        "{} Removed lore from {} hook",
+        Glyph::Check.as_str().green(),
+        kind.file_name()
+    )?;
+
+    Ok(())
+}
+
+/// Register `lore merge-index` as the merge driver for `index.json` and
+/// `search_index.json`: define it in the repository's git config and route the
+/// indexes to it from `.lore/.gitattributes`
+pub fn install_merge_driver(
+    storage: &LoreStorage,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let git = open_git(storage)?;
+    git.set_config(
+        &format!("merge.{}.name", MERGE_DRIVER),
+        "lore index merge driver",
+    )?;
+    git.set_config(
+        &format!("merge.{}.driver", MERGE_DRIVER),
+        MERGE_DRIVER_COMMAND,
+    )?;
+
+    let path = attributes_path(storage);
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+    let content = add_attributes(&existing);
+    if content != existing {
+        std::fs::write(&path, content)?;
+    }
+
+    writeln!(
+        out,
+        "{} Installed the index merge driver ({} in .git/config, {})",
+        Glyph::Check.as_str().green(),
+        format!("merge.{}", MERGE_DRIVER).cyan(),
+        ".lore/.gitattributes".cyan()
+    )?;
+    writeln!(
+        out,
+        "Commit .lore/.gitattributes; other clones run {} to define the driver.",
+        "lore hook install --merge-driver".cyan()
+    )?;
+
+    Ok(())
+}
+
+/// Remove the merge driver from git config and `.lore/.gitattributes`
+pub fn uninstall_merge_driver(
+    storage: &LoreStorage,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let git = open_git(storage)?;
+    git.unset_config(&format!("merge.{}.name", MERGE_DRIVER))?;
+    git.unset_config(&format!("merge.{}.driver", MERGE_DRIVER))?;
+
+    let path = attributes_path(storage);
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        match remove_attributes(&existing) {
+            Some(remaining) => std::fs::write(&path, remaining)?,
+            None => std::fs::remove_file(&path)?,
+        }
+    }
+
+    writeln!(
+        out,
+        "{} Removed the index merge driver",
+        Glyph::Check.as_str().green()
+    )?;
+
+    Ok(())
+}
+
+/// Entry point for the installed shims
+pub fn run(
+    storage: &LoreStorage,
+    kind: HookKind,
+    args: &[String],
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match kind {
+        HookKind::PostCommit => record::execute(
+            storage,
+            RecordOptions {
+                message: None,
+                trace: None,
+                trace_file: None,
+                template: None,
+                edit: false,
+                no_edit: true,
+                files: Vec::new(),
+                agent_id: None,
+                rejected: Vec::new(),
+                rejected_file: None,
+                tags: Vec::new(),
+                metadata: Vec::new(),
+                confidence: None,
+                references: Vec::new(),
+                attachments: Vec::new(),
+                line_range: None,
+                symbol: None,
+                anchor: None,
+                stdin: false,
+                continue_draft: false,
+                finish: false,
+                discard_draft: false,
+                no_diff: true,
+                no_follow_renames: false,
+                staged: false,
+                unstaged: false,
+                since: None,
+                from_commit: Some("HEAD".to_string()),
+                commit: None,
+                supersedes: None,
+                related: Vec::new(),
+                project: None,
+                amend: false,
+                first_parent: false,
+                dry_run: false,
+                allow_duplicate: false,
+                manifest: None,
+                json: false,
+            },
+            out,
+        ),
+        HookKind::PreCommit => {
+            let missing = staged_without_reasoning(storage)?;
+            if !missing.is_empty() {
+                eprintln!(
+                    "{} {} staged {} without recorded reasoning:",
+                    "lore:".yellow(),
+                    missing.len(),
+                    if missing.len() == 1 { "file" } else { "files" }
+                );
+                for path in &missing {
+                    eprintln!("  {} {}", Glyph::Arrow.as_str().yellow(), path);
+                }
+            }
+            Ok(())
+        }
+        HookKind::PrepareCommitMsg => {
+            let Some(message_file) = args.first() else {
+                return Err(CommandError::InvalidInput(
+                    "prepare-commit-msg needs the commit message file".to_string(),
+                )
+                .into());
+            };
+            // Leave merge and squash messages untouched
+            if matches!(args.get(1).map(String::as_str), Some("merge" | "squash")) {
+                return Ok(());
+            }
+
+            let missing = staged_without_reasoning(storage)?;
+            if missing.is_empty() {
+                return Ok(());
+            }
+
+            let mut message = std::fs::read_to_string(message_file)?;
+            if !message.ends_with('\n') {
+                message.push('\n');
+            }
+            message.push_str("# lore: no reasoning recorded for:\n");
+            for path in &missing {
+                message.push_str(&format!("#   {}\n", path));
+            }
+            std::fs::write(message_file, message)?;
+            Ok(())
+        }
+    }
+}
+
+fn open_git(storage: &LoreStorage) -> Result<GitContext, Box<dyn std::error::Error>> {
+    GitContext::open(storage.root())
+        .map_err(|_| CommandError::Git("Git hooks require a git repository".to_string()).into())
+}
+
+/// Staged, non-deleted files that have no entries in the index
+fn staged_without_reasoning(
+    storage: &LoreStorage,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let index = storage.load_index()?;
+    let git = GitContext::open(storage.root())?;
+
+    Ok(git
+        .changed_files()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| c.staged && c.change_type != ChangeType::Deleted)
+        .filter(|c| !index.files.contains_key(&c.path))
+        .map(|c| c.path)
+        .collect())
+}
+
+fn has_block(content: &str) -> bool {
+    content.lines().any(|line| line.trim() == BLOCK_START)
+}
+
+/// Append the lore block to an existing hook script, or create a new one
+fn add_block(existing: Option<&str>, kind: HookKind) -> String {
+    let mut content = match existing {
+        Some(existing) => {
+            let mut content = existing.to_string();
+            if !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content
+        }
+        None => "#!/bin/sh\n".to_string(),
+    };
+
+    content.push_str(&format!(
+        "{}\nif command -v lore >/dev/null 2>&1; then\n    {}\nfi\n{}\n",
+        BLOCK_START,
+        kind.command(),
+        BLOCK_END
+    ));
+    content
+}
+
+/// Strip the lore block. Returns `None` if only a shebang (or nothing) is left.
+fn remove_block(content: &str) -> Option<String> {
+    let mut remaining = Vec::new();
+    let mut in_block = false;
+
+    for line in content.lines() {
+        match line.trim() {
+            BLOCK_START => in_block = true,
+            BLOCK_END => in_block = false,
+            _ if !in_block => remaining.push(line),
+            _ => {}
+        }
+    }
+
+    let meaningful = remaining
+        .iter()
+        .any(|line| !line.trim().is_empty() && !line.starts_with("#!"));
+    if !meaningful {
+        return None;
+    }
+
+    let mut out = remaining.join("\n");
+    out.push('\n');
+    Some(out)
+}
+
+/// `.gitattributes` inside `.lore`, whose patterns are relative to `.lore`
+fn attributes_path(storage: &LoreStorage) -> std::path::PathBuf {
+    storage.root().join(".lore").join(".gitattributes")
+}
+
+/// Lines routing the indexes to the merge driver. The audit log is append-only
+/// JSON lines, so git's built-in union driver keeps both sides' events.
+fn merge_attributes() -> [String; 3] {
+    [
+        format!("index.json merge={}", MERGE_DRIVER),
+        format!("search_index.json merge={}", MERGE_DRIVER),
+        "audit.log merge=union".to_string(),
+    ]
+}
+
+/// Add whichever merge driver lines `existing` is missing
+fn add_attributes(existing: &str) -> String {
+    let mut content = existing.to_string();
+    for line in merge_attributes() {
+        if !existing.lines().any(|l| l.trim() == line) {
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str(&line);
+            content.push('\n');
+        }
+    }
+    content
+}
+
+/// Strip the merge driver lines. Returns `None` if nothing else is left.
+fn remove_attributes(content: &str) -> Option<String> {
+    let ours = merge_attributes();
+    let remaining: Vec<&str> = content
+        .lines()
+        .filter(|line| !ours.iter().any(|ours| line.trim() == ours))
+        .collect();
+    if remaining.iter().all(|line| line.trim().is_empty()) {
+        return None;
+    }
+    Some(remaining.join("\n") + "\n")
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    std::fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_driver_install_is_idempotent_and_uninstalls() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        git2::Repository::init(temp_dir.path()).unwrap();
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+        let path = attributes_path(&storage);
+        std::fs::write(&path, "*.json text").unwrap();
+
+        install_merge_driver(&storage, &mut Vec::new()).unwrap();
+        install_merge_driver(&storage, &mut Vec::new()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "*.json text\nindex.json merge=lore-index\nsearch_index.json merge=lore-index\naudit.log merge=union\n"
+        );
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        assert_eq!(
+            git.config_value("merge.lore-index.driver").as_deref(),
+            Some(MERGE_DRIVER_COMMAND)
+        );
+
+        uninstall_merge_driver(&storage, &mut Vec::new()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "*.json text\n");
+        assert!(git.config_value("merge.lore-index.driver").is_none());
+        assert_eq!(remove_attributes(&add_attributes("")), None);
+    }
+
+    #[test]
+    fn test_add_block_to_new_hook() {
+        let content = add_block(None, HookKind::PostCommit);
+        assert!(content.starts_with("#!/bin/sh\n"));
+        assert!(content.contains("lore record --from-commit HEAD"));
+        assert!(has_block(&content));
+    }
+
+    #[test]
+    fn test_remove_block_deletes_lore_only_hook() {
+        let content = add_block(None, HookKind::PreCommit);
+        assert_eq!(remove_block(&content), None);
+    }
+
+    #[test]
+    fn test_remove_block_preserves_existing_hook() {
+        let original = "#!/bin/sh\nmake lint\n";
+        let content = add_block(Some(original), HookKind::PreCommit);
+        assert!(content.starts_with(original));
+
+        assert_eq!(remove_block(&content).as_deref(), Some(original));
+    }
+
+    #[test]
+    fn test_has_block_ignores_foreign_hooks() {
+        assert!(!has_block("#!/bin/sh\necho lore\n"));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::commands::CommandError;
+use crate::output::Glyph;
+use crate::storage::{parse_export, LoreStorage};
+use colored::Colorize;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub struct ImportOptions {
+    pub file: PathBuf,
+    pub overwrite: bool,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: ImportOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Parse and validate the whole document before writing anything
+    let content = std::fs::read_to_string(&options.file)
+        .map_err(|e| CommandError::cannot_read(options.file.display(), e))?;
+    let export = parse_export(&content).map_err(|e| {
+        CommandError::InvalidInput(format!("Invalid {}: {}", options.file.display(), e))
+    })?;
+
+    let report = storage.import(&export, options.overwrite)?;
+
+    writeln!(
+        out,
+        "{} Imported {} new {} from {}",
+        Glyph::Check.as_str().green(),
+        report.imported.to_string().green(),
+        if report.imported == 1 {
+            "entry"
+        } else {
+            "entries"
+        },
+        options.file.display().to_string().cyan()
+    )?;
+    if report.replaced > 0 {
+        writeln!(
+            out,
+            "  {} {} replaced",
+            Glyph::Arrow.as_str().yellow(),
+            report.replaced
+        )?;
+    }
+    if report.skipped > 0 {
+        writeln!(
+            out,
+            "  {} {} already present (use --overwrite to replace)",
+            Glyph::Arrow.as_str().yellow(),
+            report.skipped
+        )?;
+    }
+
+    Ok(())
+}
@@ -1,36 +1,104 @@
-use crate::storage::LoreStorage;
+use crate::output::Glyph;
+use crate::storage::{LoreStorage, StorageError};
 use colored::Colorize;
+use std::io::Write;
 use std::path::PathBuf;
 
 pub fn execute(
     path: Option<PathBuf>,
     agent_id: Option<String>,
+    repair: bool,
+    out: &mut impl Write,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let root = path.unwrap_or_else(|| std::env::current_dir().unwrap());
     let storage = LoreStorage::new(root.clone());
 
+    if repair {
+        return execute_repair(out, &storage, agent_id.as_deref());
+    }
+
     match storage.init(agent_id.as_deref()) {
         Ok(()) => {
-            println!("{} Initialized Lore in {}", "✓".green(), root.display());
-            println!();
-            println!("Next steps:");
-            println!(
+            writeln!(
+                out,
+                "{} Initialized Lore in {}",
+                Glyph::Check.as_str().green(),
+                root.display()
+            )?;
+            writeln!(out)?;
+            writeln!(out, "Next steps:")?;
+            writeln!(
+                out,
                 "  {} Record reasoning for your code changes",
                 "lore record".cyan()
-            );
-            println!(
+            )?;
+            writeln!(
+                out,
                 "  {} Understand why code exists",
                 "lore explain <file>".cyan()
-            );
-            println!(
+            )?;
+            writeln!(
+                out,
                 "  {} Search through reasoning history",
                 "lore search <query>".cyan()
-            );
+            )?;
             Ok(())
         }
+        Err(e @ StorageError::AlreadyInitialized) => {
+            eprintln!("{} {}", "Error:".red(), e);
+            eprintln!(
+                "If the store is incomplete, run {} to restore it.",
+                "lore init --repair".cyan()
+            );
+            Err(e.into())
+        }
         Err(e) => {
             eprintln!("{} {}", "Error:".red(), e);
             Err(e.into())
         }
     }
 }
+
+fn execute_repair(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    agent_id: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = storage.repair(agent_id)?;
+
+    if report.created.is_empty() {
+        writeln!(
+            out,
+            "{} Store is intact; nothing to recreate",
+            "Info:".blue()
+        )?;
+    } else {
+        for name in &report.created {
+            writeln!(out, "{} Recreated {}", Glyph::Check.as_str().green(), name)?;
+        }
+    }
+
+    writeln!(
+        out,
+        "{} Indexed {} {}",
+        Glyph::Check.as_str().green(),
+        report.reindex.indexed,
+        if report.reindex.indexed == 1 {
+            "entry"
+        } else {
+            "entries"
+        }
+    )?;
+
+    if !report.reindex.failed.is_empty() {
+        for name in &report.reindex.failed {
+            eprintln!(
+                "{} Skipping unreadable entry file {}",
+                "Warning:".yellow(),
+                name
+            );
+        }
+    }
+
+    Ok(())
+}
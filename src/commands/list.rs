@@ -1,84 +1,198 @@
-use crate::storage::{find_lore_root, LoreStorage};
+use crate::diagnostics::{Diagnostics, WarningCode};
+use crate::filter::{page, retain_in_range, retain_project, retain_tagged};
+use crate::models::EntryMeta;
+use crate::output::{self, heavy_rule, rule, Format};
+use crate::storage::LoreStorage;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
+use std::io::Write;
+
+/// Order of `lore list`, from `--sort`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// Newest first
+    #[default]
+    Date,
+    /// By path, grouping each file's entries (newest first within a file)
+    File,
+    /// By agent ID, grouping each agent's entries (newest first within an agent)
+    Agent,
+}
 
 pub struct ListOptions {
-    pub json: bool,
+    pub format: Format,
+    pub sort: SortKey,
     pub limit: Option<usize>,
+    pub offset: usize,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub any_tag: bool,
+    pub project: Option<String>,
+    pub reverse: bool,
 }
 
-pub fn execute(options: ListOptions) -> Result<(), Box<dyn std::error::Error>> {
-    // Find lore root
-    let current_dir = std::env::current_dir()?;
-    let root =
-        find_lore_root(&current_dir).ok_or("Lore not initialized. Run 'lore init' first.")?;
+pub fn execute(
+    storage: &LoreStorage,
+    options: ListOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut metas = storage.get_entry_metas()?;
+
+    // Entry files that fail to parse (or were added without updating the
+    // index) aren't listed
+    let mut diagnostics = Diagnostics::new(options.format.is_document());
+    let unreadable = storage.count_entry_files()?.saturating_sub(metas.len());
+    if unreadable > 0 {
+        diagnostics.warn(
+            WarningCode::UnreadableEntries,
+            format!(
+                "{} entry files are not in the index or could not be parsed. Run 'lore reindex' for details.",
+                unreadable
+            ),
+            None,
+        );
+    }
 
-    let storage = LoreStorage::new(root);
-    let mut entries = storage.get_all_entries()?;
+    retain_in_range(&mut metas, options.since, options.until);
+    retain_tagged(&mut metas, &options.tags, options.any_tag);
+    retain_project(&mut metas, options.project.as_deref());
 
-    // Apply limit
-    if let Some(limit) = options.limit {
-        entries.truncate(limit);
+    sort_metas(&mut metas, options.sort);
+
+    // Page through the sorted entries, then flip for display. Only the
+    // entries that will be shown are read from disk.
+    page(&mut metas, options.offset, options.limit);
+    let mut entries = storage.load_entries(&metas);
+    if options.reverse {
+        entries.reverse();
+    }
+
+    if options.format.is_structured() {
+        output::print_report(out, diagnostics, &entries, options.format)?;
+        return Ok(());
     }
 
     if entries.is_empty() {
-        println!("{} No entries recorded yet.", "Info:".blue());
-        println!();
-        println!(
+        writeln!(out, "{} No entries recorded yet.", "Info:".blue())?;
+        writeln!(out)?;
+        writeln!(
+            out,
             "Record reasoning with: {}",
             "lore record -m \"your message\"".cyan()
-        );
+        )?;
         return Ok(());
     }
 
-    if options.json {
-        let json = serde_json::to_string_pretty(&entries)?;
-        println!("{}", json);
-    } else {
-        println!();
-        println!("{}", "═".repeat(70).dimmed());
-        println!("{} ({} total)", "Lore Entries".bold(), entries.len());
-        println!("{}", "═".repeat(70).dimmed());
-        println!();
-
-        // Header
-        println!(
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(70).dimmed())?;
+    writeln!(out, "{} ({} total)", "Lore Entries".bold(), entries.len())?;
+    writeln!(out, "{}", heavy_rule(70).dimmed())?;
+    writeln!(out)?;
+
+    // Header
+    writeln!(
+        out,
+        "{:<40} {:<15} {:<15}",
+        "FILE".bold(),
+        "AGENT".bold(),
+        "DATE".bold()
+    )?;
+    writeln!(out, "{}", rule(70).dimmed())?;
+
+    for entry in &entries {
+        let file_display = if entry.target_file.len() > 38 {
+            format!("...{}", &entry.target_file[entry.target_file.len() - 35..])
+        } else {
+            entry.target_file.clone()
+        };
+
+        let agent_display = if entry.agent_id.len() > 13 {
+            format!("{}...", &entry.agent_id[..10])
+        } else {
+            entry.agent_id.clone()
+        };
+
+        let date = entry.timestamp.format("%Y-%m-%d").to_string();
+
+        writeln!(
+            out,
             "{:<40} {:<15} {:<15}",
-            "FILE".bold(),
-            "AGENT".bold(),
-            "DATE".bold()
-        );
-        println!("{}", "─".repeat(70).dimmed());
-
-        for entry in &entries {
-            let file_display = if entry.target_file.len() > 38 {
-                format!("...{}", &entry.target_file[entry.target_file.len() - 35..])
-            } else {
-                entry.target_file.clone()
-            };
-
-            let agent_display = if entry.agent_id.len() > 13 {
-                format!("{}...", &entry.agent_id[..10])
-            } else {
-                entry.agent_id.clone()
-            };
-
-            let date = entry.timestamp.format("%Y-%m-%d").to_string();
-
-            println!(
-                "{:<40} {:<15} {:<15}",
-                file_display.cyan(),
-                agent_display.yellow(),
-                date.dimmed()
-            );
-        }
-
-        println!();
-        println!("{}", "─".repeat(70).dimmed());
-        println!(
-            "{}",
-            "Use 'lore explain <file>' to see full reasoning".dimmed()
-        );
+            file_display.cyan(),
+            agent_display.yellow(),
+            date.dimmed()
+        )?;
     }
 
+    writeln!(out)?;
+    writeln!(out, "{}", rule(70).dimmed())?;
+    writeln!(
+        out,
+        "{}",
+        "Use 'lore explain <file>' to see full reasoning".dimmed()
+    )?;
+
     Ok(())
 }
+
+/// Order newest-first `metas` by `key`. The sort is stable, so entries for the
+/// same file or agent stay newest first.
+fn sort_metas(metas: &mut [EntryMeta], key: SortKey) {
+    match key {
+        SortKey::Date => {}
+        SortKey::File => metas.sort_by(|a, b| a.target_file.cmp(&b.target_file)),
+        SortKey::Agent => metas.sort_by(|a, b| a.agent_id.cmp(&b.agent_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ThoughtObject;
+    use chrono::TimeZone;
+
+    fn meta(file: &str, agent: &str, day: u32) -> EntryMeta {
+        let mut entry = ThoughtObject::new(
+            file.to_string(),
+            "hash".to_string(),
+            agent.to_string(),
+            format!("{} {}", file, day),
+            String::new(),
+        );
+        entry.timestamp = Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap();
+        EntryMeta::from(&entry)
+    }
+
+    fn intents(metas: &[EntryMeta]) -> Vec<&str> {
+        metas.iter().map(|m| m.intent.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_metas_keeps_newest_first_within_groups() {
+        // Newest first, as get_entry_metas returns them
+        let metas = vec![
+            meta("b.rs", "alice", 4),
+            meta("a.rs", "bob", 3),
+            meta("b.rs", "bob", 2),
+            meta("a.rs", "alice", 1),
+        ];
+
+        let mut by_file = metas.clone();
+        sort_metas(&mut by_file, SortKey::File);
+        assert_eq!(
+            intents(&by_file),
+            vec!["a.rs 3", "a.rs 1", "b.rs 4", "b.rs 2"]
+        );
+
+        let mut by_agent = metas.clone();
+        sort_metas(&mut by_agent, SortKey::Agent);
+        assert_eq!(
+            intents(&by_agent),
+            vec!["b.rs 4", "a.rs 1", "a.rs 3", "b.rs 2"]
+        );
+
+        let mut by_date = metas.clone();
+        sort_metas(&mut by_date, SortKey::Date);
+        assert_eq!(intents(&by_date), intents(&metas));
+    }
+}
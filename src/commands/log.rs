@@ -0,0 +1,325 @@
+use crate::models::ThoughtObject;
+use crate::output::rule;
+use crate::storage::LoreStorage;
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Maximum characters of reasoning trace shown per entry
+const TRACE_PREVIEW_CHARS: usize = 80;
+
+/// Lines of reasoning trace shown per entry with `--full`
+const FULL_TRACE_LINES: usize = 3;
+
+/// Width assumed when `COLUMNS` isn't set
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+/// How much of each entry `lore log` prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One line per entry
+    Oneline,
+    /// Header, intent and a short preview of the trace
+    Default,
+    /// Header, intent and the first lines of the trace
+    Full,
+}
+
+pub struct LogOptions {
+    pub limit: Option<usize>,
+    pub author_filter: Option<String>,
+    pub file_filter: Option<String>,
+    pub format: LogFormat,
+    pub reverse: bool,
+    pub json: bool,
+}
+
+/// Entries recorded on one day, as emitted by `log --json`
+#[derive(Serialize)]
+struct DayGroup<'a> {
+    date: NaiveDate,
+    entries: Vec<&'a ThoughtObject>,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: LogOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Filter and limit using the index, then read only the entries shown
+    let mut metas = storage.get_entry_metas()?;
+
+    if let Some(file_filter) = &options.file_filter {
+        metas.retain(|m| m.target_file.contains(file_filter));
+    }
+
+    if let Some(author_filter) = &options.author_filter {
+        metas.retain(|m| m.agent_id.contains(author_filter));
+    }
+
+    // Limit keeps the newest entries; --reverse then shows them oldest first
+    if let Some(limit) = options.limit {
+        metas.truncate(limit);
+    }
+    if options.reverse {
+        metas.reverse();
+    }
+
+    let entries = storage.load_entries(&metas);
+
+    if options.json {
+        let groups: Vec<DayGroup> = group_by_day(&entries)
+            .into_iter()
+            .map(|(date, entries)| DayGroup { date, entries })
+            .collect();
+        writeln!(out, "{}", serde_json::to_string_pretty(&groups)?)?;
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        writeln!(out, "{} No matching entries.", "Info:".blue())?;
+        return Ok(());
+    }
+
+    // Write entry by entry rather than building the whole log in memory
+    write_log(out, &entries, options.format)?;
+    Ok(())
+}
+
+fn write_log(out: &mut impl Write, entries: &[ThoughtObject], format: LogFormat) -> io::Result<()> {
+    let now = Utc::now();
+    let width = terminal_width();
+    let mut current_day = None;
+
+    for entry in entries {
+        let day = entry.timestamp.date_naive();
+        if current_day != Some(day) {
+            if current_day.is_some() {
+                writeln!(out)?;
+            }
+            writeln!(
+                out,
+                "{}",
+                format!("{} {} {}", rule(2), day.format("%A, %Y-%m-%d"), rule(2)).bold()
+            )?;
+            current_day = Some(day);
+        }
+
+        let short_id = &entry.id[..8.min(entry.id.len())];
+
+        if format == LogFormat::Oneline {
+            let prefix = format!(
+                "{} {} {} {} ",
+                short_id,
+                entry.timestamp.format("%H:%M"),
+                entry.agent_id,
+                entry.target_file
+            );
+            let intent = truncate(
+                &single_line(&entry.intent),
+                width.saturating_sub(prefix.chars().count()),
+            );
+            writeln!(
+                out,
+                "{} {} {} {} {}",
+                short_id.yellow(),
+                entry.timestamp.format("%H:%M").to_string().dimmed(),
+                entry.agent_id.yellow(),
+                entry.target_file.cyan(),
+                intent
+            )?;
+            continue;
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{} {} {} {}",
+            "entry".yellow(),
+            short_id.yellow(),
+            entry.target_file.cyan(),
+            format!("({})", entry.agent_id).dimmed()
+        )?;
+        writeln!(
+            out,
+            "{}",
+            format!(
+                "{} · {}",
+                relative_time(entry.timestamp, now),
+                entry.timestamp.format("%Y-%m-%d %H:%M")
+            )
+            .dimmed()
+        )?;
+        writeln!(out)?;
+        writeln!(out, "    {}", entry.intent.bold())?;
+
+        if format == LogFormat::Full {
+            for line in entry
+                .reasoning_trace
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .take(FULL_TRACE_LINES)
+            {
+                writeln!(out, "    {}", line)?;
+            }
+        } else {
+            let preview = trace_preview(&entry.reasoning_trace);
+            if !preview.is_empty() {
+                writeln!(out, "    {}", preview)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Split newest- (or oldest-) first entries into runs that share a UTC date,
+/// preserving their order
+fn group_by_day(entries: &[ThoughtObject]) -> Vec<(NaiveDate, Vec<&ThoughtObject>)> {
+    let mut groups: Vec<(NaiveDate, Vec<&ThoughtObject>)> = Vec::new();
+    for entry in entries {
+        let day = entry.timestamp.date_naive();
+        match groups.last_mut() {
+            Some((date, group)) if *date == day => group.push(entry),
+            _ => groups.push((day, vec![entry])),
+        }
+    }
+    groups
+}
+
+/// Terminal width from `COLUMNS`, falling back to 80
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Truncate to at most `max` characters, ending with "..." if anything was cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let keep = max.saturating_sub(3);
+    format!("{}...", text.chars().take(keep).collect::<String>())
+}
+
+/// Human-friendly age of `time` relative to `now`, e.g. "3 hours ago"
+fn relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let delta = now.signed_duration_since(time);
+    let (amount, unit) = if delta.num_seconds() < 60 {
+        return "just now".to_string();
+    } else if delta.num_minutes() < 60 {
+        (delta.num_minutes(), "minute")
+    } else if delta.num_hours() < 24 {
+        (delta.num_hours(), "hour")
+    } else if delta.num_days() < 30 {
+        (delta.num_days(), "day")
+    } else if delta.num_days() < 365 {
+        (delta.num_days() / 30, "month")
+    } else {
+        (delta.num_days() / 365, "year")
+    };
+
+    format!(
+        "{} {}{} ago",
+        amount,
+        unit,
+        if amount == 1 { "" } else { "s" }
+    )
+}
+
+/// First line-collapsed ~80 characters of a reasoning trace
+fn trace_preview(trace: &str) -> String {
+    let flat = single_line(trace);
+    if flat.chars().count() <= TRACE_PREVIEW_CHARS {
+        return flat;
+    }
+    let cut: String = flat.chars().take(TRACE_PREVIEW_CHARS).collect();
+    format!("{}...", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn entry_at(day: u32, hour: u32) -> ThoughtObject {
+        let mut entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            format!("Day {} hour {}", day, hour),
+            "Line one\n\nLine two\nLine three\nLine four".to_string(),
+        );
+        entry.timestamp = Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap();
+        entry
+    }
+
+    #[test]
+    fn test_relative_time_units() {
+        let now = Utc::now();
+        assert_eq!(relative_time(now - Duration::seconds(5), now), "just now");
+        assert_eq!(
+            relative_time(now - Duration::minutes(1), now),
+            "1 minute ago"
+        );
+        assert_eq!(relative_time(now - Duration::hours(3), now), "3 hours ago");
+        assert_eq!(relative_time(now - Duration::days(2), now), "2 days ago");
+        assert_eq!(relative_time(now - Duration::days(65), now), "2 months ago");
+        assert_eq!(relative_time(now - Duration::days(400), now), "1 year ago");
+    }
+
+    #[test]
+    fn test_trace_preview_collapses_and_truncates() {
+        assert_eq!(trace_preview("line one\nline two"), "line one line two");
+
+        let long = "é".repeat(200);
+        let preview = trace_preview(&long);
+        assert!(preview.ends_with("..."));
+        assert_eq!(preview.chars().count(), TRACE_PREVIEW_CHARS + 3);
+    }
+
+    #[test]
+    fn test_group_by_day_preserves_order() {
+        let entries = vec![entry_at(2, 18), entry_at(2, 9), entry_at(1, 12)];
+        let groups = group_by_day(&entries);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, NaiveDate::from_ymd_opt(2024, 3, 2).unwrap());
+        assert_eq!(groups[0].1.len(), 2);
+        assert_eq!(groups[0].1[0].intent, "Day 2 hour 18");
+        assert_eq!(groups[1].1[0].intent, "Day 1 hour 12");
+    }
+
+    #[test]
+    fn test_write_log_headers_and_formats() {
+        colored::control::set_override(false);
+        let entries = vec![entry_at(2, 18), entry_at(1, 12)];
+
+        let mut out = Vec::new();
+        write_log(&mut out, &entries, LogFormat::Oneline).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("── Saturday, 2024-03-02 ──"));
+        assert!(text.contains("── Friday, 2024-03-01 ──"));
+        assert_eq!(text.lines().filter(|l| l.contains("a.rs")).count(), 2);
+        assert!(!text.contains("Line one"));
+
+        let mut out = Vec::new();
+        write_log(&mut out, &entries[..1], LogFormat::Full).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("    Line three"));
+        assert!(!text.contains("Line four"));
+    }
+
+    #[test]
+    fn test_truncate_to_width() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a longer intent", 10), "a longe...");
+    }
+}
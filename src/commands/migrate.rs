@@ -0,0 +1,45 @@
+use crate::output::Glyph;
+use crate::storage::LoreStorage;
+use colored::Colorize;
+use std::io::Write;
+
+/// Move entry files written before sharding into `.lore/entries/<xx>/`
+pub fn execute(
+    storage: &LoreStorage,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = storage.migrate_entries()?;
+
+    for id in &report.conflicts {
+        eprintln!(
+            "{} Leaving entries/{}.json in place: its shard already has a file for that ID",
+            "Warning:".yellow(),
+            id
+        );
+    }
+
+    if report.moved == 0 && report.conflicts.is_empty() {
+        writeln!(
+            out,
+            "{} All entry files are already sharded",
+            Glyph::Check.as_str().green()
+        )?;
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "{} Moved {} entry {} into shards",
+        Glyph::Check.as_str().green(),
+        report.moved.to_string().green(),
+        if report.moved == 1 { "file" } else { "files" }
+    )?;
+    if !report.conflicts.is_empty() {
+        writeln!(
+            out,
+            "  Run {} to compare the duplicates, then delete the one to drop",
+            "lore fsck".cyan()
+        )?;
+    }
+    Ok(())
+}
@@ -1,6 +1,75 @@
+pub mod agents;
+pub mod attachment;
+pub mod audit;
+pub mod blame;
+pub mod browse;
+pub mod completions;
+pub mod config;
+pub mod coverage;
+pub mod diff;
+pub mod diff_lore;
+pub mod diff_stores;
 pub mod explain;
+pub mod export;
+pub mod fsck;
+pub mod gc;
+pub mod hook;
+pub mod import;
 pub mod init;
 pub mod list;
+pub mod log;
+pub mod migrate;
+pub mod mv;
+pub mod prune;
 pub mod record;
+pub mod reindex;
 pub mod search;
+pub mod stats;
 pub mod status;
+pub mod tags;
+pub mod template;
+
+use thiserror::Error;
+
+/// How a command that ran without error turned out, for `main` to turn into
+/// the exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    /// The command worked but found something wrong, e.g. `fsck` on a damaged
+    /// store or `status --quiet` with changes missing reasoning
+    ProblemsFound,
+}
+
+/// Failures of a command itself rather than of the store or git, so callers
+/// can tell bad input from something missing
+#[derive(Error, Debug)]
+pub enum CommandError {
+    /// Bad flags, arguments or input files
+    #[error("{0}")]
+    InvalidInput(String),
+
+    /// A file, entry or store that was asked for doesn't exist
+    #[error("{0}")]
+    NotFound(String),
+
+    /// The command needs a git repository, or a revision git can't resolve
+    #[error("{0}")]
+    Git(String),
+
+    /// Reading or writing a file failed for another reason
+    #[error("{0}")]
+    Io(String),
+}
+
+impl CommandError {
+    /// Failure to read `path`: `NotFound` if it doesn't exist
+    pub fn cannot_read(path: impl std::fmt::Display, e: std::io::Error) -> Self {
+        let message = format!("Cannot read {}: {}", path, e);
+        if e.kind() == std::io::ErrorKind::NotFound {
+            CommandError::NotFound(message)
+        } else {
+            CommandError::Io(message)
+        }
+    }
+}
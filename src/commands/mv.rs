@@ -0,0 +1,42 @@
+use crate::commands::CommandError;
+use crate::output::Glyph;
+use crate::storage::{normalize_path, LoreStorage};
+use colored::Colorize;
+use std::io::Write;
+
+pub struct MvOptions {
+    pub old: String,
+    pub new: String,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: MvOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let old = normalize_path(&options.old);
+    let new = normalize_path(&options.new);
+    if old == new {
+        return Err(CommandError::InvalidInput(
+            "Source and destination are the same file".to_string(),
+        )
+        .into());
+    }
+
+    let moved = storage.rename_file_entries(&old, &new)?;
+    if moved == 0 {
+        return Err(CommandError::NotFound(format!("No entries recorded for {}", old)).into());
+    }
+
+    writeln!(
+        out,
+        "{} Moved {} {} from {} to {}",
+        Glyph::Check.as_str().green(),
+        moved,
+        if moved == 1 { "entry" } else { "entries" },
+        old,
+        new.cyan()
+    )?;
+
+    Ok(())
+}
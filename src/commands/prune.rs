@@ -0,0 +1,238 @@
+use crate::commands::CommandError;
+use crate::models::ThoughtObject;
+use crate::output::{rule, Glyph};
+use crate::storage::LoreStorage;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub struct PruneOptions {
+    /// Entries recorded before this time
+    pub older_than: Option<DateTime<Utc>>,
+    /// Entries whose target file is gone from the working tree
+    pub missing_target: bool,
+    /// Entries a later entry supersedes
+    pub superseded: bool,
+    /// Entries recorded by this agent (exact match)
+    pub agent: Option<String>,
+    pub execute: bool,
+    pub archive: Option<PathBuf>,
+}
+
+impl PruneOptions {
+    fn has_criteria(&self) -> bool {
+        self.older_than.is_some() || self.missing_target || self.superseded || self.agent.is_some()
+    }
+}
+
+/// Remove the entries matching every given criterion. Without `execute` this
+/// is a dry run that only lists them.
+pub fn execute(
+    storage: &LoreStorage,
+    options: PruneOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !options.has_criteria() {
+        return Err(CommandError::InvalidInput(
+            "Nothing to prune by: use --older-than, --missing-target, --superseded or --agent"
+                .to_string(),
+        )
+        .into());
+    }
+    // Fail before listing anything if the archive can't be written
+    if let Some(path) = &options.archive {
+        storage.check_archive_path(path)?;
+    }
+
+    let superseded = if options.superseded {
+        storage.get_superseded_ids()?
+    } else {
+        HashSet::new()
+    };
+    let mut entries: Vec<ThoughtObject> = storage
+        .get_all_entries()?
+        .into_iter()
+        .filter(|entry| is_prunable(entry, &options, storage.root(), &superseded))
+        .collect();
+    entries.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+
+    if entries.is_empty() {
+        writeln!(out, "{} No entries match; nothing to prune", "Info:".blue())?;
+        return Ok(());
+    }
+
+    print_entries(out, &entries)?;
+    let files: HashSet<&str> = entries.iter().map(|e| e.target_file.as_str()).collect();
+    let summary = format!(
+        "{} {} across {} {}",
+        entries.len(),
+        plural(entries.len(), "entry", "entries"),
+        files.len(),
+        plural(files.len(), "file", "files")
+    );
+
+    if !options.execute {
+        let action = match &options.archive {
+            Some(path) => format!("would be archived to {} and removed", path.display()),
+            None => "would be removed".to_string(),
+        };
+        writeln!(out, "{} {} {}", "Dry run:".yellow(), summary, action)?;
+        writeln!(out, "  Run again with {} to do it", "--execute".cyan())?;
+        return Ok(());
+    }
+
+    storage.prune(&entries, options.archive.as_deref())?;
+    match &options.archive {
+        Some(path) => {
+            writeln!(
+                out,
+                "{} Archived {} to {} and removed them",
+                Glyph::Check.as_str().green(),
+                summary,
+                path.display().to_string().cyan()
+            )?;
+            writeln!(
+                out,
+                "  Restore with {}",
+                format!("lore import {}", path.display()).cyan()
+            )?;
+        }
+        None => writeln!(out, "{} Removed {}", Glyph::Check.as_str().green(), summary)?,
+    }
+
+    Ok(())
+}
+
+/// Whether `entry` matches every criterion in `options`
+fn is_prunable(
+    entry: &ThoughtObject,
+    options: &PruneOptions,
+    root: &Path,
+    superseded: &HashSet<String>,
+) -> bool {
+    options
+        .older_than
+        .is_none_or(|cutoff| entry.timestamp < cutoff)
+        && (!options.missing_target || !root.join(&entry.target_file).exists())
+        && (!options.superseded || superseded.contains(&entry.id))
+        && options
+            .agent
+            .as_deref()
+            .is_none_or(|agent| entry.agent_id == agent)
+}
+
+/// Table of the entries to remove, oldest first
+pub(crate) fn print_entries(out: &mut impl Write, entries: &[ThoughtObject]) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:<10} {:<12} {:<40} {}",
+        "ID".bold(),
+        "DATE".bold(),
+        "FILE".bold(),
+        "AGENT".bold()
+    )?;
+    writeln!(out, "{}", rule(70).dimmed())?;
+    for entry in entries {
+        writeln!(
+            out,
+            "{:<10} {:<12} {:<40} {}",
+            entry.id[..8.min(entry.id.len())].dimmed(),
+            entry.timestamp.format("%Y-%m-%d").to_string(),
+            entry.target_file.cyan(),
+            entry.agent_id.yellow()
+        )?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+pub(crate) fn plural<'a>(n: usize, one: &'a str, many: &'a str) -> &'a str {
+    if n == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn options() -> PruneOptions {
+        PruneOptions {
+            older_than: None,
+            missing_target: false,
+            superseded: false,
+            agent: None,
+            execute: false,
+            archive: None,
+        }
+    }
+
+    fn entry(file: &str, agent: &str, age_days: i64) -> ThoughtObject {
+        let mut entry = ThoughtObject::new(
+            file.to_string(),
+            "hash".to_string(),
+            agent.to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        entry.timestamp = Utc::now() - Duration::days(age_days);
+        entry
+    }
+
+    #[test]
+    fn test_criteria_must_all_match() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("kept.rs"), "").unwrap();
+        let none = HashSet::new();
+
+        let old_gone = entry("gone.rs", "bot", 200);
+        let old_kept = entry("kept.rs", "bot", 200);
+        let new_gone = entry("gone.rs", "bot", 10);
+
+        let options = PruneOptions {
+            older_than: Some(Utc::now() - Duration::days(180)),
+            missing_target: true,
+            ..options()
+        };
+        assert!(is_prunable(&old_gone, &options, dir.path(), &none));
+        assert!(!is_prunable(&old_kept, &options, dir.path(), &none));
+        assert!(!is_prunable(&new_gone, &options, dir.path(), &none));
+    }
+
+    #[test]
+    fn test_agent_and_superseded_criteria() {
+        let dir = TempDir::new().unwrap();
+        let old = entry("a.rs", "bot", 1);
+        let superseded = HashSet::from([old.id.clone()]);
+
+        let by_agent = PruneOptions {
+            agent: Some("bot".to_string()),
+            ..options()
+        };
+        assert!(is_prunable(&old, &by_agent, dir.path(), &superseded));
+        assert!(!is_prunable(
+            &entry("a.rs", "bot-2", 1),
+            &by_agent,
+            dir.path(),
+            &superseded
+        ));
+
+        let by_supersession = PruneOptions {
+            superseded: true,
+            ..options()
+        };
+        assert!(is_prunable(&old, &by_supersession, dir.path(), &superseded));
+        assert!(!is_prunable(
+            &entry("a.rs", "bot", 1),
+            &by_supersession,
+            dir.path(),
+            &superseded
+        ));
+    }
+}
@@ -1,183 +1,1758 @@
-use crate::git::{ChangeType, GitContext};
-use crate::models::{RejectedAlternative, ThoughtObject};
-use crate::storage::{find_lore_root, hash_file, normalize_path, LoreStorage};
+use crate::commands::CommandError;
+use crate::diagnostics::{Diagnostics, WarningCode};
+use crate::filter;
+use crate::git::{truncate_diff, ChangeType, ChangedFile, GitContext};
+use crate::global_config::resolve_agent_id;
+use crate::models::{
+    find_anchor, snapshot_lines, Attachment, LineAnchor, PendingDraft, RejectedAlternative,
+    ThoughtObject,
+};
+use crate::output::{short_id, Glyph};
+use crate::storage::{
+    hash_bytes, infer_project, normalize_agent_id, normalize_path, validate_agent_id, LoreStorage,
+    Repetition,
+};
+use crate::symbols::{locate_symbol, offset_range, Language};
 use colored::Colorize;
-use std::io::{self, BufRead, Read, Write};
-use std::path::PathBuf;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 
 pub struct RecordOptions {
     pub message: Option<String>,
     pub trace: Option<String>,
     pub trace_file: Option<PathBuf>,
+    /// Template to start the trace from when none is given
+    pub template: Option<String>,
+    /// Write the trace in `$VISUAL`/`$EDITOR` when none is given
+    pub edit: bool,
+    /// Prompt for the trace on stdin even at a terminal
+    pub no_edit: bool,
     pub files: Vec<String>,
     pub agent_id: Option<String>,
-    pub rejected: Vec<String>,
+    pub rejected: Vec<RejectedAlternative>,
+    pub rejected_file: Option<PathBuf>,
     pub tags: Vec<String>,
+    pub metadata: Vec<(String, String)>,
+    pub confidence: Option<f32>,
+    pub references: Vec<String>,
+    /// Files to copy into the store with each entry
+    pub attachments: Vec<PathBuf>,
     pub line_range: Option<(usize, usize)>,
+    pub symbol: Option<String>,
+    /// Text marking where the reasoning applies, found again after edits
+    pub anchor: Option<String>,
     pub stdin: bool,
+    pub continue_draft: bool,
+    pub finish: bool,
+    pub discard_draft: bool,
+    pub no_diff: bool,
+    pub no_follow_renames: bool,
+    pub staged: bool,
+    pub unstaged: bool,
+    pub since: Option<String>,
+    pub from_commit: Option<String>,
+    /// Diff a merge commit given to `from_commit` or `commit` against its
+    /// first parent instead of rejecting it
+    pub first_parent: bool,
+    pub commit: Option<String>,
+    pub supersedes: Option<String>,
+    pub related: Vec<String>,
+    pub project: Option<String>,
+    /// Append to the newest entry for the single file instead of recording a
+    /// new one
+    pub amend: bool,
+    /// Resolve files and build the entries, but print them instead of saving
+    pub dry_run: bool,
+    /// Record a file even if its newest entry has the same hash and reasoning
+    pub allow_duplicate: bool,
+    /// YAML or JSON list of records to write in one go
+    pub manifest: Option<PathBuf>,
+    pub json: bool,
 }
 
-pub fn execute(options: RecordOptions) -> Result<(), Box<dyn std::error::Error>> {
-    // Find lore root
-    let current_dir = std::env::current_dir()?;
-    let root =
-        find_lore_root(&current_dir).ok_or("Lore not initialized. Run 'lore init' first.")?;
+/// Files to record along with how they changed
+type FilesToRecord = Vec<(String, ChangeType)>;
 
-    let storage = LoreStorage::new(root.clone());
+/// `--attach` files along with their contents
+type AttachmentFiles = Vec<(Attachment, Vec<u8>)>;
+
+/// The content shared by every entry written in a single `record` invocation
+struct EntryContent<'a> {
+    agent_id: &'a str,
+    intent: &'a str,
+    reasoning_trace: &'a str,
+    rejected_alternatives: &'a [RejectedAlternative],
+    tags: &'a [String],
+    metadata: &'a BTreeMap<String, String>,
+    confidence: Option<f32>,
+    references: &'a [String],
+    /// Attachments saved with every entry, with their contents
+    attachments: &'a [(Attachment, Vec<u8>)],
+    line_range: Option<(usize, usize)>,
+    /// Definition to locate in each file. `line_range` is then relative to it.
+    symbol: Option<&'a str>,
+    /// Text each file must contain (on the first line of the range, if any)
+    anchor: Option<&'a str>,
+    capture_diff: bool,
+    /// Commit to attribute entries to, hashing file contents as of that commit.
+    /// When `None`, entries use HEAD and the working tree.
+    commit_hash: Option<&'a str>,
+    /// Full ID of the entry these replace
+    supersedes: Option<&'a str>,
+    /// Full IDs of entries these refer to
+    related: &'a [String],
+    /// Sub-project for every entry; inferred per file when `None`
+    project: Option<&'a str>,
+    /// Build and print the entries without saving them
+    dry_run: bool,
+    /// Record even when the newest entry for a file has the same file hash
+    /// and reasoning
+    allow_duplicate: bool,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: RecordOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = storage.root();
 
     // Get agent ID
-    let agent_id = options
-        .agent_id
-        .clone()
-        .or_else(|| storage.get_default_agent_id().ok())
-        .unwrap_or_else(|| "unknown".to_string());
+    let (mut agent_id, _) = resolve_agent_id(options.agent_id.as_deref(), storage, root)?;
 
-    // Determine which files to record
-    let files_to_record: Vec<(String, ChangeType)> = if !options.files.is_empty() {
-        // User specified files
-        options
-            .files
-            .iter()
-            .map(|f| (f.clone(), ChangeType::Modified))
-            .collect()
+    if storage.normalize_agent_ids()? {
+        agent_id = normalize_agent_id(&agent_id);
+    }
+    validate_agent_id(&agent_id)?;
+
+    if options.json
+        && options.message.is_none()
+        && !options.finish
+        && !options.amend
+        && options.manifest.is_none()
+        && options.from_commit.is_none()
+    {
+        return Err(CommandError::InvalidInput(
+            "--json requires --message (record never prompts in JSON mode)".to_string(),
+        )
+        .into());
+    }
+    let mut diagnostics = Diagnostics::new(options.json);
+
+    let mut options = options;
+
+    // Record paths relative to the root however they were given
+    options.files = options
+        .files
+        .iter()
+        .map(|file| storage.relative_path(file))
+        .collect::<Result<_, _>>()?;
+
+    // Apply defaults from .lore/config.json
+    let config = storage.load_config()?;
+    for tag in config.default_tags {
+        if !options.tags.contains(&tag) {
+            options.tags.push(tag);
+        }
+    }
+    if !config.auto_diff {
+        options.no_diff = true;
+    }
+    if options.template.is_none() {
+        options.template = config.default_template;
+    }
+    // Write the trace in an editor by default when someone is at the terminal
+    options.edit =
+        options.edit || (!options.no_edit && config.edit_trace && io::stdin().is_terminal());
+
+    // Read structured alternatives before prompting, so a bad file doesn't waste a trace
+    if let Some(path) = options.rejected_file.take() {
+        options.rejected.extend(read_rejected_file(&path)?);
+    }
+
+    // Expand (possibly short) entry IDs up front so typos fail before anything is written
+    options.supersedes = options
+        .supersedes
+        .as_deref()
+        .map(|id| storage.resolve_entry_id(id))
+        .transpose()?;
+    options.related = options
+        .related
+        .iter()
+        .map(|id| storage.resolve_entry_id(id))
+        .collect::<Result<_, _>>()?;
+
+    if options.discard_draft {
+        storage.clear_pending()?;
+        writeln!(
+            out,
+            "{} Discarded pending draft",
+            Glyph::Check.as_str().green()
+        )?;
+        return Ok(());
+    }
+
+    if options.amend {
+        return amend_latest(out, storage, options, diagnostics);
+    }
+
+    if let Some(path) = options.manifest.clone() {
+        return record_manifest(out, storage, root, &path, options, &agent_id, diagnostics);
+    }
+
+    if let Some(rev) = options.from_commit.clone() {
+        return record_for_commit(out, storage, &rev, true, options, &agent_id, diagnostics);
+    }
+
+    if let Some(rev) = options.commit.clone() {
+        return record_for_commit(out, storage, &rev, false, options, &agent_id, diagnostics);
+    }
+
+    if options.continue_draft || options.finish {
+        let finish = options.finish;
+        return continue_draft(out, storage, root, options, agent_id, finish, diagnostics);
+    }
+
+    // Offer to resume or discard an interrupted draft
+    let pending = if options.dry_run {
+        None
     } else {
-        // Auto-detect from git
-        match GitContext::open(&root) {
-            Ok(git) => match git.changed_files() {
-                Ok(changes) => changes
-                    .into_iter()
-                    .filter(|c| c.change_type != ChangeType::Deleted)
-                    .map(|c| (c.path, c.change_type))
-                    .collect(),
-                Err(_) => {
-                    eprintln!(
-                        "{} No changed files detected. Specify files with --file or make changes first.",
-                        "Warning:".yellow()
-                    );
-                    return Ok(());
+        storage.load_pending()?
+    };
+    if let Some(draft) = pending {
+        let started = draft.started_at.format("%Y-%m-%d %H:%M UTC");
+        if options.json || options.stdin || !io::stdin().is_terminal() {
+            diagnostics.warn(
+                WarningCode::PendingDraft,
+                format!(
+                    "A pending draft started {} was never finished. Use --finish or --discard-draft.",
+                    started
+                ),
+                None,
+            );
+        } else {
+            let answer = prompt_for_input(out, &format!(
+                "A pending draft started {} ({} chunks) was never finished. [r]esume, [d]iscard, or [k]eep it?",
+                started, draft.chunks
+            ))?;
+            match answer.to_lowercase().as_str() {
+                "r" | "resume" => {
+                    return continue_draft(out, storage, root, options, agent_id, true, diagnostics)
                 }
-            },
-            Err(_) => {
-                eprintln!(
-                    "{} Not a git repository and no files specified.",
-                    "Error:".red()
-                );
-                return Err("Specify files with --file or initialize git".into());
+                "d" | "discard" => {
+                    storage.clear_pending()?;
+                    writeln!(
+                        out,
+                        "{} Discarded pending draft",
+                        Glyph::Check.as_str().green()
+                    )?;
+                }
+                _ => {}
             }
         }
+    }
+
+    if options.files.is_empty() && !options.no_follow_renames && !options.dry_run {
+        let interactive = !options.json && !options.stdin && io::stdin().is_terminal();
+        follow_renames(out, storage, root, interactive, options.json)?;
+    }
+
+    // Determine which files to record
+    let detect = AutoDetect {
+        staged: options.staged,
+        unstaged: options.unstaged,
+        since: options.since.as_deref(),
+    };
+    let Some(files_to_record) = resolve_files(storage, &options.files, detect, &mut diagnostics)?
+    else {
+        return finish_json(out, diagnostics, Vec::new());
     };
 
     if files_to_record.is_empty() {
-        println!("{} No files to record reasoning for.", "Info:".blue());
-        return Ok(());
+        diagnostics.warn(
+            WarningCode::NoFiles,
+            "No files to record reasoning for.",
+            None,
+        );
+        return finish_json(out, diagnostics, Vec::new());
     }
 
+    // Read attachments before prompting, so an oversized one doesn't waste a trace
+    let attachments = read_attachments(storage, &options.attachments)?;
+
     // Get reasoning trace
-    let reasoning_trace = get_reasoning_trace(&options)?;
+    let reasoning_trace = if options.json {
+        read_trace_chunk(&options)?.unwrap_or_default()
+    } else {
+        get_reasoning_trace(out, storage, &options)?
+    };
 
     // Get intent message
     let intent = options.message.unwrap_or_else(|| {
-        prompt_for_input("Enter intent/purpose (brief description):")
+        prompt_for_input(out, "Enter intent/purpose (brief description):")
             .unwrap_or_else(|_| "No intent provided".to_string())
     });
 
-    // Parse rejected alternatives
-    let rejected_alternatives: Vec<RejectedAlternative> = options
-        .rejected
+    let rejected_alternatives = options.rejected;
+    let metadata = options.metadata.into_iter().collect();
+
+    let content = EntryContent {
+        agent_id: &agent_id,
+        intent: &intent,
+        reasoning_trace: &reasoning_trace,
+        rejected_alternatives: &rejected_alternatives,
+        tags: &options.tags,
+        metadata: &metadata,
+        confidence: options.confidence,
+        references: &options.references,
+        attachments: &attachments,
+        line_range: options.line_range,
+        symbol: options.symbol.as_deref(),
+        anchor: options.anchor.as_deref(),
+        capture_diff: !options.no_diff,
+        commit_hash: None,
+        supersedes: options.supersedes.as_deref(),
+        related: &options.related,
+        project: options.project.as_deref(),
+        dry_run: options.dry_run,
+        allow_duplicate: options.allow_duplicate,
+    };
+    let recorded = write_entries(
+        out,
+        storage,
+        root,
+        &files_to_record,
+        &content,
+        &mut diagnostics,
+    )?;
+
+    if diagnostics.is_json() {
+        return finish_json(out, diagnostics, recorded);
+    }
+
+    writeln!(out)?;
+    if options.dry_run {
+        writeln!(
+            out,
+            "{} {} entries would be recorded; nothing was written.",
+            "Dry run:".yellow(),
+            recorded.len()
+        )?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{} entries recorded. Use {} to review.",
+        recorded.len().to_string().green(),
+        "lore explain <file>".cyan()
+    )?;
+
+    Ok(())
+}
+
+/// Print the `--json` envelope with the recorded entries and any warnings.
+/// Does nothing in text mode, where warnings were already printed.
+fn finish_json(
+    out: &mut impl Write,
+    diagnostics: Diagnostics,
+    recorded: Vec<ThoughtObject>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if diagnostics.is_json() {
+        let report = diagnostics.into_report(recorded);
+        writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+    }
+    Ok(())
+}
+
+/// Record every entry in a manifest (see `read_manifest`), after checking all
+/// of them. Tags given on the command line (and `default_tags`) are added to
+/// each record's own.
+fn record_manifest(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    root: &Path,
+    path: &Path,
+    options: RecordOptions,
+    agent_id: &str,
+    mut diagnostics: Diagnostics,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let records = read_manifest(path, root, storage.load_config()?.max_trace_size)?;
+    let attachments = read_attachments(storage, &options.attachments)?;
+    let metadata = options.metadata.into_iter().collect();
+
+    // Build every record's entries before saving any, so the manifest is
+    // recorded in full or not at all
+    let mut built = Vec::new();
+    for record in &records {
+        let mut tags = record.tags.clone();
+        for tag in &options.tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        let content = EntryContent {
+            agent_id,
+            intent: &record.intent,
+            reasoning_trace: &record.trace,
+            rejected_alternatives: &record.rejected,
+            tags: &tags,
+            metadata: &metadata,
+            confidence: options.confidence,
+            references: &options.references,
+            attachments: &attachments,
+            line_range: record.line_range,
+            symbol: None,
+            anchor: None,
+            capture_diff: !options.no_diff,
+            commit_hash: None,
+            supersedes: None,
+            related: &[],
+            project: options.project.as_deref(),
+            dry_run: options.dry_run,
+            allow_duplicate: options.allow_duplicate,
+        };
+        built.extend(build_entries(
+            out,
+            storage,
+            root,
+            &record.files,
+            &content,
+            &mut diagnostics,
+        )?);
+    }
+    let recorded = save_entries(
+        out,
+        storage,
+        built,
+        &attachments,
+        options.dry_run,
+        &diagnostics,
+    )?;
+
+    if diagnostics.is_json() {
+        return finish_json(out, diagnostics, recorded);
+    }
+
+    writeln!(out)?;
+    let verb = if options.dry_run {
+        "would be recorded"
+    } else {
+        "recorded"
+    };
+    writeln!(
+        out,
+        "{} entries {} from {} records in {}.",
+        recorded.len().to_string().green(),
+        verb,
+        records.len(),
+        path.display().to_string().cyan()
+    )?;
+
+    Ok(())
+}
+
+/// Append the trace, tags, rejected alternatives and references to the newest
+/// entry for the one `--file`, keeping its ID
+fn amend_latest(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    options: RecordOptions,
+    diagnostics: Diagnostics,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let [file] = options.files.as_slice() else {
+        return Err(
+            CommandError::InvalidInput("--amend needs exactly one --file".to_string()).into(),
+        );
+    };
+    let file = normalize_path(file);
+    let Some(mut entry) = storage.get_entries_for_file(&file)?.into_iter().next() else {
+        return Err(CommandError::NotFound(format!(
+            "No entry for {} to amend. Record one first with 'lore record -f {}'",
+            file, file
+        ))
+        .into());
+    };
+
+    let trace = if diagnostics.is_json() {
+        read_trace_chunk(&options)?.unwrap_or_default()
+    } else {
+        get_reasoning_trace(out, storage, &options)?
+    };
+    entry.amend(
+        &trace,
+        &options.tags,
+        &options.rejected,
+        &options.references,
+        chrono::Utc::now(),
+    );
+    storage.update_entry(&entry)?;
+
+    if diagnostics.is_json() {
+        return finish_json(out, diagnostics, vec![entry]);
+    }
+    writeln!(
+        out,
+        "{} Amended entry {} for {}",
+        Glyph::Check.as_str().green(),
+        short_id(&entry.id).yellow(),
+        file.cyan()
+    )?;
+    Ok(())
+}
+
+/// Record reasoning for the files touched by an existing commit (restricted to
+/// --file if given), hashing them as they were in that commit. With
+/// `use_message`, the commit summary becomes the intent and the rest of the
+/// message the reasoning trace unless overridden with --message/--trace, and
+/// nothing is prompted for.
+fn record_for_commit(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    rev: &str,
+    use_message: bool,
+    options: RecordOptions,
+    agent_id: &str,
+    mut diagnostics: Diagnostics,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = storage.root();
+    let git = GitContext::open(root).map_err(|_| {
+        CommandError::Git("Recording a commit requires a git repository".to_string())
+    })?;
+    let commit = git
+        .commit_info(rev)
+        .map_err(|e| CommandError::Git(format!("Cannot resolve '{}': {}", rev, e)))?;
+    if commit.parent_count > 1 && !options.first_parent {
+        return Err(CommandError::InvalidInput(format!(
+            "Commit {} is a merge with {} parents. Pass --first-parent to record the files it changed relative to its first parent.",
+            &commit.id[..8],
+            commit.parent_count
+        ))
+        .into());
+    }
+
+    let only: Vec<String> = options.files.iter().map(|f| normalize_path(f)).collect();
+    let files_to_record: FilesToRecord = commit
+        .files
         .into_iter()
-        .map(|name| RejectedAlternative { name, reason: None })
+        .filter(|c| c.change_type != ChangeType::Deleted)
+        .filter(|c| only.is_empty() || only.contains(&c.path))
+        .map(|c| (c.path, c.change_type))
         .collect();
 
+    for path in only
+        .iter()
+        .filter(|p| !files_to_record.iter().any(|(f, _)| f == *p))
+    {
+        diagnostics.warn(
+            WarningCode::FileNotFound,
+            format!(
+                "Skipping {} (not changed in commit {})",
+                path,
+                &commit.id[..8]
+            ),
+            Some(path),
+        );
+    }
+
+    if files_to_record.is_empty() {
+        diagnostics.warn(
+            WarningCode::NoFiles,
+            format!(
+                "Commit {} has no files to record reasoning for.",
+                &commit.id[..8]
+            ),
+            None,
+        );
+        return finish_json(out, diagnostics, Vec::new());
+    }
+
+    let attachments = read_attachments(storage, &options.attachments)?;
+    let (intent, reasoning_trace) = if use_message {
+        let (summary, body) = split_commit_message(&commit.message);
+        let intent = options.message.clone().unwrap_or(summary);
+        let extra = read_trace_chunk(&options)?.unwrap_or_default();
+        (intent, join_trace(&body, &extra))
+    } else if diagnostics.is_json() {
+        let intent = options.message.clone().unwrap_or_default();
+        (intent, read_trace_chunk(&options)?.unwrap_or_default())
+    } else {
+        let reasoning_trace = get_reasoning_trace(out, storage, &options)?;
+        let intent = options.message.clone().unwrap_or_else(|| {
+            prompt_for_input(out, "Enter intent/purpose (brief description):")
+                .unwrap_or_else(|_| "No intent provided".to_string())
+        });
+        (intent, reasoning_trace)
+    };
+    let rejected_alternatives = options.rejected;
+    let metadata = options.metadata.into_iter().collect();
+
+    let content = EntryContent {
+        agent_id,
+        intent: &intent,
+        reasoning_trace: &reasoning_trace,
+        rejected_alternatives: &rejected_alternatives,
+        tags: &options.tags,
+        metadata: &metadata,
+        confidence: options.confidence,
+        references: &options.references,
+        attachments: &attachments,
+        line_range: options.line_range,
+        symbol: options.symbol.as_deref(),
+        anchor: options.anchor.as_deref(),
+        // The working tree no longer differs from the commit
+        capture_diff: false,
+        commit_hash: Some(&commit.id),
+        supersedes: options.supersedes.as_deref(),
+        related: &options.related,
+        project: options.project.as_deref(),
+        dry_run: options.dry_run,
+        allow_duplicate: options.allow_duplicate,
+    };
+    let recorded = write_entries(
+        out,
+        storage,
+        root,
+        &files_to_record,
+        &content,
+        &mut diagnostics,
+    )?;
+
+    if diagnostics.is_json() {
+        return finish_json(out, diagnostics, recorded);
+    }
+
+    writeln!(out)?;
+    if options.dry_run {
+        writeln!(
+            out,
+            "{} {} entries would be recorded from commit {}; nothing was written.",
+            "Dry run:".yellow(),
+            recorded.len(),
+            commit.id[..8].cyan()
+        )?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{} entries recorded from commit {}.",
+        recorded.len().to_string().green(),
+        commit.id[..8].cyan()
+    )?;
+
+    Ok(())
+}
+
+/// The commit body followed by any trace given alongside it, separated by a
+/// horizontal rule
+fn join_trace(body: &str, extra: &str) -> String {
+    let extra = extra.trim();
+    match (body.is_empty(), extra.is_empty()) {
+        (_, true) => body.to_string(),
+        (true, false) => extra.to_string(),
+        (false, false) => format!("{}\n\n---\n\n{}", body, extra),
+    }
+}
+
+/// Split a commit message into its summary line and the remaining body
+fn split_commit_message(message: &str) -> (String, String) {
+    let mut parts = message.trim().splitn(2, '\n');
+    let summary = parts.next().unwrap_or("").trim().to_string();
+    let body = parts.next().unwrap_or("").trim().to_string();
+    (summary, body)
+}
+
+/// Append this invocation to the pending draft, committing it if `finish` is set
+fn continue_draft(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    root: &Path,
+    options: RecordOptions,
+    agent_id: String,
+    finish: bool,
+    mut diagnostics: Diagnostics,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut draft = storage
+        .load_pending()?
+        .unwrap_or_else(|| PendingDraft::new(agent_id));
+
+    if let Some(chunk) = read_trace_chunk(&options)? {
+        draft.append_chunk(&chunk);
+    }
+    draft.add_files(&options.files);
+    draft.add_tags(&options.tags);
+    draft.add_references(&options.references);
+    draft.add_metadata(&options.metadata);
+    draft.rejected_alternatives.extend(options.rejected);
+    if let Some(message) = options.message {
+        draft.intent = Some(message);
+    }
+    if let Some(range) = options.line_range {
+        draft.line_range = Some(range);
+    }
+    if let Some(confidence) = options.confidence {
+        draft.confidence = Some(confidence);
+    }
+
+    // Persist first so the chunk survives even if finishing fails
+    storage.save_pending(&draft)?;
+
+    if !finish {
+        writeln!(
+            out,
+            "{} Appended to pending draft ({} chunks, {} chars). Use {} to commit it.",
+            Glyph::Check.as_str().green(),
+            draft.chunks,
+            draft.reasoning_trace.len(),
+            "lore record --finish".cyan()
+        )?;
+        return Ok(());
+    }
+
+    let recorded = finish_draft(
+        out,
+        storage,
+        root,
+        &draft,
+        !options.no_diff,
+        &mut diagnostics,
+    )?;
+    if diagnostics.is_json() {
+        return finish_json(out, diagnostics, recorded.unwrap_or_default());
+    }
+    let Some(recorded) = recorded else {
+        return Ok(());
+    };
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{} entries recorded from draft. Use {} to review.",
+        recorded.len().to_string().green(),
+        "lore explain <file>".cyan()
+    )?;
+
+    Ok(())
+}
+
+/// Write entries for a completed draft and clear it. Returns `None` if there
+/// were no files to record, in which case the draft is left untouched.
+fn finish_draft(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    root: &Path,
+    draft: &PendingDraft,
+    capture_diff: bool,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<Vec<ThoughtObject>>, Box<dyn std::error::Error>> {
+    let Some(files_to_record) =
+        resolve_files(storage, &draft.files, AutoDetect::default(), diagnostics)?
+    else {
+        return Ok(None);
+    };
+
+    if files_to_record.is_empty() {
+        diagnostics.warn(
+            WarningCode::NoFiles,
+            "No files to record reasoning for.",
+            None,
+        );
+        return Ok(None);
+    }
+
+    let intent = match &draft.intent {
+        Some(intent) => intent.clone(),
+        None if diagnostics.is_json() => {
+            return Err(CommandError::InvalidInput(
+                "--json requires --message (record never prompts in JSON mode)".to_string(),
+            )
+            .into())
+        }
+        None => prompt_for_input(out, "Enter intent/purpose (brief description):")
+            .unwrap_or_else(|_| "No intent provided".to_string()),
+    };
+
+    let content = EntryContent {
+        agent_id: &draft.agent_id,
+        intent: &intent,
+        reasoning_trace: &draft.reasoning_trace,
+        rejected_alternatives: &draft.rejected_alternatives,
+        tags: &draft.tags,
+        metadata: &draft.metadata,
+        confidence: draft.confidence,
+        references: &draft.references,
+        attachments: &[],
+        line_range: draft.line_range,
+        symbol: None,
+        anchor: None,
+        capture_diff,
+        commit_hash: None,
+        supersedes: None,
+        related: &[],
+        project: None,
+        dry_run: false,
+        allow_duplicate: false,
+    };
+    let recorded = write_entries(out, storage, root, &files_to_record, &content, diagnostics)?;
+    storage.clear_pending()?;
+
+    Ok(Some(recorded))
+}
+
+/// Move the entries of files git reports as renamed to their new path, asking
+/// first when `interactive`. Returns the number of entries moved.
+fn follow_renames(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    root: &Path,
+    interactive: bool,
+    quiet: bool,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let Ok(changes) = GitContext::open(root).and_then(|git| git.changed_files()) else {
+        return Ok(0);
+    };
+
+    let index = storage.load_index()?;
+    let mut moved = 0;
+    for change in changes {
+        let Some(old_path) = change.old_path else {
+            continue;
+        };
+        let count = index.get_entries_for_file(&old_path).map_or(0, Vec::len);
+        if count == 0 {
+            continue;
+        }
+
+        if interactive {
+            let answer = prompt_for_input(
+                out,
+                &format!(
+                    "{} was renamed to {}. Move its {} entries there? [Y/n]",
+                    old_path, change.path, count
+                ),
+            )?;
+            if answer.eq_ignore_ascii_case("n") || answer.eq_ignore_ascii_case("no") {
+                continue;
+            }
+        }
+
+        let count = storage.rename_file_entries(&old_path, &change.path)?;
+        if !quiet {
+            writeln!(
+                out,
+                "{} Moved {} entries from {} to {}",
+                Glyph::Check.as_str().green(),
+                count,
+                old_path,
+                change.path.cyan()
+            )?;
+        }
+        moved += count;
+    }
+
+    Ok(moved)
+}
+
+/// Which changes git auto-detection picks files from
+#[derive(Debug, Default, Clone, Copy)]
+struct AutoDetect<'a> {
+    /// Only files with staged changes
+    staged: bool,
+    /// Only files with unstaged changes
+    unstaged: bool,
+    /// Every file changed since this revision instead of the uncommitted changes
+    since: Option<&'a str>,
+}
+
+impl AutoDetect<'_> {
+    /// Whether a change passes `--staged`/`--unstaged`
+    fn selects(&self, change: &ChangedFile) -> bool {
+        (!self.staged || change.staged) && (!self.unstaged || change.unstaged)
+    }
+
+    /// "staged" or "unstaged", when one of them is set
+    fn kind(&self) -> Option<&'static str> {
+        if self.staged {
+            Some("staged")
+        } else if self.unstaged {
+            Some("unstaged")
+        } else {
+            None
+        }
+    }
+}
+
+/// Determine which files to record: the given ones, or changed files from git
+/// minus those matched by `.lore/.loreignore`. Returns `None` when git reports
+/// no changes (a warning has been emitted). Given files must have staged (or
+/// unstaged) changes when `--staged` (or `--unstaged`) is set.
+fn resolve_files(
+    storage: &LoreStorage,
+    files: &[String],
+    detect: AutoDetect,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<FilesToRecord>, Box<dyn std::error::Error>> {
+    if let (false, Some(kind)) = (files.is_empty(), detect.kind()) {
+        let changes = GitContext::open(storage.root())
+            .and_then(|git| git.changed_files())
+            .map_err(|e| CommandError::Git(format!("--{} needs git: {}", kind, e)))?;
+        return files
+            .iter()
+            .map(|file| {
+                let path = normalize_path(file);
+                changes
+                    .iter()
+                    .find(|c| c.path == path && detect.selects(c))
+                    .map(|c| (file.clone(), c.change_type))
+                    .ok_or_else(|| {
+                        CommandError::InvalidInput(format!("{} has no {} changes", path, kind))
+                            .into()
+                    })
+            })
+            .collect::<Result<_, _>>()
+            .map(Some);
+    }
+
+    if !files.is_empty() {
+        // User specified files
+        return Ok(Some(
+            files
+                .iter()
+                .map(|f| (f.clone(), ChangeType::Modified))
+                .collect(),
+        ));
+    }
+
+    // Auto-detect from git
+    let root = storage.root();
+    let ignore = storage.loreignore()?;
+    if let (Some(rev), Ok(git)) = (detect.since, GitContext::open(root)) {
+        let changes = git
+            .files_changed_since(rev)
+            .map_err(|e| CommandError::Git(format!("Cannot resolve '{}': {}", rev, e)))?;
+        let files: FilesToRecord = changes
+            .into_iter()
+            .filter(|c| c.change_type != ChangeType::Deleted)
+            .filter(|c| !ignore.is_ignored(&c.path))
+            .map(|c| (c.path, c.change_type))
+            .collect();
+        if files.is_empty() {
+            diagnostics.warn(
+                WarningCode::NoChanges,
+                format!("No files changed since {}.", rev),
+                None,
+            );
+            return Ok(None);
+        }
+        return Ok(Some(files));
+    }
+
+    match GitContext::open(root) {
+        Ok(git) => match git.changed_files() {
+            Ok(changes) => {
+                let changed = !changes.is_empty();
+                let selected: Vec<ChangedFile> = changes
+                    .into_iter()
+                    .filter(|c| c.change_type != ChangeType::Deleted)
+                    .filter(|c| detect.selects(c))
+                    .collect();
+                if let (true, true, Some(kind)) = (selected.is_empty(), changed, detect.kind()) {
+                    // Distinguish from a clean tree, where nothing is changed at all
+                    diagnostics.warn(
+                        WarningCode::NoChanges,
+                        format!(
+                            "No {} changes (other files have changes). Drop --{} to record them.",
+                            kind, kind
+                        ),
+                        None,
+                    );
+                    return Ok(None);
+                }
+                Ok(Some(
+                    selected
+                        .into_iter()
+                        .filter(|c| !ignore.is_ignored(&c.path))
+                        .map(|c| (c.path, c.change_type))
+                        .collect(),
+                ))
+            }
+            Err(_) => {
+                diagnostics.warn(
+                    WarningCode::NoChanges,
+                    "No changed files detected. Specify files with --file or make changes first.",
+                    None,
+                );
+                Ok(None)
+            }
+        },
+        Err(_) => Err(CommandError::Git(
+            "Not a git repository and no files specified. Specify files with --file or initialize git"
+                .to_string(),
+        )
+        .into()),
+    }
+}
+
+/// Create and save an entry for each file, returning the recorded entries
+fn write_entries(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    root: &Path,
+    files_to_record: &[(String, ChangeType)],
+    content: &EntryContent,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<ThoughtObject>, Box<dyn std::error::Error>> {
+    let built = build_entries(out, storage, root, files_to_record, content, diagnostics)?;
+    save_entries(
+        out,
+        storage,
+        built,
+        content.attachments,
+        content.dry_run,
+        diagnostics,
+    )
+}
+
+/// Create an entry for each file without saving anything, so a file that
+/// fails validation leaves no entries or attachments behind
+fn build_entries(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    root: &Path,
+    files_to_record: &[(String, ChangeType)],
+    content: &EntryContent,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<(ThoughtObject, ChangeType)>, Box<dyn std::error::Error>> {
     // Get commit hash if available
-    let commit_hash = GitContext::open(&root)
-        .ok()
-        .and_then(|git| git.head_commit().ok());
+    let git = GitContext::open(root).ok();
+    let commit_hash = match content.commit_hash {
+        Some(hash) => Some(hash.to_string()),
+        None => git.as_ref().and_then(|git| git.head_commit().ok()),
+    };
+    let commit_author = git
+        .as_ref()
+        .and_then(|git| match content.commit_hash {
+            Some(rev) => git.commit_author(rev).ok(),
+            None => git.head_author().ok(),
+        })
+        .map(|(name, email)| format!("{} <{}>", name, email));
+    let config = storage.load_config()?;
+    check_trace_size(content.reasoning_trace, config.max_trace_size)
+        .map_err(CommandError::InvalidInput)?;
 
-    // Record entry for each file
-    let mut recorded_count = 0;
+    // Build an entry for each file
+    let mut built = Vec::new();
 
-    for (file_path, change_type) in &files_to_record {
-        let normalized = normalize_path(file_path);
+    for (file_path, change_type) in files_to_record {
+        // Keep the recorded spelling on case-insensitive filesystems
+        let normalized = storage.resolve_file_path(file_path, false)?;
         let full_path = root.join(&normalized);
 
+        // Read the file as of the target commit, or from the working tree
+        let file_content = match (content.commit_hash, git.as_ref()) {
+            (Some(rev), Some(git)) => git.file_at_commit(rev, &normalized)?,
+            _ => std::fs::read(&full_path).ok(),
+        };
+
         // Skip if file doesn't exist (was deleted)
-        if !full_path.exists() {
-            println!("{} Skipping {} (file not found)", "→".yellow(), normalized);
+        let Some(file_content) = file_content else {
+            let reason = match content.commit_hash {
+                Some(rev) => format!("not in commit {}", &rev[..8.min(rev.len())]),
+                None => "file not found".to_string(),
+            };
+            diagnostics.warn(
+                WarningCode::FileNotFound,
+                format!("Skipping {} ({})", normalized, reason),
+                Some(&normalized),
+            );
             continue;
-        }
+        };
 
         // Hash the file
-        let file_hash = hash_file(&full_path)?;
+        let file_hash = hash_bytes(&file_content);
 
         // Create thought object
         let mut entry = ThoughtObject::new(
             normalized.clone(),
             file_hash,
-            agent_id.clone(),
-            intent.clone(),
-            reasoning_trace.clone(),
+            content.agent_id.to_string(),
+            content.intent.to_string(),
+            content.reasoning_trace.to_string(),
         )
-        .with_rejected(rejected_alternatives.clone())
-        .with_tags(options.tags.clone());
+        .with_rejected(content.rejected_alternatives.to_vec())
+        .with_tags(content.tags.to_vec())
+        .with_related(content.related.to_vec())
+        .with_metadata(content.metadata.clone())
+        .with_attachments(
+            content
+                .attachments
+                .iter()
+                .map(|(attachment, _)| attachment.clone())
+                .collect(),
+        );
+
+        if let Some(id) = content.supersedes {
+            entry = entry.with_supersedes(id.to_string());
+        }
+
+        if let Some(confidence) = content.confidence {
+            entry = entry.with_confidence(confidence);
+        }
+
+        if !content.references.is_empty() {
+            entry = entry.with_references(content.references.to_vec());
+        }
 
         if let Some(hash) = &commit_hash {
             entry = entry.with_commit(hash.clone());
         }
 
-        if let Some((start, end)) = options.line_range {
+        if let Some(author) = &commit_author {
+            entry = entry.with_commit_author(author.clone());
+        }
+
+        let project = match content.project {
+            Some(project) => Some(project.trim_end_matches('/').to_string()),
+            None => infer_project(root, &normalized),
+        };
+        if let Some(project) = project {
+            entry = entry.with_project(project);
+        }
+
+        let text = String::from_utf8_lossy(&file_content);
+        let line_range = match content.symbol {
+            Some(symbol) => match locate_symbol(&normalized, &text, symbol) {
+                Some(span) => Some(content.line_range.map_or(span, |r| offset_range(span, r))),
+                None => {
+                    let reason = if Language::from_path(&normalized).is_some() {
+                        format!("{} not found in {}", symbol, normalized)
+                    } else {
+                        format!("Can't locate symbols in {}", normalized)
+                    };
+                    diagnostics.warn(
+                        WarningCode::SymbolNotFound,
+                        format!("{}; recording the whole file", reason),
+                        Some(&normalized),
+                    );
+                    None
+                }
+            },
+            None => content.line_range,
+        };
+
+        if let Some(anchor) = content.anchor {
+            let near = line_range.map_or(1, |(start, _)| start);
+            match (find_anchor(&text, anchor, near), line_range) {
+                (Some(line), Some((start, _))) if line != start => {
+                    return Err(CommandError::InvalidInput(format!(
+                        "--anchor '{}' must be on the first line of the range ({}: line {}, found at line {})",
+                        anchor, normalized, start, line
+                    ))
+                    .into());
+                }
+                (Some(_), _) => entry = entry.with_anchor(anchor.to_string()),
+                (None, _) => diagnostics.warn(
+                    WarningCode::AnchorNotFound,
+                    format!(
+                        "'{}' not found in {}; recording without an anchor",
+                        anchor, normalized
+                    ),
+                    Some(&normalized),
+                ),
+            }
+        }
+
+        if let Some((start, end)) = line_range {
             entry = entry.with_line_range(start, end);
+            if let Some(anchor) = LineAnchor::capture(&text, (start, end)) {
+                entry = entry.with_line_anchor(anchor);
+            }
+            if let Some(snapshot) = snapshot_lines(&text, (start, end)) {
+                entry = entry.with_code_snapshot(snapshot);
+            }
+        }
+
+        if content.capture_diff {
+            if let Some(diff) = git.as_ref().and_then(|git| git.diff_file(&normalized).ok()) {
+                if !diff.is_empty() {
+                    entry = entry.with_diff(truncate_diff(diff, config.max_diff_bytes));
+                }
+            }
+        }
+
+        match storage.repetition_of_latest(&entry)? {
+            Some(Repetition::Duplicate(id)) if !content.allow_duplicate => {
+                diagnostics.warn(
+                    WarningCode::Duplicate,
+                    format!(
+                        "Skipping {} (same reasoning as entry {} and the file is unchanged; use --allow-duplicate to record it anyway)",
+                        normalized,
+                        &id[..8.min(id.len())]
+                    ),
+                    Some(&normalized),
+                );
+                continue;
+            }
+            Some(Repetition::UnchangedFile(id)) if !diagnostics.is_json() => {
+                writeln!(
+                    out,
+                    "{} {} is unchanged since its last entry ({})",
+                    "Info:".blue(),
+                    normalized.cyan(),
+                    &id[..8.min(id.len())]
+                )?;
+            }
+            _ => {}
+        }
+
+        built.push((entry, *change_type));
+    }
+
+    Ok(built)
+}
+
+/// Save built entries with their attachments (or only preview them for a
+/// dry run), returning them
+fn save_entries(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    built: Vec<(ThoughtObject, ChangeType)>,
+    attachments: &[(Attachment, Vec<u8>)],
+    dry_run: bool,
+    diagnostics: &Diagnostics,
+) -> Result<Vec<ThoughtObject>, Box<dyn std::error::Error>> {
+    let mut recorded = Vec::new();
+    for (entry, change_type) in built {
+        if dry_run {
+            if !diagnostics.is_json() {
+                print_preview(out, &entry, &change_type)?;
+            }
+            recorded.push(entry);
+            continue;
         }
 
-        // Save entry
+        storage.save_attachments(&entry.id, attachments)?;
         storage.save_entry(&entry)?;
 
-        println!(
-            "{} Recorded reasoning for {} ({})",
-            "✓".green(),
-            normalized.cyan(),
-            change_type
-        );
-        recorded_count += 1;
+        if !diagnostics.is_json() {
+            writeln!(
+                out,
+                "{} Recorded reasoning for {} ({})",
+                Glyph::Check.as_str().green(),
+                entry.target_file.cyan(),
+                change_type
+            )?;
+        }
+        recorded.push(entry);
     }
 
-    println!();
-    println!(
-        "{} entries recorded. Use {} to review.",
-        recorded_count.to_string().green(),
-        "lore explain <file>".cyan()
-    );
+    Ok(recorded)
+}
 
+/// Show an entry `--dry-run` would have saved
+fn print_preview(
+    out: &mut impl Write,
+    entry: &ThoughtObject,
+    change_type: &ChangeType,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "{} Would record reasoning for {} ({})",
+        Glyph::Bullet.as_str().yellow(),
+        entry.target_file.cyan(),
+        change_type
+    )?;
+    writeln!(out, "    {} {}", "Hash:".bold(), entry.file_hash.dimmed())?;
+    writeln!(out, "    {} {}", "Intent:".bold(), entry.intent)?;
+    if let Some(commit) = &entry.commit_hash {
+        writeln!(
+            out,
+            "    {} {}",
+            "Commit:".bold(),
+            commit[..8.min(commit.len())].cyan()
+        )?;
+    }
+    if let Some((start, end)) = entry.line_range {
+        writeln!(out, "    {} {}-{}", "Lines:".bold(), start, end)?;
+    }
+    if !entry.tags.is_empty() {
+        let tags: Vec<String> = entry.tags.iter().map(|t| format!("#{}", t)).collect();
+        writeln!(out, "    {} {}", "Tags:".bold(), tags.join(", ").magenta())?;
+    }
+    for alternative in &entry.rejected_alternatives {
+        match &alternative.reason {
+            Some(reason) => writeln!(
+                out,
+                "    {} {} - {}",
+                "Rejected:".bold(),
+                alternative.name.red(),
+                reason.dimmed()
+            )?,
+            None => writeln!(out, "    {} {}", "Rejected:".bold(), alternative.name.red())?,
+        }
+    }
+    for attachment in &entry.attachments {
+        writeln!(
+            out,
+            "    {} {} ({} bytes)",
+            "Attach:".bold(),
+            attachment.filename.cyan(),
+            attachment.size
+        )?;
+    }
     Ok(())
 }
 
-fn get_reasoning_trace(options: &RecordOptions) -> Result<String, Box<dyn std::error::Error>> {
+/// Read the `--attach` files, refusing any larger than `max_attachment_size`
+/// and two with the same file name (they share a directory in the store)
+fn read_attachments(
+    storage: &LoreStorage,
+    paths: &[PathBuf],
+) -> Result<AttachmentFiles, Box<dyn std::error::Error>> {
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    let max = storage.load_config()?.max_attachment_size;
+
+    let mut attachments = AttachmentFiles::new();
+    for path in paths {
+        let Some(filename) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            return Err(CommandError::InvalidInput(format!(
+                "--attach {} does not name a file",
+                path.display()
+            ))
+            .into());
+        };
+        if attachments.iter().any(|(a, _)| a.filename == filename) {
+            return Err(CommandError::InvalidInput(format!(
+                "Two attachments are named '{}'; rename one of them",
+                filename
+            ))
+            .into());
+        }
+
+        let size = std::fs::metadata(path)
+            .map_err(|e| CommandError::cannot_read(path.display(), e))?
+            .len();
+        if size > max as u64 {
+            return Err(CommandError::InvalidInput(format!(
+                "Attachment {} is {} bytes, more than max_attachment_size ({} bytes)",
+                path.display(),
+                size,
+                max
+            ))
+            .into());
+        }
+        let content =
+            std::fs::read(path).map_err(|e| CommandError::cannot_read(path.display(), e))?;
+
+        attachments.push((
+            Attachment {
+                filename,
+                size: content.len() as u64,
+                sha256: hash_bytes(&content),
+            },
+            content,
+        ));
+    }
+    Ok(attachments)
+}
+
+/// Read `--rejected-file`: a list of `{name, reason}` objects, as JSON for a
+/// `.json` file and YAML otherwise. Names and reasons are trimmed like
+/// `--rejected` values, and an empty reason counts as none.
+fn read_rejected_file(path: &Path) -> Result<Vec<RejectedAlternative>, Box<dyn std::error::Error>> {
+    let alternatives: Vec<RejectedAlternative> = read_structured(path)?;
+    clean_rejected(alternatives).map_err(|e| {
+        CommandError::InvalidInput(format!("Invalid {}: {}", path.display(), e)).into()
+    })
+}
+
+/// Parse a file as JSON if it has a `.json` extension, and as YAML otherwise
+fn read_structured<T: serde::de::DeserializeOwned>(
+    path: &Path,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| CommandError::cannot_read(path.display(), e))?;
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let parsed = if is_json {
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        serde_yaml::from_str(&content).map_err(|e| e.to_string())
+    };
+    parsed.map_err(|e| {
+        CommandError::InvalidInput(format!("Invalid {}: {}", path.display(), e)).into()
+    })
+}
+
+/// Trim rejected alternatives like `--rejected` values, dropping empty
+/// reasons. Fails on an alternative without a name.
+fn clean_rejected(
+    alternatives: Vec<RejectedAlternative>,
+) -> Result<Vec<RejectedAlternative>, String> {
+    alternatives
+        .into_iter()
+        .map(|alt| {
+            let name = alt.name.trim().to_string();
+            if name.is_empty() {
+                return Err("a rejected alternative has no name".to_string());
+            }
+            Ok(RejectedAlternative {
+                name,
+                reason: alt
+                    .reason
+                    .map(|reason| reason.trim().to_string())
+                    .filter(|reason| !reason.is_empty()),
+            })
+        })
+        .collect()
+}
+
+/// One record in a `record --from` manifest
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestRecord {
+    files: Vec<String>,
+    intent: String,
+    #[serde(default)]
+    trace: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    rejected: Vec<RejectedAlternative>,
+    /// Line range as "start-end", like `--lines`
+    #[serde(default)]
+    lines: Option<String>,
+}
+
+/// A manifest record that passed validation
+struct ValidRecord {
+    files: FilesToRecord,
+    intent: String,
+    trace: String,
+    tags: Vec<String>,
+    rejected: Vec<RejectedAlternative>,
+    line_range: Option<(usize, usize)>,
+}
+
+/// Refuse a reasoning trace larger than `max_trace_size`
+fn check_trace_size(trace: &str, max: Option<usize>) -> Result<(), String> {
+    match max {
+        Some(max) if trace.len() > max => Err(format!(
+            "Reasoning trace is {} bytes, more than max_trace_size ({} bytes)",
+            trace.len(),
+            max
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Read a `record --from` manifest and check every record before anything is
+/// written: each needs an intent and at least one file, every file must exist
+/// under `root`, line ranges must parse, and traces must fit `max_trace_size`
+fn read_manifest(
+    path: &Path,
+    root: &Path,
+    max_trace_size: Option<usize>,
+) -> Result<Vec<ValidRecord>, Box<dyn std::error::Error>> {
+    let records: Vec<ManifestRecord> = read_structured(path)?;
+    if records.is_empty() {
+        return Err(
+            CommandError::InvalidInput(format!("{} has no records", path.display())).into(),
+        );
+    }
+
+    records
+        .into_iter()
+        .enumerate()
+        .map(|(i, record)| {
+            validate_record(record, root, max_trace_size).map_err(|e| {
+                CommandError::InvalidInput(format!(
+                    "Invalid {}: record {}: {}",
+                    path.display(),
+                    i + 1,
+                    e
+                ))
+                .into()
+            })
+        })
+        .collect()
+}
+
+fn validate_record(
+    record: ManifestRecord,
+    root: &Path,
+    max_trace_size: Option<usize>,
+) -> Result<ValidRecord, String> {
+    let intent = record.intent.trim().to_string();
+    if intent.is_empty() {
+        return Err("intent is empty".to_string());
+    }
+    check_trace_size(&record.trace, max_trace_size)?;
+    if record.files.is_empty() {
+        return Err("no files".to_string());
+    }
+    let files = record
+        .files
+        .iter()
+        .map(|file| {
+            let normalized = normalize_path(file);
+            if root.join(&normalized).is_file() {
+                Ok((normalized, ChangeType::Modified))
+            } else {
+                Err(format!("{} not found", file))
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    let line_range = record
+        .lines
+        .as_deref()
+        .map(filter::parse_line_range)
+        .transpose()?;
+
+    Ok(ValidRecord {
+        files,
+        intent,
+        trace: record.trace,
+        tags: record.tags,
+        rejected: clean_rejected(record.rejected)?,
+        line_range,
+    })
+}
+
+fn get_reasoning_trace(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    options: &RecordOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
     // Check for trace from various sources
+    if let Some(trace) = read_trace_chunk(options)? {
+        return Ok(trace);
+    }
+
+    let template = options
+        .template
+        .as_deref()
+        .map(|name| storage.load_template(name))
+        .transpose()?;
+
+    if options.edit {
+        let initial = format!("{}{}", EDITOR_HEADER, template.as_deref().unwrap_or(""));
+        if let Some(trace) = trace_from_editor(out, storage, &initial)? {
+            return Ok(trace);
+        }
+    }
+
+    // Prompt for reasoning
+    match template {
+        Some(template) => prompt_for_sections(out, &template),
+        None => prompt_for_multiline_input(
+            out,
+            "Enter reasoning trace (end with a line containing only '.', or Ctrl+D):",
+        ),
+    }
+}
+
+/// Instructions at the top of the file opened in the editor
+const EDITOR_HEADER: &str = "\
+#lore: Write the reasoning behind this change: the context, the decision, the
+#lore: alternatives considered and the risks. Blank lines are kept.
+#lore: Lines starting with '#lore:' are ignored. An empty trace aborts the record.
+";
+
+/// The user's editor: `$VISUAL`, then `$EDITOR`
+fn editor_command() -> Option<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+}
+
+/// Edit `initial` in the user's editor and return the trace without `#lore:`
+/// lines. Text left behind by an editor that failed earlier is picked up
+/// instead of `initial`. `None` when no editor could be used, in which case
+/// the caller falls back to prompting.
+fn trace_from_editor(
+    out: &mut impl Write,
+    storage: &LoreStorage,
+    initial: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let buffer = storage.trace_buffer_path();
+    let initial = match std::fs::read_to_string(&buffer) {
+        Ok(leftover) => {
+            writeln!(
+                out,
+                "{} Continuing the trace left in {} by an earlier editor session",
+                "Info:".blue(),
+                buffer.display()
+            )?;
+            leftover
+        }
+        Err(_) => initial.to_string(),
+    };
+
+    let edited = match editor_command() {
+        Some(editor) => edit_in_editor(&editor, &buffer, &initial),
+        None => Err("Neither $VISUAL nor $EDITOR is set; falling back to prompts".into()),
+    };
+    match edited {
+        Ok(edited) => {
+            std::fs::remove_file(&buffer)?;
+            let trace = strip_instructions(&edited);
+            if trace.is_empty() {
+                return Err(CommandError::InvalidInput(
+                    "Empty reasoning trace, aborting".to_string(),
+                )
+                .into());
+            }
+            Ok(Some(trace))
+        }
+        Err(e) => {
+            writeln!(out, "{} {}", "Warning:".yellow(), e)?;
+            if buffer.exists() {
+                writeln!(
+                    out,
+                    "  Your text is kept in {} and reopened by the next record.",
+                    buffer.display()
+                )?;
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Let the user edit `initial` in `editor` through the file at `path`. The
+/// file is left in place if the editor can't be started or exits non-zero.
+fn edit_in_editor(
+    editor: &str,
+    path: &Path,
+    initial: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    std::fs::write(path, initial)?;
+
+    // Allow editors with arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| format!("Couldn't run {}: {}; falling back to prompts", editor, e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}; falling back to prompts", editor, status).into());
+    }
+
+    Ok(std::fs::read_to_string(path)?)
+}
+
+/// Drop `#lore:` instruction lines from an edited trace
+fn strip_instructions(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with("#lore:"))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// A heading in a template, with the `#lore:` instructions that follow it
+#[derive(Debug, PartialEq)]
+struct TemplateSection<'a> {
+    header: &'a str,
+    hints: Vec<&'a str>,
+}
+
+/// The headings of a template, e.g. `## Context`. Instructions before the
+/// first heading are dropped.
+fn template_sections(template: &str) -> Vec<TemplateSection<'_>> {
+    let mut sections: Vec<TemplateSection> = Vec::new();
+    for line in template.lines().map(str::trim) {
+        if let Some(hint) = line.strip_prefix("#lore:") {
+            if let Some(section) = sections.last_mut() {
+                section.hints.push(hint.trim());
+            }
+        } else if line.starts_with('#') {
+            sections.push(TemplateSection {
+                header: line,
+                hints: Vec::new(),
+            });
+        }
+    }
+    sections
+}
+
+/// Prompt for each of the template's sections in turn. Sections left empty
+/// are omitted from the trace.
+fn prompt_for_sections(
+    out: &mut impl Write,
+    template: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let sections = template_sections(template);
+    if sections.is_empty() {
+        return prompt_for_multiline_input(
+            out,
+            "Enter reasoning trace (end with a line containing only '.', or Ctrl+D):",
+        );
+    }
+
+    writeln!(
+        out,
+        "{}",
+        "Enter reasoning trace, one section at a time (end each with a line containing only '.'):"
+            .cyan()
+    )?;
+    let mut parts = Vec::new();
+    for section in sections {
+        writeln!(out)?;
+        writeln!(out, "{}", section.header.bold())?;
+        for hint in &section.hints {
+            writeln!(out, "  {}", hint.dimmed())?;
+        }
+        let body = read_lines_until_dot()?;
+        if !body.is_empty() {
+            parts.push(format!("{}\n{}", section.header, body));
+        }
+    }
+
+    Ok(parts.join("\n\n"))
+}
+
+/// Read the trace from --trace, --trace-file or --stdin without prompting
+fn read_trace_chunk(options: &RecordOptions) -> Result<Option<String>, Box<dyn std::error::Error>> {
     if let Some(trace) = &options.trace {
-        return Ok(trace.clone());
+        return Ok(Some(trace.clone()));
     }
 
     if let Some(trace_file) = &options.trace_file {
         let content = std::fs::read_to_string(trace_file)?;
-        return Ok(content);
+        return Ok(Some(content));
     }
 
     if options.stdin {
-        println!(
-            "{}",
-            "Reading reasoning trace from stdin (Ctrl+D to end):".yellow()
-        );
+        // Only a person typing needs the hint, and it must stay out of --json output
+        if !options.json && io::stdin().is_terminal() {
+            eprintln!(
+                "{}",
+                "Reading reasoning trace from stdin (Ctrl+D to end):".yellow()
+            );
+        }
         let mut buffer = String::new();
         io::stdin().read_to_string(&mut buffer)?;
-        return Ok(buffer);
+        return Ok(Some(buffer));
     }
 
-    // Prompt for reasoning
-    prompt_for_multiline_input("Enter reasoning trace (empty line to finish):")
+    Ok(None)
 }
 
-fn prompt_for_input(prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-    print!("{} ", prompt.cyan());
-    io::stdout().flush()?;
+fn prompt_for_input(
+    out: &mut impl Write,
+    prompt: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    write!(out, "{} ", prompt.cyan())?;
+    out.flush()?;
 
     let stdin = io::stdin();
     let mut line = String::new();
@@ -186,15 +1761,23 @@ fn prompt_for_input(prompt: &str) -> Result<String, Box<dyn std::error::Error>>
     Ok(line.trim().to_string())
 }
 
-fn prompt_for_multiline_input(prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-    println!("{}", prompt.cyan());
+fn prompt_for_multiline_input(
+    out: &mut impl Write,
+    prompt: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    writeln!(out, "{}", prompt.cyan())?;
+    read_lines_until_dot()
+}
 
+/// Read lines until EOF or a line containing only `.`, so pasted text can
+/// contain blank lines
+fn read_lines_until_dot() -> Result<String, Box<dyn std::error::Error>> {
     let stdin = io::stdin();
     let mut lines = Vec::new();
 
     for line in stdin.lock().lines() {
         let line = line?;
-        if line.is_empty() {
+        if line == "." {
             break;
         }
         lines.push(line);
@@ -202,3 +1785,605 @@ fn prompt_for_multiline_input(prompt: &str) -> Result<String, Box<dyn std::error
 
     Ok(lines.join("\n"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, LoreStorage) {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+        std::fs::write(temp_dir.path().join("a.rs"), "fn a() {}").unwrap();
+        (temp_dir, storage)
+    }
+
+    #[test]
+    fn test_finish_draft_records_entries_and_clears_draft() {
+        let (temp_dir, storage) = setup();
+
+        let mut draft = PendingDraft::new("agent".to_string());
+        draft.add_files(&["a.rs".to_string()]);
+        draft.intent = Some("Streamed intent".to_string());
+        draft.append_chunk("Chunk one");
+        draft.append_chunk("Chunk two");
+        storage.save_pending(&draft).unwrap();
+
+        let mut diagnostics = Diagnostics::new(false);
+        let recorded = finish_draft(
+            &mut Vec::new(),
+            &storage,
+            temp_dir.path(),
+            &draft,
+            true,
+            &mut diagnostics,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(recorded.len(), 1);
+
+        let entries = storage.get_entries_for_file("a.rs").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].reasoning_trace, "Chunk one\nChunk two");
+        assert_eq!(entries[0].intent, "Streamed intent");
+        assert!(storage.load_pending().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_follow_renames_moves_entries_of_renamed_files() {
+        let (temp_dir, storage) = setup();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .unwrap();
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        git(&["add", "a.rs"]);
+        git(&["commit", "-m", "Add a.rs"]);
+
+        let entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+        git(&["mv", "a.rs", "b.rs"]);
+
+        let moved =
+            follow_renames(&mut Vec::new(), &storage, temp_dir.path(), false, true).unwrap();
+        assert_eq!(moved, 1);
+        assert!(storage.get_entries_for_file("a.rs").unwrap().is_empty());
+        let entries = storage.get_entries_for_file("b.rs").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].target_file, "b.rs");
+    }
+
+    #[test]
+    fn test_resolve_files_staged_and_unstaged() {
+        let (temp_dir, storage) = setup();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .unwrap();
+        };
+        git(&["init"]);
+        std::fs::write(temp_dir.path().join("b.rs"), "fn b() {}").unwrap();
+        git(&["add", "a.rs"]);
+
+        let resolve = |staged, unstaged| {
+            let mut diagnostics = Diagnostics::new(true);
+            let detect = AutoDetect {
+                staged,
+                unstaged,
+                since: None,
+            };
+            let mut files: Vec<String> = resolve_files(&storage, &[], detect, &mut diagnostics)
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect();
+            files.sort();
+            files
+        };
+        assert_eq!(resolve(false, false), vec!["a.rs", "b.rs"]);
+        assert_eq!(resolve(true, false), vec!["a.rs"]);
+        assert_eq!(resolve(false, true), vec!["b.rs"]);
+
+        // .loreignore applies to auto-detected files only
+        std::fs::write(temp_dir.path().join(".lore/.loreignore"), "b.rs\n").unwrap();
+        assert_eq!(resolve(false, false), vec!["a.rs"]);
+        let mut diagnostics = Diagnostics::new(true);
+        let explicit = resolve_files(
+            &storage,
+            &["b.rs".to_string()],
+            AutoDetect::default(),
+            &mut diagnostics,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(explicit.len(), 1);
+
+        // Given files must have the requested kind of change
+        let only = |staged, unstaged| AutoDetect {
+            staged,
+            unstaged,
+            since: None,
+        };
+        let files = ["./a.rs".to_string()];
+        let staged = resolve_files(&storage, &files, only(true, false), &mut diagnostics)
+            .unwrap()
+            .unwrap();
+        assert_eq!(staged, vec![("./a.rs".to_string(), ChangeType::Added)]);
+        let err = resolve_files(&storage, &files, only(false, true), &mut diagnostics).unwrap_err();
+        assert_eq!(err.to_string(), "a.rs has no unstaged changes");
+
+        // Nothing staged, but the tree isn't clean
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        git(&["commit", "-m", "Add a.rs"]);
+        assert!(
+            resolve_files(&storage, &[], only(true, false), &mut diagnostics)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            diagnostics.warnings().last().unwrap().code,
+            WarningCode::NoChanges
+        );
+    }
+
+    #[test]
+    fn test_read_rejected_file_json_and_yaml() {
+        let dir = TempDir::new().unwrap();
+        let json = dir.path().join("rejected.json");
+        std::fs::write(
+            &json,
+            r#"[{"name": " Auth0 SDK ", "reason": "too heavy"}, {"name": "Redis"}]"#,
+        )
+        .unwrap();
+        let alternatives = read_rejected_file(&json).unwrap();
+        assert_eq!(alternatives.len(), 2);
+        assert_eq!(alternatives[0].name, "Auth0 SDK");
+        assert_eq!(alternatives[0].reason.as_deref(), Some("too heavy"));
+        assert!(alternatives[1].reason.is_none());
+
+        let yaml = dir.path().join("rejected.yaml");
+        std::fs::write(
+            &yaml,
+            "- name: Polling\n  reason: wastes requests\n- name: Webhooks\n  reason: ''\n",
+        )
+        .unwrap();
+        let alternatives = read_rejected_file(&yaml).unwrap();
+        assert_eq!(alternatives[0].reason.as_deref(), Some("wastes requests"));
+        assert!(alternatives[1].reason.is_none());
+    }
+
+    #[test]
+    fn test_read_attachments_enforces_size_and_unique_names() {
+        let (temp_dir, storage) = setup();
+        let log = temp_dir.path().join("test.log");
+        std::fs::write(&log, "failed: 1").unwrap();
+
+        let attachments = read_attachments(&storage, std::slice::from_ref(&log)).unwrap();
+        assert_eq!(attachments[0].0.filename, "test.log");
+        assert_eq!(attachments[0].0.size, 9);
+        assert_eq!(attachments[0].1, b"failed: 1");
+
+        let other = temp_dir.path().join("other");
+        std::fs::create_dir(&other).unwrap();
+        std::fs::write(other.join("test.log"), "").unwrap();
+        assert!(read_attachments(&storage, &[log.clone(), other.join("test.log")]).is_err());
+
+        let mut config = storage.load_config().unwrap();
+        config.max_attachment_size = 4;
+        storage.save_config(&config).unwrap();
+        let err = read_attachments(&storage, &[log]).unwrap_err();
+        assert!(err.to_string().contains("max_attachment_size"));
+    }
+
+    #[test]
+    fn test_read_manifest() {
+        let (temp_dir, _storage) = setup();
+        let path = temp_dir.path().join("manifest.yaml");
+        std::fs::write(
+            &path,
+            "- files: [a.rs]\n  intent: Split parser\n  trace: Too long\n  tags: [parser]\n  rejected:\n    - name: ' Macros '\n  lines: 1-3\n- files: [./a.rs]\n  intent: Second\n",
+        )
+        .unwrap();
+
+        let records = read_manifest(&path, temp_dir.path(), None).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].intent, "Split parser");
+        assert_eq!(records[0].rejected[0].name, "Macros");
+        assert_eq!(records[0].line_range, Some((1, 3)));
+        assert_eq!(records[1].files[0].0, "a.rs");
+        assert!(records[1].trace.is_empty());
+    }
+
+    #[test]
+    fn test_read_manifest_fails_on_any_bad_record() {
+        let (temp_dir, _storage) = setup();
+        let path = temp_dir.path().join("manifest.json");
+        let check = |manifest: &str| {
+            std::fs::write(&path, manifest).unwrap();
+            read_manifest(&path, temp_dir.path(), Some(8))
+                .err()
+                .map(|e| e.to_string())
+        };
+
+        let err = check(
+            r#"[{"files": ["a.rs"], "intent": "ok"}, {"files": ["gone.rs"], "intent": "x"}]"#,
+        )
+        .unwrap();
+        assert!(err.contains("record 2: gone.rs not found"), "{}", err);
+        assert!(check(r#"[{"files": [], "intent": "x"}]"#).is_some());
+        assert!(check(r#"[{"files": ["a.rs"], "intent": " "}]"#).is_some());
+        assert!(check(r#"[{"files": ["a.rs"], "intent": "x", "lines": "9-2"}]"#).is_some());
+        assert!(check(r#"[{"files": ["a.rs"], "intent": "x", "typo": 1}]"#).is_some());
+        assert!(check("[]").is_some());
+        let err = check(
+            r#"[{"files": ["a.rs"], "intent": "ok"}, {"files": ["a.rs"], "intent": "x", "trace": "Far too long"}]"#,
+        )
+        .unwrap();
+        assert!(
+            err.contains("record 2: Reasoning trace is 12 bytes"),
+            "{}",
+            err
+        );
+        assert!(check(r#"[{"files": ["a.rs"], "intent": "x"}]"#).is_none());
+    }
+
+    #[test]
+    fn test_read_rejected_file_rejects_bad_input() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rejected.json");
+
+        std::fs::write(&path, r#"[{"name": "  "}]"#).unwrap();
+        assert!(read_rejected_file(&path).is_err());
+
+        std::fs::write(&path, r#"{"name": "not a list"}"#).unwrap();
+        assert!(read_rejected_file(&path).is_err());
+
+        assert!(read_rejected_file(&dir.path().join("missing.yaml")).is_err());
+    }
+
+    #[test]
+    fn test_strip_instructions() {
+        let edited = "#lore: Why was this needed?\n## Context\nSessions expired\n  #lore: indented too\n## Decision\nUse JWT\n";
+        assert_eq!(
+            strip_instructions(edited),
+            "## Context\nSessions expired\n## Decision\nUse JWT"
+        );
+    }
+
+    #[test]
+    fn test_untouched_editor_header_is_empty_trace() {
+        assert_eq!(strip_instructions(EDITOR_HEADER), "");
+
+        let edited = format!("{}First paragraph\n\nSecond paragraph\n", EDITOR_HEADER);
+        assert_eq!(
+            strip_instructions(&edited),
+            "First paragraph\n\nSecond paragraph"
+        );
+    }
+
+    #[test]
+    fn test_template_sections_collect_hints() {
+        let template =
+            "#lore: dropped, no heading yet\n## Context\n#lore: What prompted this?\n\n## Risks\n";
+        assert_eq!(
+            template_sections(template),
+            vec![
+                TemplateSection {
+                    header: "## Context",
+                    hints: vec!["What prompted this?"],
+                },
+                TemplateSection {
+                    header: "## Risks",
+                    hints: Vec::new(),
+                },
+            ]
+        );
+        assert!(template_sections("Just text").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_in_editor_keeps_buffer_on_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let buffer = temp_dir.path().join("TRACE_EDITMSG.md");
+
+        assert_eq!(
+            edit_in_editor("true", &buffer, "## Context\n").unwrap(),
+            "## Context\n"
+        );
+
+        let err = edit_in_editor("false", &buffer, "typed text").unwrap_err();
+        assert!(err.to_string().contains("exited with"));
+        assert_eq!(std::fs::read_to_string(&buffer).unwrap(), "typed text");
+
+        assert!(edit_in_editor("no-such-editor-xyz", &buffer, "").is_err());
+    }
+
+    #[test]
+    fn test_split_commit_message() {
+        assert_eq!(
+            split_commit_message("Fix auth\n\nTokens expired early.\nSee #12\n"),
+            (
+                "Fix auth".to_string(),
+                "Tokens expired early.\nSee #12".to_string()
+            )
+        );
+        assert_eq!(
+            split_commit_message("Just a summary\n"),
+            ("Just a summary".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn test_join_trace_appends_after_commit_body() {
+        assert_eq!(join_trace("Body", "  More\n"), "Body\n\n---\n\nMore");
+        assert_eq!(join_trace("Body", ""), "Body");
+        assert_eq!(join_trace("", "More"), "More");
+    }
+
+    #[test]
+    fn test_finish_draft_skips_missing_files() {
+        let (temp_dir, storage) = setup();
+
+        let mut draft = PendingDraft::new("agent".to_string());
+        draft.add_files(&["a.rs".to_string(), "missing.rs".to_string()]);
+        draft.intent = Some("Intent".to_string());
+        draft.append_chunk("Reasoning");
+
+        let mut diagnostics = Diagnostics::new(true);
+        let recorded = finish_draft(
+            &mut Vec::new(),
+            &storage,
+            temp_dir.path(),
+            &draft,
+            true,
+            &mut diagnostics,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(diagnostics.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_write_entries_for_commit_hashes_blob_not_working_tree() {
+        let (temp_dir, storage) = setup();
+        for args in [
+            &["init"][..],
+            &["config", "user.email", "test@test.com"],
+            &["config", "user.name", "Test User"],
+            &["add", "a.rs"],
+            &["commit", "-m", "Add a"],
+        ] {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .expect("Failed to run git");
+        }
+        let commit = GitContext::open(temp_dir.path())
+            .unwrap()
+            .head_commit()
+            .unwrap();
+        std::fs::remove_file(temp_dir.path().join("a.rs")).unwrap();
+
+        let content = EntryContent {
+            agent_id: "agent",
+            intent: "Intent",
+            reasoning_trace: "Reasoning",
+            rejected_alternatives: &[],
+            tags: &[],
+            metadata: &BTreeMap::new(),
+            confidence: None,
+            references: &[],
+            attachments: &[],
+            line_range: None,
+            symbol: None,
+            anchor: None,
+            capture_diff: false,
+            commit_hash: Some(&commit),
+            supersedes: None,
+            related: &[],
+            project: None,
+            dry_run: false,
+            allow_duplicate: false,
+        };
+        let files = vec![("a.rs".to_string(), ChangeType::Added)];
+
+        let mut diagnostics = Diagnostics::new(true);
+        let recorded = write_entries(
+            &mut Vec::new(),
+            &storage,
+            temp_dir.path(),
+            &files,
+            &content,
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].file_hash, hash_bytes(b"fn a() {}"));
+        assert_eq!(recorded[0].commit_hash.as_deref(), Some(commit.as_str()));
+        assert_eq!(
+            recorded[0].commit_author.as_deref(),
+            Some("Test User <test@test.com>")
+        );
+    }
+
+    #[test]
+    fn test_dry_run_builds_entries_without_saving() {
+        let (temp_dir, storage) = setup();
+
+        let content = EntryContent {
+            agent_id: "agent",
+            intent: "Intent",
+            reasoning_trace: "Reasoning",
+            rejected_alternatives: &[],
+            tags: &["auth".to_string()],
+            metadata: &BTreeMap::new(),
+            confidence: None,
+            references: &[],
+            attachments: &[],
+            line_range: None,
+            symbol: None,
+            anchor: None,
+            capture_diff: false,
+            commit_hash: None,
+            supersedes: None,
+            related: &[],
+            project: None,
+            dry_run: true,
+            allow_duplicate: false,
+        };
+        let files = vec![("a.rs".to_string(), ChangeType::Modified)];
+
+        let mut diagnostics = Diagnostics::new(true);
+        let previewed = write_entries(
+            &mut Vec::new(),
+            &storage,
+            temp_dir.path(),
+            &files,
+            &content,
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        assert_eq!(previewed.len(), 1);
+        assert_eq!(previewed[0].file_hash, hash_bytes(b"fn a() {}"));
+        assert_eq!(previewed[0].tags, vec!["auth".to_string()]);
+        assert!(!storage.entry_exists(&previewed[0].id));
+        assert_eq!(storage.load_index().unwrap().entry_count, 0);
+        assert!(storage.load_audit_log().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failing_file_leaves_no_entries_or_attachments() {
+        let (temp_dir, storage) = setup();
+        std::fs::write(temp_dir.path().join("b.rs"), "// b\nfn b() {}").unwrap();
+        let log = b"bench: 12ms".to_vec();
+        let attachments = vec![(
+            Attachment {
+                filename: "bench.txt".to_string(),
+                size: log.len() as u64,
+                sha256: hash_bytes(&log),
+            },
+            log,
+        )];
+
+        // The anchor is on line 1 of a.rs, but not of b.rs
+        let content = EntryContent {
+            agent_id: "agent",
+            intent: "Intent",
+            reasoning_trace: "Reasoning",
+            rejected_alternatives: &[],
+            tags: &[],
+            metadata: &BTreeMap::new(),
+            confidence: None,
+            references: &[],
+            attachments: &attachments,
+            line_range: Some((1, 1)),
+            symbol: None,
+            anchor: Some("fn"),
+            capture_diff: false,
+            commit_hash: None,
+            supersedes: None,
+            related: &[],
+            project: None,
+            dry_run: false,
+            allow_duplicate: false,
+        };
+        let files = vec![
+            ("a.rs".to_string(), ChangeType::Modified),
+            ("b.rs".to_string(), ChangeType::Modified),
+        ];
+
+        let mut diagnostics = Diagnostics::new(true);
+        assert!(write_entries(
+            &mut Vec::new(),
+            &storage,
+            temp_dir.path(),
+            &files,
+            &content,
+            &mut diagnostics
+        )
+        .is_err());
+        assert_eq!(storage.load_index().unwrap().entry_count, 0);
+        assert!(!temp_dir.path().join(".lore/attachments").exists());
+    }
+
+    #[test]
+    fn test_json_report_includes_skipped_file_warning() {
+        let (temp_dir, storage) = setup();
+
+        let metadata = BTreeMap::from([
+            ("model".to_string(), "gpt-4".to_string()),
+            ("ticket".to_string(), "https://example.com/T-1".to_string()),
+        ]);
+        let content = EntryContent {
+            agent_id: "agent",
+            intent: "Intent",
+            reasoning_trace: "Reasoning",
+            rejected_alternatives: &[],
+            tags: &[],
+            metadata: &metadata,
+            confidence: None,
+            references: &[],
+            attachments: &[],
+            line_range: None,
+            symbol: None,
+            anchor: None,
+            capture_diff: false,
+            commit_hash: None,
+            supersedes: None,
+            related: &[],
+            project: None,
+            dry_run: false,
+            allow_duplicate: false,
+        };
+        let files = vec![
+            ("a.rs".to_string(), ChangeType::Modified),
+            ("missing.rs".to_string(), ChangeType::Modified),
+        ];
+
+        let mut diagnostics = Diagnostics::new(true);
+        let recorded = write_entries(
+            &mut Vec::new(),
+            &storage,
+            temp_dir.path(),
+            &files,
+            &content,
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(diagnostics.into_report(recorded)).unwrap();
+        assert_eq!(json["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(json["entries"][0]["target_file"], "a.rs");
+        assert_eq!(json["entries"][0]["metadata"]["model"], "gpt-4");
+        assert_eq!(
+            json["entries"][0]["metadata"]["ticket"],
+            "https://example.com/T-1"
+        );
+
+        let warnings = json["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0]["code"], "file_not_found");
+        assert_eq!(warnings[0]["file"], "missing.rs");
+    }
+}
@@ -0,0 +1,88 @@
+use crate::output::Glyph;
+use crate::storage::{self, LoreStorage};
+use colored::Colorize;
+use std::io::Write;
+
+/// Width of the progress bar, in characters
+const PROGRESS_WIDTH: usize = 40;
+
+pub struct ReindexOptions {
+    pub parallel: bool,
+    pub progress: bool,
+    pub resume: bool,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: ReindexOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let show_progress = |done: usize, total: usize| {
+        let filled = (done * PROGRESS_WIDTH)
+            .checked_div(total)
+            .unwrap_or(PROGRESS_WIDTH);
+        eprint!(
+            "\r[{}{}] {}/{}",
+            "#".repeat(filled),
+            " ".repeat(PROGRESS_WIDTH - filled),
+            done,
+            total
+        );
+    };
+
+    let report = storage.rebuild_index_with(&storage::ReindexOptions {
+        parallel: options.parallel,
+        resume: options.resume,
+        progress: options
+            .progress
+            .then_some(&show_progress as &dyn Fn(usize, usize)),
+    })?;
+    if options.progress {
+        eprintln!();
+    }
+
+    if report.resumed > 0 {
+        writeln!(
+            out,
+            "{} Resumed with {} entries from an interrupted rebuild",
+            "Info:".blue(),
+            report.resumed
+        )?;
+    }
+
+    for name in &report.failed {
+        eprintln!(
+            "{} Skipping unreadable entry file {}",
+            "Warning:".yellow(),
+            name
+        );
+    }
+
+    writeln!(
+        out,
+        "{} Rebuilt index with {} {}",
+        Glyph::Check.as_str().green(),
+        report.indexed.to_string().green(),
+        if report.indexed == 1 {
+            "entry"
+        } else {
+            "entries"
+        }
+    )?;
+
+    if !report.failed.is_empty() {
+        writeln!(
+            out,
+            "{} {} entry {} could not be parsed",
+            "Warning:".yellow(),
+            report.failed.len(),
+            if report.failed.len() == 1 {
+                "file"
+            } else {
+                "files"
+            }
+        )?;
+    }
+
+    Ok(())
+}
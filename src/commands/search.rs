@@ -1,130 +1,338 @@
+use crate::commands::CommandError;
+use crate::filter::{
+    has_metadata, is_confident, overlaps_range, page, retain_in_range, retain_project,
+    retain_tagged,
+};
 use crate::models::ThoughtObject;
-use crate::storage::{find_lore_root, LoreStorage};
+use crate::output::{self, heavy_rule, rule, Format, Glyph};
+use crate::query::Query;
+use crate::storage::{EntryMatcher, LoreStorage};
+use chrono::{DateTime, Utc};
 use colored::Colorize;
+use regex::Regex;
+use serde::Serialize;
+use std::io::{self, Write};
 
 pub struct SearchOptions {
-    pub query: String,
-    pub json: bool,
+    pub query: Option<String>,
+    pub format: Format,
     pub limit: Option<usize>,
+    pub offset: usize,
     pub file_filter: Option<String>,
     pub agent_filter: Option<String>,
+    pub regex: bool,
+    pub ignore_case: bool,
+    pub word: bool,
+    pub any: bool,
+    pub with_snippet: bool,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub any_tag: bool,
+    pub metadata: Vec<(String, String)>,
+    pub min_confidence: Option<f32>,
+    pub project: Option<String>,
+    pub line_range: Option<(usize, usize)>,
 }
 
-pub fn execute(options: SearchOptions) -> Result<(), Box<dyn std::error::Error>> {
-    // Find lore root
-    let current_dir = std::env::current_dir()?;
-    let root =
-        find_lore_root(&current_dir).ok_or("Lore not initialized. Run 'lore init' first.")?;
+/// A matching entry with its relevance, as emitted by `search --format json`
+#[derive(Serialize)]
+struct SearchResult {
+    #[serde(flatten)]
+    entry: ThoughtObject,
+    /// Absent for a pure line-range query, which isn't ranked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<usize>,
+    /// The reasoning snippet shown in text mode (`--with-snippet`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+    /// Where the query matches within `snippet` (`--with-snippet`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highlight_spans: Option<Vec<HighlightSpan>>,
+}
+
+/// A highlighted part of a snippet, in character (not byte) offsets, end exclusive
+#[derive(Debug, Serialize, PartialEq, Eq)]
+struct HighlightSpan {
+    start: usize,
+    end: usize,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: SearchOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if options.with_snippet && !options.format.is_structured() {
+        return Err(CommandError::InvalidInput(
+            "--with-snippet needs --format json, yaml or toml".to_string(),
+        )
+        .into());
+    }
 
-    let storage = LoreStorage::new(root);
+    // Terms joined by AND/OR/NOT (not in regex mode, which has its own operators)
+    let expression = match &options.query {
+        Some(query) if !options.regex => Some(Query::parse(query, options.any).map_err(|e| {
+            CommandError::InvalidInput(format!("Invalid search query '{}': {}", query, e))
+        })?),
+        _ => None,
+    };
 
-    // Search for matching entries
-    let mut entries = storage.search(&options.query)?;
+    // Build the matcher used for searching (regex mode) and for snippets/highlighting
+    let matcher = build_matcher(&options, expression.as_ref())?;
+
+    // No text matcher for a pure line-range query
+    let entry_matcher = match (expression, matcher.clone()) {
+        (Some(expression), _) => Some(EntryMatcher::expression(expression, options.word)),
+        (None, Some(re)) => Some(EntryMatcher::Regex(re)),
+        (None, None) => None,
+    };
+
+    // Narrow down using the index before reading any entry files
+    let mut metas = storage.get_entry_metas()?;
 
-    // Apply additional filters
     if let Some(file_filter) = &options.file_filter {
-        entries.retain(|e| e.target_file.contains(file_filter));
+        metas.retain(|m| m.target_file.contains(file_filter));
     }
 
     if let Some(agent_filter) = &options.agent_filter {
-        entries.retain(|e| e.agent_id.contains(agent_filter));
+        metas.retain(|m| m.agent_id.contains(agent_filter));
+    }
+
+    retain_in_range(&mut metas, options.since, options.until);
+    retain_tagged(&mut metas, &options.tags, options.any_tag);
+    retain_project(&mut metas, options.project.as_deref());
+
+    // Skip entries the search index rules out
+    if let Some(EntryMatcher::Expression { query, .. }) = &entry_matcher {
+        if let Some(ids) = storage.search_candidates(query)? {
+            metas.retain(|m| ids.contains(&m.id));
+        }
     }
 
-    // Apply limit
-    if let Some(limit) = options.limit {
-        entries.truncate(limit);
+    // Match the full text of the remaining entries. Ranking needs every match,
+    // but a pure line-range query keeps the index order, so stop reading entry
+    // files once the page is full.
+    let wanted = match &entry_matcher {
+        Some(_) => None,
+        None => options.limit.map(|limit| options.offset + limit),
+    };
+    let entries: Vec<ThoughtObject> = storage
+        .stream_entries(&metas)
+        .filter(|e| entry_matcher.as_ref().is_none_or(|m| m.matches(e)))
+        .filter(|e| {
+            overlaps_range(e, options.line_range)
+                && has_metadata(e, &options.metadata)
+                && is_confident(e, options.min_confidence)
+        })
+        .take(wanted.unwrap_or(usize::MAX))
+        .collect();
+
+    // Most relevant first; equal scores keep the newest-first order
+    let mut results: Vec<SearchResult> = entries
+        .into_iter()
+        .map(|entry| SearchResult {
+            score: entry_matcher.as_ref().map(|m| m.score(&entry)),
+            entry,
+            snippet: None,
+            highlight_spans: None,
+        })
+        .collect();
+    results.sort_by_key(|r| std::cmp::Reverse(r.score));
+
+    // Apply offset and limit
+    page(&mut results, options.offset, options.limit);
+
+    if options.format.is_structured() && options.with_snippet {
+        for result in &mut results {
+            let snippet = reasoning_snippet(&result.entry, matcher.as_ref());
+            result.highlight_spans = Some(
+                matcher
+                    .as_ref()
+                    .map(|matcher| highlight_spans(&snippet, matcher))
+                    .unwrap_or_default(),
+            );
+            result.snippet = Some(snippet);
+        }
     }
 
-    if entries.is_empty() {
-        println!(
-            "{} No entries found matching '{}'",
+    let description = describe_query(&options);
+
+    // Structured output is just empty, so consumers don't have to parse a message
+    if results.is_empty() && !options.format.is_structured() {
+        writeln!(
+            out,
+            "{} No entries found matching {}",
             "Info:".blue(),
-            options.query.cyan()
-        );
+            description.cyan()
+        )?;
         return Ok(());
     }
 
-    if options.json {
-        // Output as JSON
-        let json = serde_json::to_string_pretty(&entries)?;
-        println!("{}", json);
+    if options.format.is_structured() {
+        output::print(out, &results, options.format)?;
     } else {
         // Pretty print search results
-        print_search_results(&options.query, &entries);
+        print_search_results(out, &description, matcher.as_ref(), &results)?;
     }
 
     Ok(())
 }
 
-fn print_search_results(query: &str, entries: &[ThoughtObject]) {
-    println!();
-    println!("{}", "═".repeat(60).dimmed());
-    println!(
+/// How the query is shown in output: the quoted text and/or the line range
+fn describe_query(options: &SearchOptions) -> String {
+    match (&options.query, options.line_range) {
+        (Some(query), None) => format!("'{}'", query),
+        (Some(query), Some((start, end))) => format!("'{}' in lines {}-{}", query, start, end),
+        (None, Some((start, end))) => format!("lines {}-{}", start, end),
+        (None, None) => String::new(),
+    }
+}
+
+/// Compile the query into a regex, or `None` when there is no text query.
+/// Plain queries are escaped and always case-insensitive; `--regex` queries are
+/// case-sensitive unless `--ignore-case`. `--word` wraps the pattern in word boundaries.
+/// For a boolean expression the regex matches any of its non-negated terms.
+fn build_matcher(
+    options: &SearchOptions,
+    expression: Option<&Query>,
+) -> Result<Option<Regex>, Box<dyn std::error::Error>> {
+    let Some(query) = &options.query else {
+        return Ok(None);
+    };
+
+    let mut pattern = if options.regex {
+        query.clone()
+    } else if let Some(expression) = expression {
+        let terms: Vec<String> = expression
+            .positive_terms()
+            .into_iter()
+            .map(regex::escape)
+            .collect();
+        // An all-negated query has nothing to highlight
+        if terms.is_empty() {
+            r"[^\s\S]".to_string()
+        } else {
+            terms.join("|")
+        }
+    } else {
+        regex::escape(query)
+    };
+
+    if options.word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+
+    if !options.regex || options.ignore_case {
+        pattern = format!("(?i){}", pattern);
+    }
+
+    Regex::new(&pattern).map(Some).map_err(|e| {
+        CommandError::InvalidInput(format!("Invalid regular expression '{}': {}", query, e)).into()
+    })
+}
+
+fn print_search_results(
+    out: &mut impl Write,
+    query: &str,
+    matcher: Option<&Regex>,
+    results: &[SearchResult],
+) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    writeln!(
+        out,
         "{} {} ({} results)",
         "Search:".bold(),
         query.cyan().bold(),
-        entries.len()
-    );
-    println!("{}", "═".repeat(60).dimmed());
-
-    for entry in entries {
-        println!();
-        println!("{} {}", "File:".bold(), entry.target_file.cyan());
-        println!(
+        results.len()
+    )?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+
+    for SearchResult { entry, .. } in results {
+        writeln!(out)?;
+        writeln!(out, "{} {}", "File:".bold(), entry.target_file.cyan())?;
+        writeln!(
+            out,
             "{} {} {} {}",
             "Agent:".bold(),
             entry.agent_id.yellow(),
-            "│".dimmed(),
+            Glyph::Bar.as_str().dimmed(),
             entry
                 .timestamp
                 .format("%Y-%m-%d %H:%M")
                 .to_string()
                 .dimmed()
-        );
+        )?;
+
+        if let Some((start, end)) = entry.line_range {
+            writeln!(out, "{} Lines {}-{}", "Range:".bold(), start, end)?;
+        }
 
         // Show intent
-        println!("{} {}", "Intent:".bold(), entry.intent);
+        writeln!(out, "{} {}", "Intent:".bold(), entry.intent)?;
 
         // Show snippet of reasoning trace with highlighted query
-        let snippet = create_snippet(&entry.reasoning_trace, query, 150);
+        let snippet = reasoning_snippet(entry, matcher);
         if !snippet.is_empty() {
-            println!("{}", "Reasoning snippet:".dimmed());
-            println!("  {}", highlight_query(&snippet, query));
+            writeln!(out, "{}", "Reasoning snippet:".dimmed())?;
+            match matcher {
+                Some(matcher) => writeln!(out, "  {}", highlight_query(&snippet, matcher))?,
+                None => writeln!(out, "  {}", snippet)?,
+            }
         }
 
         // Show rejected alternatives that match
         let matching_rejected: Vec<_> = entry
             .rejected_alternatives
             .iter()
-            .filter(|alt| alt.name.to_lowercase().contains(&query.to_lowercase()))
+            .filter_map(|alt| {
+                let matcher = matcher?;
+                // Show the reason when it's what matched
+                let reason = alt.reason.as_deref().filter(|r| matcher.is_match(r));
+                (matcher.is_match(&alt.name) || reason.is_some()).then_some((alt, reason))
+            })
             .collect();
 
         if !matching_rejected.is_empty() {
-            println!("{}", "Rejected alternatives:".dimmed());
-            for alt in matching_rejected {
-                println!("  {} {}", "✗".red(), alt.name);
+            writeln!(out, "{}", "Rejected alternatives:".dimmed())?;
+            for (alt, reason) in matching_rejected {
+                match reason {
+                    Some(reason) => writeln!(
+                        out,
+                        "  {} {} - {}",
+                        Glyph::Cross.as_str().red(),
+                        alt.name,
+                        reason.dimmed()
+                    )?,
+                    None => writeln!(out, "  {} {}", Glyph::Cross.as_str().red(), alt.name)?,
+                }
             }
         }
 
-        println!("{}", "─".repeat(60).dimmed());
+        writeln!(out, "{}", rule(60).dimmed())?;
     }
 
-    println!();
-    println!(
+    writeln!(out)?;
+    writeln!(
+        out,
         "{}",
         "Tip: Use 'lore explain <file>' for full details".dimmed()
-    );
+    )?;
+    Ok(())
 }
 
-/// Create a snippet around the matching query
-fn create_snippet(text: &str, query: &str, max_len: usize) -> String {
-    let text_lower = text.to_lowercase();
-    let query_lower = query.to_lowercase();
+/// The part of an entry's reasoning shown in results
+fn reasoning_snippet(entry: &ThoughtObject, matcher: Option<&Regex>) -> String {
+    create_snippet(&entry.reasoning_trace, matcher, 150)
+}
 
-    if let Some(pos) = text_lower.find(&query_lower) {
+/// Create a snippet around the first match
+fn create_snippet(text: &str, matcher: Option<&Regex>, max_len: usize) -> String {
+    if let Some(m) = matcher.and_then(|matcher| matcher.find(text)) {
         // Find snippet boundaries
-        let start = pos.saturating_sub(50);
-        let end = (pos + query.len() + 100).min(text.len());
+        let start = floor_char_boundary(text, m.start().saturating_sub(50));
+        let end = floor_char_boundary(text, (m.end() + 100).min(text.len()));
 
         let mut snippet: String = text[start..end].to_string();
 
@@ -142,7 +350,7 @@ fn create_snippet(text: &str, query: &str, max_len: usize) -> String {
 
         // Truncate if still too long
         if snippet.len() > max_len {
-            snippet.truncate(max_len);
+            snippet.truncate(floor_char_boundary(&snippet, max_len));
             snippet = format!("{}...", snippet);
         }
 
@@ -158,24 +366,170 @@ fn create_snippet(text: &str, query: &str, max_len: usize) -> String {
     }
 }
 
-/// Highlight query matches in text
-fn highlight_query(text: &str, query: &str) -> String {
-    let text_lower = text.to_lowercase();
-    let query_lower = query.to_lowercase();
+/// Character ranges of the matches in `text`, for clients that do their own highlighting
+fn highlight_spans(text: &str, matcher: &Regex) -> Vec<HighlightSpan> {
+    let char_offset = |byte: usize| text[..byte].chars().count();
+    matcher
+        .find_iter(text)
+        .map(|m| HighlightSpan {
+            start: char_offset(m.start()),
+            end: char_offset(m.end()),
+        })
+        .collect()
+}
 
+/// Highlight matches in text
+fn highlight_query(text: &str, matcher: &Regex) -> String {
     let mut result = String::new();
     let mut last_end = 0;
 
-    for (start, _) in text_lower.match_indices(&query_lower) {
+    for m in matcher.find_iter(text) {
         // Add text before match
-        result.push_str(&text[last_end..start]);
+        result.push_str(&text[last_end..m.start()]);
         // Add highlighted match
-        let end = start + query.len();
-        result.push_str(&text[start..end].yellow().bold().to_string());
-        last_end = end;
+        result.push_str(&m.as_str().yellow().bold().to_string());
+        last_end = m.end();
     }
 
     // Add remaining text
     result.push_str(&text[last_end..]);
     result
 }
+
+/// Largest char boundary in `text` that is <= `index`
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(query: &str, regex: bool, ignore_case: bool) -> SearchOptions {
+        options_with_word(query, regex, ignore_case, false)
+    }
+
+    fn options_with_word(query: &str, regex: bool, ignore_case: bool, word: bool) -> SearchOptions {
+        SearchOptions {
+            query: Some(query.to_string()),
+            format: Format::Pretty,
+            limit: None,
+            offset: 0,
+            file_filter: None,
+            agent_filter: None,
+            regex,
+            ignore_case,
+            word,
+            any: false,
+            with_snippet: false,
+            since: None,
+            until: None,
+            tags: Vec::new(),
+            any_tag: false,
+            metadata: Vec::new(),
+            min_confidence: None,
+            project: None,
+            line_range: None,
+        }
+    }
+
+    #[test]
+    fn test_build_matcher_plain_query_is_escaped() {
+        let matcher = build_matcher(&options("a.b", false, false), None)
+            .unwrap()
+            .unwrap();
+        assert!(matcher.is_match("A.B"));
+        assert!(!matcher.is_match("axb"));
+    }
+
+    #[test]
+    fn test_build_matcher_regex_ignore_case() {
+        let matcher = build_matcher(&options("jwt", true, false), None)
+            .unwrap()
+            .unwrap();
+        assert!(!matcher.is_match("JWT"));
+
+        let matcher = build_matcher(&options("jwt", true, true), None)
+            .unwrap()
+            .unwrap();
+        assert!(matcher.is_match("JWT"));
+    }
+
+    #[test]
+    fn test_build_matcher_invalid_regex() {
+        let result = build_matcher(&options("(unclosed", true, false), None);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid regular expression"));
+    }
+
+    #[test]
+    fn test_build_matcher_word_boundaries() {
+        let matcher = build_matcher(&options_with_word("cat", false, false, true), None)
+            .unwrap()
+            .unwrap();
+        assert!(matcher.is_match("the Cat sat"));
+        assert!(!matcher.is_match("concatenate"));
+
+        let matcher = build_matcher(&options_with_word("ca.", true, false, true), None)
+            .unwrap()
+            .unwrap();
+        assert!(matcher.is_match("cat"));
+        assert!(!matcher.is_match("concatenate"));
+    }
+
+    #[test]
+    fn test_build_matcher_highlights_positive_terms() {
+        let query = "jwt OR refresh NOT cookie";
+        let expression = Query::parse(query, false).unwrap();
+        let matcher = build_matcher(&options(query, false, false), Some(&expression))
+            .unwrap()
+            .unwrap();
+        assert!(matcher.is_match("JWT"));
+        assert!(matcher.is_match("refresh"));
+        assert!(!matcher.is_match("cookie"));
+    }
+
+    #[test]
+    fn test_json_snippet_matches_text_snippet() {
+        let entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            format!("{}Zürich → JWT rotation, then jwt refresh", "x".repeat(80)),
+        );
+        let options = options("jwt", false, false);
+        let matcher = build_matcher(&options, None).unwrap().unwrap();
+
+        let snippet = reasoning_snippet(&entry, Some(&matcher));
+        assert_eq!(
+            snippet,
+            create_snippet(&entry.reasoning_trace, Some(&matcher), 150)
+        );
+
+        // Spans are character offsets into the snippet
+        let spans = highlight_spans(&snippet, &matcher);
+        assert_eq!(spans.len(), 2);
+        let chars: Vec<char> = snippet.chars().collect();
+        for span in &spans {
+            let matched: String = chars[span.start..span.end].iter().collect();
+            assert_eq!(matched.to_lowercase(), "jwt");
+        }
+    }
+
+    #[test]
+    fn test_create_snippet_uses_first_regex_match() {
+        let text = format!("{}needle 42 here", "x".repeat(200));
+        let matcher = Regex::new(r"needle \d+").unwrap();
+        let snippet = create_snippet(&text, Some(&matcher), 150);
+        assert!(snippet.starts_with("..."));
+        assert!(snippet.contains("needle 42"));
+    }
+}
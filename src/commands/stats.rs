@@ -0,0 +1,713 @@
+use crate::commands::tags::{count_tags, TagCount};
+use crate::filter::{retain_in_range, retain_tagged};
+use crate::git::GitContext;
+use crate::models::{EntryFields, ThoughtObject};
+use crate::output::{heavy_rule, Glyph};
+use crate::storage::{LoreIgnore, LoreStorage};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::Path;
+
+/// Width of the longest bar in the activity histogram
+const BAR_WIDTH: usize = 40;
+
+/// How far back in history to count commits per file
+const HISTORY_COMMITS: usize = 1000;
+
+/// Number of tags, agents and files shown in the text report (`--json` has
+/// them all)
+const TOP_LIMIT: usize = 10;
+
+/// Granularity of the activity histogram
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Day,
+    /// ISO 8601 weeks, starting on Monday
+    Week,
+    Month,
+    Quarter,
+}
+
+impl Bucket {
+    /// First day of the bucket containing `date`
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Bucket::Day => date,
+            Bucket::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+            Bucket::Month => date.with_day(1).unwrap(),
+            Bucket::Quarter => {
+                NaiveDate::from_ymd_opt(date.year(), date.month0() / 3 * 3 + 1, 1).unwrap()
+            }
+        }
+    }
+
+    /// First day of the bucket before the one starting at `start`
+    fn prev(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Bucket::Day => start - Duration::days(1),
+            Bucket::Week => start - Duration::weeks(1),
+            Bucket::Month => start - Months::new(1),
+            Bucket::Quarter => start - Months::new(3),
+        }
+    }
+
+    /// First day of the bucket after the one starting at `start`
+    fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Bucket::Day => start + Duration::days(1),
+            Bucket::Week => start + Duration::weeks(1),
+            Bucket::Month => start + Months::new(1),
+            Bucket::Quarter => start + Months::new(3),
+        }
+    }
+
+    /// Label of the bucket containing `date`: `2024-03-05`, `2024-W10`, `2024-03`
+    /// or `2024-Q1`. Weeks are labelled with their ISO week-numbering year, so
+    /// 2024-12-30 falls in `2025-W01`.
+    pub fn key(self, date: NaiveDate) -> String {
+        match self {
+            Bucket::Day => date.format("%Y-%m-%d").to_string(),
+            Bucket::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Bucket::Month => date.format("%Y-%m").to_string(),
+            Bucket::Quarter => format!("{}-Q{}", date.year(), date.month0() / 3 + 1),
+        }
+    }
+}
+
+pub struct StatsOptions {
+    pub json: bool,
+    pub bucket: Bucket,
+    /// Show only this many buckets, ending with the current one
+    pub last: Option<usize>,
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries recorded by this agent (exact match)
+    pub agent: Option<String>,
+    /// Only entries carrying all of these tags
+    pub tags: Vec<String>,
+}
+
+/// Number of entries recorded in one histogram bucket
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ActivityBucket {
+    pub bucket: String,
+    pub count: usize,
+}
+
+/// Entries recorded by one agent
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct AgentCount {
+    pub agent: String,
+    pub count: usize,
+    pub last_activity: DateTime<Utc>,
+}
+
+/// Entries recorded for one file
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct FileCount {
+    pub file: String,
+    pub count: usize,
+}
+
+/// How well a file's history is documented: entries recorded for it against
+/// commits that touched it
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FileCoverage {
+    pub file: String,
+    pub commits: usize,
+    pub entries: usize,
+    pub entries_per_commit: f64,
+}
+
+/// Machine-readable report emitted by `stats --json`
+#[derive(Debug, Serialize)]
+pub struct StatsReport {
+    pub entry_count: usize,
+    pub bucket: Bucket,
+    pub activity: Vec<ActivityBucket>,
+    /// Mean reasoning trace length, in characters
+    pub avg_trace_length: f64,
+    /// Entries that record at least one rejected alternative
+    pub with_alternatives: usize,
+    pub alternatives_ratio: f64,
+    /// Entries recorded against a git commit, and those recorded without one
+    pub with_commit: usize,
+    pub without_commit: usize,
+    pub tags: Vec<TagCount>,
+    /// Agents by number of entries, most first
+    pub agents: Vec<AgentCount>,
+    /// Files by number of entries, most first
+    pub top_files: Vec<FileCount>,
+    /// Files changed in git, least documented first. Empty outside a git
+    /// repository.
+    pub least_documented: Vec<FileCoverage>,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: StatsOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = storage.get_all_entries()?;
+    retain_in_range(&mut entries, options.since, None);
+    retain_tagged(&mut entries, &options.tags, false);
+    if let Some(agent) = &options.agent {
+        entries.retain(|e| &e.agent_id == agent);
+    }
+
+    let with_alternatives = entries
+        .iter()
+        .filter(|e| !e.rejected_alternatives.is_empty())
+        .count();
+    let with_commit = entries.iter().filter(|e| e.commit_hash.is_some()).count();
+    let scoped = options.since.is_some() || options.agent.is_some() || !options.tags.is_empty();
+
+    let root = storage.root();
+    let least_documented =
+        match GitContext::open(root).and_then(|git| git.commit_counts(HISTORY_COMMITS)) {
+            Ok(commits) => {
+                // Scoped stats only credit files with the entries in scope
+                let files = if scoped {
+                    entries_by_file(&entries)
+                } else {
+                    storage.load_index()?.files
+                };
+                let ignore = storage.loreignore()?;
+                least_documented(&files, &commits, |file| {
+                    is_tracked_file(root, &ignore, file)
+                })
+            }
+            Err(_) => Vec::new(),
+        };
+
+    let report = StatsReport {
+        entry_count: entries.len(),
+        bucket: options.bucket,
+        activity: match options.last {
+            Some(count) => {
+                recent_activity(&entries, options.bucket, count, Utc::now().date_naive())
+            }
+            None => activity(&entries, options.bucket),
+        },
+        avg_trace_length: average_trace_length(&entries),
+        with_alternatives,
+        alternatives_ratio: ratio(with_alternatives, entries.len()),
+        with_commit,
+        without_commit: entries.len() - with_commit,
+        tags: count_tags(&entries),
+        agents: count_agents(&entries),
+        top_files: count_files(&entries),
+        least_documented,
+    };
+
+    if options.json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+        return Ok(());
+    }
+
+    if report.entry_count == 0 {
+        if scoped {
+            writeln!(out, "{} No entries match.", "Info:".blue())?;
+        } else {
+            writeln!(out, "{} No entries recorded yet.", "Info:".blue())?;
+        }
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    writeln!(
+        out,
+        "{} ({} entries)",
+        "Lore Stats".bold(),
+        report.entry_count
+    )?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+    writeln!(out)?;
+
+    writeln!(out, "{}", "Activity:".bold())?;
+    let max = report.activity.iter().map(|b| b.count).max().unwrap_or(0);
+    let width = report
+        .activity
+        .iter()
+        .map(|b| b.bucket.len())
+        .max()
+        .unwrap_or(0);
+    for bucket in &report.activity {
+        // Any activity gets at least one block
+        let bar = (bucket.count * BAR_WIDTH).div_ceil(max.max(1));
+        writeln!(
+            out,
+            "  {:<width$}  {} {}",
+            bucket.bucket,
+            Glyph::Block.as_str().repeat(bar).green(),
+            bucket.count.to_string().dimmed()
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", "Reasoning:".bold())?;
+    writeln!(
+        out,
+        "  Average trace length:   {} characters",
+        format!("{:.0}", report.avg_trace_length).green()
+    )?;
+    writeln!(
+        out,
+        "  Rejected alternatives:  {} of {} entries ({})",
+        report.with_alternatives.to_string().green(),
+        report.entry_count,
+        format!("{:.0}%", report.alternatives_ratio * 100.0).cyan()
+    )?;
+    writeln!(
+        out,
+        "  Linked to a commit:     {} of {} entries ({} without)",
+        report.with_commit.to_string().green(),
+        report.entry_count,
+        report.without_commit.to_string().yellow()
+    )?;
+
+    if !report.tags.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Tags:".bold())?;
+        for tag in report.tags.iter().take(TOP_LIMIT) {
+            writeln!(
+                out,
+                "  {:<30} {} ({})",
+                format!("#{}", tag.tag).magenta(),
+                tag.count.to_string().green(),
+                format!("{:.0}%", ratio(tag.count, report.entry_count) * 100.0).dimmed()
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", "Agents:".bold())?;
+    for agent in report.agents.iter().take(TOP_LIMIT) {
+        writeln!(
+            out,
+            "  {:<30} {} {}",
+            agent.agent.yellow(),
+            agent.count.to_string().green(),
+            format!("(last {})", agent.last_activity.format("%Y-%m-%d")).dimmed()
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", "Most documented files:".bold())?;
+    for file in report.top_files.iter().take(TOP_LIMIT) {
+        writeln!(
+            out,
+            "  {:<40} {}",
+            file.file.cyan(),
+            file.count.to_string().green()
+        )?;
+    }
+
+    if !report.least_documented.is_empty() {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{} {}",
+            "Least documented:".bold(),
+            format!("(entries per commit, last {} commits)", HISTORY_COMMITS).dimmed()
+        )?;
+        for coverage in report.least_documented.iter().take(TOP_LIMIT) {
+            writeln!(
+                out,
+                "  {:<40} {} entries / {} commits",
+                coverage.file.cyan(),
+                coverage.entries.to_string().yellow(),
+                coverage.commits
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(60).dimmed())?;
+
+    Ok(())
+}
+
+/// `part / whole`, or 0 when `whole` is 0
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+fn average_trace_length(entries: &[ThoughtObject]) -> f64 {
+    let total = entries
+        .iter()
+        .map(|e| e.reasoning_trace.chars().count())
+        .sum();
+    ratio(total, entries.len())
+}
+
+/// Entry IDs per target file, as in the index
+fn entries_by_file(entries: &[ThoughtObject]) -> BTreeMap<String, Vec<String>> {
+    let mut files: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for entry in entries {
+        files
+            .entry(entry.target_file.clone())
+            .or_default()
+            .push(entry.id.clone());
+    }
+    files
+}
+
+/// Entries per agent, most first, then by agent ID
+pub fn count_agents(entries: &[ThoughtObject]) -> Vec<AgentCount> {
+    let mut by_agent: HashMap<&str, AgentCount> = HashMap::new();
+    for entry in entries {
+        let agent = by_agent
+            .entry(&entry.agent_id)
+            .or_insert_with(|| AgentCount {
+                agent: entry.agent_id.clone(),
+                count: 0,
+                last_activity: entry.timestamp,
+            });
+        agent.count += 1;
+        agent.last_activity = agent.last_activity.max(entry.timestamp);
+    }
+
+    let mut agents: Vec<AgentCount> = by_agent.into_values().collect();
+    agents.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.agent.cmp(&b.agent)));
+    agents
+}
+
+/// Entries per target file, most first, then by path
+pub fn count_files(entries: &[ThoughtObject]) -> Vec<FileCount> {
+    let mut files: Vec<FileCount> = entries_by_file(entries)
+        .into_iter()
+        .map(|(file, ids)| FileCount {
+            file,
+            count: ids.len(),
+        })
+        .collect();
+    files.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.file.cmp(&b.file)));
+    files
+}
+
+/// Whether `file` still exists in the working tree and isn't excluded by
+/// `.lore/.loreignore`
+fn is_tracked_file(root: &Path, ignore: &LoreIgnore, file: &str) -> bool {
+    root.join(file).is_file() && !ignore.is_ignored(file)
+}
+
+/// Files touched by at least one commit, ordered by entries per commit (fewest
+/// first), then by how often they changed
+pub fn least_documented(
+    files: &BTreeMap<String, Vec<String>>,
+    commits: &HashMap<String, usize>,
+    include: impl Fn(&str) -> bool,
+) -> Vec<FileCoverage> {
+    let mut coverage: Vec<FileCoverage> = commits
+        .iter()
+        .filter(|(file, _)| include(file))
+        .map(|(file, &commits)| {
+            let entries = files.get(file).map_or(0, Vec::len);
+            FileCoverage {
+                file: file.clone(),
+                commits,
+                entries,
+                entries_per_commit: ratio(entries, commits),
+            }
+        })
+        .collect();
+
+    coverage.sort_by(|a, b| {
+        a.entries_per_commit
+            .total_cmp(&b.entries_per_commit)
+            .then_with(|| b.commits.cmp(&a.commits))
+            .then_with(|| a.file.cmp(&b.file))
+    });
+    coverage
+}
+
+/// Entries per bucket, oldest first, from the first bucket with activity to the
+/// last, including empty buckets in between
+pub fn activity<E: EntryFields>(entries: &[E], bucket: Bucket) -> Vec<ActivityBucket> {
+    let mut counts: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for entry in entries {
+        let date = entry.timestamp().date_naive();
+        *counts.entry(bucket.start(date)).or_default() += 1;
+    }
+
+    let (Some(&first), Some(&last)) = (counts.keys().next(), counts.keys().next_back()) else {
+        return Vec::new();
+    };
+
+    let mut histogram = Vec::new();
+    let mut start = first;
+    while start <= last {
+        histogram.push(ActivityBucket {
+            bucket: bucket.key(start),
+            count: counts.get(&start).copied().unwrap_or(0),
+        });
+        start = bucket.next(start);
+    }
+    histogram
+}
+
+/// Entries per bucket for the `count` buckets ending with the one containing
+/// `today`, oldest first, including empty buckets. Entries outside them are
+/// not counted.
+pub fn recent_activity<E: EntryFields>(
+    entries: &[E],
+    bucket: Bucket,
+    count: usize,
+    today: NaiveDate,
+) -> Vec<ActivityBucket> {
+    let mut starts = Vec::with_capacity(count);
+    let mut start = bucket.start(today);
+    for _ in 0..count {
+        starts.push(start);
+        start = bucket.prev(start);
+    }
+    starts.reverse();
+
+    let mut counts: HashMap<NaiveDate, usize> = starts.iter().map(|&s| (s, 0)).collect();
+    for entry in entries {
+        let start = bucket.start(entry.timestamp().date_naive());
+        if let Some(count) = counts.get_mut(&start) {
+            *count += 1;
+        }
+    }
+
+    starts
+        .into_iter()
+        .map(|start| ActivityBucket {
+            bucket: bucket.key(start),
+            count: counts[&start],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ThoughtObject;
+    use chrono::{DateTime, Utc};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    /// Noon UTC on the given day
+    fn at(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        date(year, month, day)
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            .and_utc()
+    }
+
+    fn entry_at(timestamp: DateTime<Utc>) -> ThoughtObject {
+        let mut entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        entry.timestamp = timestamp;
+        entry
+    }
+
+    fn summary(histogram: &[ActivityBucket]) -> Vec<(&str, usize)> {
+        histogram
+            .iter()
+            .map(|b| (b.bucket.as_str(), b.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_least_documented_orders_by_entries_per_commit() {
+        let files = BTreeMap::from([
+            ("documented.rs".to_string(), vec!["a".to_string(); 2]),
+            ("sparse.rs".to_string(), vec!["b".to_string()]),
+        ]);
+        let commits = HashMap::from([
+            ("documented.rs".to_string(), 2),
+            ("sparse.rs".to_string(), 4),
+            ("bare.rs".to_string(), 1),
+            ("busy.rs".to_string(), 5),
+            ("deleted.rs".to_string(), 9),
+        ]);
+
+        let coverage = least_documented(&files, &commits, |file| file != "deleted.rs");
+        let summary: Vec<_> = coverage
+            .iter()
+            .map(|c| (c.file.as_str(), c.entries, c.commits))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("busy.rs", 0, 5),
+                ("bare.rs", 0, 1),
+                ("sparse.rs", 1, 4),
+                ("documented.rs", 2, 2),
+            ]
+        );
+        assert_eq!(coverage[2].entries_per_commit, 0.25);
+    }
+
+    #[test]
+    fn test_average_trace_length_and_ratio() {
+        let mut short = entry_at(at(2024, 1, 1));
+        short.reasoning_trace = "abcd".to_string();
+        let mut long = entry_at(at(2024, 1, 2));
+        long.reasoning_trace = "ab".repeat(4);
+
+        assert_eq!(average_trace_length(&[short, long]), 6.0);
+        assert_eq!(average_trace_length(&[]), 0.0);
+        assert_eq!(ratio(1, 4), 0.25);
+        assert_eq!(ratio(1, 0), 0.0);
+    }
+
+    #[test]
+    fn test_key_across_year_boundary() {
+        assert_eq!(Bucket::Day.key(date(2023, 12, 31)), "2023-12-31");
+        assert_eq!(Bucket::Month.key(date(2023, 12, 31)), "2023-12");
+        assert_eq!(Bucket::Month.key(date(2024, 1, 1)), "2024-01");
+        assert_eq!(Bucket::Quarter.key(date(2023, 12, 31)), "2023-Q4");
+        assert_eq!(Bucket::Quarter.key(date(2024, 1, 1)), "2024-Q1");
+    }
+
+    #[test]
+    fn test_week_key_uses_iso_week_year() {
+        // Monday 2024-12-30 starts week 1 of 2025
+        assert_eq!(Bucket::Week.key(date(2024, 12, 30)), "2025-W01");
+        assert_eq!(Bucket::Week.key(date(2025, 1, 5)), "2025-W01");
+        // Friday 2021-01-01 is still in week 53 of 2020
+        assert_eq!(Bucket::Week.key(date(2021, 1, 1)), "2020-W53");
+        assert_eq!(Bucket::Week.key(date(2021, 1, 4)), "2021-W01");
+        // Sunday closes the week it belongs to
+        assert_eq!(Bucket::Week.key(date(2024, 3, 10)), "2024-W10");
+        assert_eq!(Bucket::Week.key(date(2024, 3, 11)), "2024-W11");
+    }
+
+    #[test]
+    fn test_week_start_is_monday() {
+        assert_eq!(Bucket::Week.start(date(2021, 1, 3)), date(2020, 12, 28));
+        assert_eq!(Bucket::Week.start(date(2020, 12, 28)), date(2020, 12, 28));
+    }
+
+    #[test]
+    fn test_activity_by_week_fills_gaps() {
+        let entries = vec![
+            entry_at(at(2020, 12, 31)),
+            entry_at(at(2021, 1, 3)),
+            entry_at(at(2021, 1, 12)),
+        ];
+
+        assert_eq!(
+            summary(&activity(&entries, Bucket::Week)),
+            vec![("2020-W53", 2), ("2021-W01", 0), ("2021-W02", 1)]
+        );
+    }
+
+    #[test]
+    fn test_activity_by_quarter() {
+        let entries = vec![
+            entry_at(at(2023, 11, 15)),
+            entry_at(at(2024, 1, 2)),
+            entry_at(at(2024, 3, 31)),
+            entry_at(at(2024, 8, 1)),
+        ];
+
+        assert_eq!(
+            summary(&activity(&entries, Bucket::Quarter)),
+            vec![
+                ("2023-Q4", 1),
+                ("2024-Q1", 2),
+                ("2024-Q2", 0),
+                ("2024-Q3", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_activity_by_month_and_empty() {
+        let entries = vec![entry_at(at(2024, 1, 31)), entry_at(at(2024, 3, 1))];
+        assert_eq!(
+            summary(&activity(&entries, Bucket::Month)),
+            vec![("2024-01", 1), ("2024-02", 0), ("2024-03", 1)]
+        );
+
+        assert!(activity::<ThoughtObject>(&[], Bucket::Month).is_empty());
+    }
+
+    #[test]
+    fn test_recent_activity_ends_with_current_bucket() {
+        let entries = vec![
+            entry_at(at(2023, 12, 1)),
+            entry_at(at(2024, 2, 29)),
+            entry_at(at(2024, 3, 1)),
+            entry_at(at(2024, 3, 20)),
+        ];
+
+        assert_eq!(
+            summary(&recent_activity(
+                &entries,
+                Bucket::Month,
+                3,
+                date(2024, 3, 25)
+            )),
+            vec![("2024-01", 0), ("2024-02", 1), ("2024-03", 2)]
+        );
+        assert_eq!(
+            summary(&recent_activity(
+                &entries,
+                Bucket::Week,
+                2,
+                date(2024, 3, 25)
+            )),
+            vec![("2024-W12", 1), ("2024-W13", 0)]
+        );
+    }
+
+    #[test]
+    fn test_count_agents_and_files() {
+        let mut first = entry_at(at(2024, 1, 1));
+        first.agent_id = "bot".to_string();
+        let mut second = entry_at(at(2024, 1, 5));
+        second.agent_id = "bot".to_string();
+        second.target_file = "b.rs".to_string();
+        let human = entry_at(at(2024, 1, 9));
+        let entries = vec![first, second, human];
+
+        assert_eq!(
+            count_agents(&entries),
+            vec![
+                AgentCount {
+                    agent: "bot".to_string(),
+                    count: 2,
+                    last_activity: at(2024, 1, 5),
+                },
+                AgentCount {
+                    agent: "agent".to_string(),
+                    count: 1,
+                    last_activity: at(2024, 1, 9),
+                },
+            ]
+        );
+
+        let files: Vec<_> = count_files(&entries)
+            .into_iter()
+            .map(|f| (f.file, f.count))
+            .collect();
+        assert_eq!(
+            files,
+            vec![("a.rs".to_string(), 2), ("b.rs".to_string(), 1)]
+        );
+    }
+}
@@ -1,133 +1,447 @@
-use crate::git::GitContext;
-use crate::storage::{find_lore_root, LoreStorage};
+use crate::commands::{CommandError, Outcome};
+use crate::git::{ChangeType, GitContext};
+use crate::global_config::resolve_agent_id;
+use crate::models::LoreIndex;
+use crate::output::{self, heavy_rule, Format, Glyph};
+use crate::storage::{agent_stats, LoreStorage, StorageError};
 use colored::Colorize;
-use std::collections::HashMap;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
 
-pub fn execute() -> Result<(), Box<dyn std::error::Error>> {
-    let current_dir = std::env::current_dir()?;
+/// Number of changed files listed before the text output truncates
+const CHANGED_FILES_SHOWN: usize = 5;
+
+/// Number of files listed under "Most documented files"
+const MOST_DOCUMENTED_SHOWN: usize = 5;
+
+pub struct StatusOptions {
+    pub format: Format,
+    pub quiet: bool,
+    pub staged_only: bool,
+}
+
+/// Machine-readable status report emitted by `status --format json`
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub repository: String,
+    pub entry_count: usize,
+    pub files_tracked: usize,
+    pub entry_files: usize,
+    /// Agent ID `lore record` would use, and where it came from
+    pub agent_id: String,
+    pub agent_source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_commit: Option<String>,
+    /// `None` when the repository is not a git repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_without_reasoning: Option<Vec<ChangedFileStatus>>,
+    pub most_documented: Vec<FileEntryCount>,
+    pub agents: Vec<AgentEntryCount>,
+}
+
+impl StatusReport {
+    /// Whether a changed file still needs reasoning. Deletions don't count,
+    /// since there is nothing left to record reasoning for.
+    pub fn missing_reasoning(&self) -> bool {
+        self.changed_without_reasoning
+            .iter()
+            .flatten()
+            .any(|c| c.change_type != ChangeType::Deleted)
+    }
+}
+
+/// A changed file that has no recorded reasoning
+#[derive(Debug, Serialize)]
+pub struct ChangedFileStatus {
+    pub path: String,
+    pub change_type: ChangeType,
+    pub ignored: bool,
+}
+
+/// A file and how many entries it has
+#[derive(Debug, Serialize)]
+pub struct FileEntryCount {
+    pub file: String,
+    pub entries: usize,
+}
+
+/// An agent and how many entries it recorded
+#[derive(Debug, Serialize)]
+pub struct AgentEntryCount {
+    pub agent_id: String,
+    pub entry_count: usize,
+}
+
+/// Report on `storage`, or explain how to set Lore up if there is none
+pub fn execute(
+    storage: Option<&LoreStorage>,
+    options: StatusOptions,
+    out: &mut impl Write,
+) -> Result<Outcome, Box<dyn std::error::Error>> {
+    let plain = !options.format.is_structured() && !options.quiet;
 
     // Check if lore is initialized
-    let root = match find_lore_root(&current_dir) {
-        Some(r) => r,
+    let storage = match storage {
+        Some(storage) => storage,
+        None if !plain => {
+            return Err(StorageError::NotInitialized.into());
+        }
         None => {
-            println!("{} Lore is not initialized", "Status:".yellow());
-            println!();
-            println!("Initialize with: {}", "lore init".cyan());
-            return Ok(());
+            writeln!(out, "{} Lore is not initialized", "Status:".yellow())?;
+            writeln!(out)?;
+            writeln!(out, "Initialize with: {}", "lore init".cyan())?;
+            return Ok(Outcome::Success);
         }
     };
 
-    let storage = LoreStorage::new(root.clone());
-    let index = storage.load_index()?;
+    let root = storage.root();
+    let index = match storage.load_index() {
+        Ok(index) => index,
+        Err(StorageError::Json(e)) if plain => {
+            writeln!(out, "{} Index is corrupted: {}", "Status:".red(), e)?;
+            writeln!(out)?;
+            writeln!(out, "Rebuild it with: {}", "lore reindex".cyan())?;
+            return Ok(Outcome::Success);
+        }
+        Err(e) => return Err(e.into()),
+    };
 
-    println!();
-    println!("{}", "═".repeat(50).dimmed());
-    println!("{}", "Lore Status".bold());
-    println!("{}", "═".repeat(50).dimmed());
-    println!();
+    let report = collect_report(root, storage, &index, options.staged_only)?;
+
+    if options.quiet {
+        if report.changed_without_reasoning.is_none() {
+            return Err(
+                CommandError::Git("status --quiet requires a git repository".to_string()).into(),
+            );
+        }
+        if report.missing_reasoning() {
+            return Ok(Outcome::ProblemsFound);
+        }
+        return Ok(Outcome::Success);
+    }
+
+    if options.format.is_structured() {
+        output::print(out, &report, options.format)?;
+    } else {
+        print_report(out, &report)?;
+    }
+    Ok(Outcome::Success)
+}
+
+/// Print the report in the default, human-readable format
+fn print_report(out: &mut impl Write, report: &StatusReport) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(50).dimmed())?;
+    writeln!(out, "{}", "Lore Status".bold())?;
+    writeln!(out, "{}", heavy_rule(50).dimmed())?;
+    writeln!(out)?;
 
     // Repository info
-    println!("{} {}", "Repository:".bold(), root.display());
-    println!(
+    writeln!(out, "{} {}", "Repository:".bold(), report.repository)?;
+    writeln!(
+        out,
         "{} {}",
         "Total entries:".bold(),
-        index.entry_count.to_string().green()
-    );
-    println!(
+        report.entry_count.to_string().green()
+    )?;
+    writeln!(
+        out,
         "{} {}",
         "Files tracked:".bold(),
-        index.files.len().to_string().green()
-    );
+        report.files_tracked.to_string().green()
+    )?;
+
+    // Which agent ID `lore record` would use, and why
+    writeln!(
+        out,
+        "{} {} {}",
+        "Agent:".bold(),
+        report.agent_id.yellow(),
+        format!("({})", report.agent_source).dimmed()
+    )?;
+
+    // Index consistency
+    if report.entry_files != report.entry_count {
+        writeln!(
+            out,
+            "{} index lists {} entries but {} entry files exist. Run {} to fix.",
+            "Warning:".yellow(),
+            report.entry_count,
+            report.entry_files,
+            "lore reindex".cyan()
+        )?;
+    }
 
     // Git status
-    match GitContext::open(&root) {
-        Ok(git) => {
-            if let Ok(commit) = git.head_commit() {
-                println!(
+    match &report.changed_without_reasoning {
+        Some(files_without_lore) => {
+            if let Some(commit) = &report.head_commit {
+                writeln!(
+                    out,
                     "{} {} ({})",
                     "Git HEAD:".bold(),
                     commit[..8].cyan(),
                     "tracking enabled".green()
-                );
+                )?;
             }
 
             // Show changed files without lore entries
-            if let Ok(changed) = git.changed_files() {
-                let files_without_lore: Vec<_> = changed
-                    .iter()
-                    .filter(|c| !index.files.contains_key(&c.path))
-                    .collect();
-
-                if !files_without_lore.is_empty() {
-                    println!();
-                    println!("{}", "Changed files without reasoning:".yellow().bold());
-                    for file in files_without_lore.iter().take(5) {
-                        println!("  {} {}", "→".yellow(), file.path);
-                    }
-                    if files_without_lore.len() > 5 {
-                        println!(
-                            "  {} {} more...",
-                            "→".yellow(),
-                            files_without_lore.len() - 5
-                        );
-                    }
-                    println!();
-                    println!(
-                        "{}",
-                        "Consider running 'lore record' to capture your reasoning".dimmed()
-                    );
+            if !files_without_lore.is_empty() {
+                writeln!(out)?;
+                writeln!(
+                    out,
+                    "{}",
+                    "Changed files without reasoning:".yellow().bold()
+                )?;
+                for file in files_without_lore.iter().take(CHANGED_FILES_SHOWN) {
+                    writeln!(out, "  {} {}", Glyph::Arrow.as_str().yellow(), file.path)?;
+                }
+                if files_without_lore.len() > CHANGED_FILES_SHOWN {
+                    writeln!(
+                        out,
+                        "  {} {} more... (use --format json for the full list)",
+                        Glyph::Arrow.as_str().yellow(),
+                        files_without_lore.len() - CHANGED_FILES_SHOWN
+                    )?;
                 }
+                writeln!(out)?;
+                writeln!(
+                    out,
+                    "{}",
+                    "Consider running 'lore record' to capture your reasoning".dimmed()
+                )?;
             }
         }
-        Err(_) => {
-            println!("{} {} (Git not available)", "Git:".bold(), "N/A".dimmed());
+        None => {
+            writeln!(
+                out,
+                "{} {} (Git not available)",
+                "Git:".bold(),
+                "N/A".dimmed()
+            )?;
         }
     }
 
     // Most documented files
-    if !index.files.is_empty() {
-        println!();
-        println!("{}", "Most documented files:".bold());
-
-        let mut file_counts: Vec<_> = index.files.iter().collect();
-        file_counts.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+    if !report.most_documented.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Most documented files:".bold())?;
 
-        for (file, entries) in file_counts.iter().take(5) {
-            println!(
+        for file in &report.most_documented {
+            writeln!(
+                out,
                 "  {} ({} {})",
-                file.cyan(),
-                entries.len(),
-                if entries.len() == 1 {
+                file.file.cyan(),
+                file.entries,
+                if file.entries == 1 {
                     "entry"
                 } else {
                     "entries"
                 }
-            );
+            )?;
         }
     }
 
     // Agent stats
-    let entries = storage.get_all_entries()?;
-    if !entries.is_empty() {
-        let mut agent_counts: HashMap<&str, usize> = HashMap::new();
-        for entry in &entries {
-            *agent_counts.entry(&entry.agent_id).or_insert(0) += 1;
+    if !report.agents.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Contributors:".bold())?;
+        for agent in &report.agents {
+            writeln!(
+                out,
+                "  {} ({} {})",
+                agent.agent_id.yellow(),
+                agent.entry_count,
+                if agent.entry_count == 1 {
+                    "entry"
+                } else {
+                    "entries"
+                }
+            )?;
+        }
+        if report.agents.len() > 1 {
+            writeln!(out, "  {}", "Use 'lore agents' for details".dimmed())?;
         }
+    }
 
-        println!();
-        println!("{}", "Contributors:".bold());
-        for (agent, count) in agent_counts.iter() {
-            println!(
-                "  {} ({} {})",
-                agent.yellow(),
-                count,
-                if *count == 1 { "entry" } else { "entries" }
-            );
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(50).dimmed())?;
+    Ok(())
+}
+
+/// Build the full, untruncated status report. With `staged_only`, only staged
+/// changes are checked for reasoning.
+fn collect_report(
+    root: &Path,
+    storage: &LoreStorage,
+    index: &LoreIndex,
+    staged_only: bool,
+) -> Result<StatusReport, Box<dyn std::error::Error>> {
+    let git = GitContext::open(root).ok();
+    let (agent_id, agent_source) = resolve_agent_id(None, storage, root)?;
+
+    let mut most_documented: Vec<FileEntryCount> = index
+        .files
+        .iter()
+        .map(|(file, ids)| FileEntryCount {
+            file: file.clone(),
+            entries: ids.len(),
+        })
+        .collect();
+    most_documented.sort_by_key(|f| std::cmp::Reverse(f.entries));
+    most_documented.truncate(MOST_DOCUMENTED_SHOWN);
+
+    let agents = agent_stats(&storage.get_entry_metas()?)
+        .into_iter()
+        .map(|agent| AgentEntryCount {
+            agent_id: agent.agent_id,
+            entry_count: agent.entry_count,
+        })
+        .collect();
+
+    Ok(StatusReport {
+        repository: root.display().to_string(),
+        entry_count: index.entry_count,
+        files_tracked: index.files.len(),
+        entry_files: storage.count_entry_files()?,
+        agent_id,
+        agent_source: agent_source.to_string(),
+        head_commit: git.as_ref().and_then(|g| g.head_commit().ok()),
+        changed_without_reasoning: git
+            .as_ref()
+            .map(|g| changed_without_reasoning(g, index, staged_only)),
+        most_documented,
+        agents,
+    })
+}
+
+/// Changed files (per git) that have no entries in the index
+fn changed_without_reasoning(
+    git: &GitContext,
+    index: &LoreIndex,
+    staged_only: bool,
+) -> Vec<ChangedFileStatus> {
+    git.changed_files()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| !staged_only || c.staged)
+        .filter(|c| !index.files.contains_key(&c.path))
+        .map(|c| ChangedFileStatus {
+            ignored: git.is_ignored(&c.path),
+            path: c.path,
+            change_type: c.change_type,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_report_lists_all_changed_files_beyond_text_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        Command::new("git")
+            .args(["init"])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to init git repo");
+
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+
+        let total = CHANGED_FILES_SHOWN + 3;
+        for i in 0..total {
+            std::fs::write(temp_dir.path().join(format!("file{}.rs", i)), "x").unwrap();
         }
+
+        let index = storage.load_index().unwrap();
+        let report = collect_report(temp_dir.path(), &storage, &index, false).unwrap();
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+
+        let changed = json["changed_without_reasoning"].as_array().unwrap();
+        assert_eq!(changed.len(), total);
+        assert!(changed.iter().all(|c| c["change_type"] == "added"));
+        assert!(changed.iter().all(|c| c["ignored"] == false));
     }
 
-    println!();
-    println!("{}", "═".repeat(50).dimmed());
+    #[test]
+    fn test_staged_only_report_and_missing_reasoning() {
+        let temp_dir = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .expect("Failed to run git");
+        };
+        git(&["init"]);
 
-    Ok(())
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+        std::fs::write(temp_dir.path().join("staged.rs"), "x").unwrap();
+        std::fs::write(temp_dir.path().join("unstaged.rs"), "x").unwrap();
+        git(&["add", "staged.rs"]);
+
+        let index = storage.load_index().unwrap();
+        let report = collect_report(temp_dir.path(), &storage, &index, true).unwrap();
+        let changed = report.changed_without_reasoning.as_ref().unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].path, "staged.rs");
+        assert!(report.missing_reasoning());
+
+        let entry = crate::models::ThoughtObject::new(
+            "staged.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+
+        let index = storage.load_index().unwrap();
+        let report = collect_report(temp_dir.path(), &storage, &index, true).unwrap();
+        assert!(!report.missing_reasoning());
+        assert_eq!(report.most_documented[0].file, "staged.rs");
+        assert_eq!(report.agents[0].entry_count, 1);
+
+        // Without --staged-only the untracked file still needs reasoning
+        let report = collect_report(temp_dir.path(), &storage, &index, false).unwrap();
+        assert!(report.missing_reasoning());
+    }
+
+    #[test]
+    fn test_quiet_returns_problems_found_without_output() {
+        let temp_dir = TempDir::new().unwrap();
+        Command::new("git")
+            .args(["init"])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to init git repo");
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        storage.init(None).unwrap();
+        let options = |quiet| StatusOptions {
+            format: Format::Pretty,
+            quiet,
+            staged_only: false,
+        };
+
+        let mut out = Vec::new();
+        let outcome = execute(Some(&storage), options(true), &mut out).unwrap();
+        assert_eq!(outcome, Outcome::Success);
+        assert!(out.is_empty());
+
+        std::fs::write(temp_dir.path().join("a.rs"), "x").unwrap();
+        let outcome = execute(Some(&storage), options(true), &mut out).unwrap();
+        assert_eq!(outcome, Outcome::ProblemsFound);
+        assert!(out.is_empty());
+
+        // The full report still succeeds, and goes to the writer
+        let outcome = execute(Some(&storage), options(false), &mut out).unwrap();
+        assert_eq!(outcome, Outcome::Success);
+        assert!(String::from_utf8(out).unwrap().contains("a.rs"));
+    }
 }
@@ -0,0 +1,151 @@
+use crate::models::EntryFields;
+use crate::output::{heavy_rule, rule};
+use crate::storage::LoreStorage;
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+pub struct TagsOptions {
+    pub json: bool,
+}
+
+/// A tag and how many times it was used
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+pub fn execute(
+    storage: &LoreStorage,
+    options: TagsOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Tags are in the index, so no entry file needs reading
+    let metas = storage.get_entry_metas()?;
+    let tags = count_tags(&metas);
+
+    if options.json {
+        let json = serde_json::to_string_pretty(&tags)?;
+        writeln!(out, "{}", json)?;
+        return Ok(());
+    }
+
+    if tags.is_empty() {
+        writeln!(out, "{} No tags recorded yet.", "Info:".blue())?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Tag entries with: {}",
+            "lore record -T <tag> -m \"your message\"".cyan()
+        )?;
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", heavy_rule(50).dimmed())?;
+    writeln!(out, "{} ({} total)", "Tags".bold(), tags.len())?;
+    writeln!(out, "{}", heavy_rule(50).dimmed())?;
+    writeln!(out)?;
+
+    for tag in &tags {
+        writeln!(
+            out,
+            "  {:<30} {}",
+            format!("#{}", tag.tag).magenta(),
+            tag.count.to_string().green()
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", rule(50).dimmed())?;
+    writeln!(
+        out,
+        "{}",
+        "Use 'lore list --tag <tag>' to see tagged entries".dimmed()
+    )?;
+
+    Ok(())
+}
+
+/// Count tag occurrences case-insensitively, sorted by count descending then
+/// alphabetically. Each tag is displayed in its most commonly used spelling.
+pub fn count_tags(entries: &[impl EntryFields]) -> Vec<TagCount> {
+    // lowercase tag -> (spelling -> count)
+    let mut buckets: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+    for tag in entries.iter().flat_map(|e| e.tags()) {
+        *buckets
+            .entry(tag.to_lowercase())
+            .or_default()
+            .entry(tag)
+            .or_insert(0) += 1;
+    }
+
+    let mut tags: Vec<TagCount> = buckets
+        .into_values()
+        .map(|spellings| {
+            let count = spellings.values().sum();
+            let (tag, _) = spellings
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+                .unwrap();
+            TagCount {
+                tag: tag.to_string(),
+                count,
+            }
+        })
+        .collect();
+
+    tags.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.tag.to_lowercase().cmp(&b.tag.to_lowercase()))
+    });
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ThoughtObject;
+
+    fn entry(tags: &[&str]) -> ThoughtObject {
+        ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_tags(tags.iter().map(|t| t.to_string()).collect())
+    }
+
+    #[test]
+    fn test_count_tags_sorted_by_count_then_name() {
+        let entries = vec![entry(&["perf", "auth"]), entry(&["auth"]), entry(&["docs"])];
+
+        let tags = count_tags(&entries);
+        let summary: Vec<_> = tags.iter().map(|t| (t.tag.as_str(), t.count)).collect();
+        assert_eq!(summary, vec![("auth", 2), ("docs", 1), ("perf", 1)]);
+    }
+
+    #[test]
+    fn test_count_tags_case_insensitive_with_common_spelling() {
+        let entries = vec![
+            entry(&["security"]),
+            entry(&["Security"]),
+            entry(&["security"]),
+        ];
+
+        let tags = count_tags(&entries);
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].tag, "security");
+        assert_eq!(tags[0].count, 3);
+    }
+
+    #[test]
+    fn test_count_tags_empty() {
+        assert!(count_tags(&[entry(&[])]).is_empty());
+    }
+}
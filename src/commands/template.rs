@@ -0,0 +1,61 @@
+use crate::commands::CommandError;
+use crate::output::Glyph;
+use crate::storage::LoreStorage;
+use colored::Colorize;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub struct TemplateAddOptions {
+    pub name: String,
+    pub file: PathBuf,
+    pub force: bool,
+}
+
+/// Save the contents of a file as a reasoning template
+pub fn add(
+    storage: &LoreStorage,
+    options: TemplateAddOptions,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(&options.file)
+        .map_err(|e| CommandError::cannot_read(options.file.display(), e))?;
+    storage.save_template(&options.name, &content, options.force)?;
+
+    writeln!(
+        out,
+        "{} Saved template {}",
+        Glyph::Check.as_str().green(),
+        options.name.cyan()
+    )?;
+    writeln!(
+        out,
+        "  Use it with: {}",
+        format!("lore record --template {}", options.name).cyan()
+    )?;
+    Ok(())
+}
+
+/// List saved templates, marking the configured default
+pub fn list(storage: &LoreStorage, out: &mut impl Write) -> Result<(), Box<dyn std::error::Error>> {
+    let names = storage.list_templates()?;
+    if names.is_empty() {
+        writeln!(out, "{} No templates saved yet.", "Info:".blue())?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Add one with: {}",
+            "lore template add <name> --file template.md".cyan()
+        )?;
+        return Ok(());
+    }
+
+    let default = storage.load_config()?.default_template;
+    for name in names {
+        if default.as_deref() == Some(name.as_str()) {
+            writeln!(out, "  {} {}", name.cyan(), "(default)".dimmed())?;
+        } else {
+            writeln!(out, "  {}", name.cyan())?;
+        }
+    }
+    Ok(())
+}
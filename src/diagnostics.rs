@@ -0,0 +1,119 @@
+use colored::Colorize;
+use serde::Serialize;
+
+/// Machine-readable category of a warning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningCode {
+    /// A file passed to `record` does not exist
+    FileNotFound,
+    /// Git reported no changed files to record
+    NoChanges,
+    /// There were no files to record reasoning for
+    NoFiles,
+    /// An unfinished draft exists in `.lore/pending.json`
+    PendingDraft,
+    /// The file has changed since the reasoning was recorded
+    Stale,
+    /// The file the reasoning refers to no longer exists
+    FileMissing,
+    /// Some entry files could not be parsed and were left out
+    UnreadableEntries,
+    /// `--symbol` wasn't found, so the whole file was recorded
+    SymbolNotFound,
+    /// `--anchor` text wasn't in the file, so the entry has no anchor
+    AnchorNotFound,
+    /// The file has no entries because they moved with it to a new path
+    FileMoved,
+    /// An `explain` file, directory or pattern matched no file with reasoning
+    NoMatch,
+    /// The newest entry for the file has the same file hash, intent and
+    /// reasoning, so `record` skipped it
+    Duplicate,
+}
+
+/// A warning emitted while running a command
+#[derive(Debug, Clone, Serialize)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// `--json` envelope carrying a command's data together with its warnings
+#[derive(Debug, Serialize)]
+pub struct JsonReport<T: Serialize> {
+    pub entries: T,
+    pub warnings: Vec<Warning>,
+}
+
+/// Collects warnings for `--json` output, or prints them to stderr otherwise
+pub struct Diagnostics {
+    json: bool,
+    warnings: Vec<Warning>,
+}
+
+impl Diagnostics {
+    pub fn new(json: bool) -> Self {
+        Self {
+            json,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Whether output is JSON (and human-oriented messages should be suppressed)
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    pub fn warn(&mut self, code: WarningCode, message: impl Into<String>, file: Option<&str>) {
+        let message = message.into();
+        if !self.json {
+            eprintln!("{} {}", "Warning:".yellow(), message);
+        }
+        self.warnings.push(Warning {
+            code,
+            message,
+            file: file.map(str::to_string),
+        });
+    }
+
+    #[cfg(test)]
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Wrap `entries` and the collected warnings in the `--json` envelope
+    pub fn into_report<T: Serialize>(self, entries: T) -> JsonReport<T> {
+        JsonReport {
+            entries,
+            warnings: self.warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warning_serializes_code_in_snake_case() {
+        let mut diagnostics = Diagnostics::new(true);
+        diagnostics.warn(WarningCode::FileNotFound, "Skipping a.rs", Some("a.rs"));
+
+        let json = serde_json::to_value(diagnostics.into_report(Vec::<u8>::new())).unwrap();
+        assert_eq!(json["warnings"][0]["code"], "file_not_found");
+        assert_eq!(json["warnings"][0]["file"], "a.rs");
+        assert_eq!(json["entries"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_warning_without_file_omits_field() {
+        let mut diagnostics = Diagnostics::new(true);
+        diagnostics.warn(WarningCode::NoChanges, "Nothing changed", None);
+
+        let json = serde_json::to_value(&diagnostics.warnings()[0]).unwrap();
+        assert!(json.get("file").is_none());
+    }
+}
@@ -0,0 +1,539 @@
+use crate::models::{EntryFields, RejectedAlternative, ThoughtObject};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+/// Parse a time bound for `--since`/`--until`.
+///
+/// Accepts an RFC3339 timestamp (`2024-02-14T10:00:00Z`), a plain date
+/// (`2024-02-14`, midnight UTC), or a relative offset from now such as
+/// `12h`, `7d` or `2w`.
+pub fn parse_time_bound(s: &str) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+
+    if let Some(unit) = s.chars().last() {
+        let amount = &s[..s.len() - unit.len_utf8()];
+        if let Ok(amount) = amount.parse::<i64>() {
+            // `None` inside when the amount is too large for a TimeDelta
+            let duration = match unit {
+                'h' => Some(TimeDelta::try_hours(amount)),
+                'd' => Some(TimeDelta::try_days(amount)),
+                'w' => Some(TimeDelta::try_weeks(amount)),
+                _ => None,
+            };
+            if let Some(duration) = duration {
+                return duration
+                    .and_then(|duration| Utc::now().checked_sub_signed(duration))
+                    .ok_or_else(|| format!("time bound out of range: '{}'", s));
+            }
+        }
+    }
+
+    Err(format!(
+        "invalid time '{}': expected RFC3339 (2024-02-14T10:00:00Z), a date (2024-02-14), or a relative offset (12h, 7d, 2w)",
+        s
+    ))
+}
+
+/// Keep only entries whose timestamp falls within `[since, until]`
+pub fn retain_in_range<E: EntryFields>(
+    entries: &mut Vec<E>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) {
+    entries.retain(|e| {
+        since.is_none_or(|since| e.timestamp() >= since)
+            && until.is_none_or(|until| e.timestamp() <= until)
+    });
+}
+
+/// Keep only entries carrying the given tags (case-insensitive, exact per tag).
+/// All tags must be present unless `any` is set, in which case one suffices.
+pub fn retain_tagged<E: EntryFields>(entries: &mut Vec<E>, tags: &[String], any: bool) {
+    if tags.is_empty() {
+        return;
+    }
+
+    let wanted: Vec<String> = tags.iter().map(|t| t.to_lowercase()).collect();
+    entries.retain(|e| {
+        let has = |tag: &String| e.tags().iter().any(|t| t.to_lowercase() == *tag);
+        if any {
+            wanted.iter().any(has)
+        } else {
+            wanted.iter().all(has)
+        }
+    });
+}
+
+/// Keep only entries recorded for `project` (trailing slashes are ignored)
+pub fn retain_project<E: EntryFields>(entries: &mut Vec<E>, project: Option<&str>) {
+    if let Some(project) = project {
+        let project = project.trim_end_matches('/');
+        entries.retain(|e| e.project() == Some(project));
+    }
+}
+
+/// Parse a 1-based inclusive line range such as `20-40` (or a single line, `20`)
+pub fn parse_line_range(s: &str) -> Result<(usize, usize), String> {
+    let invalid = || {
+        format!(
+            "invalid line range '{}' (expected START-END, e.g. 20-40)",
+            s
+        )
+    };
+    let (start, end) = match s.trim().split_once('-') {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None => (s.trim(), s.trim()),
+    };
+    let start: usize = start.parse().map_err(|_| invalid())?;
+    let end: usize = end.parse().map_err(|_| invalid())?;
+    if start == 0 || start > end {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+/// Parse a metadata pair such as `model=gpt-4`. The key can't be empty or
+/// contain whitespace; the value is everything after the first `=`.
+pub fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    let invalid = |reason: &str| format!("invalid metadata '{}': {}", s, reason);
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| invalid("expected KEY=VALUE"))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(invalid("the key is empty"));
+    }
+    if key.contains(char::is_whitespace) {
+        return Err(invalid("the key contains whitespace"));
+    }
+    Ok((key.to_string(), value.trim().to_string()))
+}
+
+/// Parse a confidence for `--confidence`/`--min-confidence`: a number from
+/// 0.0 to 1.0
+pub fn parse_confidence(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        _ => Err(format!(
+            "invalid confidence '{}': expected a number from 0.0 to 1.0, e.g. 0.8",
+            s
+        )),
+    }
+}
+
+/// Parse a `--rejected` value: `"name: reason"`, split on the first colon,
+/// or just a name
+pub fn parse_rejected(s: &str) -> Result<RejectedAlternative, String> {
+    let (name, reason) = match s.split_once(':') {
+        Some((name, reason)) => (name.trim(), Some(reason.trim())),
+        None => (s.trim(), None),
+    };
+    if name.is_empty() {
+        return Err(format!(
+            "invalid rejected alternative '{}': expected \"name\" or \"name: reason\"",
+            s
+        ));
+    }
+    Ok(RejectedAlternative {
+        name: name.to_string(),
+        reason: reason.filter(|r| !r.is_empty()).map(str::to_string),
+    })
+}
+
+/// Parse a `--ref`: any non-empty text, trimmed (a URL or an issue key)
+pub fn parse_reference(s: &str) -> Result<String, String> {
+    match s.trim() {
+        "" => Err("reference must not be empty".to_string()),
+        reference => Ok(reference.to_string()),
+    }
+}
+
+/// Keep only entries recorded with at least `min` confidence. Entries without
+/// a confidence don't pass a minimum.
+pub fn retain_confident(entries: &mut Vec<ThoughtObject>, min: Option<f32>) {
+    entries.retain(|e| is_confident(e, min));
+}
+
+/// Whether `entry` passes `--min-confidence`, for filtering one entry at a time
+pub fn is_confident(entry: &ThoughtObject, min: Option<f32>) -> bool {
+    min.is_none_or(|min| entry.confidence.is_some_and(|confidence| confidence >= min))
+}
+
+/// Keep only entries with every one of the given metadata pairs (exact match)
+pub fn retain_with_metadata(entries: &mut Vec<ThoughtObject>, pairs: &[(String, String)]) {
+    entries.retain(|e| has_metadata(e, pairs));
+}
+
+/// Whether `entry` has every one of the given metadata pairs
+pub fn has_metadata(entry: &ThoughtObject, pairs: &[(String, String)]) -> bool {
+    pairs
+        .iter()
+        .all(|(key, value)| entry.metadata.get(key) == Some(value))
+}
+
+/// Skip the first `offset` items, then keep at most `limit`, for paging
+/// through sorted, filtered results with `--offset` and `--limit`
+pub fn page<T>(items: &mut Vec<T>, offset: usize, limit: Option<usize>) {
+    items.drain(..offset.min(items.len()));
+    if let Some(limit) = limit {
+        items.truncate(limit);
+    }
+}
+
+/// Whether two inclusive line ranges share at least one line
+pub fn ranges_overlap(a: (usize, usize), b: (usize, usize)) -> bool {
+    a.0 <= b.1 && b.0 <= a.1
+}
+
+/// Number of lines two inclusive ranges share (0 when disjoint)
+pub fn overlap_len(a: (usize, usize), b: (usize, usize)) -> usize {
+    (a.1.min(b.1) + 1).saturating_sub(a.0.max(b.0))
+}
+
+/// Keep only entries whose line range overlaps `range`. Whole-file entries are dropped.
+pub fn retain_overlapping(entries: &mut Vec<ThoughtObject>, range: Option<(usize, usize)>) {
+    entries.retain(|e| overlaps_range(e, range));
+}
+
+/// Whether `entry` passes a line-range filter (always true without one)
+pub fn overlaps_range(entry: &ThoughtObject, range: Option<(usize, usize)>) -> bool {
+    range.is_none_or(|range| entry.line_range.is_some_and(|r| ranges_overlap(r, range)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn entry_at(timestamp: DateTime<Utc>) -> ThoughtObject {
+        let mut entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        entry.timestamp = timestamp;
+        entry
+    }
+
+    fn entry_tagged(tags: &[&str]) -> ThoughtObject {
+        ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_tags(tags.iter().map(|t| t.to_string()).collect())
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_time_bound_rfc3339() {
+        let parsed = parse_time_bound("2024-02-14T10:00:00+02:00").unwrap();
+        assert_eq!(parsed, Utc.with_ymd_and_hms(2024, 2, 14, 8, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_parse_time_bound_date() {
+        let parsed = parse_time_bound("2024-02-14").unwrap();
+        assert_eq!(parsed, Utc.with_ymd_and_hms(2024, 2, 14, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_parse_time_bound_relative() {
+        let now = Utc::now();
+
+        let days = parse_time_bound("7d").unwrap();
+        assert!((now - days - Duration::days(7)).num_seconds().abs() < 5);
+
+        let weeks = parse_time_bound("2w").unwrap();
+        assert!((now - weeks - Duration::weeks(2)).num_seconds().abs() < 5);
+
+        let hours = parse_time_bound("12h").unwrap();
+        assert!((now - hours - Duration::hours(12)).num_seconds().abs() < 5);
+    }
+
+    #[test]
+    fn test_parse_time_bound_invalid() {
+        assert!(parse_time_bound("yesterday").is_err());
+        assert!(parse_time_bound("7x").is_err());
+        assert!(parse_time_bound("").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_bound_out_of_range() {
+        for input in [
+            "999999999d",
+            "99999999999w",
+            "-999999999d",
+            "9223372036854775807h",
+        ] {
+            let err = parse_time_bound(input).unwrap_err();
+            assert!(err.contains("out of range"), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_retain_in_range() {
+        let mut entries = vec![
+            entry_at(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()),
+            entry_at(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()),
+            entry_at(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()),
+        ];
+
+        retain_in_range(
+            &mut entries,
+            Some(Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2024, 2, 15, 0, 0, 0).unwrap()),
+        );
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].timestamp,
+            Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_retain_in_range_open_ended() {
+        let mut entries = vec![
+            entry_at(Utc::now() - Duration::days(40)),
+            entry_at(Utc::now() - Duration::days(3)),
+        ];
+
+        retain_in_range(&mut entries, Some(parse_time_bound("30d").unwrap()), None);
+
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_retain_tagged_all() {
+        let mut entries = vec![
+            entry_tagged(&["auth", "security"]),
+            entry_tagged(&["auth"]),
+            entry_tagged(&["perf"]),
+        ];
+
+        retain_tagged(&mut entries, &tags(&["auth", "security"]), false);
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].tags, vec!["auth", "security"]);
+    }
+
+    #[test]
+    fn test_retain_tagged_any() {
+        let mut entries = vec![
+            entry_tagged(&["auth"]),
+            entry_tagged(&["perf"]),
+            entry_tagged(&["docs"]),
+        ];
+
+        retain_tagged(&mut entries, &tags(&["auth", "perf"]), true);
+
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_retain_tagged_exact_and_case_insensitive() {
+        let mut entries = vec![entry_tagged(&["authz"]), entry_tagged(&["Auth"])];
+
+        retain_tagged(&mut entries, &tags(&["AUTH"]), false);
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].tags, vec!["Auth"]);
+    }
+
+    #[test]
+    fn test_retain_tagged_no_tags_keeps_everything() {
+        let mut entries = vec![entry_tagged(&[]), entry_tagged(&["auth"])];
+
+        retain_tagged(&mut entries, &[], false);
+
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_retain_project() {
+        let mut entries = vec![
+            entry_at(Utc::now()).with_project("crates/core".to_string()),
+            entry_at(Utc::now()).with_project("crates/cli".to_string()),
+            entry_at(Utc::now()),
+        ];
+
+        retain_project(&mut entries, None);
+        assert_eq!(entries.len(), 3);
+
+        retain_project(&mut entries, Some("crates/core/"));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].project.as_deref(), Some("crates/core"));
+    }
+
+    #[test]
+    fn test_ranges_overlap() {
+        // Overlapping
+        assert!(ranges_overlap((10, 20), (15, 25)));
+        assert!(ranges_overlap((15, 25), (10, 20)));
+        assert!(ranges_overlap((10, 30), (15, 20)));
+        // Adjacent ranges share a boundary line only when they touch
+        assert!(ranges_overlap((10, 20), (20, 30)));
+        assert!(!ranges_overlap((10, 20), (21, 30)));
+        // Disjoint
+        assert!(!ranges_overlap((1, 5), (10, 20)));
+        assert!(!ranges_overlap((10, 20), (1, 5)));
+    }
+
+    #[test]
+    fn test_overlap_len() {
+        assert_eq!(overlap_len((10, 20), (15, 25)), 6);
+        assert_eq!(overlap_len((10, 30), (15, 20)), 6);
+        assert_eq!(overlap_len((10, 20), (20, 30)), 1);
+        assert_eq!(overlap_len((10, 20), (21, 30)), 0);
+        assert_eq!(overlap_len((21, 30), (1, 5)), 0);
+    }
+
+    #[test]
+    fn test_parse_line_range() {
+        assert_eq!(parse_line_range("20-40"), Ok((20, 40)));
+        assert_eq!(parse_line_range("7"), Ok((7, 7)));
+        assert!(parse_line_range("40-20").is_err());
+        assert!(parse_line_range("0-3").is_err());
+        assert!(parse_line_range("a-b").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_value() {
+        assert_eq!(
+            parse_key_value("model=gpt-4"),
+            Ok(("model".to_string(), "gpt-4".to_string()))
+        );
+        assert_eq!(
+            parse_key_value("url=https://x.test/?a=b"),
+            Ok(("url".to_string(), "https://x.test/?a=b".to_string()))
+        );
+        assert_eq!(
+            parse_key_value("note="),
+            Ok(("note".to_string(), String::new()))
+        );
+        assert!(parse_key_value("model").is_err());
+        assert!(parse_key_value("=gpt-4").is_err());
+        assert!(parse_key_value("my model=gpt-4").is_err());
+    }
+
+    #[test]
+    fn test_parse_confidence_range() {
+        assert_eq!(parse_confidence("0"), Ok(0.0));
+        assert_eq!(parse_confidence(" 0.85 "), Ok(0.85));
+        assert_eq!(parse_confidence("1.0"), Ok(1.0));
+        assert!(parse_confidence("1.5").is_err());
+        assert!(parse_confidence("-0.1").is_err());
+        assert!(parse_confidence("NaN").is_err());
+        assert!(parse_confidence("high").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejected_splits_on_first_colon() {
+        let alt = parse_rejected("Auth0 SDK: too heavy: 2MB").unwrap();
+        assert_eq!(alt.name, "Auth0 SDK");
+        assert_eq!(alt.reason.as_deref(), Some("too heavy: 2MB"));
+
+        let alt = parse_rejected("  Redis  ").unwrap();
+        assert_eq!(alt.name, "Redis");
+        assert!(alt.reason.is_none());
+
+        assert!(parse_rejected("Redis:  ").unwrap().reason.is_none());
+        assert!(parse_rejected(": no name").is_err());
+        assert!(parse_rejected("").is_err());
+    }
+
+    #[test]
+    fn test_parse_reference_trims_and_rejects_empty() {
+        assert_eq!(parse_reference("  JIRA-1234 "), Ok("JIRA-1234".to_string()));
+        assert!(parse_reference("").is_err());
+        assert!(parse_reference("   ").is_err());
+    }
+
+    #[test]
+    fn test_retain_confident_drops_unrated_entries() {
+        let now = Utc::now();
+        let mut entries = vec![
+            entry_at(now).with_confidence(0.9),
+            entry_at(now).with_confidence(0.4),
+            entry_at(now),
+        ];
+
+        retain_confident(&mut entries, None);
+        assert_eq!(entries.len(), 3);
+
+        retain_confident(&mut entries, Some(0.5));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].confidence, Some(0.9));
+    }
+
+    #[test]
+    fn test_retain_with_metadata_requires_every_pair() {
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let with = |metadata: &[(&str, &str)]| {
+            entry_at(Utc::now()).with_metadata(pairs(metadata).into_iter().collect())
+        };
+        let mut entries = vec![
+            with(&[("model", "gpt-4"), ("temperature", "0.2")]),
+            with(&[("model", "gpt-4")]),
+            with(&[("model", "claude")]),
+            with(&[]),
+        ];
+
+        retain_with_metadata(&mut entries, &pairs(&[("model", "gpt-4")]));
+        assert_eq!(entries.len(), 2);
+
+        retain_with_metadata(
+            &mut entries,
+            &pairs(&[("model", "gpt-4"), ("temperature", "0.2")]),
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].metadata["temperature"], "0.2");
+    }
+
+    #[test]
+    fn test_retain_overlapping_drops_whole_file_entries() {
+        let mut entries = vec![
+            entry_at(Utc::now()).with_line_range(1, 5),
+            entry_at(Utc::now()).with_line_range(18, 22),
+            entry_at(Utc::now()),
+        ];
+        retain_overlapping(&mut entries, Some((20, 40)));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].line_range, Some((18, 22)));
+    }
+
+    #[test]
+    fn test_page() {
+        let mut items: Vec<u32> = (1..=5).collect();
+        page(&mut items, 2, Some(2));
+        assert_eq!(items, vec![3, 4]);
+
+        let mut items: Vec<u32> = (1..=5).collect();
+        page(&mut items, 4, None);
+        assert_eq!(items, vec![5]);
+
+        let mut items: Vec<u32> = (1..=5).collect();
+        page(&mut items, 10, Some(2));
+        assert!(items.is_empty());
+    }
+}
@@ -1,5 +1,7 @@
-use git2::{Repository, StatusOptions};
-use std::path::Path;
+use git2::{DiffFormat, DiffOptions, Oid, Repository, StatusOptions};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -38,7 +40,9 @@ impl GitContext {
         let mut opts = StatusOptions::new();
         opts.include_untracked(true)
             .recurse_untracked_dirs(true)
-            .include_ignored(false);
+            .include_ignored(false)
+            .renames_head_to_index(true)
+            .renames_index_to_workdir(true);
 
         let statuses = self.repo.statuses(Some(&mut opts))?;
 
@@ -46,7 +50,28 @@ impl GitContext {
 
         for entry in statuses.iter() {
             let status = entry.status();
-            let path = entry.path().unwrap_or("").to_string();
+            let mut path = entry.path().unwrap_or("").to_string();
+            let mut old_path = None;
+
+            // For renames the entry's path is the old one; report the new path
+            if status.is_index_renamed() || status.is_wt_renamed() {
+                let staged = entry.head_to_index();
+                let unstaged = entry.index_to_workdir();
+                let old = staged
+                    .as_ref()
+                    .filter(|_| status.is_index_renamed())
+                    .or(unstaged.as_ref())
+                    .and_then(|delta| delta.old_file().path());
+                let new = unstaged
+                    .as_ref()
+                    .filter(|_| status.is_wt_renamed())
+                    .or(staged.as_ref())
+                    .and_then(|delta| delta.new_file().path());
+                if let (Some(old), Some(new)) = (old, new) {
+                    old_path = Some(old.to_string_lossy().replace('\\', "/"));
+                    path = new.to_string_lossy().replace('\\', "/");
+                }
+            }
 
             if path.is_empty() || path.starts_with(".lore/") {
                 continue;
@@ -61,11 +86,17 @@ impl GitContext {
                 || status.is_index_modified()
                 || status.is_index_deleted()
                 || status.is_index_renamed();
+            let unstaged = status.is_wt_new()
+                || status.is_wt_modified()
+                || status.is_wt_deleted()
+                || status.is_wt_renamed();
 
             changes.push(ChangedFile {
                 path,
+                old_path,
                 change_type,
                 staged,
+                unstaged,
             });
         }
 
@@ -76,43 +107,343 @@ impl GitContext {
         Ok(changes)
     }
 
+    /// Get the unified diff of a file against HEAD (staged and unstaged changes).
+    /// Untracked files are diffed as all-additions. Returns an empty string if
+    /// the file is unchanged.
+    pub fn diff_file(&self, path: &str) -> Result<String, GitError> {
+        let head_tree = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_tree()?),
+            Err(_) => None, // Unborn branch: everything is new
+        };
+
+        let mut opts = DiffOptions::new();
+        opts.pathspec(path)
+            .disable_pathspec_match(true)
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true);
+
+        let diff = self
+            .repo
+            .diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut opts))?;
+
+        let mut patch = String::new();
+        diff.print(DiffFormat::Patch, |_delta, _hunk, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                patch.push(line.origin());
+            }
+            patch.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+
+        Ok(patch)
+    }
+
+    /// Resolve a revspec (branch, tag, `HEAD~3`, short hash) to a full commit hash
+    pub fn resolve(&self, rev: &str) -> Result<String, GitError> {
+        let commit = self.repo.revparse_single(rev)?.peel_to_commit()?;
+        Ok(commit.id().to_string())
+    }
+
+    /// The best common ancestor of two revisions, as `git merge-base` finds it
+    pub fn merge_base(&self, a: &str, b: &str) -> Result<String, GitError> {
+        let a = self.repo.revparse_single(a)?.peel_to_commit()?.id();
+        let b = self.repo.revparse_single(b)?.peel_to_commit()?.id();
+        Ok(self.repo.merge_base(a, b)?.to_string())
+    }
+
+    /// Whether `commit` is `descendant` or one of its ancestors. False for
+    /// commits this repository doesn't have, e.g. ones rebased away.
+    pub fn is_ancestor_or_equal(&self, commit: &str, descendant: &str) -> bool {
+        let (Ok(commit), Ok(descendant)) = (Oid::from_str(commit), Oid::from_str(descendant))
+        else {
+            return false;
+        };
+        commit == descendant
+            || self
+                .repo
+                .graph_descendant_of(descendant, commit)
+                .unwrap_or(false)
+    }
+
+    /// Name and email of the author of the HEAD commit
+    pub fn head_author(&self) -> Result<(String, String), GitError> {
+        let commit = self.repo.head()?.peel_to_commit()?;
+        let author = signature_parts(&commit.author());
+        Ok(author)
+    }
+
+    /// Name and email of the author of a commit. Accepts any revspec.
+    pub fn commit_author(&self, rev: &str) -> Result<(String, String), GitError> {
+        let commit = self.repo.revparse_single(rev)?.peel_to_commit()?;
+        let author = signature_parts(&commit.author());
+        Ok(author)
+    }
+
+    /// Describe a commit: its full hash, message and the files it touched
+    pub fn commit_info(&self, rev: &str) -> Result<CommitInfo, GitError> {
+        let commit = self.repo.revparse_single(rev)?.peel_to_commit()?;
+
+        Ok(CommitInfo {
+            id: commit.id().to_string(),
+            message: commit.message().unwrap_or("").to_string(),
+            parent_count: commit.parent_count(),
+            files: self.files_in_commit(rev)?,
+        })
+    }
+
+    /// Files changed by a commit relative to its first parent (or every file,
+    /// for a root commit). Accepts any revspec, e.g. `HEAD~3`.
+    pub fn files_in_commit(&self, rev: &str) -> Result<Vec<ChangedFile>, GitError> {
+        let commit = self.repo.revparse_single(rev)?.peel_to_commit()?;
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+
+        let diff = self
+            .repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+
+        Ok(diff
+            .deltas()
+            .filter_map(|d| Self::delta_to_change(&d))
+            .collect())
+    }
+
+    /// How many of the last `max_commits` commits reachable from HEAD touched
+    /// each file, keyed by the path the file had in that commit
+    pub fn commit_counts(&self, max_commits: usize) -> Result<HashMap<String, usize>, GitError> {
+        let mut walk = self.repo.revwalk()?;
+        walk.push_head()?;
+
+        let mut counts = HashMap::new();
+        for oid in walk.take(max_commits) {
+            for change in self.files_in_commit(&oid?.to_string())? {
+                *counts.entry(change.path).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Files that differ between a commit and the working tree (staged and
+    /// unstaged changes included, untracked files as additions), e.g. every
+    /// file touched on a branch since it left `main`. Accepts any revspec.
+    pub fn files_changed_since(&self, rev: &str) -> Result<Vec<ChangedFile>, GitError> {
+        let tree = self.repo.revparse_single(rev)?.peel_to_commit()?.tree()?;
+
+        let mut opts = DiffOptions::new();
+        opts.include_untracked(true).recurse_untracked_dirs(true);
+        let mut diff = self
+            .repo
+            .diff_tree_to_workdir_with_index(Some(&tree), Some(&mut opts))?;
+        diff.find_similar(None)?;
+
+        Ok(diff
+            .deltas()
+            .filter_map(|d| Self::delta_to_change(&d))
+            .collect())
+    }
+
+    /// Files under `dir` (relative to the repository root) that differ between
+    /// two revisions, read from their trees rather than the working tree.
+    /// Unlike the other diffs, `.lore/` is included.
+    pub fn files_changed_between(
+        &self,
+        from: &str,
+        to: &str,
+        dir: &str,
+    ) -> Result<Vec<ChangedFile>, GitError> {
+        let from = self.repo.revparse_single(from)?.peel_to_commit()?.tree()?;
+        let to = self.repo.revparse_single(to)?.peel_to_commit()?.tree()?;
+
+        let mut opts = DiffOptions::new();
+        opts.pathspec(dir);
+        let diff = self
+            .repo
+            .diff_tree_to_tree(Some(&from), Some(&to), Some(&mut opts))?;
+
+        Ok(diff
+            .deltas()
+            .filter_map(|d| Self::delta_change(&d))
+            .collect())
+    }
+
+    /// A changed file from a diff delta, skipping `.lore/` and unsupported
+    /// delta kinds
+    fn delta_to_change(delta: &git2::DiffDelta) -> Option<ChangedFile> {
+        Self::delta_change(delta).filter(|change| !change.path.starts_with(".lore/"))
+    }
+
+    /// A changed file from a diff delta, skipping unsupported delta kinds
+    fn delta_change(delta: &git2::DiffDelta) -> Option<ChangedFile> {
+        let change_type = match delta.status() {
+            git2::Delta::Added | git2::Delta::Copied | git2::Delta::Untracked => ChangeType::Added,
+            git2::Delta::Modified => ChangeType::Modified,
+            git2::Delta::Deleted => ChangeType::Deleted,
+            git2::Delta::Renamed => ChangeType::Renamed,
+            _ => return None,
+        };
+        let file = match change_type {
+            ChangeType::Deleted => delta.old_file(),
+            _ => delta.new_file(),
+        };
+        let path = file.path()?.to_string_lossy().replace('\\', "/");
+        let old_path = match change_type {
+            ChangeType::Renamed => delta
+                .old_file()
+                .path()
+                .map(|old| old.to_string_lossy().replace('\\', "/")),
+            _ => None,
+        };
+        Some(ChangedFile {
+            path,
+            old_path,
+            change_type,
+            staged: false,
+            unstaged: false,
+        })
+    }
+
+    /// Contents of `path` as of `rev`, or `None` if the file isn't in that commit
+    pub fn file_at_commit(&self, rev: &str, path: &str) -> Result<Option<Vec<u8>>, GitError> {
+        let tree = self.repo.revparse_single(rev)?.peel_to_commit()?.tree()?;
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let object = entry.to_object(&self.repo)?;
+        Ok(object.as_blob().map(|blob| blob.content().to_vec()))
+    }
+
+    /// Set a value in the repository's own config (`.git/config`)
+    pub fn set_config(&self, key: &str, value: &str) -> Result<(), GitError> {
+        self.repo
+            .config()?
+            .open_level(git2::ConfigLevel::Local)?
+            .set_str(key, value)?;
+        Ok(())
+    }
+
+    /// Remove a key from the repository's own config. Not an error if unset.
+    pub fn unset_config(&self, key: &str) -> Result<(), GitError> {
+        let mut config = self.repo.config()?.open_level(git2::ConfigLevel::Local)?;
+        match config.remove(key) {
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// A value from the repository's config, including global and system settings
+    pub fn config_value(&self, key: &str) -> Option<String> {
+        self.repo.config().ok()?.get_string(key).ok()
+    }
+
+    /// Directory git runs hooks from, honouring `core.hooksPath`
+    pub fn hooks_dir(&self) -> Result<PathBuf, GitError> {
+        let configured = self.repo.config()?.get_path("core.hooksPath").ok();
+        Ok(match configured {
+            Some(path) if path.is_absolute() => path,
+            // Relative hooksPath is resolved against the working tree root
+            Some(path) => self
+                .repo
+                .workdir()
+                .unwrap_or_else(|| self.repo.path())
+                .join(path),
+            None => self.repo.path().join("hooks"),
+        })
+    }
+
+    /// The configured `user.name`, or `user.email` if no name is set
+    pub fn user_identity(&self) -> Option<String> {
+        let config = self.repo.config().ok()?;
+        ["user.name", "user.email"]
+            .iter()
+            .filter_map(|key| config.get_string(key).ok())
+            .map(|value| value.trim().to_string())
+            .find(|value| !value.is_empty())
+    }
+
     /// Get the repo root directory
-    #[allow(dead_code)]
     pub fn workdir(&self) -> Option<&Path> {
         self.repo.workdir()
     }
 
     /// Check if a path is ignored by git
-    #[allow(dead_code)]
     pub fn is_ignored(&self, path: &str) -> bool {
         self.repo.is_path_ignored(Path::new(path)).unwrap_or(false)
     }
 
     /// Determine the change type from a git status
     fn determine_change_type(status: &git2::Status) -> Option<ChangeType> {
-        if status.is_index_new() || status.is_wt_new() {
+        // Checked first: a file renamed and then edited is still a rename
+        if status.is_index_renamed() || status.is_wt_renamed() {
+            Some(ChangeType::Renamed)
+        } else if status.is_index_new() || status.is_wt_new() {
             Some(ChangeType::Added)
         } else if status.is_index_modified() || status.is_wt_modified() {
             Some(ChangeType::Modified)
         } else if status.is_index_deleted() || status.is_wt_deleted() {
             Some(ChangeType::Deleted)
-        } else if status.is_index_renamed() || status.is_wt_renamed() {
-            Some(ChangeType::Renamed)
         } else {
             None
         }
     }
 }
 
+/// Name and email of a signature, replacing any invalid UTF-8
+fn signature_parts(signature: &git2::Signature) -> (String, String) {
+    (
+        String::from_utf8_lossy(signature.name_bytes()).to_string(),
+        String::from_utf8_lossy(signature.email_bytes()).to_string(),
+    )
+}
+
+/// Truncate a diff to at most `max_bytes`, appending a marker if anything was cut
+pub fn truncate_diff(mut diff: String, max_bytes: usize) -> String {
+    if diff.len() <= max_bytes {
+        return diff;
+    }
+
+    let mut cut = max_bytes;
+    while !diff.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    diff.truncate(cut);
+    if !diff.ends_with('\n') {
+        diff.push('\n');
+    }
+    diff.push_str(&format!("... [diff truncated at {} bytes]\n", max_bytes));
+    diff
+}
+
+/// A commit as seen by `record --from-commit`
+#[derive(Debug, Clone)]
+pub struct CommitInfo {
+    pub id: String,
+    pub message: String,
+    /// More than one for a merge commit, whose `files` are diffed against
+    /// the first parent
+    pub parent_count: usize,
+    pub files: Vec<ChangedFile>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ChangedFile {
     pub path: String,
+    /// Where the file was before, for renames
+    pub old_path: Option<String>,
     pub change_type: ChangeType,
-    #[allow(dead_code)]
+    /// Has changes in the index
     pub staged: bool,
+    /// Has changes in the working tree not yet staged (including untracked files)
+    pub unstaged: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ChangeType {
     Added,
     Modified,
@@ -192,6 +523,13 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_user_identity_prefers_name() {
+        let temp_dir = create_git_repo();
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        assert_eq!(git.user_identity().as_deref(), Some("Test User"));
+    }
+
     #[test]
     fn test_git_context_open_not_a_repo() {
         let temp_dir = TempDir::new().unwrap();
@@ -213,6 +551,50 @@ mod tests {
         assert_eq!(hash.len(), 40); // SHA1 hash length
     }
 
+    #[test]
+    fn test_resolve_and_ancestry() {
+        let temp_dir = create_git_repo_with_commit();
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let first = git.head_commit().unwrap();
+
+        std::fs::write(temp_dir.path().join("second.txt"), "second").unwrap();
+        for args in [&["add", "."][..], &["commit", "-m", "Second"]] {
+            Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .expect("Failed to run git");
+        }
+        let second = git.head_commit().unwrap();
+
+        assert_eq!(git.resolve("HEAD~1").unwrap(), first);
+        assert_eq!(git.resolve(&second[..7]).unwrap(), second);
+        assert!(git.resolve("no-such-branch").is_err());
+
+        assert!(git.is_ancestor_or_equal(&first, &second));
+        assert!(git.is_ancestor_or_equal(&second, &second));
+        assert!(!git.is_ancestor_or_equal(&second, &first));
+        assert!(!git.is_ancestor_or_equal(&"0".repeat(40), &second));
+        assert!(!git.is_ancestor_or_equal("not a hash", &second));
+    }
+
+    #[test]
+    fn test_head_author() {
+        let temp_dir = create_git_repo_with_commit();
+        let git = GitContext::open(temp_dir.path()).unwrap();
+
+        let author = ("Test User".to_string(), "test@test.com".to_string());
+        assert_eq!(git.head_author().unwrap(), author);
+        assert_eq!(git.commit_author("HEAD").unwrap(), author);
+
+        // No commits yet
+        let empty = create_git_repo();
+        assert!(GitContext::open(empty.path())
+            .unwrap()
+            .head_author()
+            .is_err());
+    }
+
     #[test]
     fn test_git_context_workdir() {
         let temp_dir = create_git_repo();
@@ -301,6 +683,51 @@ mod tests {
         assert!(!git.is_ignored("test.txt"));
     }
 
+    #[test]
+    fn test_diff_file_modified() {
+        let temp_dir = create_git_repo_with_commit();
+        std::fs::write(temp_dir.path().join("initial.txt"), "modified content\n").unwrap();
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let diff = git.diff_file("initial.txt").unwrap();
+
+        assert!(diff.contains("-initial content"));
+        assert!(diff.contains("+modified content"));
+    }
+
+    #[test]
+    fn test_diff_file_untracked_is_all_additions() {
+        let temp_dir = create_git_repo_with_commit();
+        std::fs::write(temp_dir.path().join("new.txt"), "line one\nline two\n").unwrap();
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let diff = git.diff_file("new.txt").unwrap();
+
+        assert!(diff.contains("+line one"));
+        assert!(diff.contains("+line two"));
+    }
+
+    #[test]
+    fn test_diff_file_unchanged_is_empty() {
+        let temp_dir = create_git_repo_with_commit();
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let diff = git.diff_file("initial.txt").unwrap();
+
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_truncate_diff() {
+        let diff = "+abc\n+def\n".to_string();
+        assert_eq!(truncate_diff(diff.clone(), 100), diff);
+
+        let truncated = truncate_diff(diff, 5);
+        assert!(truncated.starts_with("+abc\n"));
+        assert!(!truncated.contains("def"));
+        assert!(truncated.ends_with("[diff truncated at 5 bytes]\n"));
+    }
+
     #[test]
     fn test_change_type_display() {
         assert_eq!(format!("{}", ChangeType::Added), "added");
@@ -319,8 +746,10 @@ mod tests {
     fn test_changed_file_struct() {
         let changed = ChangedFile {
             path: "src/main.rs".to_string(),
+            old_path: None,
             change_type: ChangeType::Modified,
             staged: true,
+            unstaged: false,
         };
 
         assert_eq!(changed.path, "src/main.rs");
@@ -332,8 +761,10 @@ mod tests {
     fn test_changed_file_clone() {
         let original = ChangedFile {
             path: "test.rs".to_string(),
+            old_path: None,
             change_type: ChangeType::Added,
             staged: false,
+            unstaged: true,
         };
 
         let cloned = original.clone();
@@ -369,6 +800,249 @@ mod tests {
         let staged_file = changes.iter().find(|c| c.path == "staged.txt");
         assert!(staged_file.is_some());
         assert!(staged_file.unwrap().staged);
+        assert!(!staged_file.unwrap().unstaged);
+
+        // Editing it again leaves it both staged and unstaged
+        std::fs::write(temp_dir.path().join("staged.txt"), "more content").unwrap();
+        let changes = git.changed_files().unwrap();
+        let staged_file = changes.iter().find(|c| c.path == "staged.txt").unwrap();
+        assert!(staged_file.staged);
+        assert!(staged_file.unstaged);
+    }
+
+    #[test]
+    fn test_git_context_changed_files_staged_rename() {
+        let temp_dir = create_git_repo_with_commit();
+
+        Command::new("git")
+            .args(["mv", "initial.txt", "renamed.txt"])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to rename file");
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let changes = git.changed_files().unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "renamed.txt");
+        assert_eq!(changes[0].old_path.as_deref(), Some("initial.txt"));
+        assert_eq!(changes[0].change_type, ChangeType::Renamed);
+        assert!(changes[0].staged);
+    }
+
+    #[test]
+    fn test_git_context_changed_files_unstaged_rename() {
+        let temp_dir = create_git_repo_with_commit();
+
+        std::fs::rename(
+            temp_dir.path().join("initial.txt"),
+            temp_dir.path().join("moved.txt"),
+        )
+        .unwrap();
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let changes = git.changed_files().unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "moved.txt");
+        assert_eq!(changes[0].old_path.as_deref(), Some("initial.txt"));
+        assert_eq!(changes[0].change_type, ChangeType::Renamed);
+    }
+
+    #[test]
+    fn test_commit_info_lists_files_in_commit() {
+        let temp_dir = create_git_repo_with_commit();
+
+        std::fs::write(temp_dir.path().join("initial.txt"), "changed").unwrap();
+        std::fs::write(temp_dir.path().join("new.txt"), "new").unwrap();
+        Command::new("git")
+            .args(["add", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to add files");
+        Command::new("git")
+            .args(["commit", "-m", "Second commit\n\nWith a body"])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to create commit");
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let info = git.commit_info("HEAD").unwrap();
+
+        assert_eq!(info.id, git.head_commit().unwrap());
+        assert_eq!(info.message.trim_end(), "Second commit\n\nWith a body");
+        assert_eq!(info.files.len(), 2);
+        assert!(info
+            .files
+            .iter()
+            .any(|f| f.path == "initial.txt" && f.change_type == ChangeType::Modified));
+        assert!(info
+            .files
+            .iter()
+            .any(|f| f.path == "new.txt" && f.change_type == ChangeType::Added));
+    }
+
+    #[test]
+    fn test_files_in_commit_with_revspec() {
+        let temp_dir = create_git_repo_with_commit();
+
+        std::fs::write(temp_dir.path().join("second.txt"), "second").unwrap();
+        Command::new("git")
+            .args(["add", "."])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to add files");
+        Command::new("git")
+            .args(["commit", "-m", "Second"])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to create commit");
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let files = git.files_in_commit("HEAD~1").unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "initial.txt");
+    }
+
+    #[test]
+    fn test_files_changed_since_spans_commits_and_working_tree() {
+        let temp_dir = create_git_repo_with_commit();
+        let run = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .expect("Failed to run git");
+        };
+
+        std::fs::write(temp_dir.path().join("committed.txt"), "one").unwrap();
+        run(&["add", "committed.txt"]);
+        run(&["commit", "-m", "Second"]);
+        std::fs::write(temp_dir.path().join("initial.txt"), "edited").unwrap();
+        std::fs::write(temp_dir.path().join("untracked.txt"), "new").unwrap();
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let mut changes = git.files_changed_since("HEAD~1").unwrap();
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.change_type))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("committed.txt", ChangeType::Added),
+                ("initial.txt", ChangeType::Modified),
+                ("untracked.txt", ChangeType::Added),
+            ]
+        );
+
+        assert!(git.files_changed_since("no-such-ref").is_err());
+    }
+
+    #[test]
+    fn test_commit_counts() {
+        let temp_dir = create_git_repo_with_commit();
+        let run = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .expect("Failed to run git");
+        };
+
+        for content in ["one", "two"] {
+            std::fs::write(temp_dir.path().join("initial.txt"), content).unwrap();
+            std::fs::write(temp_dir.path().join("other.txt"), content).unwrap();
+            run(&["add", "."]);
+            run(&["commit", "-m", content]);
+        }
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let counts = git.commit_counts(100).unwrap();
+        assert_eq!(counts.get("initial.txt"), Some(&3));
+        assert_eq!(counts.get("other.txt"), Some(&2));
+
+        // Only the newest commit
+        let counts = git.commit_counts(1).unwrap();
+        assert_eq!(counts.get("initial.txt"), Some(&1));
+    }
+
+    #[test]
+    fn test_file_at_commit_reads_blob() {
+        let temp_dir = create_git_repo_with_commit();
+        std::fs::write(temp_dir.path().join("initial.txt"), "changed on disk").unwrap();
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        assert_eq!(
+            git.file_at_commit("HEAD", "initial.txt").unwrap().unwrap(),
+            b"initial content"
+        );
+        assert!(git.file_at_commit("HEAD", "missing.txt").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_files_changed_between_reads_trees_under_dir() {
+        let temp_dir = create_git_repo_with_commit();
+        let run = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(temp_dir.path())
+                .output()
+                .expect("Failed to run git");
+        };
+        let base = GitContext::open(temp_dir.path())
+            .unwrap()
+            .head_commit()
+            .unwrap();
+
+        std::fs::create_dir_all(temp_dir.path().join(".lore/entries/ab")).unwrap();
+        std::fs::write(temp_dir.path().join(".lore/entries/ab/abc.json"), "{}").unwrap();
+        std::fs::write(temp_dir.path().join("other.txt"), "other").unwrap();
+        run(&["add", "."]);
+        run(&["commit", "-m", "Add lore"]);
+
+        // Not committed, so not part of the diff
+        std::fs::write(temp_dir.path().join(".lore/entries/ab/abd.json"), "{}").unwrap();
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let files = git
+            .files_changed_between(&base, "HEAD", ".lore/entries")
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, ".lore/entries/ab/abc.json");
+        assert_eq!(files[0].change_type, ChangeType::Added);
+
+        assert_eq!(git.merge_base(&base, "HEAD").unwrap(), base);
+    }
+
+    #[test]
+    fn test_commit_info_root_commit() {
+        let temp_dir = create_git_repo_with_commit();
+        let git = GitContext::open(temp_dir.path()).unwrap();
+
+        let info = git.commit_info("HEAD").unwrap();
+        assert_eq!(info.files.len(), 1);
+        assert_eq!(info.files[0].path, "initial.txt");
+    }
+
+    #[test]
+    fn test_hooks_dir_default_and_configured() {
+        let temp_dir = create_git_repo();
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        assert!(git.hooks_dir().unwrap().ends_with(".git/hooks"));
+
+        Command::new("git")
+            .args(["config", "core.hooksPath", ".githooks"])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to set hooksPath");
+
+        let git = GitContext::open(temp_dir.path()).unwrap();
+        let hooks = git.hooks_dir().unwrap();
+        assert!(hooks.ends_with(".githooks"));
+        assert!(hooks.starts_with(git.workdir().unwrap()));
     }
 
     #[test]
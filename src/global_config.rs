@@ -0,0 +1,233 @@
+use crate::git::GitContext;
+use crate::storage::LoreStorage;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that sets the agent ID for every invocation
+pub const AGENT_ID_ENV: &str = "LORE_AGENT_ID";
+
+/// Keys `lore config --global` can read and write
+pub const GLOBAL_CONFIG_KEYS: &[&str] = &["agent_id"];
+
+/// Per-user settings, shared by every repository
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlobalConfig {
+    /// This user's agent ID, used when neither `--agent` nor `LORE_AGENT_ID` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+
+    /// Settings this version doesn't know about, kept so saving doesn't drop them
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl GlobalConfig {
+    /// `$XDG_CONFIG_HOME/lore/config.json`, falling back to `~/.config/lore/config.json`
+    pub fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("lore").join("config.json"))
+    }
+
+    /// Load the per-user config (defaults if there is none)
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn load_from(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the per-user config, creating its directory if needed
+    pub fn save(&self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let path =
+            Self::path().ok_or("Can't locate the user config directory (HOME is not set)")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        match key {
+            "agent_id" => Ok(self.agent_id.clone()),
+            _ => Err(unknown_key(key)),
+        }
+    }
+
+    /// Set `key`; an empty value unsets it
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+        match key {
+            "agent_id" => self.agent_id = value,
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_key(key: &str) -> String {
+    format!(
+        "Unknown global config key '{}'. Valid keys: {}",
+        key,
+        GLOBAL_CONFIG_KEYS.join(", ")
+    )
+}
+
+/// Where the agent ID in use came from, in order of precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentSource {
+    Flag,
+    Environment,
+    UserConfig,
+    RepoConfig,
+    Git,
+    Default,
+}
+
+impl fmt::Display for AgentSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AgentSource::Flag => "from --agent",
+            AgentSource::Environment => "from environment",
+            AgentSource::UserConfig => "from user config",
+            AgentSource::RepoConfig => "from repository config",
+            AgentSource::Git => "from git user",
+            AgentSource::Default => "default",
+        })
+    }
+}
+
+/// Candidate agent IDs from each source, before precedence is applied
+#[derive(Debug, Default)]
+pub struct AgentCandidates {
+    pub flag: Option<String>,
+    pub environment: Option<String>,
+    pub user_config: Option<String>,
+    pub repo_config: Option<String>,
+    pub git: Option<String>,
+}
+
+impl AgentCandidates {
+    /// The first non-empty candidate, or "unknown"
+    pub fn choose(self) -> (String, AgentSource) {
+        [
+            (self.flag, AgentSource::Flag),
+            (self.environment, AgentSource::Environment),
+            (self.user_config, AgentSource::UserConfig),
+            (self.repo_config, AgentSource::RepoConfig),
+            (self.git, AgentSource::Git),
+        ]
+        .into_iter()
+        .find_map(|(candidate, source)| {
+            candidate
+                .filter(|id| !id.trim().is_empty())
+                .map(|id| (id, source))
+        })
+        .unwrap_or_else(|| ("unknown".to_string(), AgentSource::Default))
+    }
+}
+
+/// Work out which agent ID to record with: `--agent`, then `LORE_AGENT_ID`,
+/// then the per-user config, then the repository's `default_agent_id`, then the
+/// git user, and finally "unknown"
+pub fn resolve_agent_id(
+    flag: Option<&str>,
+    storage: &LoreStorage,
+    root: &Path,
+) -> Result<(String, AgentSource), Box<dyn std::error::Error>> {
+    let candidates = AgentCandidates {
+        flag: flag.map(str::to_string),
+        environment: std::env::var(AGENT_ID_ENV).ok(),
+        user_config: GlobalConfig::load()?.agent_id,
+        // "unknown" is what `lore init` writes when no agent is given
+        repo_config: storage
+            .load_config()?
+            .default_agent_id
+            .filter(|id| id != "unknown"),
+        git: GitContext::open(root)
+            .ok()
+            .and_then(|git| git.user_identity()),
+    };
+    Ok(candidates.choose())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn some(id: &str) -> Option<String> {
+        Some(id.to_string())
+    }
+
+    #[test]
+    fn test_choose_follows_precedence() {
+        let candidates = AgentCandidates {
+            flag: None,
+            environment: some("ci-bot"),
+            user_config: some("alice"),
+            repo_config: some("team"),
+            git: some("Alice Example"),
+        };
+        assert_eq!(
+            candidates.choose(),
+            ("ci-bot".to_string(), AgentSource::Environment)
+        );
+
+        let candidates = AgentCandidates {
+            flag: some("claude"),
+            environment: some("ci-bot"),
+            ..AgentCandidates::default()
+        };
+        assert_eq!(candidates.choose().1, AgentSource::Flag);
+    }
+
+    #[test]
+    fn test_choose_skips_empty_and_falls_back() {
+        let candidates = AgentCandidates {
+            environment: some(""),
+            git: some("Alice Example"),
+            ..AgentCandidates::default()
+        };
+        assert_eq!(
+            candidates.choose(),
+            ("Alice Example".to_string(), AgentSource::Git)
+        );
+
+        assert_eq!(
+            AgentCandidates::default().choose(),
+            ("unknown".to_string(), AgentSource::Default)
+        );
+    }
+
+    #[test]
+    fn test_global_config_load_and_set() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+
+        assert!(GlobalConfig::load_from(&path).unwrap().agent_id.is_none());
+
+        std::fs::write(&path, r#"{"agent_id": "alice", "theme": "dark"}"#).unwrap();
+        let mut config = GlobalConfig::load_from(&path).unwrap();
+        assert_eq!(config.get("agent_id").unwrap().as_deref(), Some("alice"));
+        assert_eq!(config.other["theme"], "dark");
+
+        config.set("agent_id", " ").unwrap();
+        assert!(config.agent_id.is_none());
+        assert!(config.set("default_tags", "x").is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! Lore - A reasoning engine for code
+//!
+//! Stores the "why" behind code changes in a `.lore` directory next to the
+//! code. The `lore` binary is a thin command-line layer over this crate; tools
+//! that want the same data without parsing CLI output can use [`storage`],
+//! [`models`], [`query`] and [`git`] directly.
+//!
+//! ```
+//! use lore::models::ThoughtObject;
+//! use lore::query::Query;
+//! use lore::storage::{EntryMatcher, LoreStorage};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let dir = tempfile::tempdir()?;
+//! let storage = LoreStorage::new(dir.path());
+//! storage.init(Some("docs-bot"))?;
+//!
+//! // Record an entry
+//! let entry = ThoughtObject::new(
+//!     "src/auth.rs".to_string(),
+//!     "sha256:...".to_string(),
+//!     "docs-bot".to_string(),
+//!     "Switch to JWT validation".to_string(),
+//!     "Sessions didn't survive the load balancer.".to_string(),
+//! )
+//! .with_tags(vec!["auth".to_string()]);
+//! storage.save_entry(&entry)?;
+//!
+//! // Everything recorded for a file, newest first
+//! let entries = storage.get_entries_for_file("src/auth.rs")?;
+//! assert_eq!(entries[0].intent, "Switch to JWT validation");
+//!
+//! // Search across all entries
+//! let matcher = EntryMatcher::expression(Query::parse("jwt AND balancer", false)?, false);
+//! let found: Vec<_> = storage
+//!     .get_all_entries()?
+//!     .into_iter()
+//!     .filter(|e| matcher.matches(e))
+//!     .collect();
+//! assert_eq!(found.len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod git;
+pub mod models;
+pub mod query;
+pub mod storage;
+
+/// Implementations of the `lore` subcommands. They write to the `out` they are
+/// given and are shaped around the CLI; prefer [`storage`] for programmatic
+/// access.
+#[doc(hidden)]
+pub mod commands;
+#[doc(hidden)]
+pub mod filter;
+#[doc(hidden)]
+pub mod output;
+
+mod diagnostics;
+mod global_config;
+mod symbols;
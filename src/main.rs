@@ -1,9 +1,12 @@
-mod commands;
-mod git;
-mod models;
-mod storage;
-
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Utc};
+use clap::{CommandFactory, Parser, Subcommand};
+use lore::commands::{CommandError, Outcome};
+use lore::git::GitError;
+use lore::output::{self, ColorChoice, ErrorFormat, Format};
+use lore::storage::{find_lore_root, LoreStorage, StorageError};
+use lore::{commands, filter, models};
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
 use std::path::PathBuf;
 
 /// Lore - A reasoning engine for code
@@ -15,10 +18,29 @@ use std::path::PathBuf;
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// When to color output
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Never color output (same as --color never)
+    #[arg(long, global = true, conflicts_with = "color")]
+    no_color: bool,
+
+    /// Draw separators and symbols with ASCII (the default when the locale
+    /// isn't UTF-8)
+    #[arg(long, global = true)]
+    ascii: bool,
+
+    /// How to print errors on stderr; json gives {"error": {"kind", "message"}}
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+// Parsed once per run, so the size of the largest variant doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize a new Lore repository
@@ -30,6 +52,10 @@ enum Commands {
         /// Default agent/author ID
         #[arg(short, long)]
         agent: Option<String>,
+
+        /// Restore missing files in an existing .lore directory without wiping data
+        #[arg(long)]
+        repair: bool,
     },
 
     /// Record reasoning for code changes
@@ -46,6 +72,18 @@ enum Commands {
         #[arg(long)]
         trace_file: Option<PathBuf>,
 
+        /// Start the trace from a saved template when none is given (see `lore template`)
+        #[arg(long, value_name = "NAME")]
+        template: Option<String>,
+
+        /// Write the trace in $VISUAL/$EDITOR (the default at a terminal, see `edit_trace`)
+        #[arg(long, conflicts_with = "no_edit")]
+        edit: bool,
+
+        /// Prompt for the trace instead of opening an editor; end it with a lone '.'
+        #[arg(long)]
+        no_edit: bool,
+
         /// Specific files to record (auto-detects from git if not specified)
         #[arg(short, long, action = clap::ArgAction::Append)]
         file: Vec<String>,
@@ -54,54 +92,221 @@ enum Commands {
         #[arg(short, long)]
         agent: Option<String>,
 
-        /// Rejected alternatives (can be used multiple times)
-        #[arg(short, long, action = clap::ArgAction::Append)]
-        rejected: Vec<String>,
+        /// Rejected alternative as "name" or "name: reason" (can be used multiple times)
+        #[arg(short, long, value_parser = filter::parse_rejected, action = clap::ArgAction::Append)]
+        rejected: Vec<models::RejectedAlternative>,
+
+        /// JSON or YAML list of {name, reason} rejected alternatives
+        #[arg(long, value_name = "PATH")]
+        rejected_file: Option<PathBuf>,
 
         /// Tags for categorization (can be used multiple times)
         #[arg(short = 'T', long, action = clap::ArgAction::Append)]
         tag: Vec<String>,
 
-        /// Line range in format "start-end" (e.g., "10-45")
-        #[arg(short, long)]
-        lines: Option<String>,
+        /// Extra key=value data such as model=gpt-4 or ticket=URL (can be used multiple times)
+        #[arg(long, value_name = "KEY=VALUE", value_parser = filter::parse_key_value, action = clap::ArgAction::Append)]
+        meta: Vec<(String, String)>,
+
+        /// How sure you are of this reasoning, from 0.0 to 1.0
+        #[arg(long, value_parser = filter::parse_confidence)]
+        confidence: Option<f32>,
+
+        /// Issue key or URL of a ticket or pull request this change came from (can be used multiple times)
+        #[arg(long = "ref", value_name = "URL", value_parser = filter::parse_reference, action = clap::ArgAction::Append)]
+        reference: Vec<String>,
+
+        /// File to store with the entry, e.g. a benchmark log (can be used multiple times)
+        #[arg(long, value_name = "PATH", action = clap::ArgAction::Append, conflicts_with_all = ["amend", "continue_draft", "finish", "discard_draft"])]
+        attach: Vec<PathBuf>,
+
+        /// Line range in format "start-end" (e.g., "10-45"); relative to the symbol with --symbol
+        #[arg(short, long, value_parser = filter::parse_line_range)]
+        lines: Option<(usize, usize)>,
+
+        /// Record the definition of this function, class or type (Rust, JS/TS, Python)
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["continue_draft", "finish", "discard_draft"])]
+        symbol: Option<String>,
+
+        /// Text marking where the reasoning applies (e.g. "fn authenticate"); explain and
+        /// blame find it again after edits. With --lines or --symbol, it must be on the
+        /// range's first line.
+        #[arg(long, value_name = "TEXT", conflicts_with_all = ["continue_draft", "finish", "discard_draft"])]
+        anchor: Option<String>,
 
         /// Read reasoning trace from stdin
         #[arg(long)]
         stdin: bool,
+
+        /// Append this trace to a pending draft instead of recording immediately
+        #[arg(long = "continue", conflicts_with = "discard_draft")]
+        continue_draft: bool,
+
+        /// Append to the pending draft (if any) and record it
+        #[arg(long, conflicts_with = "discard_draft")]
+        finish: bool,
+
+        /// Discard the pending draft without recording it
+        #[arg(long)]
+        discard_draft: bool,
+
+        /// Don't capture the git diff of each file
+        #[arg(long)]
+        no_diff: bool,
+
+        /// Don't move the entries of files git reports as renamed to their new path
+        #[arg(long)]
+        no_follow_renames: bool,
+
+        /// Only record files with staged changes (with --file, fail unless they're staged)
+        #[arg(long, conflicts_with = "unstaged")]
+        staged: bool,
+
+        /// Only record files with unstaged changes (with --file, fail unless they have some)
+        #[arg(long)]
+        unstaged: bool,
+
+        /// Record every file changed since this revision (committed or not), e.g. a branch's base
+        #[arg(long, value_name = "REV", conflicts_with_all = ["file", "staged", "unstaged", "from_commit", "commit", "continue_draft", "finish", "discard_draft"])]
+        since: Option<String>,
+
+        /// Record the files changed in this commit (default HEAD), using its message as intent and trace
+        #[arg(long, value_name = "REV", num_args = 0..=1, default_missing_value = "HEAD", group = "commit_source", conflicts_with_all = ["continue_draft", "finish", "discard_draft"])]
+        from_commit: Option<String>,
+
+        /// Attach reasoning to the files changed in an existing commit, hashed as of that commit
+        #[arg(long, value_name = "REV", group = "commit_source", conflicts_with_all = ["from_commit", "continue_draft", "finish", "discard_draft"])]
+        commit: Option<String>,
+
+        /// Record a merge commit's changes relative to its first parent (merges are rejected otherwise)
+        #[arg(long, requires = "commit_source")]
+        first_parent: bool,
+
+        /// ID (or unique prefix) of an earlier entry this one replaces
+        #[arg(long, value_name = "ID", conflicts_with_all = ["continue_draft", "finish", "discard_draft"])]
+        supersedes: Option<String>,
+
+        /// ID (or unique prefix) of a related entry (can be used multiple times)
+        #[arg(long, value_name = "ID", action = clap::ArgAction::Append, conflicts_with_all = ["continue_draft", "finish", "discard_draft"])]
+        related: Vec<String>,
+
+        /// Sub-project to file the entries under (default: directory of the nearest Cargo.toml/package.json)
+        #[arg(long, conflicts_with_all = ["continue_draft", "finish", "discard_draft"])]
+        project: Option<String>,
+
+        /// Append the trace, tags and rejected alternatives to the newest entry for the --file
+        #[arg(long, requires = "file", conflicts_with_all = ["message", "lines", "symbol", "anchor", "confidence", "meta", "staged", "unstaged", "since", "from_commit", "commit", "supersedes", "related", "project", "continue_draft", "finish", "discard_draft"])]
+        amend: bool,
+
+        /// Show the entries that would be recorded (files, hashes, commit, tags) without saving them
+        #[arg(long, conflicts_with_all = ["amend", "continue_draft", "finish", "discard_draft"])]
+        dry_run: bool,
+
+        /// Record even if a file's newest entry has the same content hash, intent and trace
+        #[arg(long)]
+        allow_duplicate: bool,
+
+        /// Record every entry in a YAML (or .json) list of {files, intent, trace, tags, rejected, lines}
+        #[arg(long = "from", value_name = "PATH", conflicts_with_all = ["message", "trace", "trace_file", "stdin", "template", "edit", "file", "rejected", "rejected_file", "lines", "symbol", "anchor", "staged", "unstaged", "since", "from_commit", "commit", "supersedes", "related", "amend", "continue_draft", "finish", "discard_draft"])]
+        manifest: Option<PathBuf>,
+
+        /// Output the recorded entries and any warnings as JSON (never prompts)
+        #[arg(long, conflicts_with_all = ["continue_draft", "discard_draft"])]
+        json: bool,
     },
 
     /// Explain the reasoning behind a file
     Explain {
-        /// File to explain
-        file: String,
+        /// Files to explain, directories to explain every file under, or glob
+        /// patterns (e.g. 'src/auth/*.rs') matched against the files with reasoning
+        #[arg(required = true)]
+        files: Vec<String>,
 
-        /// Show all history, not just most recent
+        /// Show all history, not just most recent, including superseded entries
         #[arg(short, long)]
         all: bool,
 
-        /// Output as JSON
-        #[arg(long)]
+        /// Output format
+        #[arg(long, value_enum, default_value_t = Format::Pretty)]
+        format: Format,
+
+        /// Same as --format json
+        #[arg(long, hide = true, conflicts_with = "format")]
         json: bool,
 
+        /// One compact JSON entry per line, for streaming (same as --format ndjson)
+        #[arg(long, conflicts_with_all = ["format", "json"])]
+        ndjson: bool,
+
         /// Limit number of entries to show
         #[arg(short, long)]
         limit: Option<usize>,
+
+        /// Skip this many entries before applying --limit (e.g. --offset 20 --limit 20 for page two)
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// Only show entries whose recorded file hash matches the file on disk
+        #[arg(long)]
+        current_only: bool,
+
+        /// Show the diff captured with each entry
+        #[arg(long)]
+        show_diff: bool,
+
+        /// Show oldest first (applied after --limit, which keeps the newest)
+        #[arg(long)]
+        reverse: bool,
+
+        /// Print reasoning exactly as stored, without indentation (keeps code blocks intact)
+        #[arg(long, visible_alias = "no-indent")]
+        raw_reasoning: bool,
+
+        /// Only show entries whose line range overlaps this one (e.g., 40-80), most overlap
+        /// first, followed by file-level reasoning
+        #[arg(long, value_parser = filter::parse_line_range, conflicts_with = "reverse")]
+        lines: Option<(usize, usize)>,
+
+        /// With --lines, leave out file-level reasoning
+        #[arg(long, requires = "lines")]
+        strict: bool,
+
+        /// Show reasoning as of a past commit: only entries recorded at or before it
+        #[arg(long, value_name = "COMMIT")]
+        at: Option<String>,
+
+        /// Match the recorded path regardless of case (automatic on case-insensitive filesystems)
+        #[arg(short, long)]
+        ignore_case: bool,
     },
 
     /// Search through reasoning history
     Search {
-        /// Search query (searches intent, reasoning, rejected alternatives)
-        query: String,
+        /// Search query (searches intent, reasoning, rejected alternatives).
+        /// Optional when --lines is given.
+        #[arg(required_unless_present = "lines")]
+        query: Option<String>,
 
-        /// Output as JSON
-        #[arg(long)]
+        /// Output format
+        #[arg(long, value_enum, default_value_t = Format::Pretty)]
+        format: Format,
+
+        /// Same as --format json
+        #[arg(long, hide = true, conflicts_with = "format")]
         json: bool,
 
+        /// One compact JSON entry per line, for streaming (same as --format ndjson)
+        #[arg(long, conflicts_with_all = ["format", "json"])]
+        ndjson: bool,
+
         /// Limit number of results
         #[arg(short, long)]
         limit: Option<usize>,
 
+        /// Skip this many entries before applying --limit (e.g. --offset 20 --limit 20 for page two)
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
         /// Filter by file path (substring match)
         #[arg(short, long)]
         file: Option<String>,
@@ -109,99 +314,1388 @@ enum Commands {
         /// Filter by agent ID (substring match)
         #[arg(short, long)]
         agent: Option<String>,
+
+        /// Treat the query as a regular expression
+        #[arg(long)]
+        regex: bool,
+
+        /// Match case-insensitively (regex mode; plain search always ignores case)
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Only match whole words (so "cat" doesn't match "concatenate")
+        #[arg(short, long)]
+        word: bool,
+
+        /// Match entries containing any of the terms instead of all of them
+        #[arg(long, conflicts_with = "regex")]
+        any: bool,
+
+        /// With --format json, yaml or toml, add each result's snippet and its highlight spans
+        #[arg(long)]
+        with_snippet: bool,
+
+        /// Only include entries recorded at or after this time (RFC3339, date, or relative like 7d/2w)
+        #[arg(long, value_parser = filter::parse_time_bound)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only include entries recorded at or before this time (RFC3339, date, or relative like 7d/2w)
+        #[arg(long, value_parser = filter::parse_time_bound)]
+        until: Option<DateTime<Utc>>,
+
+        /// Only include entries with this tag (repeatable; all must match unless --any-tag)
+        #[arg(short = 'T', long, action = clap::ArgAction::Append)]
+        tag: Vec<String>,
+
+        /// Match entries carrying any of the --tag values instead of all of them
+        #[arg(long)]
+        any_tag: bool,
+
+        /// Only include entries with this metadata (repeatable; all must match)
+        #[arg(long, value_name = "KEY=VALUE", value_parser = filter::parse_key_value, action = clap::ArgAction::Append)]
+        meta: Vec<(String, String)>,
+
+        /// Only entries recorded with at least this confidence (0.0-1.0)
+        #[arg(long, value_parser = filter::parse_confidence)]
+        min_confidence: Option<f32>,
+
+        /// Only include entries recorded for this sub-project (e.g., crates/core)
+        #[arg(long)]
+        project: Option<String>,
+
+        /// Only include entries whose line range overlaps this one (e.g., 20-40)
+        #[arg(long, value_parser = filter::parse_line_range)]
+        lines: Option<(usize, usize)>,
     },
 
     /// List all recorded entries
     List {
-        /// Output as JSON
-        #[arg(long)]
+        /// Output format
+        #[arg(long, value_enum, default_value_t = Format::Pretty)]
+        format: Format,
+
+        /// Same as --format json
+        #[arg(long, hide = true, conflicts_with = "format")]
         json: bool,
 
+        /// One compact JSON entry per line, for streaming (same as --format ndjson)
+        #[arg(long, conflicts_with_all = ["format", "json"])]
+        ndjson: bool,
+
         /// Limit number of entries to show
         #[arg(short, long)]
         limit: Option<usize>,
+
+        /// Skip this many entries before applying --limit (e.g. --offset 20 --limit 20 for page two)
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// Only include entries recorded at or after this time (RFC3339, date, or relative like 7d/2w)
+        #[arg(long, value_parser = filter::parse_time_bound)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only include entries recorded at or before this time (RFC3339, date, or relative like 7d/2w)
+        #[arg(long, value_parser = filter::parse_time_bound)]
+        until: Option<DateTime<Utc>>,
+
+        /// Only include entries with this tag (repeatable; all must match unless --any-tag)
+        #[arg(short = 'T', long, action = clap::ArgAction::Append)]
+        tag: Vec<String>,
+
+        /// Match entries carrying any of the --tag values instead of all of them
+        #[arg(long)]
+        any_tag: bool,
+
+        /// Only include entries recorded for this sub-project (e.g., crates/core)
+        #[arg(long)]
+        project: Option<String>,
+
+        /// Order entries by date (newest first), file or agent
+        #[arg(long, value_enum, default_value_t = commands::list::SortKey::Date)]
+        sort: commands::list::SortKey,
+
+        /// Show in reverse order (applied after --offset/--limit, which page in --sort order)
+        #[arg(long)]
+        reverse: bool,
+    },
+
+    /// Manage git hooks that run lore automatically
+    Hook {
+        #[command(subcommand)]
+        action: HookAction,
+    },
+
+    /// Show a chronological feed of reasoning across all files
+    Log {
+        /// Limit number of entries to show
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Filter by author/agent ID (substring match)
+        #[arg(short, long, visible_alias = "agent")]
+        author: Option<String>,
+
+        /// Filter by file path (substring match)
+        #[arg(short, long)]
+        file: Option<String>,
+
+        /// One line per entry: short ID, time, agent, file and intent
+        #[arg(long, conflicts_with = "full")]
+        oneline: bool,
+
+        /// Include the first lines of each reasoning trace
+        #[arg(long)]
+        full: bool,
+
+        /// Show oldest first (applied after --limit, which keeps the newest)
+        #[arg(long)]
+        reverse: bool,
+
+        /// Output entries grouped by day as JSON
+        #[arg(long)]
+        json: bool,
     },
 
     /// Show Lore status for the current repository
-    Status,
+    Status {
+        /// Output format; structured formats include the full list of changed files without reasoning
+        #[arg(long, value_enum, default_value_t = Format::Pretty, conflicts_with = "quiet")]
+        format: Format,
+
+        /// Same as --format json
+        #[arg(long, hide = true, conflicts_with_all = ["format", "quiet"])]
+        json: bool,
+
+        /// Print nothing; exit with status 1 if a changed file has no reasoning (for CI)
+        #[arg(long, short)]
+        quiet: bool,
+
+        /// Only consider staged changes
+        #[arg(long)]
+        staged_only: bool,
+    },
+
+    /// Export entries, index and config to a single portable JSON document
+    Export {
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = commands::export::ExportFormat::Json)]
+        format: commands::export::ExportFormat,
+
+        /// Only export entries for files matching this substring
+        #[arg(short, long)]
+        file: Option<String>,
+
+        /// Only export entries recorded at or after this time (RFC3339, date, or relative like 7d/2w)
+        #[arg(long, value_parser = filter::parse_time_bound)]
+        since: Option<DateTime<Utc>>,
+    },
+
+    /// Import entries from a `lore export` document
+    Import {
+        /// Export document to import
+        file: PathBuf,
+
+        /// Skip entries whose ID already exists (default)
+        #[arg(long, conflicts_with = "overwrite")]
+        merge: bool,
+
+        /// Replace entries whose ID already exists
+        #[arg(long)]
+        overwrite: bool,
+    },
+
+    /// Move a file's entries to a new path, e.g. after `git mv`
+    Mv {
+        /// Path the entries are recorded against
+        old: String,
+
+        /// Path to move them to
+        new: String,
+    },
+
+    /// Compare two entries: intent, a line diff of the reasoning, and added or
+    /// removed tags and rejected alternatives
+    Diff {
+        /// The earlier entry's ID (or a unique prefix)
+        old: String,
+
+        /// The later entry's ID (or a unique prefix)
+        new: String,
+
+        /// Output the changes as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Merge two versions of .lore/index.json or search_index.json for git
+    /// (see `lore hook install --merge-driver`)
+    MergeIndex {
+        /// Our version (%A), overwritten with the result
+        ours: PathBuf,
+
+        /// Their version (%B)
+        theirs: PathBuf,
+
+        /// The common ancestor's version (%O)
+        base: PathBuf,
+    },
+
+    /// Show the entries committed to .lore between two revisions, e.g. on a branch
+    DiffLore {
+        /// Revision the branch started from; compared from its merge base with HEAD_REV
+        #[arg(default_value = "origin/main")]
+        base: String,
+
+        /// Revision with the new reasoning
+        #[arg(value_name = "HEAD_REV", default_value = "HEAD")]
+        head: String,
+
+        /// Output the added, removed and modified entries in full as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare this store's entries with another store's
+    DiffStores {
+        /// Root of the other project (or its .lore directory), e.g. a checkout of the base branch
+        other: PathBuf,
+
+        /// Only print a one-line summary of the counts
+        #[arg(long, conflicts_with = "json")]
+        stat: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Browse entries interactively: a list on the left, the selected entry on the right
+    Browse,
+
+    /// Annotate each line of a file with the reasoning that covers it
+    Blame {
+        /// File to annotate
+        file: String,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show what fraction of a file's lines are covered by recorded reasoning
+    Coverage {
+        /// File to check
+        file: String,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the append-only log of changes to the store (.lore/audit.log)
+    Audit {
+        /// Only show this kind of change
+        #[arg(long, value_enum)]
+        action: Option<models::AuditAction>,
+
+        /// Only show changes to this entry (full ID or a prefix)
+        #[arg(long, value_name = "ID")]
+        entry: Option<String>,
+
+        /// Filter by agent ID (substring match)
+        #[arg(long)]
+        agent: Option<String>,
+
+        /// Only show changes at or after this time (RFC3339, date, or relative like 7d/2w)
+        #[arg(long, value_parser = filter::parse_time_bound)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only show the most recent N changes
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List all tags with usage counts
+    Tags {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Remove old or obsolete entries (a dry run unless --execute is given)
+    Prune {
+        /// Entries recorded before this time (RFC3339, date, or relative like 180d/26w)
+        #[arg(long, value_name = "AGE", value_parser = filter::parse_time_bound)]
+        older_than: Option<DateTime<Utc>>,
+
+        /// Entries whose target file no longer exists in the working tree
+        #[arg(long)]
+        missing_target: bool,
+
+        /// Entries a later entry supersedes
+        #[arg(long)]
+        superseded: bool,
+
+        /// Entries recorded by this agent (exact match)
+        #[arg(long, value_name = "ID")]
+        agent: Option<String>,
+
+        /// Remove the entries instead of only listing them
+        #[arg(long)]
+        execute: bool,
+
+        /// Save the removed entries to this file first, as a bundle `lore import` can restore
+        #[arg(long, value_name = "PATH")]
+        archive: Option<PathBuf>,
+    },
+
+    /// Find entries whose file was deleted; only reports them unless --delete
+    Gc {
+        /// Remove the orphaned entries (asks first unless --yes)
+        #[arg(long, conflicts_with = "keep_orphans")]
+        delete: bool,
+
+        /// Only report orphaned entries (the default)
+        #[arg(long)]
+        keep_orphans: bool,
+
+        /// Don't ask before deleting
+        #[arg(short, long, requires = "delete")]
+        yes: bool,
+
+        /// List the orphaned entries without deleting anything, even with --delete
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Move entry files from the flat layout of older versions into shards
+    Migrate,
+
+    /// Rebuild the index from the entries directory
+    Reindex {
+        /// Parse entry files on all cores
+        #[arg(long)]
+        parallel: bool,
+
+        /// Show a progress bar on stderr
+        #[arg(long)]
+        progress: bool,
+
+        /// Resume an interrupted rebuild, skipping entries it already indexed
+        #[arg(long = "continue")]
+        resume: bool,
+    },
+
+    /// Check the store for dangling, unindexed, unparseable and duplicate entries
+    Fsck {
+        /// Also report entries whose target file no longer exists
+        #[arg(long)]
+        check_targets: bool,
+
+        /// Rebuild the index to repair what can be repaired (never deletes entry files)
+        #[arg(long)]
+        fix: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Read and change settings in .lore/config.json
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Show aggregate statistics, including an activity histogram
+    Stats {
+        /// Histogram granularity
+        #[arg(long, value_enum, default_value_t = commands::stats::Bucket::Month)]
+        bucket: commands::stats::Bucket,
+
+        /// Only show the last N buckets, ending with the current one
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
+
+        /// Only include entries recorded at or after this time (RFC3339, date, or relative like 7d/2w)
+        #[arg(long, value_parser = filter::parse_time_bound)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only include entries recorded by this agent
+        #[arg(long)]
+        agent: Option<String>,
+
+        /// Only include entries with this tag (repeatable; all must match)
+        #[arg(short = 'T', long, action = clap::ArgAction::Append)]
+        tag: Vec<String>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List contributing agents with activity stats
+    Agents {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+
+        #[command(subcommand)]
+        action: Option<AgentsAction>,
+    },
+
+    /// Manage reasoning templates used by `record --template`
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+
+    /// Read files stored with entries by `record --attach`
+    Attachment {
+        #[command(subcommand)]
+        action: AttachmentAction,
+    },
+
+    /// Print a shell completion script, e.g. `lore completions bash > ~/.local/share/bash-completion/completions/lore`
+    Completions {
+        /// Shell to generate the script for
+        shell: clap_complete::Shell,
+    },
+
+    /// Print files with recorded lore, one per line (used by completion scripts)
+    #[command(name = "__complete-files", hide = true)]
+    CompleteFiles,
+}
+
+#[derive(Subcommand)]
+enum HookAction {
+    /// Install a git hook that runs lore
+    Install {
+        /// Which hook to install
+        #[arg(long, value_enum, default_value_t = commands::hook::HookKind::PostCommit)]
+        hook: commands::hook::HookKind,
+
+        /// Add lore to an existing hook that lore didn't create
+        #[arg(long)]
+        force: bool,
+
+        /// Remove the hook instead (same as `lore hook uninstall`)
+        #[arg(long, conflicts_with = "force")]
+        uninstall: bool,
+
+        /// Instead of a hook, register `lore merge-index` as the git merge driver for
+        /// .lore/index.json and .lore/search_index.json
+        #[arg(long, conflicts_with_all = ["hook", "force"])]
+        merge_driver: bool,
+    },
+
+    /// Remove the lines lore added to a git hook
+    Uninstall {
+        /// Which hook to uninstall
+        #[arg(long, value_enum, default_value_t = commands::hook::HookKind::PostCommit)]
+        hook: commands::hook::HookKind,
+
+        /// Remove the index merge driver instead of a hook
+        #[arg(long, conflicts_with = "hook")]
+        merge_driver: bool,
+    },
+
+    /// Run a hook's checks (invoked by the installed hook scripts)
+    #[command(hide = true)]
+    Run {
+        hook: commands::hook::HookKind,
+
+        /// Arguments git passed to the hook
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print a setting (prints nothing if it is unset)
+    Get {
+        key: String,
+
+        /// Read the per-user config (~/.config/lore/config.json)
+        #[arg(long)]
+        global: bool,
+    },
+
+    /// Change a setting. Lists are comma-separated; an empty value unsets.
+    Set {
+        key: String,
+        value: String,
+
+        /// Write the per-user config (~/.config/lore/config.json)
+        #[arg(long)]
+        global: bool,
+    },
+
+    /// Show every setting
+    List {
+        /// Show the per-user config (~/.config/lore/config.json)
+        #[arg(long)]
+        global: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateAction {
+    /// Save a template under .lore/templates/
+    Add {
+        /// Template name (letters, digits, '-' and '_')
+        name: String,
+
+        /// Markdown file with the template's sections
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Replace an existing template with the same name
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List saved templates
+    List,
+}
+
+#[derive(Subcommand)]
+enum AttachmentAction {
+    /// Copy an attachment out of the store
+    Get {
+        /// Entry ID (or unique prefix)
+        id: String,
+
+        /// Attachment file name, as listed by `lore explain`
+        filename: String,
+
+        /// Where to write it, or '-' for stdout (default: the file name, in the current directory)
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AgentsAction {
+    /// Rename an agent across all existing entries
+    Rename {
+        /// Current agent ID (exact match)
+        old: String,
+
+        /// New agent ID
+        new: String,
+    },
+}
+
+/// What went wrong, which decides the exit code and the `kind` reported by
+/// `--error-format json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorKind {
+    /// Anything else, including I/O failures (exit code 1, also used by
+    /// checks such as `fsck` that find problems)
+    Other,
+    /// No `.lore` directory was found (2)
+    NotInitialized,
+    /// A file, entry, template or store doesn't exist (3)
+    NotFound,
+    /// Bad arguments, flags or input files (4)
+    InvalidInput,
+    /// Store files that can't be parsed (5)
+    Corrupt,
+    /// Not a git repository, or git couldn't resolve something (6)
+    Git,
+}
+
+impl ErrorKind {
+    fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::NotInitialized => 2,
+            ErrorKind::NotFound => 3,
+            ErrorKind::InvalidInput => 4,
+            ErrorKind::Corrupt => 5,
+            ErrorKind::Git => 6,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Other => "error",
+            ErrorKind::NotInitialized => "not_initialized",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::InvalidInput => "invalid_input",
+            ErrorKind::Corrupt => "corrupt",
+            ErrorKind::Git => "git",
+        }
+    }
+
+    fn of_io(e: &std::io::Error) -> Self {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            ErrorKind::NotFound
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+/// A failed command: the store's, git's or the command's own error, or
+/// anything else
+#[derive(Debug)]
+enum CliError {
+    Storage(StorageError),
+    Git(GitError),
+    Command(CommandError),
+    Other(Box<dyn std::error::Error>),
+}
+
+impl From<Box<dyn std::error::Error>> for CliError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        let e = match e.downcast::<StorageError>() {
+            Ok(e) => return CliError::Storage(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<GitError>() {
+            Ok(e) => return CliError::Git(*e),
+            Err(e) => e,
+        };
+        match e.downcast::<CommandError>() {
+            Ok(e) => CliError::Command(*e),
+            Err(e) => CliError::Other(e),
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Storage(e) => e.fmt(f),
+            CliError::Git(e) => e.fmt(f),
+            CliError::Command(e) => e.fmt(f),
+            CliError::Other(e) => e.fmt(f),
+        }
+    }
+}
+
+impl CliError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            CliError::Storage(e) => match e {
+                StorageError::NotInitialized => ErrorKind::NotInitialized,
+                StorageError::Io(e) => ErrorKind::of_io(e),
+                StorageError::Json(_) => ErrorKind::Corrupt,
+                StorageError::FileNotFound(_)
+                | StorageError::EntryNotFound(_)
+                | StorageError::TemplateNotFound(_)
+                | StorageError::AttachmentNotFound(..) => ErrorKind::NotFound,
+                StorageError::AlreadyInitialized
+                | StorageError::InvalidAgentId(_)
+                | StorageError::UnsupportedExportVersion(_)
+                | StorageError::UnsupportedSchemaVersion(..)
+                | StorageError::AmbiguousEntryId(_)
+                | StorageError::InvalidIgnore(_)
+                | StorageError::TemplateExists(_)
+                | StorageError::InvalidTemplateName(_)
+                | StorageError::ArchiveInsideEntries(_)
+                | StorageError::ArchiveExists(_)
+                | StorageError::OutsideRoot(..)
+                | StorageError::InvalidAttachmentName(_) => ErrorKind::InvalidInput,
+            },
+            CliError::Git(_) => ErrorKind::Git,
+            CliError::Command(e) => match e {
+                CommandError::InvalidInput(_) => ErrorKind::InvalidInput,
+                CommandError::NotFound(_) => ErrorKind::NotFound,
+                CommandError::Git(_) => ErrorKind::Git,
+                CommandError::Io(_) => ErrorKind::Other,
+            },
+            CliError::Other(e) => match e.downcast_ref::<std::io::Error>() {
+                Some(e) => ErrorKind::of_io(e),
+                None => ErrorKind::Other,
+            },
+        }
+    }
+}
+
+/// Print `message` on stderr in `format` and exit with `kind`'s code
+fn fail(kind: ErrorKind, message: &str, format: ErrorFormat) -> ! {
+    match format {
+        ErrorFormat::Text => eprintln!("{}", message.trim_end()),
+        ErrorFormat::Json => eprintln!(
+            "{}",
+            serde_json::json!({
+                "error": { "kind": kind.name(), "message": message.trim_end() }
+            })
+        ),
+    }
+    std::process::exit(kind.exit_code());
+}
+
+/// Whether `--error-format json` was asked for, for reporting errors found
+/// before the arguments could be parsed
+fn wants_json_errors(args: &[OsString]) -> bool {
+    args.iter().enumerate().any(|(i, arg)| {
+        arg == "--error-format=json"
+            || (arg == "--error-format" && args.get(i + 1).is_some_and(|next| next == "json"))
+    })
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        // --help and --version
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) if wants_json_errors(&args) => {
+            // The first line, without clap's usage hints
+            let rendered = e.render().to_string();
+            let first = rendered.lines().next().unwrap_or_default();
+            let message = first.strip_prefix("error: ").unwrap_or(first);
+            fail(ErrorKind::InvalidInput, message, ErrorFormat::Json)
+        }
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(ErrorKind::InvalidInput.exit_code());
+        }
+    };
 
-    let result = match cli.command {
-        Commands::Init { path, agent } => commands::init::execute(path, agent),
+    // Decide once, before anything is printed
+    let color = if cli.no_color {
+        ColorChoice::Never
+    } else {
+        cli.color
+    };
+    let no_color = std::env::var_os("NO_COLOR");
+    colored::control::set_override(
+        color.enabled(no_color.as_deref(), std::io::stdout().is_terminal()),
+    );
+    output::set_ascii(cli.ascii || !output::supports_utf8(|name| std::env::var(name).ok()));
+
+    match run(cli.command, &mut std::io::stdout()) {
+        Ok(Outcome::Success) => {}
+        Ok(Outcome::ProblemsFound) => std::process::exit(1),
+        // The reader (e.g. `head`) went away; stop quietly
+        Err(e) if is_broken_pipe(e.as_ref()) => {}
+        Err(e) => {
+            let e = CliError::from(e);
+            fail(e.kind(), &e.to_string(), cli.error_format);
+        }
+    }
+}
+
+fn is_broken_pipe(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+}
+
+/// The store containing the current directory, if any
+fn find_storage() -> Result<Option<LoreStorage>, Box<dyn std::error::Error>> {
+    let current_dir = std::env::current_dir()?;
+    Ok(find_lore_root(&current_dir).map(LoreStorage::new))
+}
+
+/// The store containing the current directory
+fn open_storage() -> Result<LoreStorage, Box<dyn std::error::Error>> {
+    find_storage()?.ok_or_else(|| StorageError::NotInitialized.into())
+}
+
+fn run(command: Commands, out: &mut impl Write) -> Result<Outcome, Box<dyn std::error::Error>> {
+    let result = match command {
+        Commands::Init {
+            path,
+            agent,
+            repair,
+        } => commands::init::execute(path, agent, repair, out),
 
         Commands::Record {
             message,
             trace,
             trace_file,
+            template,
+            edit,
+            no_edit,
             file,
             agent,
             rejected,
+            rejected_file,
             tag,
+            meta,
+            confidence,
+            reference,
+            attach,
             lines,
+            symbol,
+            anchor,
             stdin,
-        } => {
-            let line_range = lines.and_then(|l| {
-                let parts: Vec<&str> = l.split('-').collect();
-                if parts.len() == 2 {
-                    let start = parts[0].parse().ok()?;
-                    let end = parts[1].parse().ok()?;
-                    Some((start, end))
-                } else {
-                    None
-                }
-            });
-
-            commands::record::execute(commands::record::RecordOptions {
+            continue_draft,
+            finish,
+            discard_draft,
+            no_diff,
+            no_follow_renames,
+            staged,
+            unstaged,
+            since,
+            from_commit,
+            commit,
+            first_parent,
+            supersedes,
+            related,
+            project,
+            amend,
+            dry_run,
+            allow_duplicate,
+            manifest,
+            json,
+        } => commands::record::execute(
+            &open_storage()?,
+            commands::record::RecordOptions {
                 message,
                 trace,
                 trace_file,
+                template,
+                edit,
+                no_edit,
                 files: file,
                 agent_id: agent,
                 rejected,
+                rejected_file,
                 tags: tag,
-                line_range,
+                metadata: meta,
+                confidence,
+                references: reference,
+                attachments: attach,
+                line_range: lines,
+                symbol,
+                anchor,
                 stdin,
-            })
-        }
+                continue_draft,
+                finish,
+                discard_draft,
+                no_diff,
+                no_follow_renames,
+                staged,
+                unstaged,
+                since,
+                from_commit,
+                commit,
+                first_parent,
+                supersedes,
+                related,
+                project,
+                amend,
+                dry_run,
+                allow_duplicate,
+                manifest,
+                json,
+            },
+            out,
+        ),
 
         Commands::Explain {
-            file,
+            files,
             all,
+            format,
             json,
+            ndjson,
             limit,
-        } => commands::explain::execute(commands::explain::ExplainOptions {
-            file,
-            all,
-            json,
-            limit,
-        }),
+            offset,
+            current_only,
+            show_diff,
+            reverse,
+            raw_reasoning,
+            lines,
+            strict,
+            at,
+            ignore_case,
+        } => commands::explain::execute(
+            &open_storage()?,
+            commands::explain::ExplainOptions {
+                files,
+                all,
+                format: format.or_json(json).or_ndjson(ndjson),
+                limit,
+                offset,
+                current_only,
+                show_diff,
+                reverse,
+                raw_reasoning,
+                line_range: lines,
+                strict,
+                at,
+                ignore_case,
+            },
+            out,
+        ),
 
         Commands::Search {
             query,
+            format,
             json,
+            ndjson,
             limit,
+            offset,
             file,
             agent,
-        } => commands::search::execute(commands::search::SearchOptions {
-            query,
+            regex,
+            ignore_case,
+            word,
+            any,
+            with_snippet,
+            since,
+            until,
+            tag,
+            any_tag,
+            meta,
+            min_confidence,
+            project,
+            lines,
+        } => commands::search::execute(
+            &open_storage()?,
+            commands::search::SearchOptions {
+                query,
+                format: format.or_json(json).or_ndjson(ndjson),
+                limit,
+                offset,
+                file_filter: file,
+                agent_filter: agent,
+                regex,
+                ignore_case,
+                word,
+                any,
+                with_snippet,
+                since,
+                until,
+                tags: tag,
+                any_tag,
+                metadata: meta,
+                min_confidence,
+                project,
+                line_range: lines,
+            },
+            out,
+        ),
+
+        Commands::List {
+            format,
+            json,
+            ndjson,
+            limit,
+            offset,
+            since,
+            until,
+            tag,
+            any_tag,
+            project,
+            sort,
+            reverse,
+        } => commands::list::execute(
+            &open_storage()?,
+            commands::list::ListOptions {
+                format: format.or_json(json).or_ndjson(ndjson),
+                sort,
+                limit,
+                offset,
+                since,
+                until,
+                tags: tag,
+                any_tag,
+                project,
+                reverse,
+            },
+            out,
+        ),
+
+        Commands::Hook { action } => match action {
+            HookAction::Install {
+                uninstall: true,
+                merge_driver: true,
+                ..
+            }
+            | HookAction::Uninstall {
+                merge_driver: true, ..
+            } => commands::hook::uninstall_merge_driver(&open_storage()?, out),
+            HookAction::Install {
+                merge_driver: true, ..
+            } => commands::hook::install_merge_driver(&open_storage()?, out),
+            HookAction::Install {
+                hook,
+                uninstall: true,
+                ..
+            } => commands::hook::uninstall(&open_storage()?, hook, out),
+            HookAction::Install { hook, force, .. } => {
+                commands::hook::install(&open_storage()?, hook, force, out)
+            }
+            HookAction::Uninstall { hook, .. } => {
+                commands::hook::uninstall(&open_storage()?, hook, out)
+            }
+            HookAction::Run { hook, args } => {
+                commands::hook::run(&open_storage()?, hook, &args, out)
+            }
+        },
+
+        Commands::Log {
+            limit,
+            author,
+            file,
+            oneline,
+            full,
+            reverse,
+            json,
+        } => commands::log::execute(
+            &open_storage()?,
+            commands::log::LogOptions {
+                limit,
+                author_filter: author,
+                file_filter: file,
+                format: if oneline {
+                    commands::log::LogFormat::Oneline
+                } else if full {
+                    commands::log::LogFormat::Full
+                } else {
+                    commands::log::LogFormat::Default
+                },
+                reverse,
+                json,
+            },
+            out,
+        ),
+
+        Commands::Status {
+            format,
             json,
+            quiet,
+            staged_only,
+        } => {
+            return commands::status::execute(
+                find_storage()?.as_ref(),
+                commands::status::StatusOptions {
+                    format: format.or_json(json),
+                    quiet,
+                    staged_only,
+                },
+                out,
+            )
+        }
+
+        Commands::Export {
+            output,
+            format,
+            file,
+            since,
+        } => commands::export::execute(
+            &open_storage()?,
+            commands::export::ExportOptions {
+                output,
+                format,
+                file_filter: file,
+                since,
+            },
+            out,
+        ),
+
+        Commands::Import {
+            file,
+            merge: _,
+            overwrite,
+        } => commands::import::execute(
+            &open_storage()?,
+            commands::import::ImportOptions { file, overwrite },
+            out,
+        ),
+
+        Commands::Mv { old, new } => {
+            commands::mv::execute(&open_storage()?, commands::mv::MvOptions { old, new }, out)
+        }
+
+        Commands::Diff { old, new, json } => commands::diff::execute(
+            &open_storage()?,
+            commands::diff::DiffOptions { old, new, json },
+            out,
+        ),
+
+        Commands::MergeIndex { ours, theirs, base } => {
+            lore::storage::merge_index_files(&ours, &theirs, &base).map_err(Into::into)
+        }
+
+        Commands::DiffLore { base, head, json } => commands::diff_lore::execute(
+            &open_storage()?,
+            commands::diff_lore::DiffLoreOptions { base, head, json },
+            out,
+        ),
+
+        Commands::DiffStores { other, stat, json } => commands::diff_stores::execute(
+            &open_storage()?,
+            commands::diff_stores::DiffStoresOptions { other, stat, json },
+            out,
+        ),
+
+        Commands::Browse => commands::browse::execute(&open_storage()?, out),
+
+        Commands::Blame { file, json } => commands::blame::execute(
+            &open_storage()?,
+            commands::blame::BlameOptions { file, json },
+            out,
+        ),
+
+        Commands::Coverage { file, json } => commands::coverage::execute(
+            &open_storage()?,
+            commands::coverage::CoverageOptions { file, json },
+            out,
+        ),
+
+        Commands::Audit {
+            action,
+            entry,
+            agent,
+            since,
             limit,
-            file_filter: file,
-            agent_filter: agent,
-        }),
+            json,
+        } => commands::audit::execute(
+            &open_storage()?,
+            commands::audit::AuditOptions {
+                action,
+                entry,
+                agent,
+                since,
+                limit,
+                json,
+            },
+            out,
+        ),
 
-        Commands::List { json, limit } => {
-            commands::list::execute(commands::list::ListOptions { json, limit })
+        Commands::Tags { json } => {
+            commands::tags::execute(&open_storage()?, commands::tags::TagsOptions { json }, out)
         }
 
-        Commands::Status => commands::status::execute(),
+        Commands::Fsck {
+            check_targets,
+            fix,
+            json,
+        } => {
+            return commands::fsck::execute(
+                &open_storage()?,
+                commands::fsck::FsckOptions {
+                    check_targets,
+                    fix,
+                    json,
+                },
+                out,
+            )
+        }
+
+        Commands::Gc {
+            delete,
+            keep_orphans: _,
+            yes,
+            dry_run,
+        } => commands::gc::execute(
+            &open_storage()?,
+            commands::gc::GcOptions {
+                delete,
+                yes,
+                dry_run,
+            },
+            out,
+        ),
+
+        Commands::Prune {
+            older_than,
+            missing_target,
+            superseded,
+            agent,
+            execute,
+            archive,
+        } => commands::prune::execute(
+            &open_storage()?,
+            commands::prune::PruneOptions {
+                older_than,
+                missing_target,
+                superseded,
+                agent,
+                execute,
+                archive,
+            },
+            out,
+        ),
+
+        Commands::Migrate => commands::migrate::execute(&open_storage()?, out),
+
+        Commands::Reindex {
+            parallel,
+            progress,
+            resume,
+        } => commands::reindex::execute(
+            &open_storage()?,
+            commands::reindex::ReindexOptions {
+                parallel,
+                progress,
+                resume,
+            },
+            out,
+        ),
+
+        Commands::Config { action } => match action {
+            ConfigAction::Get { key, global } => commands::config::get(&key, global, out),
+            ConfigAction::Set { key, value, global } => {
+                commands::config::set(&key, &value, global, out)
+            }
+            ConfigAction::List { global } => commands::config::list(global, out),
+        },
+
+        Commands::Stats {
+            bucket,
+            last,
+            since,
+            agent,
+            tag,
+            json,
+        } => commands::stats::execute(
+            &open_storage()?,
+            commands::stats::StatsOptions {
+                json,
+                bucket,
+                last,
+                since,
+                agent,
+                tags: tag,
+            },
+            out,
+        ),
+
+        Commands::Agents { json, action } => match action {
+            Some(AgentsAction::Rename { old, new }) => {
+                commands::agents::rename(&open_storage()?, old, new, out)
+            }
+            None => commands::agents::execute(
+                &open_storage()?,
+                commands::agents::AgentsOptions { json },
+                out,
+            ),
+        },
+
+        Commands::Template { action } => match action {
+            TemplateAction::Add { name, file, force } => commands::template::add(
+                &open_storage()?,
+                commands::template::TemplateAddOptions { name, file, force },
+                out,
+            ),
+            TemplateAction::List => commands::template::list(&open_storage()?, out),
+        },
+
+        Commands::Attachment { action } => match action {
+            AttachmentAction::Get {
+                id,
+                filename,
+                output,
+            } => commands::attachment::get(
+                &open_storage()?,
+                commands::attachment::AttachmentGetOptions {
+                    id,
+                    filename,
+                    output,
+                },
+                out,
+            ),
+        },
+
+        Commands::Completions { shell } => commands::completions::execute(
+            &mut Cli::command(),
+            commands::completions::CompletionsOptions { shell },
+            out,
+        ),
+
+        // Completion scripts call this on every <TAB>, so stay quiet when
+        // there is no store or the index can't be read
+        Commands::CompleteFiles => {
+            if let Ok(Some(storage)) = find_storage() {
+                let _ = commands::completions::complete_files(&storage, out);
+            }
+            Ok(())
+        }
     };
+    result.map(|()| Outcome::Success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind_of(e: impl std::error::Error + 'static) -> ErrorKind {
+        CliError::from(Box::new(e) as Box<dyn std::error::Error>).kind()
+    }
+
+    #[test]
+    fn test_errors_are_classified_through_the_box() {
+        assert_eq!(
+            kind_of(StorageError::NotInitialized),
+            ErrorKind::NotInitialized
+        );
+        assert_eq!(
+            kind_of(StorageError::EntryNotFound("a1b2".to_string())),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            kind_of(StorageError::Json(
+                serde_json::from_str::<u8>("x").unwrap_err()
+            )),
+            ErrorKind::Corrupt
+        );
+        assert_eq!(kind_of(GitError::NotARepo), ErrorKind::Git);
+        assert_eq!(
+            kind_of(CommandError::InvalidInput("bad".to_string())),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            kind_of(std::io::Error::from(std::io::ErrorKind::NotFound)),
+            ErrorKind::NotFound
+        );
+
+        let message: Box<dyn std::error::Error> = "Something else".into();
+        let e = CliError::from(message);
+        assert_eq!(e.kind(), ErrorKind::Other);
+        assert_eq!(e.to_string(), "Something else");
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let kinds = [
+            ErrorKind::Other,
+            ErrorKind::NotInitialized,
+            ErrorKind::NotFound,
+            ErrorKind::InvalidInput,
+            ErrorKind::Corrupt,
+            ErrorKind::Git,
+        ];
+        let codes: std::collections::HashSet<i32> = kinds.iter().map(|k| k.exit_code()).collect();
+        assert_eq!(codes.len(), kinds.len());
+        assert_eq!(ErrorKind::NotInitialized.exit_code(), 2);
+    }
 
-    if let Err(e) = result {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    #[test]
+    fn test_wants_json_errors() {
+        let args = |list: &[&str]| list.iter().map(OsString::from).collect::<Vec<_>>();
+        assert!(wants_json_errors(&args(&[
+            "lore",
+            "--error-format",
+            "json",
+            "x"
+        ])));
+        assert!(wants_json_errors(&args(&[
+            "lore",
+            "x",
+            "--error-format=json"
+        ])));
+        assert!(!wants_json_errors(&args(&[
+            "lore",
+            "--error-format",
+            "text"
+        ])));
+        assert!(!wants_json_errors(&args(&["lore", "json"])));
     }
 }
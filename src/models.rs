@@ -1,19 +1,46 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// Layout version of serialized entries. Bumped when a field of
+/// `ThoughtObject` is renamed or reshaped, with an upgrade step added to
+/// `LoreStorage::migrate` so older entry files still load.
+pub const ENTRY_SCHEMA_VERSION: u32 = 1;
+
 /// A ThoughtObject represents the reasoning context behind a code change
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThoughtObject {
+    /// `ENTRY_SCHEMA_VERSION` the entry was written with (0 when missing,
+    /// for entries from before it was recorded)
+    #[serde(default)]
+    pub schema_version: u32,
+
     /// Unique identifier for this entry
     pub id: String,
 
     /// The file this reasoning applies to
     pub target_file: String,
 
+    /// Paths the file had before being renamed, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_paths: Vec<String>,
+
     /// Optional line range [start, end] if reasoning applies to specific lines
     #[serde(skip_serializing_if = "Option::is_none")]
     pub line_range: Option<(usize, usize)>,
 
+    /// Boundary lines of `line_range`, used to find it again after edits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_anchor: Option<LineAnchor>,
+
+    /// Text (like `fn authenticate`) marking where this reasoning applies, found
+    /// again by substring search after edits. A `line_range` starts at it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+
+    /// The code in `line_range` at time of recording (capped, see `MAX_SNAPSHOT_LINES`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_snapshot: Option<String>,
+
     /// SHA256 hash of the file content at time of recording
     pub file_hash: String,
 
@@ -21,12 +48,20 @@ pub struct ThoughtObject {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub commit_hash: Option<String>,
 
+    /// Author of that commit, as `Name <email>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_author: Option<String>,
+
     /// Identifier for the agent/author that created this entry
     pub agent_id: String,
 
     /// When this entry was created
     pub timestamp: DateTime<Utc>,
 
+    /// When reasoning was last appended with `record --amend`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+
     /// Brief description of the intent/purpose
     pub intent: String,
 
@@ -40,16 +75,179 @@ pub struct ThoughtObject {
     /// Optional tags for categorization
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tags: Vec<String>,
+
+    /// Unified diff of the file against HEAD at time of recording
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+
+    /// IDs of other entries this reasoning refers to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related_entries: Vec<String>,
+
+    /// ID of an earlier entry this one replaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supersedes: Option<String>,
+
+    /// Sub-project the file belongs to, for monorepos sharing one `.lore`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+
+    /// Free-form key/value pairs set by integrations (model, ticket URL, ...)
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub metadata: std::collections::BTreeMap<String, String>,
+
+    /// How sure the author was of this reasoning, from 0.0 to 1.0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+
+    /// Issues, pull requests or other links where the change was discussed
+    /// (URLs or issue keys like JIRA-1234)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+
+    /// Files stored with the entry under `.lore/attachments/<id>/`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+/// Whether an entry still describes the file as it is on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// The file hash matches the one recorded
+    Current,
+    /// The file has changed since the entry was recorded
+    Stale,
+    /// The target file no longer exists
+    FileMissing,
+}
+
+impl Freshness {
+    pub fn is_stale(&self) -> bool {
+        *self != Freshness::Current
+    }
+}
+
+/// Maximum number of lines kept in a `code_snapshot`
+pub const MAX_SNAPSHOT_LINES: usize = 100;
+
+/// Copy the lines of a 1-based inclusive range, keeping at most
+/// `MAX_SNAPSHOT_LINES` and noting how many were left out. Returns `None` if
+/// the range starts past the end of the file.
+pub fn snapshot_lines(content: &str, (start, end): (usize, usize)) -> Option<String> {
+    if start == 0 || start > end {
+        return None;
+    }
+    let lines: Vec<&str> = content
+        .lines()
+        .skip(start - 1)
+        .take(end - start + 1)
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+
+    let mut snapshot = lines[..lines.len().min(MAX_SNAPSHOT_LINES)].join("\n");
+    if lines.len() > MAX_SNAPSHOT_LINES {
+        snapshot.push_str(&format!(
+            "\n... ({} more lines)",
+            lines.len() - MAX_SNAPSHOT_LINES
+        ));
+    }
+    Some(snapshot)
+}
+
+/// The trimmed content of the first and last lines of a recorded range
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineAnchor {
+    pub start: String,
+    pub end: String,
+}
+
+/// Where a recorded line range is in the current version of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeLocation {
+    /// The boundary lines are still at the recorded line numbers
+    Unchanged,
+    /// The boundary lines were found at these (1-based, inclusive) lines
+    Moved(usize, usize),
+    /// The boundary lines could not be found
+    Lost,
+}
+
+impl LineAnchor {
+    /// Capture the boundary lines of a 1-based inclusive range. Returns `None`
+    /// if the range is out of bounds.
+    pub fn capture(content: &str, (start, end): (usize, usize)) -> Option<Self> {
+        let lines: Vec<&str> = content.lines().collect();
+        if start == 0 || start > end || end > lines.len() {
+            return None;
+        }
+        Some(Self {
+            start: lines[start - 1].trim().to_string(),
+            end: lines[end - 1].trim().to_string(),
+        })
+    }
+
+    /// Find the recorded range in `content`. When the boundary lines occur more
+    /// than once, the match closest to the recorded position wins, preferring
+    /// one that keeps the recorded length.
+    pub fn resolve(&self, content: &str, (start, end): (usize, usize)) -> RangeLocation {
+        let lines: Vec<&str> = content.lines().map(str::trim).collect();
+        let matches = |line: usize, text: &str| lines.get(line).is_some_and(|l| *l == text);
+
+        if start >= 1 && matches(start - 1, &self.start) && matches(end - 1, &self.end) {
+            return RangeLocation::Unchanged;
+        }
+
+        let span = end.saturating_sub(start);
+        let best = (0..lines.len())
+            .filter(|&i| lines[i] == self.start)
+            .filter_map(|i| {
+                if matches(i + span, &self.end) {
+                    return Some((i, i + span));
+                }
+                (i..lines.len())
+                    .find(|&j| lines[j] == self.end)
+                    .map(|j| (i, j))
+            })
+            .min_by_key(|&(i, j)| ((i + 1).abs_diff(start), j - i != span));
+
+        match best {
+            Some((i, j)) => RangeLocation::Moved(i + 1, j + 1),
+            None => RangeLocation::Lost,
+        }
+    }
+}
+
+/// The 1-based line of `content` containing `anchor`, choosing the one nearest
+/// line `near` when there are several
+pub fn find_anchor(content: &str, anchor: &str, near: usize) -> Option<usize> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.contains(anchor))
+        .map(|(i, _)| i + 1)
+        .min_by_key(|line| line.abs_diff(near))
 }
 
 /// A rejected alternative with optional reasoning
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RejectedAlternative {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
 }
 
+/// A file copied into the store alongside an entry (`record --attach`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub filename: String,
+    /// Size in bytes
+    pub size: u64,
+    /// SHA256 hash of the contents
+    pub sha256: String,
+}
+
 impl ThoughtObject {
     pub fn new(
         target_file: String,
@@ -59,17 +257,32 @@ impl ThoughtObject {
         reasoning_trace: String,
     ) -> Self {
         Self {
+            schema_version: ENTRY_SCHEMA_VERSION,
             id: uuid::Uuid::new_v4().to_string(),
             target_file,
+            previous_paths: Vec::new(),
             line_range: None,
+            line_anchor: None,
+            anchor: None,
+            code_snapshot: None,
             file_hash,
             commit_hash: None,
+            commit_author: None,
             agent_id,
             timestamp: Utc::now(),
+            updated_at: None,
             intent,
             reasoning_trace,
             rejected_alternatives: Vec::new(),
             tags: Vec::new(),
+            diff: None,
+            related_entries: Vec::new(),
+            supersedes: None,
+            project: None,
+            metadata: std::collections::BTreeMap::new(),
+            confidence: None,
+            references: Vec::new(),
+            attachments: Vec::new(),
         }
     }
 
@@ -78,11 +291,114 @@ impl ThoughtObject {
         self
     }
 
+    pub fn with_line_anchor(mut self, anchor: LineAnchor) -> Self {
+        self.line_anchor = Some(anchor);
+        self
+    }
+
+    pub fn with_anchor(mut self, anchor: String) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    /// The line where the anchor is in `content` (nearest the recorded range
+    /// when it occurs more than once), or `None` without an anchor or when it
+    /// no longer appears
+    pub fn anchor_line(&self, content: &str) -> Option<usize> {
+        let near = self.line_range.map_or(1, |(start, _)| start);
+        find_anchor(content, self.anchor.as_deref()?, near)
+    }
+
+    /// `line_range` moved to start at the anchor's current line, when both are set
+    /// and the anchor is still in `content`
+    pub fn anchored_range(&self, content: &str) -> Option<(usize, usize)> {
+        let (start, end) = self.line_range?;
+        let line = self.anchor_line(content)?;
+        Some((line, line + (end - start)))
+    }
+
+    pub fn with_code_snapshot(mut self, snapshot: String) -> Self {
+        self.code_snapshot = Some(snapshot);
+        self
+    }
+
+    pub fn with_related(mut self, related_entries: Vec<String>) -> Self {
+        self.related_entries = related_entries;
+        self
+    }
+
+    pub fn with_supersedes(mut self, id: String) -> Self {
+        self.supersedes = Some(id);
+        self
+    }
+
+    pub fn with_project(mut self, project: String) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    /// Append `trace` to the reasoning under a separator marking when it was
+    /// added, and merge in tags, rejected alternatives and references not
+    /// already present
+    pub fn amend(
+        &mut self,
+        trace: &str,
+        tags: &[String],
+        rejected: &[RejectedAlternative],
+        references: &[String],
+        now: DateTime<Utc>,
+    ) {
+        let trace = trace.trim();
+        if !trace.is_empty() {
+            let separator = format!("--- amended {} ---", now.format("%Y-%m-%d %H:%M UTC"));
+            self.reasoning_trace = if self.reasoning_trace.trim().is_empty() {
+                format!("{}\n\n{}", separator, trace)
+            } else {
+                format!(
+                    "{}\n\n{}\n\n{}",
+                    self.reasoning_trace.trim_end(),
+                    separator,
+                    trace
+                )
+            };
+        }
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+        for alternative in rejected {
+            if !self
+                .rejected_alternatives
+                .iter()
+                .any(|r| r.name == alternative.name)
+            {
+                self.rejected_alternatives.push(alternative.clone());
+            }
+        }
+        for reference in references {
+            if !self.references.contains(reference) {
+                self.references.push(reference.clone());
+            }
+        }
+        self.updated_at = Some(now);
+    }
+
     pub fn with_commit(mut self, commit_hash: String) -> Self {
         self.commit_hash = Some(commit_hash);
         self
     }
 
+    pub fn with_metadata(mut self, metadata: std::collections::BTreeMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_commit_author(mut self, author: String) -> Self {
+        self.commit_author = Some(author);
+        self
+    }
+
     pub fn with_rejected(mut self, alternatives: Vec<RejectedAlternative>) -> Self {
         self.rejected_alternatives = alternatives;
         self
@@ -92,16 +408,515 @@ impl ThoughtObject {
         self.tags = tags;
         self
     }
+
+    pub fn with_diff(mut self, diff: String) -> Self {
+        self.diff = Some(diff);
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.confidence = Some(confidence);
+        self
+    }
+
+    pub fn with_references(mut self, references: Vec<String>) -> Self {
+        self.references = references;
+        self
+    }
+
+    pub fn with_attachments(mut self, attachments: Vec<Attachment>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    /// Compare the recorded hash against the current one (`None` if the file is gone)
+    pub fn freshness(&self, current_hash: Option<&str>) -> Freshness {
+        match current_hash {
+            Some(hash) if hash == self.file_hash => Freshness::Current,
+            Some(_) => Freshness::Stale,
+            None => Freshness::FileMissing,
+        }
+    }
+}
+
+/// An in-progress entry built up across several `lore record --continue` calls
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingDraft {
+    /// Files the draft will be recorded for (auto-detected from git if empty)
+    #[serde(default)]
+    pub files: Vec<String>,
+
+    /// Agent/author that started the draft
+    pub agent_id: String,
+
+    /// Brief description of the intent/purpose, if given yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intent: Option<String>,
+
+    /// Reasoning trace accumulated so far
+    pub reasoning_trace: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected_alternatives: Vec<RejectedAlternative>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub metadata: std::collections::BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_range: Option<(usize, usize)>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+
+    /// Number of chunks appended so far
+    pub chunks: usize,
+
+    /// When the draft was started
+    pub started_at: DateTime<Utc>,
+
+    /// When the last chunk was appended
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PendingDraft {
+    pub fn new(agent_id: String) -> Self {
+        let now = Utc::now();
+        Self {
+            files: Vec::new(),
+            agent_id,
+            intent: None,
+            reasoning_trace: String::new(),
+            rejected_alternatives: Vec::new(),
+            tags: Vec::new(),
+            metadata: std::collections::BTreeMap::new(),
+            line_range: None,
+            confidence: None,
+            references: Vec::new(),
+            chunks: 0,
+            started_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Append a chunk of reasoning, separated from the previous one by a newline
+    pub fn append_chunk(&mut self, chunk: &str) {
+        if chunk.is_empty() {
+            return;
+        }
+        if !self.reasoning_trace.is_empty() && !self.reasoning_trace.ends_with('\n') {
+            self.reasoning_trace.push('\n');
+        }
+        self.reasoning_trace.push_str(chunk);
+        self.chunks += 1;
+        self.updated_at = Utc::now();
+    }
+
+    /// Add files, skipping ones already in the draft
+    pub fn add_files(&mut self, files: &[String]) {
+        for file in files {
+            if !self.files.contains(file) {
+                self.files.push(file.clone());
+            }
+        }
+    }
+
+    /// Add metadata pairs, replacing the value of keys already in the draft
+    pub fn add_metadata(&mut self, pairs: &[(String, String)]) {
+        self.metadata.extend(pairs.iter().cloned());
+    }
+
+    /// Add tags, skipping ones already in the draft
+    pub fn add_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+
+    /// Add references, skipping ones already in the draft
+    pub fn add_references(&mut self, references: &[String]) {
+        for reference in references {
+            if !self.references.contains(reference) {
+                self.references.push(reference.clone());
+            }
+        }
+    }
 }
 
+/// Diffs larger than this many bytes are truncated unless configured otherwise
+pub const DEFAULT_MAX_DIFF_BYTES: usize = 100 * 1024;
+
+/// Attachments larger than this many bytes are refused unless configured otherwise
+pub const DEFAULT_MAX_ATTACHMENT_SIZE: usize = 1024 * 1024;
+
+/// Keys `lore config` can read and write
+pub const CONFIG_KEYS: &[&str] = &[
+    "default_agent_id",
+    "default_tags",
+    "auto_diff",
+    "normalize_agent_ids",
+    "max_diff_bytes",
+    "max_trace_size",
+    "default_template",
+    "edit_trace",
+    "max_attachment_size",
+];
+
+/// Settings stored in `.lore/config.json`. Every field has a default so config
+/// files written by older versions still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoreConfig {
+    #[serde(default = "default_config_version")]
+    pub version: String,
+
+    /// Agent ID used when `record` isn't given `--agent`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_agent_id: Option<String>,
+
+    /// Tags added to every recorded entry, alongside any `--tag` flags
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_tags: Vec<String>,
+
+    /// Capture the git diff of each file at record time (`--no-diff` overrides)
+    #[serde(default = "default_true")]
+    pub auto_diff: bool,
+
+    /// Normalize agent IDs (lowercase, dashes) at record time
+    #[serde(default)]
+    pub normalize_agent_ids: bool,
+
+    /// Captured diffs larger than this are truncated
+    #[serde(default = "default_max_diff_bytes")]
+    pub max_diff_bytes: usize,
+
+    /// Reject reasoning traces larger than this many bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_trace_size: Option<usize>,
+
+    /// Template `record` starts from when no trace is given (`--template` overrides)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_template: Option<String>,
+
+    /// Open `$VISUAL`/`$EDITOR` for the trace when `record` runs at a terminal
+    /// without one (`--no-edit` overrides)
+    #[serde(default = "default_true")]
+    pub edit_trace: bool,
+
+    /// Refuse `record --attach` files larger than this many bytes
+    #[serde(default = "default_max_attachment_size")]
+    pub max_attachment_size: usize,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+
+    /// Settings this version doesn't know about, kept so saving doesn't drop them
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+fn default_config_version() -> String {
+    "0.1.0".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_diff_bytes() -> usize {
+    DEFAULT_MAX_DIFF_BYTES
+}
+
+fn default_max_attachment_size() -> usize {
+    DEFAULT_MAX_ATTACHMENT_SIZE
+}
+
+impl Default for LoreConfig {
+    fn default() -> Self {
+        Self {
+            version: default_config_version(),
+            default_agent_id: None,
+            default_tags: Vec::new(),
+            auto_diff: true,
+            normalize_agent_ids: false,
+            max_diff_bytes: DEFAULT_MAX_DIFF_BYTES,
+            max_trace_size: None,
+            default_template: None,
+            edit_trace: true,
+            max_attachment_size: DEFAULT_MAX_ATTACHMENT_SIZE,
+            created_at: None,
+            other: serde_json::Map::new(),
+        }
+    }
+}
+
+impl LoreConfig {
+    /// The value of `key` as shown by `lore config get`, or `None` if it's unset
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(match key {
+            "default_agent_id" => self.default_agent_id.clone(),
+            "default_tags" => Some(self.default_tags.join(",")).filter(|t| !t.is_empty()),
+            "auto_diff" => Some(self.auto_diff.to_string()),
+            "normalize_agent_ids" => Some(self.normalize_agent_ids.to_string()),
+            "max_diff_bytes" => Some(self.max_diff_bytes.to_string()),
+            "max_trace_size" => self.max_trace_size.map(|size| size.to_string()),
+            "default_template" => self.default_template.clone(),
+            "edit_trace" => Some(self.edit_trace.to_string()),
+            "max_attachment_size" => Some(self.max_attachment_size.to_string()),
+            _ => return Err(unknown_key(key)),
+        })
+    }
+
+    /// Set `key` from its command-line form. Tags are comma-separated, and an
+    /// empty value clears an optional setting.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match key {
+            "default_agent_id" => {
+                self.default_agent_id = Some(value.to_string()).filter(|v| !v.is_empty())
+            }
+            "default_tags" => {
+                self.default_tags = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "auto_diff" => self.auto_diff = parse_bool(key, value)?,
+            "normalize_agent_ids" => self.normalize_agent_ids = parse_bool(key, value)?,
+            "edit_trace" => self.edit_trace = parse_bool(key, value)?,
+            "max_diff_bytes" => self.max_diff_bytes = parse_size(key, value)?,
+            "max_attachment_size" => self.max_attachment_size = parse_size(key, value)?,
+            "max_trace_size" if value.is_empty() => self.max_trace_size = None,
+            "max_trace_size" => self.max_trace_size = Some(parse_size(key, value)?),
+            "default_template" => {
+                self.default_template = Some(value.to_string()).filter(|v| !v.is_empty())
+            }
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_key(key: &str) -> String {
+    format!(
+        "Unknown config key '{}'. Valid keys: {}",
+        key,
+        CONFIG_KEYS.join(", ")
+    )
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("{} must be true or false, got '{}'", key, value)),
+    }
+}
+
+fn parse_size(key: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be a number of bytes, got '{}'", key, value))
+}
+
+/// Current version of the `lore export` document format
+pub const EXPORT_VERSION: u32 = 1;
+
+/// A portable bundle of a lore store produced by `lore export`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoreExport {
+    /// Format version, checked on import
+    pub version: u32,
+
+    /// When the export was created
+    pub exported_at: DateTime<Utc>,
+
+    /// The exporting repository's config
+    pub config: serde_json::Value,
+
+    /// Index covering the exported entries
+    pub index: LoreIndex,
+
+    /// The exported entries, newest first
+    pub entries: Vec<ThoughtObject>,
+}
+
+/// The parts of an entry kept in the index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntryMeta {
+    pub id: String,
+    pub target_file: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_paths: Vec<String>,
+    pub agent_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub intent: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// ID of the entry this one replaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supersedes: Option<String>,
+}
+
+impl From<&ThoughtObject> for EntryMeta {
+    fn from(entry: &ThoughtObject) -> Self {
+        Self {
+            id: entry.id.clone(),
+            target_file: entry.target_file.clone(),
+            previous_paths: entry.previous_paths.clone(),
+            agent_id: entry.agent_id.clone(),
+            timestamp: entry.timestamp,
+            intent: entry.intent.clone(),
+            tags: entry.tags.clone(),
+            project: entry.project.clone(),
+            supersedes: entry.supersedes.clone(),
+        }
+    }
+}
+
+/// Fields shared by full entries and their index metadata, so filters work on both
+pub trait EntryFields {
+    fn id(&self) -> &str;
+    fn timestamp(&self) -> DateTime<Utc>;
+    fn tags(&self) -> &[String];
+    fn project(&self) -> Option<&str>;
+    fn supersedes(&self) -> Option<&str>;
+}
+
+impl EntryFields for ThoughtObject {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn project(&self) -> Option<&str> {
+        self.project.as_deref()
+    }
+
+    fn supersedes(&self) -> Option<&str> {
+        self.supersedes.as_deref()
+    }
+}
+
+impl EntryFields for EntryMeta {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn project(&self) -> Option<&str> {
+        self.project.as_deref()
+    }
+
+    fn supersedes(&self) -> Option<&str> {
+        self.supersedes.as_deref()
+    }
+}
+
+/// What a store mutation did to an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    /// A new entry was recorded
+    Record,
+    /// An entry was written by `lore import`
+    Import,
+    /// An existing entry was rewritten, e.g. by an agent rename or file move
+    Edit,
+    /// An entry was removed by `lore prune`
+    Prune,
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditAction::Record => write!(f, "record"),
+            AuditAction::Import => write!(f, "import"),
+            AuditAction::Edit => write!(f, "edit"),
+            AuditAction::Prune => write!(f, "prune"),
+        }
+    }
+}
+
+/// One line of `.lore/audit.log`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    pub entry_id: String,
+    /// The entry's agent after the change
+    pub agent_id: String,
+    pub target_file: String,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction, entry: &ThoughtObject) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            action,
+            entry_id: entry.id.clone(),
+            agent_id: entry.agent_id.clone(),
+            target_file: entry.target_file.clone(),
+        }
+    }
+}
+
+/// Version of the entry metadata kept in the index. Bumped when a field is
+/// added to `EntryMeta`, so older indexes are rebuilt to fill it in.
+pub const INDEX_VERSION: u32 = 1;
+
 /// Index entry for quick lookups by file path
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoreIndex {
     /// Map of file paths to their entry IDs
-    pub files: std::collections::HashMap<String, Vec<String>>,
+    pub files: std::collections::BTreeMap<String, Vec<String>>,
 
     /// Total number of entries
     pub entry_count: usize,
+
+    /// Summary of each entry by ID, so listing and filtering don't have to
+    /// read every entry file. Missing in indexes written by older versions.
+    #[serde(default)]
+    pub entries: std::collections::BTreeMap<String, EntryMeta>,
+
+    /// `INDEX_VERSION` the metadata was written with (0 when missing)
+    #[serde(default)]
+    pub version: u32,
+}
+
+impl Default for LoreIndex {
+    fn default() -> Self {
+        Self {
+            files: Default::default(),
+            entry_count: 0,
+            entries: Default::default(),
+            version: INDEX_VERSION,
+        }
+    }
 }
 
 impl LoreIndex {
@@ -109,6 +924,28 @@ impl LoreIndex {
         Self::default()
     }
 
+    /// Add an entry along with its metadata
+    pub fn insert(&mut self, entry: &ThoughtObject) {
+        self.insert_meta(EntryMeta::from(entry));
+    }
+
+    /// Add an entry from its metadata alone
+    pub fn insert_meta(&mut self, meta: EntryMeta) {
+        self.add_entry(&meta.target_file, &meta.id);
+        self.entries.insert(meta.id.clone(), meta);
+    }
+
+    /// Whether every indexed entry has up-to-date metadata (false for indexes
+    /// written before metadata, or one of its fields, was stored)
+    pub fn has_metadata(&self) -> bool {
+        self.version >= INDEX_VERSION
+            && self
+                .files
+                .values()
+                .flatten()
+                .all(|id| self.entries.contains_key(id))
+    }
+
     pub fn add_entry(&mut self, file_path: &str, entry_id: &str) {
         self.files
             .entry(file_path.to_string())
@@ -120,12 +957,252 @@ impl LoreIndex {
     pub fn get_entries_for_file(&self, file_path: &str) -> Option<&Vec<String>> {
         self.files.get(file_path)
     }
+
+    /// The indexed path matching `file_path` exactly, or else ignoring case
+    /// when `ignore_case` is set (the first in path order if several differ
+    /// only in case)
+    pub fn find_file(&self, file_path: &str, ignore_case: bool) -> Option<&str> {
+        if let Some((path, _)) = self.files.get_key_value(file_path) {
+            return Some(path);
+        }
+        if !ignore_case {
+            return None;
+        }
+        let folded = file_path.to_lowercase();
+        self.files
+            .keys()
+            .find(|path| path.to_lowercase() == folded)
+            .map(String::as_str)
+    }
+
+    /// Remove an entry from every file it is listed under, dropping files left
+    /// without entries. Returns whether it was indexed.
+    pub fn remove(&mut self, entry_id: &str) -> bool {
+        let mut removed = false;
+        self.files.retain(|_, ids| {
+            let before = ids.len();
+            ids.retain(|id| id != entry_id);
+            removed |= ids.len() != before;
+            !ids.is_empty()
+        });
+        self.entries.remove(entry_id);
+        if removed {
+            self.entry_count = self.entry_count.saturating_sub(1);
+        }
+        removed
+    }
+
+    /// Three-way merge of two indexes that diverged from `base`, as the
+    /// `lore merge-index` git merge driver does. Entries added on either side
+    /// are kept and entries removed on either side are dropped. An entry's
+    /// file and metadata come from the side that changed them.
+    pub fn merge(base: &LoreIndex, ours: &LoreIndex, theirs: &LoreIndex) -> LoreIndex {
+        let (base_files, our_files, their_files) =
+            (base.file_by_id(), ours.file_by_id(), theirs.file_by_id());
+
+        let ids: std::collections::BTreeSet<&str> = our_files
+            .keys()
+            .chain(their_files.keys())
+            .copied()
+            .collect();
+        let mut kept = Vec::new();
+        for id in ids {
+            let (ours_file, theirs_file) = (our_files.get(id), their_files.get(id));
+            if base_files.contains_key(id) && (ours_file.is_none() || theirs_file.is_none()) {
+                continue;
+            }
+            let Some(file) = changed_side(base_files.get(id), ours_file, theirs_file) else {
+                continue;
+            };
+            let meta = changed_side(
+                base.entries.get(id),
+                ours.entries.get(id),
+                theirs.entries.get(id),
+            );
+            kept.push((id, *file, meta));
+        }
+
+        // Oldest first, matching the order entries are appended on record
+        kept.sort_by_key(|(id, _, meta)| (meta.map(|m| m.timestamp), *id));
+
+        let mut merged = LoreIndex {
+            version: ours.version.min(theirs.version),
+            ..LoreIndex::new()
+        };
+        for (id, file, meta) in kept {
+            match meta {
+                Some(meta) => merged.insert_meta(EntryMeta {
+                    target_file: file.to_string(),
+                    ..meta.clone()
+                }),
+                None => merged.add_entry(file, id),
+            }
+        }
+        merged
+    }
+
+    /// The file each entry is listed under
+    fn file_by_id(&self) -> std::collections::HashMap<&str, &str> {
+        self.files
+            .iter()
+            .flat_map(|(file, ids)| ids.iter().map(move |id| (id.as_str(), file.as_str())))
+            .collect()
+    }
+}
+
+/// The value one side of a three-way merge changed from `base`, else the other's
+fn changed_side<'a, T: PartialEq + ?Sized>(
+    base: Option<&'a T>,
+    ours: Option<&'a T>,
+    theirs: Option<&'a T>,
+) -> Option<&'a T> {
+    if ours != base {
+        ours.or(theirs)
+    } else {
+        theirs.or(ours)
+    }
+}
+
+/// Inverted index from the lowercase words of each entry's searchable text to
+/// the IDs of the entries containing them, so `search` only has to read the
+/// entries that can match
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct SearchIndex {
+    /// `entry_count` of the `LoreIndex` this was last updated along with. If
+    /// the two differ, entries were added without updating this index.
+    pub entry_count: usize,
+
+    /// Map of words to entry IDs
+    pub words: std::collections::BTreeMap<String, std::collections::BTreeSet<String>>,
+}
+
+impl SearchIndex {
+    pub fn add(&mut self, entry_id: &str, words: impl IntoIterator<Item = String>) {
+        for word in words {
+            self.words
+                .entry(word)
+                .or_default()
+                .insert(entry_id.to_string());
+        }
+    }
+
+    /// Forget the given entries, dropping words no other entry contains
+    pub fn remove(&mut self, entry_ids: &std::collections::HashSet<String>) {
+        self.words.retain(|_, ids| {
+            ids.retain(|id| !entry_ids.contains(id));
+            !ids.is_empty()
+        });
+    }
+
+    /// Three-way merge for the `lore merge-index` git merge driver: each word
+    /// keeps the entries either side lists under it, minus entries removed on
+    /// either side. Extra candidates are harmless, since search checks the
+    /// full text of every candidate.
+    pub fn merge(base: &SearchIndex, ours: &SearchIndex, theirs: &SearchIndex) -> SearchIndex {
+        let (base_ids, our_ids, their_ids) = (base.ids(), ours.ids(), theirs.ids());
+        let removed: std::collections::HashSet<String> = base_ids
+            .into_iter()
+            .filter(|id| !our_ids.contains(id) || !their_ids.contains(id))
+            .map(str::to_string)
+            .collect();
+
+        let mut merged = ours.clone();
+        for (word, ids) in &theirs.words {
+            merged
+                .words
+                .entry(word.clone())
+                .or_default()
+                .extend(ids.iter().cloned());
+        }
+        merged.remove(&removed);
+        merged.entry_count = merged.ids().len();
+        merged
+    }
+
+    /// Every entry ID listed under some word
+    fn ids(&self) -> std::collections::HashSet<&str> {
+        self.words.values().flatten().map(String::as_str).collect()
+    }
+
+    /// IDs of entries with a word that contains `fragment`
+    pub fn containing(&self, fragment: &str) -> std::collections::HashSet<String> {
+        self.words
+            .iter()
+            .filter(|(word, _)| word.contains(fragment))
+            .flat_map(|(_, ids)| ids.iter().cloned())
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_config_from_old_init_loads() {
+        let json = r#"{
+            "version": "0.1.0",
+            "default_agent_id": "claude",
+            "normalize_agent_ids": false,
+            "created_at": "2024-02-14T10:00:00+00:00"
+        }"#;
+        let config: LoreConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.default_agent_id.as_deref(), Some("claude"));
+        assert!(config.auto_diff);
+        assert!(config.default_tags.is_empty());
+        assert_eq!(config.max_diff_bytes, DEFAULT_MAX_DIFF_BYTES);
+        assert!(config.other.is_empty());
+    }
+
+    #[test]
+    fn test_config_keeps_unknown_settings() {
+        let config: LoreConfig = serde_json::from_str(r#"{"future_setting": 3}"#).unwrap();
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["future_setting"], 3);
+    }
+
+    #[test]
+    fn test_config_set_and_get() {
+        let mut config = LoreConfig::default();
+        assert_eq!(config.get("max_trace_size").unwrap(), None);
+
+        config.set("default_tags", "auth, security,").unwrap();
+        assert_eq!(config.default_tags, vec!["auth", "security"]);
+        assert_eq!(
+            config.get("default_tags").unwrap().as_deref(),
+            Some("auth,security")
+        );
+
+        config.set("auto_diff", "false").unwrap();
+        assert!(!config.auto_diff);
+        assert!(config.set("auto_diff", "maybe").is_err());
+
+        config.set("max_trace_size", "4096").unwrap();
+        assert_eq!(config.max_trace_size, Some(4096));
+        config.set("max_trace_size", "").unwrap();
+        assert_eq!(config.max_trace_size, None);
+
+        config.set("default_template", "adr").unwrap();
+        assert_eq!(
+            config.get("default_template").unwrap().as_deref(),
+            Some("adr")
+        );
+        config.set("default_template", "").unwrap();
+        assert_eq!(config.default_template, None);
+
+        assert!(config.edit_trace);
+        config.set("edit_trace", "off").unwrap();
+        assert_eq!(config.get("edit_trace").unwrap().as_deref(), Some("false"));
+    }
+
+    #[test]
+    fn test_config_unknown_key_lists_valid_keys() {
+        let err = LoreConfig::default().set("agent", "x").unwrap_err();
+        assert!(err.contains("Unknown config key 'agent'"));
+        assert!(err.contains("default_agent_id"));
+        assert!(LoreConfig::default().get("agent").is_err());
+    }
+
     #[test]
     fn test_thought_object_new() {
         let thought = ThoughtObject::new(
@@ -145,6 +1222,7 @@ mod tests {
         assert!(thought.commit_hash.is_none());
         assert!(thought.rejected_alternatives.is_empty());
         assert!(thought.tags.is_empty());
+        assert!(thought.diff.is_none());
         assert!(!thought.id.is_empty());
     }
 
@@ -162,6 +1240,145 @@ mod tests {
         assert_eq!(thought.line_range, Some((10, 50)));
     }
 
+    #[test]
+    fn test_amend_appends_trace_and_merges_lists() {
+        use chrono::TimeZone;
+        let mut thought = ThoughtObject::new(
+            "src/main.rs".to_string(),
+            "abc123".to_string(),
+            "test-agent".to_string(),
+            "Test".to_string(),
+            "First thoughts\n".to_string(),
+        )
+        .with_tags(vec!["auth".to_string()]);
+        let now = Utc.with_ymd_and_hms(2024, 3, 5, 14, 30, 0).unwrap();
+
+        thought.amend(
+            "Forgot this",
+            &["auth".to_string(), "jwt".to_string()],
+            &[RejectedAlternative {
+                name: "Sessions".to_string(),
+                reason: None,
+            }],
+            &[],
+            now,
+        );
+        thought.amend("", &[], &[], &["JIRA-1".to_string()], now);
+
+        assert_eq!(
+            thought.reasoning_trace,
+            "First thoughts\n\n--- amended 2024-03-05 14:30 UTC ---\n\nForgot this"
+        );
+        assert_eq!(thought.tags, vec!["auth".to_string(), "jwt".to_string()]);
+        assert_eq!(thought.rejected_alternatives.len(), 1);
+        assert_eq!(thought.references, vec!["JIRA-1".to_string()]);
+        assert_eq!(thought.updated_at, Some(now));
+    }
+
+    #[test]
+    fn test_entry_without_relationships_deserializes() {
+        let json = r#"{
+            "id": "abc",
+            "target_file": "a.rs",
+            "file_hash": "hash",
+            "agent_id": "agent",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "intent": "Intent",
+            "reasoning_trace": "Reasoning"
+        }"#;
+        let entry: ThoughtObject = serde_json::from_str(json).unwrap();
+        assert!(entry.related_entries.is_empty());
+        assert!(entry.supersedes.is_none());
+
+        let serialized = serde_json::to_string(&entry).unwrap();
+        assert!(!serialized.contains("supersedes"));
+        assert!(!serialized.contains("related_entries"));
+    }
+
+    #[test]
+    fn test_snapshot_lines() {
+        let content = "one\ntwo\nthree\nfour\n";
+        assert_eq!(
+            snapshot_lines(content, (2, 3)).as_deref(),
+            Some("two\nthree")
+        );
+        // Ranges running past the end keep what exists
+        assert_eq!(snapshot_lines(content, (4, 9)).as_deref(), Some("four"));
+        assert_eq!(snapshot_lines(content, (5, 9)), None);
+    }
+
+    #[test]
+    fn test_snapshot_lines_is_capped() {
+        let content: String = (1..=150).map(|i| format!("line {}\n", i)).collect();
+        let snapshot = snapshot_lines(&content, (1, 150)).unwrap();
+
+        assert_eq!(snapshot.lines().count(), MAX_SNAPSHOT_LINES + 1);
+        assert!(snapshot.ends_with("... (50 more lines)"));
+    }
+
+    #[test]
+    fn test_line_anchor_capture() {
+        let content = "fn a() {\n    let x = 1;\n}\n";
+        let anchor = LineAnchor::capture(content, (1, 3)).unwrap();
+        assert_eq!(anchor.start, "fn a() {");
+        assert_eq!(anchor.end, "}");
+
+        assert!(LineAnchor::capture(content, (2, 5)).is_none());
+        assert!(LineAnchor::capture(content, (0, 1)).is_none());
+    }
+
+    #[test]
+    fn test_line_anchor_resolve_unchanged() {
+        let content = "a\nb\nc\nd\n";
+        let anchor = LineAnchor::capture(content, (2, 3)).unwrap();
+        assert_eq!(anchor.resolve(content, (2, 3)), RangeLocation::Unchanged);
+    }
+
+    #[test]
+    fn test_line_anchor_resolve_moved() {
+        let anchor = LineAnchor::capture("a\nfn b() {\n  x\n}\n", (2, 4)).unwrap();
+        let edited = "new\nlines\na\nfn b() {\n  x\n}\n";
+        assert_eq!(anchor.resolve(edited, (2, 4)), RangeLocation::Moved(4, 6));
+    }
+
+    #[test]
+    fn test_line_anchor_resolve_prefers_nearest_match() {
+        let anchor = LineAnchor {
+            start: "{".to_string(),
+            end: "}".to_string(),
+        };
+        let content = "{\n}\nx\nx\nx\n{\n}\n";
+        assert_eq!(anchor.resolve(content, (5, 6)), RangeLocation::Moved(6, 7));
+    }
+
+    #[test]
+    fn test_anchored_range_follows_the_anchor() {
+        let recorded = "use x;\nfn authenticate() {\n  check()\n}\n";
+        let entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_line_range(2, 4)
+        .with_anchor("fn authenticate".to_string());
+        assert_eq!(entry.anchored_range(recorded), Some((2, 4)));
+
+        let edited = format!("use y;\n\n\n{}", recorded);
+        assert_eq!(entry.anchor_line(&edited), Some(5));
+        assert_eq!(entry.anchored_range(&edited), Some((5, 7)));
+
+        assert_eq!(entry.anchor_line("fn login() {}\n"), None);
+        assert_eq!(find_anchor("a\nb\na\n", "a", 3), Some(3));
+    }
+
+    #[test]
+    fn test_line_anchor_resolve_lost() {
+        let anchor = LineAnchor::capture("a\nb\n", (1, 2)).unwrap();
+        assert_eq!(anchor.resolve("x\ny\n", (1, 2)), RangeLocation::Lost);
+    }
+
     #[test]
     fn test_thought_object_with_commit() {
         let thought = ThoughtObject::new(
@@ -240,6 +1457,24 @@ mod tests {
         assert_eq!(thought.tags, vec!["tag1"]);
     }
 
+    #[test]
+    fn test_thought_object_freshness() {
+        let thought = ThoughtObject::new(
+            "src/main.rs".to_string(),
+            "abc123".to_string(),
+            "test-agent".to_string(),
+            "Test".to_string(),
+            "Reasoning".to_string(),
+        );
+
+        assert_eq!(thought.freshness(Some("abc123")), Freshness::Current);
+        assert_eq!(thought.freshness(Some("def456")), Freshness::Stale);
+        assert_eq!(thought.freshness(None), Freshness::FileMissing);
+        assert!(!Freshness::Current.is_stale());
+        assert!(Freshness::Stale.is_stale());
+        assert!(Freshness::FileMissing.is_stale());
+    }
+
     #[test]
     fn test_thought_object_serialization() {
         let thought = ThoughtObject::new(
@@ -260,6 +1495,44 @@ mod tests {
         assert_eq!(deserialized.reasoning_trace, thought.reasoning_trace);
     }
 
+    #[test]
+    fn test_pending_draft_append_chunks() {
+        let mut draft = PendingDraft::new("agent".to_string());
+        draft.append_chunk("First part");
+        draft.append_chunk("");
+        draft.append_chunk("Second part");
+
+        assert_eq!(draft.reasoning_trace, "First part\nSecond part");
+        assert_eq!(draft.chunks, 2);
+        assert!(draft.updated_at >= draft.started_at);
+    }
+
+    #[test]
+    fn test_pending_draft_add_files_and_tags_dedupes() {
+        let mut draft = PendingDraft::new("agent".to_string());
+        draft.add_files(&["a.rs".to_string(), "b.rs".to_string()]);
+        draft.add_files(&["a.rs".to_string()]);
+        draft.add_tags(&["auth".to_string()]);
+        draft.add_tags(&["auth".to_string(), "perf".to_string()]);
+
+        assert_eq!(draft.files, vec!["a.rs", "b.rs"]);
+        assert_eq!(draft.tags, vec!["auth", "perf"]);
+    }
+
+    #[test]
+    fn test_pending_draft_serialization() {
+        let mut draft = PendingDraft::new("agent".to_string());
+        draft.append_chunk("Chunk");
+        draft.line_range = Some((1, 5));
+
+        let json = serde_json::to_string(&draft).unwrap();
+        let deserialized: PendingDraft = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized.reasoning_trace, "Chunk");
+        assert_eq!(deserialized.chunks, 1);
+        assert_eq!(deserialized.line_range, Some((1, 5)));
+    }
+
     #[test]
     fn test_lore_index_new() {
         let index = LoreIndex::new();
@@ -309,12 +1582,115 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lore_index_legacy_format_loads() {
+        // Written before entry metadata and the version were stored
+        let legacy = r#"{"files": {"src/main.rs": ["entry-1", "entry-2"]}, "entry_count": 2}"#;
+        let index: LoreIndex = serde_json::from_str(legacy).unwrap();
+
+        assert_eq!(index.entry_count, 2);
+        assert_eq!(index.get_entries_for_file("src/main.rs").unwrap().len(), 2);
+        assert!(index.entries.is_empty());
+        assert_eq!(index.version, 0);
+        assert!(!index.has_metadata());
+        assert!(LoreIndex::new().has_metadata());
+    }
+
+    fn indexed(id: &str, file: &str, intent: &str) -> EntryMeta {
+        EntryMeta {
+            id: id.to_string(),
+            target_file: file.to_string(),
+            previous_paths: Vec::new(),
+            agent_id: "agent".to_string(),
+            timestamp: Utc::now(),
+            intent: intent.to_string(),
+            tags: Vec::new(),
+            project: None,
+            supersedes: None,
+        }
+    }
+
+    fn index_of(metas: &[&EntryMeta]) -> LoreIndex {
+        let mut index = LoreIndex::new();
+        for meta in metas {
+            index.insert_meta((*meta).clone());
+        }
+        index
+    }
+
+    #[test]
+    fn test_lore_index_merge_three_way() {
+        let kept = indexed("kept", "a.rs", "Kept");
+        let pruned = indexed("pruned", "a.rs", "Pruned");
+        let moved = indexed("moved", "old.rs", "Moved");
+        let amended = indexed("amended", "b.rs", "Before");
+        let base = index_of(&[&kept, &pruned, &moved, &amended]);
+
+        // Ours records an entry, prunes one and moves one
+        let ours_new = indexed("ours", "a.rs", "Ours");
+        let moved_ours = EntryMeta {
+            target_file: "new.rs".to_string(),
+            ..moved.clone()
+        };
+        let ours = index_of(&[&kept, &moved_ours, &amended, &ours_new]);
+
+        // Theirs records another entry and amends one
+        let theirs_new = indexed("theirs", "c.rs", "Theirs");
+        let amended_theirs = EntryMeta {
+            intent: "After".to_string(),
+            ..amended.clone()
+        };
+        let theirs = index_of(&[&kept, &pruned, &moved, &amended_theirs, &theirs_new]);
+
+        let merged = LoreIndex::merge(&base, &ours, &theirs);
+        assert_eq!(merged.entry_count, 5);
+        assert!(merged.has_metadata());
+        assert!(!merged.entries.contains_key("pruned"));
+        assert_eq!(merged.files["new.rs"], vec!["moved"]);
+        assert!(!merged.files.contains_key("old.rs"));
+        assert_eq!(merged.files["a.rs"], vec!["kept", "ours"]);
+        assert_eq!(merged.files["c.rs"], vec!["theirs"]);
+        assert_eq!(merged.entries["amended"].intent, "After");
+    }
+
+    #[test]
+    fn test_search_index_merge_unions_words_and_drops_removed() {
+        let mut base = SearchIndex::default();
+        base.add("kept", ["cache".to_string()]);
+        base.add("pruned", ["cache".to_string(), "stale".to_string()]);
+
+        let mut ours = base.clone();
+        ours.remove(&std::collections::HashSet::from(["pruned".to_string()]));
+        ours.add("ours", ["cache".to_string()]);
+
+        let mut theirs = base.clone();
+        theirs.add("theirs", ["token".to_string()]);
+
+        let merged = SearchIndex::merge(&base, &ours, &theirs);
+        assert_eq!(merged.entry_count, 3);
+        assert_eq!(merged.containing("cache").len(), 2);
+        assert!(merged.containing("stale").is_empty());
+        assert!(merged.containing("token").contains("theirs"));
+    }
+
     #[test]
     fn test_lore_index_get_entries_nonexistent_file() {
         let index = LoreIndex::new();
         assert!(index.get_entries_for_file("nonexistent.rs").is_none());
     }
 
+    #[test]
+    fn test_lore_index_find_file_ignoring_case() {
+        let mut index = LoreIndex::new();
+        index.add_entry("Src/Main.rs", "id-1");
+        index.add_entry("src/main.rs", "id-2");
+
+        assert_eq!(index.find_file("src/main.rs", false), Some("src/main.rs"));
+        assert_eq!(index.find_file("SRC/MAIN.RS", false), None);
+        // Several spellings: the first in path order
+        assert_eq!(index.find_file("SRC/MAIN.RS", true), Some("Src/Main.rs"));
+    }
+
     #[test]
     fn test_lore_index_serialization() {
         let mut index = LoreIndex::new();
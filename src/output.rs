@@ -0,0 +1,409 @@
+use crate::diagnostics::Diagnostics;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How a command prints its results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Colored, human-readable text
+    #[default]
+    Pretty,
+    Json,
+    Yaml,
+    Toml,
+    /// One compact JSON value per line, for streaming consumers
+    Ndjson,
+}
+
+impl Format {
+    /// The format to use given `--format` and the older `--json` flag
+    pub fn or_json(self, json: bool) -> Self {
+        if json {
+            Format::Json
+        } else {
+            self
+        }
+    }
+
+    /// The format to use given `--format` and the `--ndjson` flag
+    pub fn or_ndjson(self, ndjson: bool) -> Self {
+        if ndjson {
+            Format::Ndjson
+        } else {
+            self
+        }
+    }
+
+    /// Whether output is machine-readable (and human-oriented messages should
+    /// be suppressed)
+    pub fn is_structured(self) -> bool {
+        self != Format::Pretty
+    }
+
+    /// Whether output is a single document, which carries warnings in its
+    /// envelope. NDJSON streams bare entries, so warnings go to stderr.
+    pub fn is_document(self) -> bool {
+        self.is_structured() && self != Format::Ndjson
+    }
+}
+
+/// When to color output, from `--color` (or `--no-color`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    /// Color even through a pipe or with `NO_COLOR` set
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output. `no_color` is the value of the `NO_COLOR`
+    /// environment variable, which disables color when set to anything but
+    /// an empty string (see no-color.org).
+    pub fn enabled(self, no_color: Option<&std::ffi::OsStr>, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && no_color.is_none_or(|value| value.is_empty()),
+        }
+    }
+}
+
+/// How failures are reported on stderr, from `--error-format`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// The message alone
+    #[default]
+    Text,
+    /// `{"error": {"kind": "...", "message": "..."}}` on one line
+    Json,
+}
+
+/// Print ASCII stand-ins for box-drawing characters and symbols, set once by
+/// `--ascii` or a non-UTF-8 locale
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+pub fn set_ascii(ascii: bool) {
+    ASCII.store(ascii, Ordering::Relaxed);
+}
+
+/// Whether the terminal can be expected to render UTF-8, judging by the
+/// locale (`LC_ALL`, then `LC_CTYPE`, then `LANG`). Without a locale, assume
+/// UTF-8, except in Windows consoles other than Windows Terminal.
+pub fn supports_utf8(var: impl Fn(&str) -> Option<String>) -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(&var)
+        .find(|value| !value.is_empty());
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        }
+        None => !cfg!(windows) || var("WT_SESSION").is_some(),
+    }
+}
+
+/// A symbol used in text output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    Check,
+    Cross,
+    Arrow,
+    Bullet,
+    /// Separates a gutter (line numbers, IDs) from content
+    Bar,
+    /// One cell of a histogram bar
+    Block,
+    Ellipsis,
+}
+
+impl Glyph {
+    pub fn as_str(self) -> &'static str {
+        self.text(ASCII.load(Ordering::Relaxed))
+    }
+
+    fn text(self, ascii: bool) -> &'static str {
+        match (self, ascii) {
+            (Glyph::Check, false) => "✓",
+            (Glyph::Check, true) => "ok",
+            (Glyph::Cross, false) => "✗",
+            (Glyph::Cross, true) => "x",
+            (Glyph::Arrow, false) => "→",
+            (Glyph::Arrow, true) => "->",
+            (Glyph::Bullet, false) => "•",
+            (Glyph::Bullet, true) => "*",
+            (Glyph::Bar, false) => "│",
+            (Glyph::Bar, true) => "|",
+            (Glyph::Block, false) => "█",
+            (Glyph::Block, true) => "#",
+            (Glyph::Ellipsis, false) => "…",
+            (Glyph::Ellipsis, true) => "...",
+        }
+    }
+}
+
+impl fmt::Display for Glyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A light horizontal line, `width` characters long
+pub fn rule(width: usize) -> String {
+    let ascii = ASCII.load(Ordering::Relaxed);
+    if ascii { "-" } else { "─" }.repeat(width)
+}
+
+/// A heavy horizontal line framing a report's header and footer
+pub fn heavy_rule(width: usize) -> String {
+    let ascii = ASCII.load(Ordering::Relaxed);
+    if ascii { "=" } else { "═" }.repeat(width)
+}
+
+/// First 8 characters of an entry ID, as shown to users. IDs from imported
+/// stores can be shorter, and are shown whole.
+pub fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+/// Serialize `value` in `format`. `Pretty` has no serialized form of its own
+/// and renders as JSON; commands print their text view instead of calling this.
+///
+/// TOML has no top-level arrays or nulls, so for TOML a top-level array is
+/// wrapped as `{ "entries": [...] }` and null fields are left out. NDJSON
+/// writes each element of a top-level array on its own line, and anything
+/// else as a single line.
+pub fn render<T: Serialize + ?Sized>(
+    value: &T,
+    format: Format,
+) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(match format {
+        Format::Pretty | Format::Json => serde_json::to_string_pretty(value)?,
+        Format::Yaml => serde_yaml::to_string(value)?,
+        Format::Toml => {
+            let value = match without_nulls(serde_json::to_value(value)?) {
+                Value::Array(entries) => serde_json::json!({ "entries": entries }),
+                value => value,
+            };
+            toml::to_string_pretty(&value)?
+        }
+        Format::Ndjson => {
+            // Split the compact array as written, keeping each entry's field order
+            let compact = serde_json::to_string(value)?;
+            match serde_json::from_str::<Vec<&RawValue>>(&compact) {
+                Ok(items) => items
+                    .iter()
+                    .map(|item| item.get())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Err(_) => compact,
+            }
+        }
+    })
+}
+
+/// Write `value` in `format` to `out`, followed by a newline
+pub fn print<T: Serialize + ?Sized>(
+    out: &mut impl Write,
+    value: &T,
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rendered = render(value, format)?;
+    // An empty NDJSON stream has no lines at all
+    if !rendered.is_empty() {
+        writeln!(out, "{}", rendered.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Print entries together with the warnings collected while finding them: in
+/// the `--json` envelope, or for NDJSON one entry per line with the warnings
+/// already on stderr
+pub(crate) fn print_report<T: Serialize>(
+    out: &mut impl Write,
+    diagnostics: Diagnostics,
+    entries: T,
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        Format::Ndjson => print(out, &entries, format),
+        _ => print(out, &diagnostics.into_report(entries), format),
+    }
+}
+
+/// `value` with every null object field dropped (nulls in arrays can't be
+/// represented either, and are dropped too)
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .filter(|v| !v.is_null())
+                .map(without_nulls)
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ThoughtObject;
+    use chrono::{TimeZone, Utc};
+
+    fn entry() -> ThoughtObject {
+        let mut entry = ThoughtObject::new(
+            "src/auth.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Switch to JWT".to_string(),
+            "Sessions expire".to_string(),
+        )
+        .with_tags(vec!["auth".to_string()]);
+        entry.timestamp = Utc.with_ymd_and_hms(2024, 2, 14, 10, 0, 0).unwrap();
+        entry
+    }
+
+    #[test]
+    fn test_short_id_handles_short_and_multibyte_ids() {
+        assert_eq!(short_id("550e8400-e29b-41d4"), "550e8400");
+        assert_eq!(short_id("abc"), "abc");
+        assert_eq!(short_id("ééééé"), "éééé");
+        // Cutting at 8 bytes would split the é, so the ID is shown whole
+        assert_eq!(short_id("abcdefgé"), "abcdefgé");
+    }
+
+    #[test]
+    fn test_timestamps_are_rfc3339_in_every_format() {
+        let entries = vec![entry()];
+        for format in [Format::Json, Format::Yaml, Format::Toml, Format::Ndjson] {
+            let rendered = render(&entries, format).unwrap();
+            assert!(
+                rendered.contains("2024-02-14T10:00:00Z"),
+                "{:?}: {}",
+                format,
+                rendered
+            );
+        }
+    }
+
+    #[test]
+    fn test_toml_wraps_top_level_array() {
+        let rendered = render(&vec![entry(), entry()], Format::Toml).unwrap();
+        let parsed: toml::Value = toml::from_str(&rendered).unwrap();
+        let entries = parsed["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["intent"].as_str(), Some("Switch to JWT"));
+    }
+
+    #[test]
+    fn test_toml_drops_nulls() {
+        #[derive(Serialize)]
+        struct Result {
+            score: Option<usize>,
+            file: &'static str,
+        }
+        let rendered = render(
+            &Result {
+                score: None,
+                file: "a.rs",
+            },
+            Format::Toml,
+        )
+        .unwrap();
+        assert_eq!(rendered.trim(), "file = \"a.rs\"");
+    }
+
+    #[test]
+    fn test_yaml_round_trips() {
+        let rendered = render(&vec![entry()], Format::Yaml).unwrap();
+        let parsed: Vec<ThoughtObject> = serde_yaml::from_str(&rendered).unwrap();
+        assert_eq!(parsed[0].tags, vec!["auth".to_string()]);
+    }
+
+    #[test]
+    fn test_color_choice() {
+        let set = Some(std::ffi::OsStr::new("1"));
+        let empty = Some(std::ffi::OsStr::new(""));
+
+        assert!(ColorChoice::Auto.enabled(None, true));
+        assert!(ColorChoice::Auto.enabled(empty, true));
+        assert!(!ColorChoice::Auto.enabled(set, true));
+        assert!(!ColorChoice::Auto.enabled(None, false));
+
+        assert!(ColorChoice::Always.enabled(set, false));
+        assert!(!ColorChoice::Never.enabled(None, true));
+    }
+
+    #[test]
+    fn test_supports_utf8_reads_the_first_locale_set() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert!(supports_utf8(env(&[("LANG", "en_US.UTF-8")])));
+        assert!(supports_utf8(env(&[("LC_CTYPE", "C.utf8")])));
+        assert!(!supports_utf8(env(&[("LANG", "C")])));
+        assert!(!supports_utf8(env(&[
+            ("LC_ALL", "POSIX"),
+            ("LANG", "en_US.UTF-8")
+        ])));
+        assert!(supports_utf8(env(&[
+            ("LC_ALL", ""),
+            ("LANG", "de_DE.UTF-8")
+        ])));
+    }
+
+    #[test]
+    fn test_glyph_ascii_fallbacks() {
+        assert_eq!(Glyph::Check.text(false), "✓");
+        assert_eq!(Glyph::Check.text(true), "ok");
+        assert_eq!(Glyph::Arrow.text(true), "->");
+        assert!(Glyph::Ellipsis.text(true).is_ascii());
+    }
+
+    #[test]
+    fn test_json_flag_overrides_format() {
+        assert_eq!(Format::Pretty.or_json(true), Format::Json);
+        assert_eq!(Format::Yaml.or_json(false), Format::Yaml);
+        assert_eq!(Format::Pretty.or_ndjson(true), Format::Ndjson);
+    }
+
+    #[test]
+    fn test_ndjson_writes_one_compact_entry_per_line() {
+        let rendered = render(&vec![entry(), entry()], Format::Ndjson).unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            let parsed: ThoughtObject = serde_json::from_str(line).unwrap();
+            assert_eq!(parsed.intent, "Switch to JWT");
+        }
+
+        assert_eq!(
+            render(&Vec::<ThoughtObject>::new(), Format::Ndjson).unwrap(),
+            ""
+        );
+        assert_eq!(
+            render(&serde_json::json!({"a": 1}), Format::Ndjson).unwrap(),
+            r#"{"a":1}"#
+        );
+    }
+}
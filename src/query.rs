@@ -0,0 +1,325 @@
+/// A boolean search expression such as `jwt AND refresh NOT cookie`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// A word or quoted phrase, matched literally
+    Term(String),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl Query {
+    /// Parse a search query into terms joined by boolean operators.
+    ///
+    /// Operators must be uppercase. Bare words next to each other are joined
+    /// with `AND`, or with `OR` when `any` is set; `NOT` binds tighter than
+    /// `AND`, which binds tighter than `OR`. Parentheses group and
+    /// double-quoted phrases match literally.
+    pub fn parse(input: &str, any: bool) -> Result<Query, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            any,
+        };
+        let query = parser.or_expr()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(query),
+            Some(Token::Close) => Err("unmatched ')'".to_string()),
+            Some(token) => Err(format!("unexpected {}", describe(token))),
+        }
+    }
+
+    /// Evaluate the expression, using `matches_term` to test each term
+    pub fn matches(&self, matches_term: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Query::Term(term) => matches_term(term),
+            Query::And(a, b) => a.matches(matches_term) && b.matches(matches_term),
+            Query::Or(a, b) => a.matches(matches_term) || b.matches(matches_term),
+            Query::Not(q) => !q.matches(matches_term),
+        }
+    }
+
+    /// Terms that count towards a match (those not under a `NOT`), for highlighting
+    pub fn positive_terms(&self) -> Vec<&str> {
+        let mut terms = Vec::new();
+        self.collect_terms(false, &mut terms);
+        terms
+    }
+
+    /// Every term in the expression
+    pub fn terms(&self) -> Vec<&str> {
+        let mut terms = Vec::new();
+        self.collect_terms(true, &mut terms);
+        terms
+    }
+
+    fn collect_terms<'a>(&'a self, include_negated: bool, terms: &mut Vec<&'a str>) {
+        match self {
+            Query::Term(term) => terms.push(term),
+            Query::And(a, b) | Query::Or(a, b) => {
+                a.collect_terms(include_negated, terms);
+                b.collect_terms(include_negated, terms);
+            }
+            Query::Not(q) if include_negated => q.collect_terms(include_negated, terms),
+            Query::Not(_) => {}
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("'{}'", word),
+        Token::Phrase(phrase) => format!("\"{}\"", phrase),
+        Token::And => "AND".to_string(),
+        Token::Or => "OR".to_string(),
+        Token::Not => "NOT".to_string(),
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+    }
+}
+
+/// Recursive-descent parser over the token list
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Join adjacent terms with `OR` rather than `AND`
+    any: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// Whether the next token starts a term with no operator before it
+    fn at_adjacent_term(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open)
+        )
+    }
+
+    fn or_expr(&mut self) -> Result<Query, String> {
+        let mut query = self.and_expr()?;
+        loop {
+            match self.peek() {
+                Some(Token::Or) => self.pos += 1,
+                _ if self.any && self.at_adjacent_term() => {}
+                _ => return Ok(query),
+            }
+            query = Query::Or(Box::new(query), Box::new(self.and_expr()?));
+        }
+    }
+
+    fn and_expr(&mut self) -> Result<Query, String> {
+        let mut query = self.not_expr()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                _ if !self.any && self.at_adjacent_term() => {}
+                _ => return Ok(query),
+            }
+            query = Query::And(Box::new(query), Box::new(self.not_expr()?));
+        }
+    }
+
+    fn not_expr(&mut self) -> Result<Query, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.not_expr()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Query, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or("expected a search term at the end of the query")?;
+        self.pos += 1;
+
+        match token {
+            Token::Word(term) | Token::Phrase(term) => Ok(Query::Term(term)),
+            Token::Open => {
+                let query = self.or_expr()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("missing ')'".to_string());
+                }
+                self.pos += 1;
+                Ok(query)
+            }
+            token => Err(format!(
+                "expected a search term, found {}",
+                describe(&token)
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(t: &str) -> Box<Query> {
+        Box::new(Query::Term(t.to_string()))
+    }
+
+    fn parse(input: &str) -> Query {
+        Query::parse(input, false).unwrap()
+    }
+
+    #[test]
+    fn test_plain_query_is_split_into_terms() {
+        assert_eq!(parse("jwt"), Query::Term("jwt".to_string()));
+        assert_eq!(
+            parse("jwt refresh"),
+            Query::And(term("jwt"), term("refresh"))
+        );
+        // Lowercase operators are ordinary words
+        assert_eq!(parse("or not"), Query::And(term("or"), term("not")));
+    }
+
+    #[test]
+    fn test_any_joins_adjacent_terms_with_or() {
+        assert_eq!(
+            Query::parse("jwt \"refresh token\" session", true).unwrap(),
+            Query::Or(
+                Box::new(Query::Or(term("jwt"), term("refresh token"))),
+                term("session")
+            )
+        );
+        // Explicit AND still binds tighter
+        assert_eq!(
+            Query::parse("a b AND c", true).unwrap(),
+            Query::Or(term("a"), Box::new(Query::And(term("b"), term("c"))))
+        );
+    }
+
+    #[test]
+    fn test_default_operator_is_and() {
+        assert_eq!(
+            parse("jwt refresh NOT cookie"),
+            Query::And(
+                Box::new(Query::And(term("jwt"), term("refresh"))),
+                Box::new(Query::Not(term("cookie")))
+            )
+        );
+    }
+
+    #[test]
+    fn test_precedence_and_grouping() {
+        assert_eq!(
+            parse("a OR b AND c"),
+            Query::Or(term("a"), Box::new(Query::And(term("b"), term("c"))))
+        );
+        assert_eq!(
+            parse("(a OR b) AND c"),
+            Query::And(Box::new(Query::Or(term("a"), term("b"))), term("c"))
+        );
+    }
+
+    #[test]
+    fn test_quoted_phrase_is_one_term() {
+        assert_eq!(
+            parse("\"refresh token\" AND NOT jwt"),
+            Query::And(term("refresh token"), Box::new(Query::Not(term("jwt"))))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for input in [
+            "jwt AND",
+            "(jwt OR oauth",
+            "jwt OR oauth)",
+            "OR jwt",
+            "\"jwt AND",
+            "\"unterminated",
+            "  ",
+        ] {
+            assert!(Query::parse(input, false).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let text = "switched to jwt with refresh tokens";
+        let matches_term = |term: &str| text.contains(term);
+
+        assert!(parse("jwt AND refresh NOT cookie").matches(&matches_term));
+        assert!(!parse("jwt AND cookie").matches(&matches_term));
+        assert!(parse("cookie OR \"refresh tokens\"").matches(&matches_term));
+        assert!(!parse("\"tokens refresh\" OR NOT jwt").matches(&matches_term));
+    }
+
+    #[test]
+    fn test_positive_terms() {
+        let query = parse("jwt OR (refresh NOT cookie)");
+        assert_eq!(query.positive_terms(), vec!["jwt", "refresh"]);
+        assert_eq!(query.terms(), vec!["jwt", "refresh", "cookie"]);
+    }
+}
@@ -1,8 +1,18 @@
-use crate::models::{LoreIndex, ThoughtObject};
+use crate::models::{
+    Attachment, AuditAction, AuditEvent, EntryFields, EntryMeta, LoreConfig, LoreExport, LoreIndex,
+    PendingDraft, SearchIndex, ThoughtObject, ENTRY_SCHEMA_VERSION, EXPORT_VERSION, INDEX_VERSION,
+};
+use crate::query::Query;
+use chrono::{DateTime, Utc};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -21,12 +31,248 @@ pub enum StorageError {
 
     #[error("File not found: {0}")]
     FileNotFound(String),
+
+    #[error("Invalid agent ID: {0}")]
+    InvalidAgentId(String),
+
+    #[error("Unsupported export version {0} (this lore supports version {EXPORT_VERSION})")]
+    UnsupportedExportVersion(u64),
+
+    #[error("No entry with ID {0}")]
+    EntryNotFound(String),
+
+    #[error("Entry ID prefix {0} is ambiguous; use more characters")]
+    AmbiguousEntryId(String),
+
+    #[error("Invalid .loreignore: {0}")]
+    InvalidIgnore(#[from] ignore::Error),
+
+    #[error("No template named '{0}'. See 'lore template list'.")]
+    TemplateNotFound(String),
+
+    #[error("Template '{0}' already exists; use --force to replace it")]
+    TemplateExists(String),
+
+    #[error("Invalid template name '{0}': use letters, digits, '-' and '_'")]
+    InvalidTemplateName(String),
+
+    #[error("Refusing to write the archive {0} inside .lore/entries, where it would be read as an entry")]
+    ArchiveInsideEntries(PathBuf),
+
+    #[error("Archive {0} already exists")]
+    ArchiveExists(PathBuf),
+
+    #[error("{0} is outside the repository ({1})")]
+    OutsideRoot(String, PathBuf),
+
+    #[error("Entry {0} has no attachment named '{1}'")]
+    AttachmentNotFound(String, String),
+
+    #[error("Invalid attachment name '{0}': it must be a plain file name")]
+    InvalidAttachmentName(String),
+
+    #[error("Entry {0} has schema version {1}, newer than this lore supports ({ENTRY_SCHEMA_VERSION}); upgrade lore to read it")]
+    UnsupportedSchemaVersion(String, u64),
 }
 
 const LORE_DIR: &str = ".lore";
 const ENTRIES_DIR: &str = "entries";
 const INDEX_FILE: &str = "index.json";
 const CONFIG_FILE: &str = "config.json";
+const PENDING_FILE: &str = "pending.json";
+const INDEX_LOCK_FILE: &str = "index.lock";
+const PARTIAL_INDEX_FILE: &str = "index.partial.json";
+const SEARCH_INDEX_FILE: &str = "search_index.json";
+const AUDIT_LOG_FILE: &str = "audit.log";
+const LOREIGNORE_FILE: &str = ".loreignore";
+const TEMPLATES_DIR: &str = "templates";
+const ATTACHMENTS_DIR: &str = "attachments";
+const TRACE_BUFFER_FILE: &str = "TRACE_EDITMSG.md";
+
+/// Entry files parsed between checkpoints of a rebuild
+const REINDEX_BATCH: usize = 1000;
+
+/// Result of rebuilding the index from the entries directory
+#[derive(Debug, Default)]
+pub struct ReindexReport {
+    /// Number of entries written to the new index
+    pub indexed: usize,
+
+    /// Entry filenames that could not be read or parsed
+    pub failed: Vec<String>,
+
+    /// Entries taken from an interrupted rebuild instead of being read again
+    pub resumed: usize,
+}
+
+/// Result of moving entry files from the flat layout into shards
+#[derive(Debug, Default)]
+pub struct MigrateReport {
+    /// Entry files moved into their shard
+    pub moved: usize,
+
+    /// IDs left in place because their shard already has a file for them
+    pub conflicts: Vec<String>,
+}
+
+/// What an interrupted rebuild had indexed, saved to `index.partial.json`
+#[derive(Default, Serialize, Deserialize)]
+struct PartialRebuild {
+    index: LoreIndex,
+    search: SearchIndex,
+}
+
+/// How `rebuild_index_with` reads the entries directory
+#[derive(Default)]
+pub struct ReindexOptions<'a> {
+    /// Parse entry files on all cores
+    pub parallel: bool,
+    /// Keep the entries a previous, interrupted rebuild already indexed
+    pub resume: bool,
+    /// Called with (entries done, total) after each batch
+    pub progress: Option<&'a dyn Fn(usize, usize)>,
+}
+
+/// Aggregated activity for a single agent
+#[derive(Debug, Serialize)]
+pub struct AgentStats {
+    pub agent_id: String,
+    pub entry_count: usize,
+    pub first_activity: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
+    pub file_count: usize,
+    pub files: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+/// How one store's entries differ from another's, matched by entry ID
+#[derive(Debug, Default, Serialize)]
+pub struct StoreDiff {
+    /// Entries only in this store
+    pub added: Vec<EntryMeta>,
+
+    /// Entries only in the other store
+    pub removed: Vec<EntryMeta>,
+
+    /// Entries in both stores whose content differs (this store's version)
+    pub modified: Vec<EntryMeta>,
+}
+
+impl StoreDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// An index reference or entry file, as reported by `fsck`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EntryRef {
+    pub id: String,
+    pub target_file: String,
+}
+
+/// Problems found by [`LoreStorage::fsck`], by category
+#[derive(Debug, Default, Serialize)]
+pub struct FsckReport {
+    /// Why `index.json` couldn't be read, if it couldn't. Every entry then
+    /// counts as unindexed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unreadable_index: Option<String>,
+
+    /// Index references whose entry file doesn't exist
+    pub dangling: Vec<EntryRef>,
+
+    /// Entry files that can't be read or aren't valid entries
+    pub unparseable: Vec<String>,
+
+    /// Valid entries not listed in the index under their `target_file`
+    pub unindexed: Vec<EntryRef>,
+
+    /// IDs listed more than once in the index, or claimed by more than one
+    /// entry file
+    pub duplicates: Vec<String>,
+
+    /// `entry_count` in the index, when it differs from the number of valid
+    /// entry files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_count: Option<CountMismatch>,
+
+    /// Entries whose `target_file` is gone from the working tree (only checked
+    /// when asked)
+    pub missing_targets: Vec<EntryRef>,
+}
+
+/// The index's `entry_count` against the number of valid entry files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CountMismatch {
+    pub recorded: usize,
+    pub actual: usize,
+}
+
+impl FsckReport {
+    /// Total number of problems across all categories
+    pub fn problem_count(&self) -> usize {
+        usize::from(self.unreadable_index.is_some())
+            + self.dangling.len()
+            + self.unparseable.len()
+            + self.unindexed.len()
+            + self.duplicates.len()
+            + usize::from(self.entry_count.is_some())
+            + self.missing_targets.len()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.problem_count() == 0
+    }
+}
+
+/// How a new entry repeats the newest existing entry for its file, which
+/// is identified by ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repetition {
+    /// Same file hash, intent and reasoning trace
+    Duplicate(String),
+    /// Same file hash with different reasoning
+    UnchangedFile(String),
+}
+
+/// Result of importing an export document
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Entries that did not exist before
+    pub imported: usize,
+
+    /// Existing entries replaced (overwrite mode)
+    pub replaced: usize,
+
+    /// Existing entries left untouched (merge mode)
+    pub skipped: usize,
+}
+
+/// Paths `record` never picks up on its own, from `.lore/.loreignore`.
+/// Patterns use gitignore syntax and are relative to the repository root.
+#[derive(Debug)]
+pub struct LoreIgnore(Gitignore);
+
+impl LoreIgnore {
+    /// Whether `path` (relative to the repository root) or one of its parent
+    /// directories is ignored
+    pub fn is_ignored(&self, path: &str) -> bool {
+        self.0
+            .matched_path_or_any_parents(normalize_path(path), false)
+            .is_ignore()
+    }
+}
+
+/// Result of repairing an incomplete store
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    /// Files/directories that were missing and have been recreated
+    pub created: Vec<&'static str>,
+
+    /// Outcome of rebuilding the index
+    pub reindex: ReindexReport,
+}
 
 /// Storage handler for Lore data
 pub struct LoreStorage {
@@ -34,9 +280,15 @@ pub struct LoreStorage {
 }
 
 impl LoreStorage {
-    /// Create a new storage handler at the given root path
-    pub fn new(root: PathBuf) -> Self {
-        Self { root }
+    /// Create a storage handler for the repository at `root` (the directory
+    /// containing `.lore`, which need not exist yet)
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The repository root this storage belongs to
+    pub fn root(&self) -> &Path {
+        &self.root
     }
 
     /// Get the .lore directory path
@@ -54,6 +306,61 @@ impl LoreStorage {
         self.lore_dir().join(INDEX_FILE)
     }
 
+    /// Get the search index file path
+    fn search_index_path(&self) -> PathBuf {
+        self.lore_dir().join(SEARCH_INDEX_FILE)
+    }
+
+    /// Get the audit log path
+    fn audit_log_path(&self) -> PathBuf {
+        self.lore_dir().join(AUDIT_LOG_FILE)
+    }
+
+    /// Get the .loreignore path
+    fn loreignore_path(&self) -> PathBuf {
+        self.lore_dir().join(LOREIGNORE_FILE)
+    }
+
+    fn template_path(&self, name: &str) -> Result<PathBuf, StorageError> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(StorageError::InvalidTemplateName(name.to_string()));
+        }
+        Ok(self
+            .lore_dir()
+            .join(TEMPLATES_DIR)
+            .join(format!("{}.md", name)))
+    }
+
+    /// Directory holding an entry's attachments
+    fn attachment_dir(&self, id: &str) -> PathBuf {
+        self.lore_dir().join(ATTACHMENTS_DIR).join(id)
+    }
+
+    fn attachment_path(&self, id: &str, filename: &str) -> Result<PathBuf, StorageError> {
+        let valid = !matches!(filename, "" | "." | "..")
+            && !filename.contains(['/', '\\'])
+            && !filename.contains('\0');
+        if !valid {
+            return Err(StorageError::InvalidAttachmentName(filename.to_string()));
+        }
+        Ok(self.attachment_dir(id).join(filename))
+    }
+
+    /// File a reasoning trace is edited in. It is kept when the editor fails,
+    /// so typed text survives until the next `record`.
+    pub fn trace_buffer_path(&self) -> PathBuf {
+        self.lore_dir().join(TRACE_BUFFER_FILE)
+    }
+
+    /// Get the pending draft file path
+    fn pending_path(&self) -> PathBuf {
+        self.lore_dir().join(PENDING_FILE)
+    }
+
     /// Check if Lore is initialized
     pub fn is_initialized(&self) -> bool {
         self.lore_dir().exists()
@@ -68,25 +375,67 @@ impl LoreStorage {
         // Create directory structure
         fs::create_dir_all(self.entries_dir())?;
 
-        // Create empty index
+        // Create empty indexes
         let index = LoreIndex::new();
         self.save_index(&index)?;
+        self.save_search_index(&SearchIndex::default())?;
 
-        // Create config
-        let config = serde_json::json!({
-            "version": "0.1.0",
-            "default_agent_id": agent_id.unwrap_or("unknown"),
-            "created_at": chrono::Utc::now().to_rfc3339(),
-        });
-        let config_path = self.lore_dir().join(CONFIG_FILE);
-        let mut file = fs::File::create(config_path)?;
-        file.write_all(serde_json::to_string_pretty(&config)?.as_bytes())?;
+        self.write_default_config(agent_id)?;
+        self.write_gitignore()?;
+
+        Ok(())
+    }
+
+    /// Repair an existing but incomplete store: create a missing entries
+    /// directory, config and `.gitignore`, then rebuild the index from whatever
+    /// entries exist. Existing data is never removed.
+    pub fn repair(&self, agent_id: Option<&str>) -> Result<RepairReport, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let mut report = RepairReport::default();
+
+        if !self.entries_dir().exists() {
+            fs::create_dir_all(self.entries_dir())?;
+            report.created.push(ENTRIES_DIR);
+        }
+
+        if !self.lore_dir().join(CONFIG_FILE).exists() {
+            self.write_default_config(agent_id)?;
+            report.created.push(CONFIG_FILE);
+        }
+
+        if !self.lore_dir().join(".gitignore").exists() {
+            self.write_gitignore()?;
+            report.created.push(".gitignore");
+        }
+
+        if !self.index_path().exists() {
+            report.created.push(INDEX_FILE);
+        }
+        report.reindex = self.rebuild_index()?;
+
+        Ok(report)
+    }
+
+    fn write_default_config(&self, agent_id: Option<&str>) -> Result<(), StorageError> {
+        let config = LoreConfig {
+            default_agent_id: Some(agent_id.unwrap_or("unknown").to_string()),
+            created_at: Some(Utc::now()),
+            ..LoreConfig::default()
+        };
+        self.save_config(&config)
+    }
 
+    fn write_gitignore(&self) -> Result<(), StorageError> {
         // Create .gitignore to not ignore anything (we want .lore committed)
         // But we might want to ignore some temp files
         let gitignore_path = self.lore_dir().join(".gitignore");
-        fs::write(gitignore_path, "*.tmp\n*.lock\n")?;
-
+        fs::write(
+            gitignore_path,
+            "*.tmp\n*.lock\npending.json\nindex.partial.json\nTRACE_EDITMSG.md\n",
+        )?;
         Ok(())
     }
 
@@ -110,10 +459,40 @@ impl LoreStorage {
     pub fn save_index(&self, index: &LoreIndex) -> Result<(), StorageError> {
         let index_path = self.index_path();
         let content = serde_json::to_string_pretty(index)?;
-        fs::write(index_path, content)?;
+        write_atomic(&index_path, content.as_bytes())?;
         Ok(())
     }
 
+    /// Load the search index, or `None` if it is missing or unreadable (it can
+    /// always be rebuilt with `reindex`)
+    fn load_search_index(&self) -> Option<SearchIndex> {
+        let content = fs::read_to_string(self.search_index_path()).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Save the search index
+    fn save_search_index(&self, search: &SearchIndex) -> Result<(), StorageError> {
+        let content = serde_json::to_string(search)?;
+        write_atomic(&self.search_index_path(), content.as_bytes())?;
+        Ok(())
+    }
+
+    /// IDs of the entries that can match `query`, according to the search
+    /// index. `None` if the index is missing or out of date, or the query can't
+    /// be narrowed down (e.g. `NOT x`), in which case every entry has to be checked.
+    pub fn search_candidates(
+        &self,
+        query: &Query,
+    ) -> Result<Option<HashSet<String>>, StorageError> {
+        let Some(search) = self.load_search_index() else {
+            return Ok(None);
+        };
+        if search.entry_count != self.load_index()?.entry_count {
+            return Ok(None);
+        }
+        Ok(candidates(&search, query))
+    }
+
     /// Save a thought object
     pub fn save_entry(&self, entry: &ThoughtObject) -> Result<(), StorageError> {
         if !self.is_initialized() {
@@ -121,438 +500,3256 @@ impl LoreStorage {
         }
 
         // Save the entry
-        let entry_path = self.entries_dir().join(format!("{}.json", entry.id));
-        let content = serde_json::to_string_pretty(entry)?;
-        fs::write(entry_path, content)?;
+        self.write_entry_file(entry)?;
 
         // Update index
+        let _lock = self.lock_index()?;
         let mut index = self.load_index()?;
-        index.add_entry(&entry.target_file, &entry.id);
+
+        // A search index that is already behind stays behind until `reindex`
+        let search = self
+            .load_search_index()
+            .filter(|search| search.entry_count == index.entry_count);
+
+        index.insert(entry);
         self.save_index(&index)?;
+        self.append_audit(&[AuditEvent::new(AuditAction::Record, entry)])?;
+
+        if let Some(mut search) = search {
+            search.add(&entry.id, search_words(entry));
+            search.entry_count = index.entry_count;
+            self.save_search_index(&search)?;
+        }
 
         Ok(())
     }
 
-    /// Load an entry by ID
-    pub fn load_entry(&self, id: &str) -> Result<ThoughtObject, StorageError> {
-        if !self.is_initialized() {
-            return Err(StorageError::NotInitialized);
+    /// Rewrite an existing entry in place, e.g. after `record --amend`. Keeps
+    /// its ID and the entry count, and refreshes its metadata in the index and
+    /// its words in the search index.
+    pub fn update_entry(&self, entry: &ThoughtObject) -> Result<(), StorageError> {
+        if !self.entry_exists(&entry.id) {
+            return Err(StorageError::EntryNotFound(entry.id.clone()));
         }
 
-        let entry_path = self.entries_dir().join(format!("{}.json", id));
-        if !entry_path.exists() {
-            return Err(StorageError::FileNotFound(id.to_string()));
+        let _lock = self.lock_index()?;
+        let mut index = self.load_index()?;
+        let search = self
+            .load_search_index()
+            .filter(|search| search.entry_count == index.entry_count);
+
+        self.write_entry_file(entry)?;
+        index
+            .entries
+            .insert(entry.id.clone(), EntryMeta::from(entry));
+        self.save_index(&index)?;
+        self.append_audit(&[AuditEvent::new(AuditAction::Edit, entry)])?;
+
+        if let Some(mut search) = search {
+            search.remove(&HashSet::from([entry.id.clone()]));
+            search.add(&entry.id, search_words(entry));
+            self.save_search_index(&search)?;
         }
 
-        let content = fs::read_to_string(entry_path)?;
-        let entry: ThoughtObject = serde_json::from_str(&content)?;
-        Ok(entry)
+        Ok(())
     }
 
-    /// Get all entries for a file
-    pub fn get_entries_for_file(
+    /// How a new entry repeats the newest one already recorded for its file
+    pub fn repetition_of_latest(
         &self,
-        file_path: &str,
-    ) -> Result<Vec<ThoughtObject>, StorageError> {
-        let index = self.load_index()?;
+        entry: &ThoughtObject,
+    ) -> Result<Option<Repetition>, StorageError> {
+        let latest = self
+            .get_entry_metas_for_file(&entry.target_file)?
+            .first()
+            .and_then(|meta| self.load_entry(&meta.id).ok());
+        Ok(latest.and_then(|latest| repetition(&latest, entry)))
+    }
 
-        // Normalize the file path
-        let normalized = normalize_path(file_path);
+    /// Take an exclusive advisory lock on `.lore/index.lock`, blocking until any
+    /// other process holding it is done. Held for a load-modify-save of the
+    /// index so concurrent writers don't lose each other's changes. Released
+    /// when the returned file is dropped. Not reentrant: don't call it while
+    /// already holding the lock.
+    fn lock_index(&self) -> Result<fs::File, StorageError> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.lore_dir().join(INDEX_LOCK_FILE))?;
+        file.lock()?;
+        Ok(file)
+    }
 
-        let mut entries: Vec<ThoughtObject> = index
-            .get_entries_for_file(&normalized)
-            .map(|ids| {
-                ids.iter()
-                    .filter_map(|id| self.load_entry(id).ok())
-                    .collect()
-            })
-            .unwrap_or_default();
+    /// Where an entry file belongs: in a subdirectory named after the first two
+    /// characters of its ID, like git's object store, so no directory grows
+    /// too large
+    fn sharded_entry_path(&self, id: &str) -> PathBuf {
+        let shard = id.get(..2).unwrap_or(id);
+        self.entries_dir().join(shard).join(format!("{}.json", id))
+    }
 
-        // Sort by timestamp, newest first
-        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-        Ok(entries)
+    /// Where entry files were kept before sharding, directly in `entries/`
+    fn flat_entry_path(&self, id: &str) -> PathBuf {
+        self.entries_dir().join(format!("{}.json", id))
     }
 
-    /// Get all entries
-    pub fn get_all_entries(&self) -> Result<Vec<ThoughtObject>, StorageError> {
-        if !self.is_initialized() {
-            return Err(StorageError::NotInitialized);
+    /// The file holding an entry: its shard, or the flat layout for entries
+    /// recorded before sharding and not yet migrated
+    fn entry_path(&self, id: &str) -> PathBuf {
+        let sharded = self.sharded_entry_path(id);
+        if sharded.exists() {
+            return sharded;
         }
+        let flat = self.flat_entry_path(id);
+        if flat.exists() {
+            flat
+        } else {
+            sharded
+        }
+    }
 
-        let entries_dir = self.entries_dir();
-        let mut entries = Vec::new();
-
-        for entry in fs::read_dir(entries_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.extension().is_some_and(|ext| ext == "json") {
-                let content = fs::read_to_string(&path)?;
-                if let Ok(thought) = serde_json::from_str::<ThoughtObject>(&content) {
-                    entries.push(thought);
+    /// Every `.json` file in the entries directory, in either layout
+    fn entry_files(&self) -> Result<Vec<PathBuf>, StorageError> {
+        let is_json = |path: &Path| path.extension().is_some_and(|ext| ext == "json");
+        let mut files = Vec::new();
+        for dir_entry in fs::read_dir(self.entries_dir())? {
+            let dir_entry = dir_entry?;
+            let path = dir_entry.path();
+            if dir_entry.file_type()?.is_dir() {
+                for shard_entry in fs::read_dir(&path)? {
+                    let path = shard_entry?.path();
+                    if is_json(&path) {
+                        files.push(path);
+                    }
                 }
+            } else if is_json(&path) {
+                files.push(path);
             }
         }
+        Ok(files)
+    }
 
-        // Sort by timestamp, newest first
-        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-        Ok(entries)
+    /// Write an entry file without touching the index. An entry still in the
+    /// flat layout moves to its shard.
+    fn write_entry_file(&self, entry: &ThoughtObject) -> Result<(), StorageError> {
+        let entry_path = self.sharded_entry_path(&entry.id);
+        if let Some(shard) = entry_path.parent() {
+            fs::create_dir_all(shard)?;
+        }
+        let content = serde_json::to_string_pretty(entry)?;
+        write_atomic(&entry_path, content.as_bytes())?;
+        match fs::remove_file(self.flat_entry_path(&entry.id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
     }
 
-    /// Search entries by query (searches intent and reasoning_trace)
-    pub fn search(&self, query: &str) -> Result<Vec<ThoughtObject>, StorageError> {
-        let all_entries = self.get_all_entries()?;
-        let query_lower = query.to_lowercase();
+    /// Check whether an entry file exists for the given ID
+    pub fn entry_exists(&self, id: &str) -> bool {
+        self.entry_path(id).exists()
+    }
 
-        let matches: Vec<ThoughtObject> = all_entries
-            .into_iter()
-            .filter(|entry| {
-                entry.intent.to_lowercase().contains(&query_lower)
-                    || entry.reasoning_trace.to_lowercase().contains(&query_lower)
-                    || entry
-                        .rejected_alternatives
-                        .iter()
-                        .any(|alt| alt.name.to_lowercase().contains(&query_lower))
-                    || entry
-                        .tags
-                        .iter()
-                        .any(|tag| tag.to_lowercase().contains(&query_lower))
-            })
-            .collect();
+    /// Move entry files from the flat layout into their shards. An entry that
+    /// already has a file in its shard is left alone and reported.
+    pub fn migrate_entries(&self) -> Result<MigrateReport, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
 
-        Ok(matches)
+        let _lock = self.lock_index()?;
+        let mut report = MigrateReport::default();
+        let mut flat = Vec::new();
+        for dir_entry in fs::read_dir(self.entries_dir())? {
+            let path = dir_entry?.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                flat.push(path);
+            }
+        }
+        flat.sort();
+
+        for path in flat {
+            let id = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let target = self.sharded_entry_path(&id);
+            if target.exists() {
+                report.conflicts.push(id);
+                continue;
+            }
+            if let Some(shard) = target.parent() {
+                fs::create_dir_all(shard)?;
+            }
+            fs::rename(&path, &target)?;
+            report.moved += 1;
+        }
+        Ok(report)
     }
 
-    /// Get the default agent ID from config
-    pub fn get_default_agent_id(&self) -> Result<String, StorageError> {
-        let config_path = self.lore_dir().join(CONFIG_FILE);
-        if !config_path.exists() {
-            return Ok("unknown".to_string());
+    /// Resolve a full entry ID or a unique prefix of one (e.g. the 8-character
+    /// short ID shown in output) to the full ID
+    pub fn resolve_entry_id(&self, id: &str) -> Result<String, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+        if !id.is_empty() && self.entry_exists(id) {
+            return Ok(id.to_string());
         }
 
-        let content = fs::read_to_string(config_path)?;
-        let config: serde_json::Value = serde_json::from_str(&content)?;
+        let mut matches = Vec::new();
+        if !id.is_empty() {
+            for path in self.entry_files()? {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    if stem.starts_with(id) {
+                        matches.push(stem.to_string());
+                    }
+                }
+            }
+        }
 
-        Ok(config
-            .get("default_agent_id")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown")
-            .to_string())
+        match matches.len() {
+            0 => Err(StorageError::EntryNotFound(id.to_string())),
+            1 => Ok(matches.remove(0)),
+            _ => Err(StorageError::AmbiguousEntryId(id.to_string())),
+        }
     }
-}
 
-/// Hash a file's contents using SHA256
-pub fn hash_file(path: &Path) -> Result<String, StorageError> {
-    if !path.exists() {
-        return Err(StorageError::FileNotFound(
-            path.to_string_lossy().to_string(),
-        ));
+    /// IDs of every entry that some other entry supersedes
+    pub fn get_superseded_ids(&self) -> Result<HashSet<String>, StorageError> {
+        Ok(self
+            .get_entry_metas()?
+            .into_iter()
+            .filter_map(|m| m.supersedes)
+            .collect())
     }
 
-    let content = fs::read(path)?;
-    let mut hasher = Sha256::new();
-    hasher.update(&content);
-    let result = hasher.finalize();
-    Ok(hex::encode(result))
-}
-
-/// Hash a string using SHA256
-#[allow(dead_code)]
-pub fn hash_string(content: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
-    let result = hasher.finalize();
-    hex::encode(result)
-}
+    /// IDs of the entries that replace each superseded entry. A `record` for
+    /// several files writes one entry per file, so there can be more than one.
+    pub fn superseded_by(&self) -> Result<HashMap<String, Vec<String>>, StorageError> {
+        Ok(supersessions(&self.get_entry_metas()?))
+    }
 
-/// Normalize a file path (remove leading ./, convert to forward slashes)
-pub fn normalize_path(path: &str) -> String {
-    let path = path.trim_start_matches("./");
-    path.replace('\\', "/")
-}
+    /// The decision lineage ending at `id`: the entry itself, then the entry it
+    /// supersedes, and so on back to the original. Stops at an entry that no
+    /// longer exists or one already in the chain.
+    pub fn supersession_chain(&self, id: &str) -> Result<Vec<ThoughtObject>, StorageError> {
+        let mut chain = vec![self.load_entry(id)?];
+        let mut seen = HashSet::from([id.to_string()]);
 
-/// Find the lore root by searching upward from the current directory
-pub fn find_lore_root(start: &Path) -> Option<PathBuf> {
-    let mut current = start.to_path_buf();
+        while let Some(previous) = chain.last().and_then(|e| e.supersedes.clone()) {
+            if !seen.insert(previous.clone()) {
+                break;
+            }
+            match self.load_entry(&previous) {
+                Ok(entry) => chain.push(entry),
+                Err(StorageError::FileNotFound(_)) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(chain)
+    }
 
-    loop {
-        let lore_dir = current.join(LORE_DIR);
-        if lore_dir.exists() {
-            return Some(current);
+    /// Load an entry by ID
+    pub fn load_entry(&self, id: &str) -> Result<ThoughtObject, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
         }
 
-        if !current.pop() {
-            return None;
+        let entry_path = self.entry_path(id);
+        if !entry_path.exists() {
+            return Err(StorageError::FileNotFound(id.to_string()));
         }
+
+        let content = fs::read_to_string(entry_path)?;
+        parse_entry(&content)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+    /// Upgrade an entry's JSON from the schema version it was written with to
+    /// the current `ThoughtObject`. Every load goes through this, so old entry
+    /// files keep working and are written in the current layout the next time
+    /// they are saved. Entries from a newer lore are refused rather than read
+    /// with fields missing.
+    pub fn migrate(mut value: serde_json::Value) -> Result<ThoughtObject, StorageError> {
+        let mut version = value
+            .get("schema_version")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+        if version > ENTRY_SCHEMA_VERSION as u64 {
+            let id = value.get("id").and_then(|id| id.as_str()).unwrap_or("?");
+            return Err(StorageError::UnsupportedSchemaVersion(
+                id.to_string(),
+                version,
+            ));
+        }
 
-    fn create_test_storage() -> (TempDir, LoreStorage) {
-        let temp_dir = TempDir::new().unwrap();
-        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
-        (temp_dir, storage)
+        while version < ENTRY_SCHEMA_VERSION as u64 {
+            value = upgrade_entry(version, value)?;
+            version += 1;
+        }
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("schema_version".to_string(), version.into());
+        }
+        Ok(serde_json::from_value(value)?)
     }
 
-    #[test]
-    fn test_storage_not_initialized() {
-        let (_temp_dir, storage) = create_test_storage();
-        assert!(!storage.is_initialized());
+    /// Whether the filesystem holding the store ignores case (the default on
+    /// macOS and Windows), so `Src/Main.rs` and `src/main.rs` are one file
+    pub fn is_case_insensitive(&self) -> bool {
+        self.root.join(LORE_DIR.to_uppercase()).exists()
     }
 
-    #[test]
-    fn test_storage_init() {
-        let (_temp_dir, storage) = create_test_storage();
+    /// `path` relative to the store root, as entries record it. Relative paths
+    /// are taken from the working directory when it is inside the root, and
+    /// from the root otherwise, with `.` and `..` resolved; absolute ones must
+    /// be inside the root (following symlinks for files that exist).
+    pub fn relative_path(&self, path: &str) -> Result<String, StorageError> {
+        let cwd = std::env::current_dir().ok();
+        self.relative_path_from(path, cwd.as_deref())
+    }
 
-        storage.init(Some("test-agent")).unwrap();
+    fn relative_path_from(&self, path: &str, cwd: Option<&Path>) -> Result<String, StorageError> {
+        let outside = || StorageError::OutsideRoot(path.to_string(), self.root.clone());
+        let normalized = normalize_path(path);
+        let given = Path::new(&normalized);
+        let given = if given.is_absolute() {
+            given.to_path_buf()
+        } else {
+            match cwd.filter(|cwd| self.contains_dir(cwd)) {
+                Some(cwd) => cwd.join(given),
+                None => return clean_relative(given).ok_or_else(outside),
+            }
+        };
+        let given = given.as_path();
 
-        assert!(storage.is_initialized());
-        assert!(storage.lore_dir().exists());
-        assert!(storage.entries_dir().exists());
-        assert!(storage.index_path().exists());
+        if let Some(relative) = clean_absolute(given)
+            .and_then(|clean| clean_relative(clean.strip_prefix(&self.root).ok()?))
+        {
+            return Ok(relative);
+        }
+        // e.g. /tmp/repo given for a root found as /private/tmp/repo
+        let (Ok(canonical), Ok(root)) = (given.canonicalize(), self.root.canonicalize()) else {
+            return Err(outside());
+        };
+        canonical
+            .strip_prefix(root)
+            .ok()
+            .and_then(clean_relative)
+            .ok_or_else(outside)
     }
 
-    #[test]
-    fn test_storage_init_with_agent_id() {
-        let (_temp_dir, storage) = create_test_storage();
+    /// Whether `dir` is the root or somewhere below it
+    fn contains_dir(&self, dir: &Path) -> bool {
+        if dir.starts_with(&self.root) {
+            return true;
+        }
+        match (dir.canonicalize(), self.root.canonicalize()) {
+            (Ok(dir), Ok(root)) => dir.starts_with(root),
+            _ => false,
+        }
+    }
+
+    /// The indexed path `file_path` refers to, ignoring case when `ignore_case`
+    /// is set or the filesystem is case-insensitive. `file_path` is taken from
+    /// the root, as `relative_path` returns it, and comes back that way when
+    /// it isn't indexed.
+    pub fn resolve_file_path(
+        &self,
+        file_path: &str,
+        ignore_case: bool,
+    ) -> Result<String, StorageError> {
+        let normalized = self.relative_path_from(file_path, None)?;
+        let index = self.load_index()?;
+        let ignore_case = ignore_case || self.is_case_insensitive();
+        Ok(index
+            .find_file(&normalized, ignore_case)
+            .map_or(normalized.clone(), str::to_string))
+    }
+
+    /// Get all entries for a file. On a case-insensitive filesystem, the
+    /// path's case doesn't have to match the one recorded.
+    pub fn get_entries_for_file(
+        &self,
+        file_path: &str,
+    ) -> Result<Vec<ThoughtObject>, StorageError> {
+        let metas = self.get_entry_metas_for_file(file_path)?;
+        Ok(self.load_entries(&metas))
+    }
+
+    /// Index metadata for a file's entries, newest first, without reading any
+    /// entry file. Case is folded as in `get_entries_for_file`.
+    pub fn get_entry_metas_for_file(
+        &self,
+        file_path: &str,
+    ) -> Result<Vec<EntryMeta>, StorageError> {
+        let index = self.load_index_with_metadata()?;
+        let normalized = normalize_path(file_path);
+
+        let mut metas: Vec<EntryMeta> = index
+            .find_file(&normalized, self.is_case_insensitive())
+            .and_then(|path| index.get_entries_for_file(path))
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| index.entries.get(id).cloned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        metas.sort_by_key(|m| std::cmp::Reverse(m.timestamp));
+        Ok(metas)
+    }
+
+    /// Indexed files under a directory, sorted. `./src/auth`, `src/auth/` and
+    /// `src/auth` are the same directory; `.` (or nothing) is the whole repository.
+    pub fn files_under(&self, dir: &str) -> Result<Vec<String>, StorageError> {
+        let dir = normalize_path(dir);
+        let dir = dir.trim_end_matches('/');
+        let whole_repo = dir.is_empty() || dir == ".";
+
+        Ok(self
+            .load_index()?
+            .files
+            .into_keys()
+            .filter(|file| {
+                whole_repo
+                    || file
+                        .strip_prefix(dir)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .collect())
+    }
+
+    /// Where the lore of a file with no entries went, if its entries were moved
+    /// away by a rename: the current path of the newest entry that used to be
+    /// recorded against `file_path`
+    pub fn moved_to(&self, file_path: &str) -> Result<Option<String>, StorageError> {
+        let normalized = normalize_path(file_path);
+        let index = self.load_index()?;
+        if index.files.contains_key(&normalized) {
+            return Ok(None);
+        }
+
+        Ok(index
+            .entries
+            .into_values()
+            .filter(|meta| meta.previous_paths.contains(&normalized))
+            .max_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)))
+            .map(|meta| meta.target_file))
+    }
+
+    /// Get entries for a file split into (line-ranged, whole-file), newest first
+    pub fn get_partitioned_entries_for_file(
+        &self,
+        file_path: &str,
+    ) -> Result<(Vec<ThoughtObject>, Vec<ThoughtObject>), StorageError> {
+        Ok(self
+            .get_entries_for_file(file_path)?
+            .into_iter()
+            .partition(|e| e.line_range.is_some()))
+    }
+
+    /// The index, rebuilt first if it was written before its metadata (or
+    /// some field of it) was stored
+    fn load_index_with_metadata(&self) -> Result<LoreIndex, StorageError> {
+        let index = self.load_index()?;
+        if index.has_metadata() {
+            return Ok(index);
+        }
+        self.rebuild_index()?;
+        self.load_index()
+    }
+
+    /// Index metadata for every entry, newest first. Indexes written before
+    /// metadata was stored are rebuilt once to backfill it.
+    pub fn get_entry_metas(&self) -> Result<Vec<EntryMeta>, StorageError> {
+        let index = self.load_index_with_metadata()?;
+        let mut metas: Vec<EntryMeta> = index.entries.into_values().collect();
+        metas.sort_by_key(|m| std::cmp::Reverse(m.timestamp));
+        Ok(metas)
+    }
+
+    /// Load the full entries for `metas`, in the same order. Entries whose
+    /// files are missing or unreadable are skipped.
+    pub fn load_entries(&self, metas: &[EntryMeta]) -> Vec<ThoughtObject> {
+        self.stream_entries(metas).collect()
+    }
+
+    /// Like [`load_entries`](Self::load_entries), but each file is only read
+    /// when the iterator reaches it, so callers that stop early skip the rest
+    pub fn stream_entries<'a>(
+        &'a self,
+        metas: &'a [EntryMeta],
+    ) -> impl Iterator<Item = ThoughtObject> + 'a {
+        metas
+            .iter()
+            .filter_map(|meta| self.load_entry(&meta.id).ok())
+    }
+
+    /// Every indexed entry, newest first. The order comes from the index, and
+    /// entry files are read one at a time as the iterator advances, so memory
+    /// stays flat however large the store is.
+    pub fn iter_entries(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<ThoughtObject, StorageError>> + '_, StorageError> {
+        let metas = self.get_entry_metas()?;
+        Ok(metas.into_iter().map(|meta| self.load_entry(&meta.id)))
+    }
+
+    /// Get all entries
+    pub fn get_all_entries(&self) -> Result<Vec<ThoughtObject>, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let mut entries = Vec::new();
+        for path in self.entry_files()? {
+            let content = fs::read_to_string(&path)?;
+            if let Ok(thought) = parse_entry(&content) {
+                entries.push(thought);
+            }
+        }
+
+        // Sort by timestamp, newest first
+        entries.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+        Ok(entries)
+    }
+
+    /// Count the entry files on disk (regardless of whether they parse)
+    pub fn count_entry_files(&self) -> Result<usize, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        Ok(self.entry_files()?.len())
+    }
+
+    /// Rebuild the index from scratch by scanning the entries directory.
+    /// Entry files that fail to parse are skipped and reported.
+    pub fn rebuild_index(&self) -> Result<ReindexReport, StorageError> {
+        self.rebuild_index_with(&ReindexOptions::default())
+    }
+
+    /// Rebuild the index, reading entry files in batches (in parallel if asked)
+    /// and keeping only their metadata in memory. After each batch the progress
+    /// so far is saved to `index.partial.json`, which `resume` picks up after an
+    /// interruption. The result doesn't depend on the order files are read in.
+    /// The search index is rebuilt along with it.
+    pub fn rebuild_index_with(
+        &self,
+        options: &ReindexOptions,
+    ) -> Result<ReindexReport, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        // Scan under the lock so an entry saved meanwhile isn't dropped
+        let _lock = self.lock_index()?;
+        let partial_path = self.lore_dir().join(PARTIAL_INDEX_FILE);
+
+        let mut partial = PartialRebuild::default();
+        if options.resume {
+            match fs::read_to_string(&partial_path) {
+                Ok(content) => partial = serde_json::from_str(&content)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            // Left by an older version, with metadata missing a field
+            if partial.index.version < INDEX_VERSION {
+                partial = PartialRebuild::default();
+            }
+        }
+
+        let mut paths = Vec::new();
+        let mut on_disk = HashSet::new();
+        for path in self.entry_files()? {
+            let id = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !partial.index.entries.contains_key(&id) {
+                paths.push(path);
+            }
+            on_disk.insert(id);
+        }
+        paths.sort();
+
+        // Entries deleted since the interrupted run
+        partial.index.entries.retain(|id, _| on_disk.contains(id));
+        for ids in partial.search.words.values_mut() {
+            ids.retain(|id| on_disk.contains(id));
+        }
+        partial.search.words.retain(|_, ids| !ids.is_empty());
+
+        let mut report = ReindexReport {
+            resumed: partial.index.entries.len(),
+            ..ReindexReport::default()
+        };
+        let total = paths.len() + report.resumed;
+
+        let batches: Vec<&[PathBuf]> = paths.chunks(REINDEX_BATCH).collect();
+        for (i, batch) in batches.iter().enumerate() {
+            let parsed: Vec<Result<(EntryMeta, BTreeSet<String>), String>> = if options.parallel {
+                batch.par_iter().map(|path| read_entry_meta(path)).collect()
+            } else {
+                batch.iter().map(|path| read_entry_meta(path)).collect()
+            };
+
+            for result in parsed {
+                match result {
+                    Ok((meta, words)) => {
+                        partial.search.add(&meta.id, words);
+                        partial.index.insert_meta(meta);
+                    }
+                    Err(name) => report.failed.push(name),
+                }
+            }
+
+            if i + 1 < batches.len() {
+                write_atomic(&partial_path, serde_json::to_string(&partial)?.as_bytes())?;
+            }
+            if let Some(progress) = options.progress {
+                progress(partial.index.entries.len() + report.failed.len(), total);
+            }
+        }
+
+        // Oldest first, matching the order entries are appended on record
+        let mut metas: Vec<EntryMeta> = partial.index.entries.into_values().collect();
+        metas.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+
+        report.indexed = metas.len();
+        let mut index = LoreIndex::new();
+        for meta in metas {
+            index.insert_meta(meta);
+        }
+        self.save_index(&index)?;
+
+        let mut search = partial.search;
+        search.entry_count = index.entry_count;
+        self.save_search_index(&search)?;
+
+        match fs::remove_file(&partial_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        report.failed.sort();
+        Ok(report)
+    }
+
+    /// Check that the index and the entry files agree. Reads `index.json`
+    /// directly, so a corrupt index is reported rather than failing. With
+    /// `check_targets`, also reports entries whose file no longer exists.
+    pub fn fsck(&self, check_targets: bool) -> Result<FsckReport, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let mut report = FsckReport::default();
+
+        let index = match fs::read_to_string(self.index_path()) {
+            Ok(content) => match serde_json::from_str::<LoreIndex>(&content) {
+                Ok(index) => index,
+                Err(e) => {
+                    report.unreadable_index = Some(e.to_string());
+                    LoreIndex::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                report.unreadable_index = Some("index.json is missing".to_string());
+                LoreIndex::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // Valid entry files by the ID they claim
+        let mut entries: BTreeMap<String, ThoughtObject> = BTreeMap::new();
+        let mut duplicates = BTreeSet::new();
+        let mut paths = self.entry_files()?;
+        paths.sort();
+        for path in paths {
+            let parsed = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| parse_entry(&content).ok());
+            match parsed {
+                Some(entry) => {
+                    if entries.contains_key(&entry.id) {
+                        duplicates.insert(entry.id.clone());
+                    } else {
+                        entries.insert(entry.id.clone(), entry);
+                    }
+                }
+                None => report.unparseable.push(
+                    path.file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                ),
+            }
+        }
+
+        let mut indexed: HashSet<(&str, &str)> = HashSet::new();
+        let mut seen_ids = HashSet::new();
+        for (file, ids) in &index.files {
+            for id in ids {
+                if !seen_ids.insert(id.as_str()) {
+                    duplicates.insert(id.clone());
+                }
+                indexed.insert((file.as_str(), id.as_str()));
+                if !self.entry_exists(id) {
+                    report.dangling.push(EntryRef {
+                        id: id.clone(),
+                        target_file: file.clone(),
+                    });
+                }
+            }
+        }
+
+        for entry in entries.values() {
+            if !indexed.contains(&(entry.target_file.as_str(), entry.id.as_str())) {
+                report.unindexed.push(EntryRef {
+                    id: entry.id.clone(),
+                    target_file: entry.target_file.clone(),
+                });
+            }
+            if check_targets && !self.root.join(&entry.target_file).exists() {
+                report.missing_targets.push(EntryRef {
+                    id: entry.id.clone(),
+                    target_file: entry.target_file.clone(),
+                });
+            }
+        }
+
+        if report.unreadable_index.is_none() && index.entry_count != entries.len() {
+            report.entry_count = Some(CountMismatch {
+                recorded: index.entry_count,
+                actual: entries.len(),
+            });
+        }
+        report.duplicates = duplicates.into_iter().collect();
+
+        Ok(report)
+    }
+
+    /// Bundle the config and the given entries into an export document
+    pub fn export(&self, entries: Vec<ThoughtObject>) -> Result<LoreExport, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let mut index = LoreIndex::new();
+        for entry in &entries {
+            index.insert(entry);
+        }
+
+        Ok(LoreExport {
+            version: EXPORT_VERSION,
+            exported_at: chrono::Utc::now(),
+            config: serde_json::to_value(self.load_config()?)?,
+            index,
+            entries,
+        })
+    }
+
+    /// Import entries from an export document, then rebuild the index.
+    /// Existing entries are replaced if `overwrite` is set, skipped otherwise.
+    pub fn import(
+        &self,
+        export: &LoreExport,
+        overwrite: bool,
+    ) -> Result<ImportReport, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let mut report = ImportReport::default();
+        let mut events = Vec::new();
+        for entry in &export.entries {
+            if self.entry_exists(&entry.id) {
+                if !overwrite {
+                    report.skipped += 1;
+                    continue;
+                }
+                report.replaced += 1;
+            } else {
+                report.imported += 1;
+            }
+            self.write_entry_file(entry)?;
+            events.push(AuditEvent::new(AuditAction::Import, entry));
+        }
+        self.append_audit(&events)?;
+
+        self.rebuild_index()?;
+        Ok(report)
+    }
+
+    /// Check that `path` can take a `prune --archive` bundle: it must not exist
+    /// yet, and must not be inside the entries directory
+    pub fn check_archive_path(&self, path: &Path) -> Result<(), StorageError> {
+        if path.exists() {
+            return Err(StorageError::ArchiveExists(path.to_path_buf()));
+        }
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => std::env::current_dir()?,
+        };
+        let inside = match (parent.canonicalize(), self.entries_dir().canonicalize()) {
+            (Ok(parent), Ok(entries)) => parent.starts_with(entries),
+            (Err(e), _) => return Err(e.into()),
+            (_, Err(_)) => false,
+        };
+        if inside {
+            return Err(StorageError::ArchiveInsideEntries(path.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    /// Delete entries, updating the index and search index in the same
+    /// operation. With `archive`, the entries are first written there as an
+    /// export bundle that `lore import` can restore. Returns the number of
+    /// entries removed.
+    pub fn prune(
+        &self,
+        entries: &[ThoughtObject],
+        archive: Option<&Path>,
+    ) -> Result<usize, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+        if let Some(path) = archive {
+            self.check_archive_path(path)?;
+            let bundle = self.export(entries.to_vec())?;
+            write_atomic(path, serde_json::to_string_pretty(&bundle)?.as_bytes())?;
+        }
+
+        let _lock = self.lock_index()?;
+        let mut index = self.load_index()?;
+        let search = self
+            .load_search_index()
+            .filter(|search| search.entry_count == index.entry_count);
+
+        let mut events = Vec::new();
+        let mut removed = HashSet::new();
+        for entry in entries {
+            match fs::remove_file(self.entry_path(&entry.id)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            self.remove_attachments(&entry.id)?;
+            index.remove(&entry.id);
+            removed.insert(entry.id.clone());
+            events.push(AuditEvent::new(AuditAction::Prune, entry));
+        }
+        self.save_index(&index)?;
+        self.append_audit(&events)?;
+
+        if let Some(mut search) = search {
+            search.remove(&removed);
+            search.entry_count = index.entry_count;
+            self.save_search_index(&search)?;
+        }
+
+        Ok(removed.len())
+    }
+
+    /// Compare this store with `base`: entries only here are added, entries only
+    /// in `base` removed, and entries in both whose content hash differs modified.
+    /// Each list is sorted oldest first.
+    pub fn diff_against(&self, base: &LoreStorage) -> Result<StoreDiff, StorageError> {
+        let ours = self.entry_hashes()?;
+        let mut theirs = base.entry_hashes()?;
+
+        let mut diff = StoreDiff::default();
+        for (id, (hash, meta)) in ours {
+            match theirs.remove(&id) {
+                None => diff.added.push(meta),
+                Some((base_hash, _)) if base_hash != hash => diff.modified.push(meta),
+                Some(_) => {}
+            }
+        }
+        diff.removed = theirs.into_values().map(|(_, meta)| meta).collect();
+
+        for metas in [&mut diff.added, &mut diff.removed, &mut diff.modified] {
+            metas.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+        }
+        Ok(diff)
+    }
+
+    /// Content hash and metadata of every entry, by ID. Hashes the parsed entry
+    /// so formatting differences between entry files don't count as changes.
+    fn entry_hashes(&self) -> Result<HashMap<String, (String, EntryMeta)>, StorageError> {
+        self.get_all_entries()?
+            .into_iter()
+            .map(|entry| {
+                let hash = hash_string(&serde_json::to_string(&entry)?);
+                Ok((entry.id.clone(), (hash, EntryMeta::from(&entry))))
+            })
+            .collect()
+    }
+
+    /// Rename an agent across all entries, returning the number of entries rewritten
+    pub fn rename_agent(&self, old: &str, new: &str) -> Result<usize, StorageError> {
+        validate_agent_id(new)?;
+
+        let mut events = Vec::new();
+        for mut entry in self.get_all_entries()? {
+            if entry.agent_id == old {
+                entry.agent_id = new.to_string();
+                self.write_entry_file(&entry)?;
+                events.push(AuditEvent::new(AuditAction::Edit, &entry));
+            }
+        }
+        self.append_audit(&events)?;
+        let renamed = events.len();
+
+        if renamed > 0 {
+            let _lock = self.lock_index()?;
+            let mut index = self.load_index()?;
+            for meta in index.entries.values_mut() {
+                if meta.agent_id == old {
+                    meta.agent_id = new.to_string();
+                }
+            }
+            self.save_index(&index)?;
+        }
+
+        Ok(renamed)
+    }
+
+    /// Move every entry recorded against `old` to `new`, e.g. after the file was
+    /// renamed. Rewrites each entry's `target_file` and merges them with any
+    /// entries `new` already has. Returns the number of entries moved.
+    pub fn rename_file_entries(&self, old: &str, new: &str) -> Result<usize, StorageError> {
+        let old = normalize_path(old);
+        let new = normalize_path(new);
+        if old == new {
+            return Ok(0);
+        }
+
+        let _lock = self.lock_index()?;
+        let mut index = self.load_index()?;
+        let Some(ids) = index.files.remove(&old) else {
+            return Ok(0);
+        };
+
+        let mut events = Vec::new();
+        for id in &ids {
+            if let Ok(mut entry) = self.load_entry(id) {
+                entry.previous_paths.push(old.clone());
+                entry.target_file = new.clone();
+                self.write_entry_file(&entry)?;
+                events.push(AuditEvent::new(AuditAction::Edit, &entry));
+            }
+            if let Some(meta) = index.entries.get_mut(id) {
+                meta.previous_paths.push(old.clone());
+                meta.target_file = new.clone();
+            }
+        }
+
+        let moved = ids.len();
+        let merged = index.files.entry(new).or_default();
+        merged.extend(ids);
+        merged.sort_by_key(|id| {
+            index
+                .entries
+                .get(id)
+                .map(|meta| (meta.timestamp, id.clone()))
+        });
+        self.save_index(&index)?;
+        self.append_audit(&events)?;
+
+        Ok(moved)
+    }
+
+    /// Append events to `.lore/audit.log`, one JSON object per line. The log is
+    /// only ever appended to, never rewritten.
+    fn append_audit(&self, events: &[AuditEvent]) -> Result<(), StorageError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for event in events {
+            lines.push_str(&serde_json::to_string(event)?);
+            lines.push('\n');
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.audit_log_path())?;
+        file.write_all(lines.as_bytes())?;
+        Ok(())
+    }
+
+    /// Every event in the audit log, oldest first. Lines that don't parse are skipped.
+    pub fn load_audit_log(&self) -> Result<Vec<AuditEvent>, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let content = match fs::read_to_string(self.audit_log_path()) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Load `.lore/.loreignore`. A missing file ignores nothing.
+    pub fn loreignore(&self) -> Result<LoreIgnore, StorageError> {
+        let mut builder = GitignoreBuilder::new(&self.root);
+        if self.loreignore_path().exists() {
+            if let Some(e) = builder.add(self.loreignore_path()) {
+                return Err(e.into());
+            }
+        }
+        Ok(LoreIgnore(builder.build()?))
+    }
+
+    /// Whether `.lore/.loreignore` excludes `path` from auto-detected recording.
+    /// An unreadable or invalid `.loreignore` ignores nothing.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        self.loreignore()
+            .map(|ignore| ignore.is_ignored(path))
+            .unwrap_or(false)
+    }
+
+    /// Write an entry's attachments to `.lore/attachments/<id>/`. Call before
+    /// saving the entry, so a saved entry never lists a missing file.
+    pub fn save_attachments(
+        &self,
+        id: &str,
+        files: &[(Attachment, Vec<u8>)],
+    ) -> Result<(), StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(self.attachment_dir(id))?;
+        for (attachment, content) in files {
+            write_atomic(&self.attachment_path(id, &attachment.filename)?, content)?;
+        }
+        Ok(())
+    }
+
+    /// Contents of the attachment called `filename` on `entry`
+    pub fn load_attachment(
+        &self,
+        entry: &ThoughtObject,
+        filename: &str,
+    ) -> Result<Vec<u8>, StorageError> {
+        if !entry.attachments.iter().any(|a| a.filename == filename) {
+            return Err(StorageError::AttachmentNotFound(
+                entry.id.clone(),
+                filename.to_string(),
+            ));
+        }
+        let path = self.attachment_path(&entry.id, filename)?;
+        match fs::read(&path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(StorageError::FileNotFound(path.display().to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Delete an entry's attachment directory (no-op if it has none)
+    fn remove_attachments(&self, id: &str) -> Result<(), StorageError> {
+        match fs::remove_dir_all(self.attachment_dir(id)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save a reasoning template as `.lore/templates/<name>.md`
+    pub fn save_template(
+        &self,
+        name: &str,
+        content: &str,
+        overwrite: bool,
+    ) -> Result<(), StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let path = self.template_path(name)?;
+        if path.exists() && !overwrite {
+            return Err(StorageError::TemplateExists(name.to_string()));
+        }
+        fs::create_dir_all(self.lore_dir().join(TEMPLATES_DIR))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Content of the template called `name`
+    pub fn load_template(&self, name: &str) -> Result<String, StorageError> {
+        match fs::read_to_string(self.template_path(name)?) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(StorageError::TemplateNotFound(name.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Names of the saved templates, sorted
+    pub fn list_templates(&self) -> Result<Vec<String>, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let dir = match fs::read_dir(self.lore_dir().join(TEMPLATES_DIR)) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut names = Vec::new();
+        for entry in dir {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "md") {
+                if let Some(name) = path.file_stem() {
+                    names.push(name.to_string_lossy().into_owned());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Load the in-progress draft, if any
+    pub fn load_pending(&self) -> Result<Option<PendingDraft>, StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let pending_path = self.pending_path();
+        if !pending_path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(pending_path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// Save the in-progress draft
+    pub fn save_pending(&self, draft: &PendingDraft) -> Result<(), StorageError> {
+        if !self.is_initialized() {
+            return Err(StorageError::NotInitialized);
+        }
+
+        let content = serde_json::to_string_pretty(draft)?;
+        fs::write(self.pending_path(), content)?;
+        Ok(())
+    }
+
+    /// Remove the in-progress draft (no-op if there is none)
+    pub fn clear_pending(&self) -> Result<(), StorageError> {
+        let pending_path = self.pending_path();
+        if pending_path.exists() {
+            fs::remove_file(pending_path)?;
+        }
+        Ok(())
+    }
+
+    /// Load the config file (defaults if missing)
+    pub fn load_config(&self) -> Result<LoreConfig, StorageError> {
+        let config_path = self.lore_dir().join(CONFIG_FILE);
+        if !config_path.exists() {
+            return Ok(LoreConfig::default());
+        }
+
+        let content = fs::read_to_string(config_path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Write the config file
+    pub fn save_config(&self, config: &LoreConfig) -> Result<(), StorageError> {
+        let config_path = self.lore_dir().join(CONFIG_FILE);
+        let mut file = fs::File::create(config_path)?;
+        file.write_all(serde_json::to_string_pretty(config)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Whether agent IDs should be normalized at record time
+    pub fn normalize_agent_ids(&self) -> Result<bool, StorageError> {
+        Ok(self.load_config()?.normalize_agent_ids)
+    }
+}
+
+/// How `entry` repeats `latest`, if the file hash is the same
+pub fn repetition(latest: &ThoughtObject, entry: &ThoughtObject) -> Option<Repetition> {
+    if latest.file_hash != entry.file_hash {
+        return None;
+    }
+    if latest.intent == entry.intent && latest.reasoning_trace == entry.reasoning_trace {
+        Some(Repetition::Duplicate(latest.id.clone()))
+    } else {
+        Some(Repetition::UnchangedFile(latest.id.clone()))
+    }
+}
+
+/// For each entry superseded by one of `entries`, the IDs of those replacing
+/// it, oldest first
+pub fn supersessions<E: EntryFields>(entries: &[E]) -> HashMap<String, Vec<String>> {
+    let mut newer: Vec<&E> = entries
+        .iter()
+        .filter(|e| e.supersedes().is_some())
+        .collect();
+    newer.sort_by_key(|e| e.timestamp());
+
+    let mut map: HashMap<String, Vec<String>> = HashMap::new();
+    for entry in newer {
+        if let Some(old) = entry.supersedes() {
+            map.entry(old.to_string())
+                .or_default()
+                .push(entry.id().to_string());
+        }
+    }
+    map
+}
+
+/// Aggregate entries per agent, most recently active first
+pub fn agent_stats(entries: &[EntryMeta]) -> Vec<AgentStats> {
+    let mut by_agent: HashMap<&str, Vec<&EntryMeta>> = HashMap::new();
+    for entry in entries {
+        by_agent.entry(&entry.agent_id).or_default().push(entry);
+    }
+
+    let mut agents: Vec<AgentStats> = by_agent
+        .into_iter()
+        .map(|(agent_id, entries)| {
+            let files: BTreeSet<&str> = entries.iter().map(|e| e.target_file.as_str()).collect();
+            let tags: BTreeSet<&str> = entries
+                .iter()
+                .flat_map(|e| e.tags.iter().map(String::as_str))
+                .collect();
+
+            AgentStats {
+                agent_id: agent_id.to_string(),
+                entry_count: entries.len(),
+                first_activity: entries.iter().map(|e| e.timestamp).min().unwrap(),
+                last_activity: entries.iter().map(|e| e.timestamp).max().unwrap(),
+                file_count: files.len(),
+                files: files.into_iter().map(String::from).collect(),
+                tags: tags.into_iter().map(String::from).collect(),
+            }
+        })
+        .collect();
+
+    agents.sort_by(|a, b| {
+        b.last_activity
+            .cmp(&a.last_activity)
+            .then_with(|| a.agent_id.cmp(&b.agent_id))
+    });
+    agents
+}
+
+/// Hash a file's contents using SHA256
+pub fn hash_file(path: &Path) -> Result<String, StorageError> {
+    if !path.exists() {
+        return Err(StorageError::FileNotFound(
+            path.to_string_lossy().to_string(),
+        ));
+    }
+
+    let content = fs::read(path)?;
+    Ok(hash_bytes(&content))
+}
+
+/// Hash a string using SHA256
+pub fn hash_string(content: &str) -> String {
+    hash_bytes(content.as_bytes())
+}
+
+/// Calculate SHA256 hash of raw content, e.g. a blob read from git
+pub fn hash_bytes(content: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content);
+    hex::encode(hasher.finalize())
+}
+
+/// Parse an entry file, upgrading it to the current schema
+fn parse_entry(content: &str) -> Result<ThoughtObject, StorageError> {
+    LoreStorage::migrate(serde_json::from_str(content)?)
+}
+
+/// Turn an entry of schema `version` into one of `version + 1`. A layout
+/// change bumps `ENTRY_SCHEMA_VERSION` and adds an arm here.
+fn upgrade_entry(
+    version: u64,
+    value: serde_json::Value,
+) -> Result<serde_json::Value, StorageError> {
+    match version {
+        // Entries written before schema_version existed have the v1 layout
+        0 => Ok(value),
+        _ => {
+            let id = value.get("id").and_then(|id| id.as_str()).unwrap_or("?");
+            Err(StorageError::UnsupportedSchemaVersion(
+                id.to_string(),
+                version,
+            ))
+        }
+    }
+}
+
+/// Read an entry file's index metadata and search words, or its file name if
+/// it can't be parsed
+fn read_entry_meta(path: &Path) -> Result<(EntryMeta, BTreeSet<String>), String> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| parse_entry(&content).ok())
+        .map(|entry| (EntryMeta::from(&entry), search_words(&entry)))
+        .ok_or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+}
+
+/// Merge two versions of `index.json` or `search_index.json` that diverged
+/// from `base`, writing the result over `ours`, for the `lore merge-index` git
+/// merge driver. Which index it is comes from the contents. An empty file
+/// (e.g. `base` when both sides added the index) counts as an empty index.
+pub fn merge_index_files(ours: &Path, theirs: &Path, base: &Path) -> Result<(), StorageError> {
+    fn read(path: &Path) -> Result<Option<serde_json::Value>, StorageError> {
+        let content = fs::read_to_string(path)?;
+        if content.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    fn parse<T: serde::de::DeserializeOwned + Default>(
+        value: Option<serde_json::Value>,
+    ) -> Result<T, StorageError> {
+        Ok(value
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    let (our_value, their_value, base_value) = (read(ours)?, read(theirs)?, read(base)?);
+    let is_search = [&our_value, &their_value]
+        .into_iter()
+        .flatten()
+        .any(|value| value.get("words").is_some());
+
+    let content = if is_search {
+        let merged = SearchIndex::merge(
+            &parse(base_value)?,
+            &parse(our_value)?,
+            &parse(their_value)?,
+        );
+        serde_json::to_string(&merged)?
+    } else {
+        let merged = LoreIndex::merge(
+            &parse(base_value)?,
+            &parse(our_value)?,
+            &parse(their_value)?,
+        );
+        serde_json::to_string_pretty(&merged)?
+    };
+    write_atomic(ours, content.as_bytes())?;
+    Ok(())
+}
+
+/// Write `contents` to a temp file next to `path` and rename it into place, so a
+/// crash or a concurrent writer never leaves `path` half-written. The `.tmp`
+/// suffix is covered by `.lore/.gitignore`.
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+/// A relative path with `.` and `..` resolved, joined with `/`. `None` if it
+/// climbs above its starting point.
+fn clean_relative(path: &Path) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// An absolute path with `.` and `..` resolved without touching the filesystem
+fn clean_absolute(path: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                if !clean.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            other => clean.push(other),
+        }
+    }
+    Some(clean)
+}
+
+/// Normalize a file path (remove leading ./, convert to forward slashes)
+pub fn normalize_path(path: &str) -> String {
+    let path = path.trim_start_matches("./");
+    path.replace('\\', "/")
+}
+
+/// A compiled `lore search` query, tested against the intent, reasoning, tags
+/// and rejected alternatives (names and reasons) of an entry
+pub enum EntryMatcher {
+    /// Boolean expression whose terms (case-insensitive substrings, or whole
+    /// words when a regex is given) are matched against all fields combined
+    Expression {
+        query: Query,
+        word_res: HashMap<String, Regex>,
+    },
+    Regex(Regex),
+}
+
+/// Relevance weight of a match in each field: tags and references count most,
+/// then the intent, then the reasoning trace and rejected alternatives
+const TAG_WEIGHT: usize = 5;
+const INTENT_WEIGHT: usize = 3;
+const TRACE_WEIGHT: usize = 1;
+
+impl EntryMatcher {
+    pub fn expression(query: Query, whole_word: bool) -> Self {
+        let word_res = if whole_word {
+            query
+                .terms()
+                .into_iter()
+                .map(|term| (term.to_string(), word_regex(term)))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        EntryMatcher::Expression { query, word_res }
+    }
+
+    pub fn matches(&self, entry: &ThoughtObject) -> bool {
+        match self {
+            EntryMatcher::Expression { query, word_res } => {
+                let text = searchable_text(entry);
+                let text_lower = text.to_lowercase();
+                query.matches(&|term: &str| match word_res.get(term) {
+                    Some(re) => re.is_match(&text),
+                    None => text_lower.contains(&term.to_lowercase()),
+                })
+            }
+            EntryMatcher::Regex(re) => any_field(entry, |text| re.is_match(text)),
+        }
+    }
+
+    /// Relevance of a matching entry: occurrences of each (non-negated) term,
+    /// weighted by the field they occur in
+    pub fn score(&self, entry: &ThoughtObject) -> usize {
+        match self {
+            EntryMatcher::Expression { query, word_res } => {
+                let terms: Vec<(&str, String)> = query
+                    .positive_terms()
+                    .into_iter()
+                    .map(|term| (term, term.to_lowercase()))
+                    .collect();
+                weighted_count(entry, |text| {
+                    let text_lower = text.to_lowercase();
+                    terms
+                        .iter()
+                        .map(|(term, term_lower)| match word_res.get(*term) {
+                            Some(re) => re.find_iter(text).count(),
+                            None => text_lower.matches(term_lower.as_str()).count(),
+                        })
+                        .sum()
+                })
+            }
+            EntryMatcher::Regex(re) => weighted_count(entry, |text| re.find_iter(text).count()),
+        }
+    }
+}
+
+/// Sum of `count` over the searchable fields of the entry, weighted per field
+fn weighted_count(entry: &ThoughtObject, count: impl Fn(&str) -> usize) -> usize {
+    let tags: usize = entry.tags.iter().map(|tag| count(tag)).sum();
+    let references: usize = entry.references.iter().map(|r| count(r)).sum();
+    let alternatives: usize = entry
+        .rejected_alternatives
+        .iter()
+        .map(|alt| count(&alt.name) + alt.reason.as_deref().map_or(0, &count))
+        .sum();
+
+    (tags + references) * TAG_WEIGHT
+        + count(&entry.intent) * INTENT_WEIGHT
+        + (count(&entry.reasoning_trace) + alternatives) * TRACE_WEIGHT
+}
+
+/// Whether any searchable field of the entry satisfies `matches`
+fn any_field(entry: &ThoughtObject, matches: impl Fn(&str) -> bool) -> bool {
+    matches(&entry.intent)
+        || matches(&entry.reasoning_trace)
+        || entry
+            .rejected_alternatives
+            .iter()
+            .any(|alt| matches(&alt.name) || alt.reason.as_deref().is_some_and(&matches))
+        || entry.tags.iter().any(|tag| matches(tag))
+        || entry.references.iter().any(|r| matches(r))
+}
+
+/// Everything `search` looks at in an entry, joined into one string
+fn searchable_text(entry: &ThoughtObject) -> String {
+    let mut parts = vec![entry.intent.as_str(), entry.reasoning_trace.as_str()];
+    parts.extend(entry.tags.iter().map(String::as_str));
+    parts.extend(entry.references.iter().map(String::as_str));
+    for alt in &entry.rejected_alternatives {
+        parts.push(&alt.name);
+        parts.extend(alt.reason.as_deref());
+    }
+    parts.join("\n")
+}
+
+/// Runs of letters and digits in `text`
+fn split_words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+/// The lowercase words of everything `search` looks at, for the search index
+fn search_words(entry: &ThoughtObject) -> BTreeSet<String> {
+    split_words(&searchable_text(entry).to_lowercase())
+        .map(String::from)
+        .collect()
+}
+
+/// Entries in the search index that can satisfy `query`, or `None` for any
+/// entry. A term's letters and digits can only occur within indexed words, so
+/// an entry matching the term has, for each run of them, a word containing it.
+/// This over-approximates; the entries still have to be checked.
+fn candidates(search: &SearchIndex, query: &Query) -> Option<HashSet<String>> {
+    match query {
+        Query::Term(term) => split_words(&term.to_lowercase())
+            .map(|fragment| search.containing(fragment))
+            .reduce(|a, b| a.intersection(&b).cloned().collect()),
+        Query::And(a, b) => match (candidates(search, a), candidates(search, b)) {
+            (Some(a), Some(b)) => Some(a.intersection(&b).cloned().collect()),
+            (a, None) | (None, a) => a,
+        },
+        Query::Or(a, b) => {
+            let mut ids = candidates(search, a)?;
+            ids.extend(candidates(search, b)?);
+            Some(ids)
+        }
+        Query::Not(_) => None,
+    }
+}
+
+/// Case-insensitive regex matching `term` only as a whole word
+pub fn word_regex(term: &str) -> Regex {
+    Regex::new(&format!(r"(?i)\b{}\b", regex::escape(term))).expect("escaped term is a valid regex")
+}
+
+/// Parse an export document, rejecting versions this build doesn't understand
+pub fn parse_export(content: &str) -> Result<LoreExport, StorageError> {
+    let mut value: serde_json::Value = serde_json::from_str(content)?;
+    let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+    if version != EXPORT_VERSION as u64 {
+        return Err(StorageError::UnsupportedExportVersion(version));
+    }
+
+    // Entries keep the schema of the lore that exported them
+    if let Some(entries) = value.get_mut("entries").and_then(|e| e.as_array_mut()) {
+        for entry in entries {
+            *entry = serde_json::to_value(LoreStorage::migrate(entry.take())?)?;
+        }
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Normalize an agent ID (trim, lowercase, collapse internal whitespace)
+pub fn normalize_agent_id(agent_id: &str) -> String {
+    agent_id
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Reject agent IDs that are empty or whitespace-only
+pub fn validate_agent_id(agent_id: &str) -> Result<(), StorageError> {
+    if agent_id.trim().is_empty() {
+        return Err(StorageError::InvalidAgentId(
+            "agent ID cannot be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Files whose directory is treated as a sub-project root
+const PROJECT_MANIFESTS: &[&str] = &["Cargo.toml", "package.json"];
+
+/// The sub-project `file` (relative to `root`) belongs to: the directory of the
+/// nearest `Cargo.toml` or `package.json` above it, relative to `root`. Files
+/// that only have a manifest at the root itself aren't in a sub-project.
+pub fn infer_project(root: &Path, file: &str) -> Option<String> {
+    let mut dir = Path::new(file).parent()?;
+
+    while !dir.as_os_str().is_empty() {
+        if PROJECT_MANIFESTS
+            .iter()
+            .any(|manifest| root.join(dir).join(manifest).is_file())
+        {
+            return Some(normalize_path(&dir.to_string_lossy()));
+        }
+        dir = dir.parent()?;
+    }
+
+    None
+}
+
+/// Find the lore root by searching upward from the current directory
+pub fn find_lore_root(start: &Path) -> Option<PathBuf> {
+    let mut current = start.to_path_buf();
+
+    loop {
+        let lore_dir = current.join(LORE_DIR);
+        if lore_dir.exists() {
+            return Some(current);
+        }
+
+        if !current.pop() {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    /// Matcher for a plain query, as `lore search` builds it
+    fn text(query: &str, whole_word: bool) -> EntryMatcher {
+        EntryMatcher::expression(Query::parse(query, false).unwrap(), whole_word)
+    }
+
+    /// Run a matcher over every stored entry, like `lore search` without filters
+    fn search_all(storage: &LoreStorage, matcher: EntryMatcher) -> Vec<ThoughtObject> {
+        storage
+            .get_all_entries()
+            .unwrap()
+            .into_iter()
+            .filter(|e| matcher.matches(e))
+            .collect()
+    }
+
+    fn create_test_entry(file: &str) -> ThoughtObject {
+        ThoughtObject::new(
+            file.to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+    }
+
+    fn create_test_storage() -> (TempDir, LoreStorage) {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LoreStorage::new(temp_dir.path().to_path_buf());
+        (temp_dir, storage)
+    }
+
+    #[test]
+    fn test_storage_not_initialized() {
+        let (_temp_dir, storage) = create_test_storage();
+        assert!(!storage.is_initialized());
+    }
+
+    #[test]
+    fn test_storage_init() {
+        let (_temp_dir, storage) = create_test_storage();
+
+        storage.init(Some("test-agent")).unwrap();
+
+        assert!(storage.is_initialized());
+        assert!(storage.lore_dir().exists());
+        assert!(storage.entries_dir().exists());
+        assert!(storage.index_path().exists());
+    }
+
+    #[test]
+    fn test_storage_init_with_agent_id() {
+        let (_temp_dir, storage) = create_test_storage();
+
+        storage.init(Some("my-agent")).unwrap();
+
+        let config = storage.load_config().unwrap();
+        assert_eq!(config.default_agent_id.as_deref(), Some("my-agent"));
+    }
+
+    #[test]
+    fn test_storage_init_without_agent_id() {
+        let (_temp_dir, storage) = create_test_storage();
+
+        storage.init(None).unwrap();
+
+        let config = storage.load_config().unwrap();
+        assert_eq!(config.default_agent_id.as_deref(), Some("unknown"));
+    }
+
+    #[test]
+    fn test_save_config_round_trip() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(Some("my-agent")).unwrap();
+
+        let mut config = storage.load_config().unwrap();
+        assert!(config.created_at.is_some());
+        config.set("default_tags", "auth,security").unwrap();
+        storage.save_config(&config).unwrap();
+
+        let config = storage.load_config().unwrap();
+        assert_eq!(config.default_tags, vec!["auth", "security"]);
+        assert_eq!(config.default_agent_id.as_deref(), Some("my-agent"));
+    }
+
+    #[test]
+    fn test_entry_metas_come_from_the_index() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_tags(vec!["auth".to_string()])
+        .with_project("web".to_string());
+        storage.save_entry(&entry).unwrap();
+
+        // No entry file is read to produce the metadata
+        fs::remove_file(storage.entry_path(&entry.id)).unwrap();
+
+        let metas = storage.get_entry_metas().unwrap();
+        assert_eq!(metas.len(), 1);
+        assert_eq!(metas[0].intent, "Intent");
+        assert_eq!(metas[0].tags, vec!["auth"]);
+        assert_eq!(metas[0].project.as_deref(), Some("web"));
+        assert!(storage.load_entries(&metas).is_empty());
+    }
+
+    #[test]
+    fn test_iter_entries_reads_lazily_newest_first() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let mut ids = Vec::new();
+        for (i, hours) in [3, 1, 2].into_iter().enumerate() {
+            let mut entry = ThoughtObject::new(
+                format!("file{}.rs", i),
+                "hash".to_string(),
+                "agent".to_string(),
+                "Intent".to_string(),
+                "Trace".to_string(),
+            );
+            entry.timestamp = Utc::now() - chrono::Duration::hours(hours);
+            storage.save_entry(&entry).unwrap();
+            ids.push(entry.id);
+        }
+
+        let mut entries = storage.iter_entries().unwrap();
+        assert_eq!(entries.next().unwrap().unwrap().id, ids[1]);
+
+        // Files are read as the iterator reaches them, not up front
+        fs::remove_file(storage.entry_path(&ids[2])).unwrap();
+        assert!(matches!(
+            entries.next(),
+            Some(Err(StorageError::FileNotFound(_)))
+        ));
+        assert_eq!(entries.next().unwrap().unwrap().id, ids[0]);
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn test_entry_metas_backfill_old_index() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+
+        // An index written before metadata was stored
+        let old_index = format!(
+            r#"{{"files": {{"a.rs": ["{}"]}}, "entry_count": 1}}"#,
+            entry.id
+        );
+        fs::write(temp_dir.path().join(LORE_DIR).join(INDEX_FILE), old_index).unwrap();
+        assert!(!storage.load_index().unwrap().has_metadata());
+
+        let metas = storage.get_entry_metas().unwrap();
+        assert_eq!(metas.len(), 1);
+        assert_eq!(metas[0].id, entry.id);
+        assert!(storage.load_index().unwrap().has_metadata());
+    }
+
+    #[test]
+    fn test_storage_init_already_initialized() {
+        let (_temp_dir, storage) = create_test_storage();
+
+        storage.init(None).unwrap();
+        let result = storage.init(None);
+
+        assert!(matches!(result, Err(StorageError::AlreadyInitialized)));
+    }
+
+    #[test]
+    fn test_resolve_entry_id_by_prefix() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+
+        assert_eq!(storage.resolve_entry_id(&entry.id).unwrap(), entry.id);
+        assert_eq!(storage.resolve_entry_id(&entry.id[..8]).unwrap(), entry.id);
+        assert!(matches!(
+            storage.resolve_entry_id("does-not-exist"),
+            Err(StorageError::EntryNotFound(_))
+        ));
+        assert!(matches!(
+            storage.resolve_entry_id(""),
+            Err(StorageError::EntryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_superseded_ids() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let old = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Old".to_string(),
+            "Reasoning".to_string(),
+        );
+        let new = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "New".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_supersedes(old.id.clone());
+        storage.save_entry(&old).unwrap();
+        storage.save_entry(&new).unwrap();
+
+        let superseded = storage.get_superseded_ids().unwrap();
+        assert_eq!(superseded.len(), 1);
+        assert!(superseded.contains(&old.id));
+
+        let by = storage.superseded_by().unwrap();
+        assert_eq!(by.get(&old.id), Some(&vec![new.id.clone()]));
+        assert!(!by.contains_key(&new.id));
+
+        // Worked out from the index alone
+        fs::remove_file(storage.entry_path(&old.id)).unwrap();
+        fs::remove_file(storage.entry_path(&new.id)).unwrap();
+        assert!(storage.get_superseded_ids().unwrap().contains(&old.id));
+        assert_eq!(
+            storage.superseded_by().unwrap().get(&old.id),
+            Some(&vec![new.id.clone()])
+        );
+    }
+
+    #[test]
+    fn test_older_index_version_is_rebuilt_with_supersedes() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let old = create_test_entry("a.rs");
+        let new = create_test_entry("a.rs").with_supersedes(old.id.clone());
+        storage.save_entry(&old).unwrap();
+        storage.save_entry(&new).unwrap();
+
+        // Metadata written before `supersedes` was indexed
+        let index_path = temp_dir.path().join(LORE_DIR).join(INDEX_FILE);
+        let mut index: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&index_path).unwrap()).unwrap();
+        index.as_object_mut().unwrap().remove("version");
+        for meta in index["entries"].as_object_mut().unwrap().values_mut() {
+            meta.as_object_mut().unwrap().remove("supersedes");
+        }
+        fs::write(&index_path, index.to_string()).unwrap();
+        assert!(!storage.load_index().unwrap().has_metadata());
+
+        let metas = storage.get_entry_metas_for_file("a.rs").unwrap();
+        assert_eq!(metas[0].id, new.id);
+        assert_eq!(metas[0].supersedes.as_deref(), Some(old.id.as_str()));
+        assert!(storage.load_index().unwrap().has_metadata());
+    }
+
+    #[test]
+    fn test_supersession_chain_walks_back_to_original() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = |intent: &str| {
+            crate::models::ThoughtObject::new(
+                "test.rs".to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                intent.to_string(),
+                "Reasoning".to_string(),
+            )
+        };
+        let first = entry("Sessions");
+        let second = entry("JWT").with_supersedes(first.id.clone());
+        let third = entry("Back to sessions").with_supersedes(second.id.clone());
+        let dangling = entry("Orphan").with_supersedes("deleted-entry".to_string());
+        for e in [&first, &second, &third, &dangling] {
+            storage.save_entry(e).unwrap();
+        }
+
+        let intents = |id: &str| -> Vec<String> {
+            storage
+                .supersession_chain(id)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.intent)
+                .collect()
+        };
+        assert_eq!(
+            intents(&third.id),
+            vec!["Back to sessions", "JWT", "Sessions"]
+        );
+        assert_eq!(intents(&first.id), vec!["Sessions"]);
+        assert_eq!(intents(&dangling.id), vec!["Orphan"]);
+    }
+
+    #[test]
+    fn test_repair_store_missing_index() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+        std::fs::remove_file(storage.index_path()).unwrap();
+
+        let report = storage.repair(None).unwrap();
+
+        assert_eq!(report.created, vec![INDEX_FILE]);
+        assert_eq!(report.reindex.indexed, 1);
+        assert_eq!(storage.load_index().unwrap().entry_count, 1);
+        assert!(storage.load_entry(&entry.id).is_ok());
+    }
+
+    #[test]
+    fn test_repair_store_missing_config() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(Some("original")).unwrap();
+        std::fs::remove_file(storage.lore_dir().join(CONFIG_FILE)).unwrap();
+        std::fs::remove_dir_all(storage.entries_dir()).unwrap();
+
+        let report = storage.repair(Some("repaired")).unwrap();
+
+        assert!(report.created.contains(&CONFIG_FILE));
+        assert!(report.created.contains(&ENTRIES_DIR));
+        assert_eq!(
+            storage.load_config().unwrap().default_agent_id.as_deref(),
+            Some("repaired")
+        );
+        assert!(storage.entries_dir().exists());
+    }
+
+    #[test]
+    fn test_repair_intact_store_keeps_config() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(Some("original")).unwrap();
+
+        let report = storage.repair(Some("other")).unwrap();
+
+        assert!(report.created.is_empty());
+        assert_eq!(
+            storage.load_config().unwrap().default_agent_id.as_deref(),
+            Some("original")
+        );
+    }
+
+    #[test]
+    fn test_repair_not_initialized() {
+        let (_temp_dir, storage) = create_test_storage();
+        assert!(matches!(
+            storage.repair(None),
+            Err(StorageError::NotInitialized)
+        ));
+    }
+
+    #[test]
+    fn test_load_index_not_initialized() {
+        let (_temp_dir, storage) = create_test_storage();
+
+        let result = storage.load_index();
+
+        assert!(matches!(result, Err(StorageError::NotInitialized)));
+    }
+
+    #[test]
+    fn test_load_index_empty() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let index = storage.load_index().unwrap();
+
+        assert_eq!(index.entry_count, 0);
+        assert!(index.files.is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_index() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let mut index = LoreIndex::new();
+        index.add_entry("test.rs", "entry-1");
+        storage.save_index(&index).unwrap();
+
+        let loaded = storage.load_index().unwrap();
+        assert_eq!(loaded.entry_count, 1);
+        assert_eq!(
+            loaded.get_entries_for_file("test.rs"),
+            Some(&vec!["entry-1".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_save_entry() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        // Create a test file
+        let test_file = temp_dir.path().join("test.rs");
+        std::fs::write(&test_file, "fn main() {}").unwrap();
+
+        let entry = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash123".to_string(),
+            "test-agent".to_string(),
+            "Test intent".to_string(),
+            "Test reasoning".to_string(),
+        );
+        let entry_id = entry.id.clone();
+
+        storage.save_entry(&entry).unwrap();
+
+        // Verify entry was saved in its shard
+        let entry_path = storage
+            .entries_dir()
+            .join(&entry_id[..2])
+            .join(format!("{}.json", entry_id));
+        assert!(entry_path.exists());
+
+        // Verify index was updated
+        let index = storage.load_index().unwrap();
+        assert_eq!(index.entry_count, 1);
+    }
+
+    #[test]
+    fn test_save_entry_not_initialized() {
+        let (_temp_dir, storage) = create_test_storage();
+
+        let entry = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash123".to_string(),
+            "test-agent".to_string(),
+            "Test".to_string(),
+            "Reasoning".to_string(),
+        );
+
+        let result = storage.save_entry(&entry);
+        assert!(matches!(result, Err(StorageError::NotInitialized)));
+    }
+
+    #[test]
+    fn test_load_entry() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash123".to_string(),
+            "test-agent".to_string(),
+            "Test intent".to_string(),
+            "Test reasoning".to_string(),
+        );
+        let entry_id = entry.id.clone();
+
+        storage.save_entry(&entry).unwrap();
+        let loaded = storage.load_entry(&entry_id).unwrap();
+
+        assert_eq!(loaded.id, entry_id);
+        assert_eq!(loaded.target_file, "test.rs");
+        assert_eq!(loaded.intent, "Test intent");
+    }
+
+    #[test]
+    fn test_load_entry_not_found() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let result = storage.load_entry("nonexistent-id");
 
-        storage.init(Some("my-agent")).unwrap();
+        assert!(matches!(result, Err(StorageError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_entries_are_migrated_to_the_current_schema_on_load() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let entry = create_test_entry("a.rs");
+        storage.save_entry(&entry).unwrap();
+
+        // An entry written before schema_version existed
+        let path = storage.entry_path(&entry.id);
+        let mut value: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        value.as_object_mut().unwrap().remove("schema_version");
+        fs::write(&path, value.to_string()).unwrap();
+
+        let loaded = storage.load_entry(&entry.id).unwrap();
+        assert_eq!(loaded.schema_version, ENTRY_SCHEMA_VERSION);
+        assert_eq!(loaded.intent, entry.intent);
+        assert_eq!(storage.get_all_entries().unwrap().len(), 1);
+
+        storage.update_entry(&loaded).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["schema_version"], ENTRY_SCHEMA_VERSION);
+
+        // One from a newer lore is refused rather than misread
+        value["schema_version"] = (ENTRY_SCHEMA_VERSION + 1).into();
+        fs::write(&path, value.to_string()).unwrap();
+        assert!(matches!(
+            storage.load_entry(&entry.id),
+            Err(StorageError::UnsupportedSchemaVersion(..))
+        ));
+    }
+
+    #[test]
+    fn test_upgrade_entry_without_a_step_is_an_error() {
+        let value = serde_json::json!({ "id": "abc" });
+        assert!(upgrade_entry(0, value.clone()).is_ok());
+        assert!(matches!(
+            upgrade_entry(ENTRY_SCHEMA_VERSION as u64, value),
+            Err(StorageError::UnsupportedSchemaVersion(id, _)) if id == "abc"
+        ));
+    }
+
+    #[test]
+    fn test_get_entries_for_file() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry1 = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash1".to_string(),
+            "agent".to_string(),
+            "Intent 1".to_string(),
+            "Reasoning 1".to_string(),
+        );
+        let entry2 = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash2".to_string(),
+            "agent".to_string(),
+            "Intent 2".to_string(),
+            "Reasoning 2".to_string(),
+        );
+
+        storage.save_entry(&entry1).unwrap();
+        storage.save_entry(&entry2).unwrap();
+
+        let entries = storage.get_entries_for_file("test.rs").unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_get_entries_for_file_normalized_path() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = crate::models::ThoughtObject::new(
+            "src/test.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+
+        // Query with ./ prefix should still find it
+        let entries = storage.get_entries_for_file("./src/test.rs").unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_relative_path_from_absolute_and_parent_segments() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let root = temp_dir.path();
+
+        let absolute = root.join("src").join("main.rs");
+        assert_eq!(
+            storage.relative_path(absolute.to_str().unwrap()).unwrap(),
+            "src/main.rs"
+        );
+        assert_eq!(
+            storage.relative_path("./src/../src/./main.rs").unwrap(),
+            "src/main.rs"
+        );
+        assert_eq!(
+            storage.relative_path("src\\main.rs").unwrap(),
+            "src/main.rs"
+        );
+
+        // Anything resolving outside the root is rejected
+        for outside in ["../main.rs", "src/../../main.rs"] {
+            assert!(matches!(
+                storage.relative_path(outside),
+                Err(StorageError::OutsideRoot(..))
+            ));
+        }
+        let elsewhere = root.parent().unwrap().join("other").join("main.rs");
+        assert!(matches!(
+            storage.relative_path(elsewhere.to_str().unwrap()),
+            Err(StorageError::OutsideRoot(..))
+        ));
+    }
+
+    #[test]
+    fn test_relative_path_from_a_subdirectory() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let src = temp_dir.path().join("src");
+
+        let from_src = |path| storage.relative_path_from(path, Some(&src));
+        assert_eq!(from_src("main.rs").unwrap(), "src/main.rs");
+        assert_eq!(from_src("../README.md").unwrap(), "README.md");
+        assert!(matches!(
+            from_src("../../main.rs"),
+            Err(StorageError::OutsideRoot(..))
+        ));
+
+        // Run from outside the root, relative paths are taken from the root
+        let elsewhere = temp_dir.path().parent().unwrap().to_path_buf();
+        assert_eq!(
+            storage
+                .relative_path_from("src/main.rs", Some(&elsewhere))
+                .unwrap(),
+            "src/main.rs"
+        );
+    }
+
+    #[test]
+    fn test_record_absolute_path_is_explained_by_relative_path() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let absolute = temp_dir.path().join("src/main.rs");
+        let recorded = storage
+            .resolve_file_path(absolute.to_str().unwrap(), false)
+            .unwrap();
+        storage.save_entry(&create_test_entry(&recorded)).unwrap();
+
+        assert_eq!(
+            storage.get_entries_for_file("src/main.rs").unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_resolve_file_path_ignoring_case() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = crate::models::ThoughtObject::new(
+            "Src/Main.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+
+        // Recorded as Src/Main.rs, explained as src/main.rs
+        let path = storage.resolve_file_path("./src/main.rs", true).unwrap();
+        assert_eq!(path, "Src/Main.rs");
+        assert_eq!(storage.get_entries_for_file(&path).unwrap().len(), 1);
+        assert_eq!(
+            storage.resolve_file_path("src/other.rs", true).unwrap(),
+            "src/other.rs"
+        );
+
+        // Without --ignore-case, only a case-insensitive filesystem folds case
+        let folded = storage.get_entries_for_file("src/main.rs").unwrap();
+        assert_eq!(folded.len(), usize::from(storage.is_case_insensitive()));
+    }
+
+    #[test]
+    fn test_get_entries_for_file_empty() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entries = storage.get_entries_for_file("nonexistent.rs").unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_get_partitioned_entries_for_file() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let ranged = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Ranged".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_line_range(1, 5);
+        let whole = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Whole".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&ranged).unwrap();
+        storage.save_entry(&whole).unwrap();
+
+        let (ranged, whole) = storage.get_partitioned_entries_for_file("test.rs").unwrap();
+        assert_eq!(ranged.len(), 1);
+        assert_eq!(ranged[0].intent, "Ranged");
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0].intent, "Whole");
+    }
+
+    #[test]
+    fn test_get_all_entries() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry1 = crate::models::ThoughtObject::new(
+            "file1.rs".to_string(),
+            "hash1".to_string(),
+            "agent".to_string(),
+            "Intent 1".to_string(),
+            "Reasoning 1".to_string(),
+        );
+        let entry2 = crate::models::ThoughtObject::new(
+            "file2.rs".to_string(),
+            "hash2".to_string(),
+            "agent".to_string(),
+            "Intent 2".to_string(),
+            "Reasoning 2".to_string(),
+        );
+
+        storage.save_entry(&entry1).unwrap();
+        storage.save_entry(&entry2).unwrap();
+
+        let all_entries = storage.get_all_entries().unwrap();
+        assert_eq!(all_entries.len(), 2);
+    }
+
+    #[test]
+    fn test_get_all_entries_empty() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entries = storage.get_all_entries().unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_rebuild_index_after_deletion() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        for file in ["a.rs", "a.rs", "b.rs"] {
+            let entry = crate::models::ThoughtObject::new(
+                file.to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                "Intent".to_string(),
+                "Reasoning".to_string(),
+            );
+            storage.save_entry(&entry).unwrap();
+        }
+
+        std::fs::remove_file(storage.index_path()).unwrap();
+        assert_eq!(storage.load_index().unwrap().entry_count, 0);
+
+        let report = storage.rebuild_index().unwrap();
+        assert_eq!(report.indexed, 3);
+        assert!(report.failed.is_empty());
+
+        let index = storage.load_index().unwrap();
+        assert_eq!(index.entry_count, 3);
+        assert_eq!(index.get_entries_for_file("a.rs").unwrap().len(), 2);
+        assert_eq!(index.get_entries_for_file("b.rs").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rebuild_index_recovers_corrupt_index_and_reports_bad_entries() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let entry = crate::models::ThoughtObject::new(
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+
+        std::fs::write(storage.index_path(), "<<<<<<< HEAD").unwrap();
+        std::fs::write(storage.entries_dir().join("broken.json"), "{ not json").unwrap();
+        assert!(matches!(storage.load_index(), Err(StorageError::Json(_))));
+
+        let report = storage.rebuild_index().unwrap();
+        assert_eq!(report.indexed, 1);
+        assert_eq!(report.failed, vec!["broken.json".to_string()]);
+        assert_eq!(storage.load_index().unwrap().entry_count, 1);
+        assert_eq!(storage.count_entry_files().unwrap(), 2);
+    }
+
+    /// Write `count` entry files directly, skipping the per-entry index update.
+    /// Timestamps repeat so ordering ties have to be broken by ID.
+    fn write_fixture(storage: &LoreStorage, count: usize) {
+        let base = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        for i in 0..count {
+            let mut entry = create_test_entry(&format!("src/file{}.rs", i % 37));
+            entry.timestamp = base + chrono::Duration::minutes((i % 101) as i64);
+            storage.write_entry_file(&entry).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parallel_rebuild_matches_serial() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        write_fixture(&storage, 2 * REINDEX_BATCH + 500);
+
+        let serial = storage.rebuild_index().unwrap();
+        let serial_index = fs::read_to_string(storage.index_path()).unwrap();
+        let serial_search = fs::read_to_string(storage.search_index_path()).unwrap();
+
+        let parallel = storage
+            .rebuild_index_with(&ReindexOptions {
+                parallel: true,
+                ..ReindexOptions::default()
+            })
+            .unwrap();
+        let parallel_index = fs::read_to_string(storage.index_path()).unwrap();
+
+        assert_eq!(serial.indexed, 2 * REINDEX_BATCH + 500);
+        assert_eq!(parallel.indexed, serial.indexed);
+        assert_eq!(parallel_index, serial_index);
+        assert_eq!(
+            fs::read_to_string(storage.search_index_path()).unwrap(),
+            serial_search
+        );
+        assert!(!storage.lore_dir().join(PARTIAL_INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn test_rebuild_resumes_from_partial_index() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        write_fixture(&storage, 50);
+
+        storage.rebuild_index().unwrap();
+        let full_index = fs::read_to_string(storage.index_path()).unwrap();
+        let full_search = fs::read_to_string(storage.search_index_path()).unwrap();
+
+        // An interrupted run got through some entries, one since deleted
+        let mut partial = PartialRebuild::default();
+        for meta in storage.load_index().unwrap().entries.into_values().take(20) {
+            let entry = storage.load_entry(&meta.id).unwrap();
+            partial.search.add(&entry.id, search_words(&entry));
+            partial.index.insert_meta(meta);
+        }
+        let mut deleted = create_test_entry("gone.rs");
+        deleted.intent = "Only in the deleted entry".to_string();
+        partial.search.add(&deleted.id, search_words(&deleted));
+        partial.index.insert(&deleted);
+        fs::write(
+            storage.lore_dir().join(PARTIAL_INDEX_FILE),
+            serde_json::to_string(&partial).unwrap(),
+        )
+        .unwrap();
+        fs::remove_file(storage.index_path()).unwrap();
+
+        let progress = std::cell::RefCell::new(Vec::new());
+        let record = |done, total| progress.borrow_mut().push((done, total));
+        let report = storage
+            .rebuild_index_with(&ReindexOptions {
+                resume: true,
+                progress: Some(&record),
+                ..ReindexOptions::default()
+            })
+            .unwrap();
+
+        assert_eq!(report.resumed, 20);
+        assert_eq!(report.indexed, 50);
+        assert_eq!(progress.into_inner(), vec![(50, 50)]);
+        assert_eq!(
+            fs::read_to_string(storage.index_path()).unwrap(),
+            full_index
+        );
+        assert_eq!(
+            fs::read_to_string(storage.search_index_path()).unwrap(),
+            full_search
+        );
+        assert!(!storage.lore_dir().join(PARTIAL_INDEX_FILE).exists());
+    }
+
+    /// Entries matching `matcher`, reading only the search index candidates
+    fn indexed_search(storage: &LoreStorage, matcher: &EntryMatcher) -> Vec<ThoughtObject> {
+        let EntryMatcher::Expression { query, .. } = matcher else {
+            unreachable!("only expressions use the search index");
+        };
+        let mut metas = storage.get_entry_metas().unwrap();
+        if let Some(ids) = storage.search_candidates(query).unwrap() {
+            metas.retain(|m| ids.contains(&m.id));
+        }
+        storage
+            .load_entries(&metas)
+            .into_iter()
+            .filter(|e| matcher.matches(e))
+            .collect()
+    }
+
+    #[test]
+    fn test_indexed_search_matches_full_scan() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let vocabulary = [
+            "auth",
+            "Authentication",
+            "token",
+            "JWT",
+            "refresh-token",
+            "cache",
+            "cookie",
+            "retry",
+            "backoff",
+            "user_id",
+            "Ünïcode",
+            "db2",
+            "->",
+            "v1.2",
+        ];
+        let mut seed: u64 = 42;
+        let mut words = |count: usize| -> String {
+            (0..count)
+                .map(|_| {
+                    seed = seed
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    vocabulary[(seed >> 33) as usize % vocabulary.len()]
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        for i in 0..300 {
+            let mut entry = create_test_entry(&format!("src/{}.rs", i % 10));
+            entry.intent = words(3);
+            entry.reasoning_trace = words(12);
+            entry.tags = vec![words(1).to_lowercase()];
+            if i % 4 == 0 {
+                entry
+                    .rejected_alternatives
+                    .push(crate::models::RejectedAlternative {
+                        name: words(1),
+                        reason: Some(words(4)),
+                    });
+            }
+            // Most through a rebuild, the rest added incrementally
+            if i < 250 {
+                storage.write_entry_file(&entry).unwrap();
+            } else {
+                if i == 250 {
+                    storage.rebuild_index().unwrap();
+                }
+                storage.save_entry(&entry).unwrap();
+            }
+        }
+
+        let queries = [
+            "auth",
+            "AUTHENTICATION",
+            "thent",
+            "token",
+            "\"refresh-token cache\"",
+            "\"en jw\"",
+            "jwt AND cookie",
+            "jwt OR db2",
+            "retry NOT backoff",
+            "NOT cache",
+            "(auth OR cookie) AND NOT user_id",
+            "ünï",
+            "->",
+            "v1.2",
+            "missing",
+        ];
+        let all_entries = storage.get_all_entries().unwrap();
+        for query in queries {
+            for (any, whole_word) in [(false, false), (true, false), (false, true)] {
+                let matcher =
+                    EntryMatcher::expression(Query::parse(query, any).unwrap(), whole_word);
+                let mut expected: Vec<String> = all_entries
+                    .iter()
+                    .filter(|e| matcher.matches(e))
+                    .map(|e| e.id.clone())
+                    .collect();
+                let mut found: Vec<String> = indexed_search(&storage, &matcher)
+                    .into_iter()
+                    .map(|e| e.id)
+                    .collect();
+                expected.sort();
+                found.sort();
+                assert_eq!(
+                    found, expected,
+                    "{:?} (any: {}, word: {})",
+                    query, any, whole_word
+                );
+            }
+        }
+
+        // The index narrows down selective queries
+        let candidates = storage
+            .search_candidates(&Query::parse("jwt AND cookie AND db2", false).unwrap())
+            .unwrap()
+            .unwrap();
+        assert!(candidates.len() < 300);
+    }
+
+    #[test]
+    fn test_search_index_out_of_date_falls_back_to_full_scan() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let query = Query::parse("reasoning", false).unwrap();
+
+        storage.save_entry(&create_test_entry("a.rs")).unwrap();
+        assert_eq!(storage.search_candidates(&query).unwrap().unwrap().len(), 1);
+
+        // Missing: not recreated by record, only by reindex
+        fs::remove_file(storage.search_index_path()).unwrap();
+        storage.save_entry(&create_test_entry("b.rs")).unwrap();
+        assert!(storage.search_candidates(&query).unwrap().is_none());
+        storage.rebuild_index().unwrap();
+        assert_eq!(storage.search_candidates(&query).unwrap().unwrap().len(), 2);
+
+        // An entry indexed without updating the search index, e.g. by an older version
+        let mut index = storage.load_index().unwrap();
+        index.insert(&create_test_entry("c.rs"));
+        storage.save_index(&index).unwrap();
+        assert!(storage.search_candidates(&query).unwrap().is_none());
+        storage.save_entry(&create_test_entry("d.rs")).unwrap();
+        assert!(storage.search_candidates(&query).unwrap().is_none());
+
+        // Unreadable
+        storage.rebuild_index().unwrap();
+        fs::write(storage.search_index_path(), "{ not json").unwrap();
+        assert!(storage.search_candidates(&query).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_files_under_directory() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        for file in [
+            "src/auth/jwt.rs",
+            "src/auth/mod.rs",
+            "src/authz.rs",
+            "src/main.rs",
+        ] {
+            storage.save_entry(&create_test_entry(file)).unwrap();
+        }
 
-        let agent_id = storage.get_default_agent_id().unwrap();
-        assert_eq!(agent_id, "my-agent");
+        let auth = vec!["src/auth/jwt.rs".to_string(), "src/auth/mod.rs".to_string()];
+        for dir in ["src/auth", "src/auth/", "./src/auth", "./src/auth/"] {
+            assert_eq!(storage.files_under(dir).unwrap(), auth, "{}", dir);
+        }
+        assert_eq!(storage.files_under("src").unwrap().len(), 4);
+        assert_eq!(storage.files_under(".").unwrap().len(), 4);
+        assert_eq!(storage.files_under("./").unwrap().len(), 4);
+        assert!(storage.files_under("src/auth/jwt.rs").unwrap().is_empty());
+        assert!(storage.files_under("lib").unwrap().is_empty());
     }
 
     #[test]
-    fn test_storage_init_without_agent_id() {
+    fn test_half_written_temp_files_leave_store_intact() {
         let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        storage.save_entry(&create_test_entry("a.rs")).unwrap();
+
+        // A writer killed before renaming leaves only its temp files behind
+        let index = fs::read_to_string(storage.index_path()).unwrap();
+        let lore_dir = storage.lore_dir();
+        fs::write(
+            lore_dir.join("index.json.99999.tmp"),
+            &index[..index.len() / 2],
+        )
+        .unwrap();
+        fs::write(storage.entries_dir().join("b.json.99999.tmp"), "{ \"id\": ").unwrap();
+
+        assert_eq!(storage.load_index().unwrap().entry_count, 1);
+        assert_eq!(storage.count_entry_files().unwrap(), 1);
+
+        storage.save_entry(&create_test_entry("b.rs")).unwrap();
+        assert_eq!(storage.load_index().unwrap().entry_count, 2);
+        assert_eq!(storage.get_all_entries().unwrap().len(), 2);
+
+        // Our own writes don't leave temp files
+        let own_temp = format!(".{}.tmp", std::process::id());
+        for dir in [lore_dir, storage.entries_dir()] {
+            for file in fs::read_dir(dir).unwrap() {
+                let name = file.unwrap().file_name();
+                assert!(!name.to_string_lossy().ends_with(&own_temp));
+            }
+        }
+    }
 
+    #[test]
+    fn test_concurrent_save_entry_keeps_both_entries() {
+        let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
-        let agent_id = storage.get_default_agent_id().unwrap();
-        assert_eq!(agent_id, "unknown");
+        std::thread::scope(|scope| {
+            for file in ["a.rs", "b.rs"] {
+                let storage = &storage;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        storage.save_entry(&create_test_entry(file)).unwrap();
+                    }
+                });
+            }
+        });
+
+        let index = storage.load_index().unwrap();
+        assert_eq!(index.entry_count, 40);
+        assert_eq!(index.entries.len(), 40);
     }
 
     #[test]
-    fn test_storage_init_already_initialized() {
+    fn test_pending_draft_roundtrip() {
         let (_temp_dir, storage) = create_test_storage();
-
         storage.init(None).unwrap();
-        let result = storage.init(None);
 
-        assert!(matches!(result, Err(StorageError::AlreadyInitialized)));
+        assert!(storage.load_pending().unwrap().is_none());
+
+        let mut draft = PendingDraft::new("agent".to_string());
+        draft.append_chunk("First chunk");
+        storage.save_pending(&draft).unwrap();
+
+        // A later invocation resumes the draft and appends to it
+        let mut resumed = storage.load_pending().unwrap().unwrap();
+        resumed.append_chunk("Second chunk");
+        storage.save_pending(&resumed).unwrap();
+
+        let loaded = storage.load_pending().unwrap().unwrap();
+        assert_eq!(loaded.reasoning_trace, "First chunk\nSecond chunk");
+        assert_eq!(loaded.chunks, 2);
+
+        storage.clear_pending().unwrap();
+        assert!(storage.load_pending().unwrap().is_none());
     }
 
     #[test]
-    fn test_load_index_not_initialized() {
-        let (_temp_dir, storage) = create_test_storage();
+    fn test_export_import_roundtrip() {
+        let (_source_dir, source) = create_test_storage();
+        source.init(Some("exporter")).unwrap();
+
+        for file in ["a.rs", "a.rs", "b.rs"] {
+            let entry = crate::models::ThoughtObject::new(
+                file.to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                format!("Intent for {}", file),
+                "Reasoning".to_string(),
+            )
+            .with_tags(vec!["tag".to_string()]);
+            source.save_entry(&entry).unwrap();
+        }
 
-        let result = storage.load_index();
+        let export = source.export(source.get_all_entries().unwrap()).unwrap();
+        assert_eq!(export.version, EXPORT_VERSION);
+        assert_eq!(export.index.entry_count, 3);
+        assert_eq!(export.config["default_agent_id"], "exporter");
 
-        assert!(matches!(result, Err(StorageError::NotInitialized)));
+        let json = serde_json::to_string(&export).unwrap();
+        let parsed = parse_export(&json).unwrap();
+
+        let (_target_dir, target) = create_test_storage();
+        target.init(None).unwrap();
+        let report = target.import(&parsed, false).unwrap();
+        assert_eq!(report.imported, 3);
+
+        let mut original: Vec<_> = source
+            .get_all_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| serde_json::to_string(&e).unwrap())
+            .collect();
+        let mut imported: Vec<_> = target
+            .get_all_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| serde_json::to_string(&e).unwrap())
+            .collect();
+        original.sort();
+        imported.sort();
+        assert_eq!(original, imported);
+
+        let index = target.load_index().unwrap();
+        assert_eq!(index.entry_count, 3);
+        assert_eq!(index.get_entries_for_file("a.rs").unwrap().len(), 2);
     }
 
     #[test]
-    fn test_load_index_empty() {
+    fn test_prune_updates_index_and_search_index() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
+        let old = create_test_entry("gone.rs").with_tags(vec!["legacy".to_string()]);
+        let kept = create_test_entry("kept.rs");
+        storage.save_entry(&old).unwrap();
+        storage.save_entry(&kept).unwrap();
+
+        let removed = storage.prune(std::slice::from_ref(&old), None).unwrap();
+        assert_eq!(removed, 1);
 
+        assert!(!storage.entry_exists(&old.id));
         let index = storage.load_index().unwrap();
+        assert_eq!(index.entry_count, 1);
+        assert!(index.get_entries_for_file("gone.rs").is_none());
+        assert!(!index.entries.contains_key(&old.id));
+        assert!(storage.fsck(false).unwrap().is_clean());
 
-        assert_eq!(index.entry_count, 0);
-        assert!(index.files.is_empty());
+        // The search index stays in sync and forgets the entry's words
+        let query = Query::parse("legacy", false).unwrap();
+        assert_eq!(
+            storage.search_candidates(&query).unwrap(),
+            Some(HashSet::new())
+        );
+
+        let log = storage.load_audit_log().unwrap();
+        assert_eq!(log.last().unwrap().action, AuditAction::Prune);
+        assert_eq!(log.last().unwrap().entry_id, old.id);
     }
 
     #[test]
-    fn test_save_and_load_index() {
+    fn test_repetition_of_latest() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
+        let first = create_test_entry("a.rs");
+        storage.save_entry(&first).unwrap();
 
-        let mut index = LoreIndex::new();
-        index.add_entry("test.rs", "entry-1");
-        storage.save_index(&index).unwrap();
+        let mut again = create_test_entry("a.rs");
+        again.intent = first.intent.clone();
+        again.reasoning_trace = first.reasoning_trace.clone();
+        assert_eq!(
+            storage.repetition_of_latest(&again).unwrap(),
+            Some(Repetition::Duplicate(first.id.clone()))
+        );
 
-        let loaded = storage.load_index().unwrap();
-        assert_eq!(loaded.entry_count, 1);
+        again.reasoning_trace.push_str(" More.");
         assert_eq!(
-            loaded.get_entries_for_file("test.rs"),
-            Some(&vec!["entry-1".to_string()])
+            storage.repetition_of_latest(&again).unwrap(),
+            Some(Repetition::UnchangedFile(first.id.clone()))
+        );
+
+        again.file_hash = "changed".to_string();
+        assert_eq!(storage.repetition_of_latest(&again).unwrap(), None);
+        assert_eq!(
+            storage
+                .repetition_of_latest(&create_test_entry("other.rs"))
+                .unwrap(),
+            None
         );
     }
 
     #[test]
-    fn test_save_entry() {
-        let (temp_dir, storage) = create_test_storage();
+    fn test_update_entry_keeps_id_and_count() {
+        let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
+        let mut entry = create_test_entry("a.rs");
+        storage.save_entry(&entry).unwrap();
 
-        // Create a test file
-        let test_file = temp_dir.path().join("test.rs");
-        std::fs::write(&test_file, "fn main() {}").unwrap();
+        entry.amend("Later thought", &["jwt".to_string()], &[], &[], Utc::now());
+        storage.update_entry(&entry).unwrap();
 
-        let entry = crate::models::ThoughtObject::new(
-            "test.rs".to_string(),
-            "hash123".to_string(),
-            "test-agent".to_string(),
-            "Test intent".to_string(),
-            "Test reasoning".to_string(),
+        let index = storage.load_index().unwrap();
+        assert_eq!(index.entry_count, 1);
+        assert_eq!(index.get_entries_for_file("a.rs").unwrap().len(), 1);
+        assert_eq!(index.entries[&entry.id].tags, vec!["jwt".to_string()]);
+        let loaded = storage.load_entry(&entry.id).unwrap();
+        assert!(loaded.reasoning_trace.contains("Later thought"));
+        assert!(loaded.updated_at.is_some());
+
+        let query = Query::parse("later", false).unwrap();
+        assert_eq!(
+            storage.search_candidates(&query).unwrap(),
+            Some(HashSet::from([entry.id.clone()]))
+        );
+        assert_eq!(
+            storage.load_audit_log().unwrap().last().unwrap().action,
+            AuditAction::Edit
         );
-        let entry_id = entry.id.clone();
 
-        storage.save_entry(&entry).unwrap();
+        let missing = create_test_entry("a.rs");
+        assert!(matches!(
+            storage.update_entry(&missing),
+            Err(StorageError::EntryNotFound(_))
+        ));
+    }
 
-        // Verify entry was saved
-        let entry_path = storage.entries_dir().join(format!("{}.json", entry_id));
-        assert!(entry_path.exists());
+    #[test]
+    fn test_prune_archive_can_be_imported() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let old = create_test_entry("gone.rs");
+        storage.save_entry(&old).unwrap();
+
+        let archive = temp_dir.path().join("pruned.json");
+        storage
+            .prune(std::slice::from_ref(&old), Some(&archive))
+            .unwrap();
+        assert!(!storage.entry_exists(&old.id));
+
+        // An existing archive isn't overwritten
+        assert!(matches!(
+            storage.prune(&[], Some(&archive)),
+            Err(StorageError::ArchiveExists(_))
+        ));
 
-        // Verify index was updated
-        let index = storage.load_index().unwrap();
-        assert_eq!(index.entry_count, 1);
+        let bundle = parse_export(&std::fs::read_to_string(&archive).unwrap()).unwrap();
+        storage.import(&bundle, false).unwrap();
+        assert!(storage.entry_exists(&old.id));
     }
 
     #[test]
-    fn test_save_entry_not_initialized() {
+    fn test_prune_refuses_archive_inside_entries() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let entry = create_test_entry("a.rs");
+        storage.save_entry(&entry).unwrap();
+
+        let archive = temp_dir
+            .path()
+            .join(".lore")
+            .join("entries")
+            .join("archive.json");
+        assert!(matches!(
+            storage.prune(std::slice::from_ref(&entry), Some(&archive)),
+            Err(StorageError::ArchiveInsideEntries(_))
+        ));
+        assert!(storage.entry_exists(&entry.id));
+        assert!(!archive.exists());
+    }
+
+    #[test]
+    fn test_import_merge_skips_and_overwrite_replaces() {
         let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
 
         let entry = crate::models::ThoughtObject::new(
-            "test.rs".to_string(),
-            "hash123".to_string(),
-            "test-agent".to_string(),
-            "Test".to_string(),
+            "a.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Original".to_string(),
             "Reasoning".to_string(),
         );
+        storage.save_entry(&entry).unwrap();
 
-        let result = storage.save_entry(&entry);
-        assert!(matches!(result, Err(StorageError::NotInitialized)));
+        let mut changed = entry.clone();
+        changed.intent = "Changed".to_string();
+        let export = storage.export(vec![changed]).unwrap();
+
+        let report = storage.import(&export, false).unwrap();
+        assert_eq!(
+            (report.imported, report.skipped, report.replaced),
+            (0, 1, 0)
+        );
+        assert_eq!(storage.load_entry(&entry.id).unwrap().intent, "Original");
+
+        let report = storage.import(&export, true).unwrap();
+        assert_eq!(
+            (report.imported, report.skipped, report.replaced),
+            (0, 0, 1)
+        );
+        assert_eq!(storage.load_entry(&entry.id).unwrap().intent, "Changed");
+        assert_eq!(storage.load_index().unwrap().entry_count, 1);
     }
 
     #[test]
-    fn test_load_entry() {
+    fn test_parse_export_rejects_unknown_version() {
+        let result = parse_export(r#"{"version": 99, "entries": []}"#);
+        assert!(matches!(
+            result,
+            Err(StorageError::UnsupportedExportVersion(99))
+        ));
+
+        let result = parse_export(r#"{"entries": []}"#);
+        assert!(matches!(
+            result,
+            Err(StorageError::UnsupportedExportVersion(0))
+        ));
+    }
+
+    #[test]
+    fn test_search_by_intent() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
         let entry = crate::models::ThoughtObject::new(
-            "test.rs".to_string(),
-            "hash123".to_string(),
-            "test-agent".to_string(),
-            "Test intent".to_string(),
-            "Test reasoning".to_string(),
+            "auth.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Implement JWT authentication".to_string(),
+            "Some reasoning".to_string(),
         );
-        let entry_id = entry.id.clone();
-
         storage.save_entry(&entry).unwrap();
-        let loaded = storage.load_entry(&entry_id).unwrap();
 
-        assert_eq!(loaded.id, entry_id);
-        assert_eq!(loaded.target_file, "test.rs");
-        assert_eq!(loaded.intent, "Test intent");
+        let results = search_all(&storage, text("JWT", false));
+        assert_eq!(results.len(), 1);
+        assert!(results[0].intent.contains("JWT"));
     }
 
     #[test]
-    fn test_load_entry_not_found() {
+    fn test_search_by_reasoning() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
-        let result = storage.load_entry("nonexistent-id");
+        let entry = crate::models::ThoughtObject::new(
+            "auth.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Some intent".to_string(),
+            "I considered using pandas but decided against it".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
 
-        assert!(matches!(result, Err(StorageError::FileNotFound(_))));
+        let results = search_all(&storage, text("pandas", false));
+        assert_eq!(results.len(), 1);
     }
 
     #[test]
-    fn test_get_entries_for_file() {
+    fn test_search_by_tag() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
-        let entry1 = crate::models::ThoughtObject::new(
-            "test.rs".to_string(),
-            "hash1".to_string(),
-            "agent".to_string(),
-            "Intent 1".to_string(),
-            "Reasoning 1".to_string(),
-        );
-        let entry2 = crate::models::ThoughtObject::new(
-            "test.rs".to_string(),
-            "hash2".to_string(),
+        let entry = crate::models::ThoughtObject::new(
+            "auth.rs".to_string(),
+            "hash".to_string(),
             "agent".to_string(),
-            "Intent 2".to_string(),
-            "Reasoning 2".to_string(),
-        );
-
-        storage.save_entry(&entry1).unwrap();
-        storage.save_entry(&entry2).unwrap();
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_tags(vec!["security".to_string(), "auth".to_string()]);
+        storage.save_entry(&entry).unwrap();
 
-        let entries = storage.get_entries_for_file("test.rs").unwrap();
-        assert_eq!(entries.len(), 2);
+        let results = search_all(&storage, text("security", false));
+        assert_eq!(results.len(), 1);
     }
 
     #[test]
-    fn test_get_entries_for_file_normalized_path() {
+    fn test_search_by_rejected_alternative() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
         let entry = crate::models::ThoughtObject::new(
-            "src/test.rs".to_string(),
+            "auth.rs".to_string(),
             "hash".to_string(),
             "agent".to_string(),
             "Intent".to_string(),
             "Reasoning".to_string(),
-        );
+        )
+        .with_rejected(vec![crate::models::RejectedAlternative {
+            name: "Auth0 SDK".to_string(),
+            reason: None,
+        }]);
         storage.save_entry(&entry).unwrap();
 
-        // Query with ./ prefix should still find it
-        let entries = storage.get_entries_for_file("./src/test.rs").unwrap();
-        assert_eq!(entries.len(), 1);
+        let results = search_all(&storage, text("Auth0", false));
+        assert_eq!(results.len(), 1);
     }
 
     #[test]
-    fn test_get_entries_for_file_empty() {
+    fn test_search_by_reference() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
-        let entries = storage.get_entries_for_file("nonexistent.rs").unwrap();
-        assert!(entries.is_empty());
+        let entry = crate::models::ThoughtObject::new(
+            "auth.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_references(vec![
+            "JIRA-1234".to_string(),
+            "https://github.com/org/repo/pull/42".to_string(),
+        ]);
+        storage.save_entry(&entry).unwrap();
+
+        assert_eq!(search_all(&storage, text("JIRA-1234", false)).len(), 1);
+        assert_eq!(search_all(&storage, text("pull/42", false)).len(), 1);
+        assert!(search_all(&storage, text("JIRA-9999", false)).is_empty());
     }
 
     #[test]
-    fn test_get_all_entries() {
+    fn test_score_weights_fields() {
+        let entry = |intent: &str, trace: &str, tags: &[&str]| {
+            ThoughtObject::new(
+                "a.rs".to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                intent.to_string(),
+                trace.to_string(),
+            )
+            .with_tags(tags.iter().map(|t| t.to_string()).collect())
+        };
+        let matcher = text("jwt refresh", false);
+
+        let in_tag = matcher.score(&entry("Tokens", "", &["jwt"]));
+        let in_intent = matcher.score(&entry("JWT tokens", "", &[]));
+        let in_trace = matcher.score(&entry("Tokens", "uses jwt", &[]));
+        assert!(in_tag > in_intent && in_intent > in_trace && in_trace > 0);
+
+        // Every occurrence of every term counts
+        assert_eq!(
+            matcher.score(&entry("Tokens", "jwt, JWT and refresh", &[])),
+            3 * in_trace
+        );
+        // Negated terms don't
+        let negated = text("jwt NOT cookie", false);
+        assert_eq!(
+            negated.score(&entry("Tokens", "jwt", &["cookie"])),
+            in_trace
+        );
+    }
+
+    #[test]
+    fn test_search_query() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
-        let entry1 = crate::models::ThoughtObject::new(
-            "file1.rs".to_string(),
-            "hash1".to_string(),
-            "agent".to_string(),
-            "Intent 1".to_string(),
-            "Reasoning 1".to_string(),
-        );
-        let entry2 = crate::models::ThoughtObject::new(
-            "file2.rs".to_string(),
-            "hash2".to_string(),
-            "agent".to_string(),
-            "Intent 2".to_string(),
-            "Reasoning 2".to_string(),
-        );
+        for (intent, tag) in [
+            ("JWT with refresh tokens", "auth"),
+            ("JWT refresh stored in a cookie", "auth"),
+            ("Session cookies", "web"),
+        ] {
+            let entry = crate::models::ThoughtObject::new(
+                "auth.rs".to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                intent.to_string(),
+                "Reasoning".to_string(),
+            )
+            .with_tags(vec![tag.to_string()]);
+            storage.save_entry(&entry).unwrap();
+        }
 
-        storage.save_entry(&entry1).unwrap();
-        storage.save_entry(&entry2).unwrap();
+        let search = |q: &str, word: bool| {
+            let query = Query::parse(q, false).unwrap();
+            let mut intents: Vec<String> =
+                search_all(&storage, EntryMatcher::expression(query, word))
+                    .into_iter()
+                    .map(|e| e.intent)
+                    .collect();
+            intents.sort();
+            intents
+        };
 
-        let all_entries = storage.get_all_entries().unwrap();
-        assert_eq!(all_entries.len(), 2);
+        assert_eq!(
+            search("jwt AND refresh NOT cookie", false),
+            vec!["JWT with refresh tokens"]
+        );
+        // Terms may match different fields of the same entry
+        assert_eq!(search("session AND web", false), vec!["Session cookies"]);
+        assert_eq!(search("\"in a cookie\" OR web", false).len(), 2);
+        // Whole-word mode: "cookie" doesn't match "cookies"
+        assert_eq!(search("cookie NOT jwt", true), Vec::<String>::new());
     }
 
     #[test]
-    fn test_get_all_entries_empty() {
+    fn test_search_by_rejected_alternative_reason() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
-        let entries = storage.get_all_entries().unwrap();
-        assert!(entries.is_empty());
+        let entry = crate::models::ThoughtObject::new(
+            "auth.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Intent".to_string(),
+            "Reasoning".to_string(),
+        )
+        .with_rejected(vec![crate::models::RejectedAlternative {
+            name: "Auth0".to_string(),
+            reason: Some("Locks us into their pricing".to_string()),
+        }]);
+        storage.save_entry(&entry).unwrap();
+
+        assert_eq!(search_all(&storage, text("pricing", false)).len(), 1);
+        assert_eq!(search_all(&storage, text("pricing", true)).len(), 1);
+        let re = Regex::new("pric(e|ing)").unwrap();
+        assert_eq!(search_all(&storage, EntryMatcher::Regex(re)).len(), 1);
     }
 
     #[test]
-    fn test_search_by_intent() {
+    fn test_search_case_insensitive() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
@@ -560,36 +3757,47 @@ mod tests {
             "auth.rs".to_string(),
             "hash".to_string(),
             "agent".to_string(),
-            "Implement JWT authentication".to_string(),
-            "Some reasoning".to_string(),
+            "Implement JWT".to_string(),
+            "Reasoning".to_string(),
         );
         storage.save_entry(&entry).unwrap();
 
-        let results = storage.search("JWT").unwrap();
+        let results = search_all(&storage, text("jwt", false));
         assert_eq!(results.len(), 1);
-        assert!(results[0].intent.contains("JWT"));
     }
 
     #[test]
-    fn test_search_by_reasoning() {
+    fn test_search_whole_word() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
-        let entry = crate::models::ThoughtObject::new(
-            "auth.rs".to_string(),
+        let concat = crate::models::ThoughtObject::new(
+            "concat.rs".to_string(),
             "hash".to_string(),
             "agent".to_string(),
-            "Some intent".to_string(),
-            "I considered using pandas but decided against it".to_string(),
+            "Concatenate buffers".to_string(),
+            "Reasoning".to_string(),
         );
-        storage.save_entry(&entry).unwrap();
+        let cat = crate::models::ThoughtObject::new(
+            "cat.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Port the Cat command".to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&concat).unwrap();
+        storage.save_entry(&cat).unwrap();
 
-        let results = storage.search("pandas").unwrap();
-        assert_eq!(results.len(), 1);
+        let substring = search_all(&storage, text("cat", false));
+        assert_eq!(substring.len(), 2);
+
+        let words = search_all(&storage, text("cat", true));
+        assert_eq!(words.len(), 1);
+        assert_eq!(words[0].target_file, "cat.rs");
     }
 
     #[test]
-    fn test_search_by_tag() {
+    fn test_search_no_results() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
@@ -599,38 +3807,56 @@ mod tests {
             "agent".to_string(),
             "Intent".to_string(),
             "Reasoning".to_string(),
-        )
-        .with_tags(vec!["security".to_string(), "auth".to_string()]);
+        );
         storage.save_entry(&entry).unwrap();
 
-        let results = storage.search("security").unwrap();
-        assert_eq!(results.len(), 1);
+        let results = search_all(&storage, text("nonexistent", false));
+        assert!(results.is_empty());
     }
 
     #[test]
-    fn test_search_by_rejected_alternative() {
+    fn test_search_regex() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
-        let entry = crate::models::ThoughtObject::new(
+        let entry1 = crate::models::ThoughtObject::new(
             "auth.rs".to_string(),
             "hash".to_string(),
             "agent".to_string(),
-            "Intent".to_string(),
+            "Implement JWT v2".to_string(),
+            "Reasoning".to_string(),
+        );
+        let entry2 = crate::models::ThoughtObject::new(
+            "db.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            "Add connection pool".to_string(),
             "Reasoning".to_string(),
         )
         .with_rejected(vec![crate::models::RejectedAlternative {
-            name: "Auth0 SDK".to_string(),
+            name: "r2d2 crate".to_string(),
             reason: None,
         }]);
-        storage.save_entry(&entry).unwrap();
+        storage.save_entry(&entry1).unwrap();
+        storage.save_entry(&entry2).unwrap();
+
+        let results = search_all(
+            &storage,
+            EntryMatcher::Regex(Regex::new(r"JWT v\d").unwrap()),
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].target_file, "auth.rs");
 
-        let results = storage.search("Auth0").unwrap();
+        let results = search_all(
+            &storage,
+            EntryMatcher::Regex(Regex::new(r"^r\dd\d").unwrap()),
+        );
         assert_eq!(results.len(), 1);
+        assert_eq!(results[0].target_file, "db.rs");
     }
 
     #[test]
-    fn test_search_case_insensitive() {
+    fn test_search_regex_case_sensitivity() {
         let (_temp_dir, storage) = create_test_storage();
         storage.init(None).unwrap();
 
@@ -643,26 +3869,79 @@ mod tests {
         );
         storage.save_entry(&entry).unwrap();
 
-        let results = storage.search("jwt").unwrap();
+        let results = search_all(&storage, EntryMatcher::Regex(Regex::new("jwt").unwrap()));
+        assert!(results.is_empty());
+
+        let results = search_all(
+            &storage,
+            EntryMatcher::Regex(Regex::new("(?i)jwt").unwrap()),
+        );
         assert_eq!(results.len(), 1);
     }
 
-    #[test]
-    fn test_search_no_results() {
-        let (_temp_dir, storage) = create_test_storage();
-        storage.init(None).unwrap();
-
-        let entry = crate::models::ThoughtObject::new(
-            "auth.rs".to_string(),
+    fn agent_entry(agent: &str, file: &str, day: u32, tags: &[&str]) -> EntryMeta {
+        let mut entry = ThoughtObject::new(
+            file.to_string(),
             "hash".to_string(),
-            "agent".to_string(),
+            agent.to_string(),
             "Intent".to_string(),
             "Reasoning".to_string(),
+        )
+        .with_tags(tags.iter().map(|t| t.to_string()).collect());
+        entry.timestamp = Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap();
+        EntryMeta::from(&entry)
+    }
+
+    #[test]
+    fn test_agent_stats_empty() {
+        assert!(agent_stats(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_agent_stats_counts_and_dates() {
+        let entries = vec![
+            agent_entry("claude", "a.rs", 5, &["auth"]),
+            agent_entry("claude", "b.rs", 2, &["perf", "auth"]),
+            agent_entry("claude", "a.rs", 9, &[]),
+            agent_entry("cursor", "c.rs", 3, &[]),
+        ];
+
+        let agents = agent_stats(&entries);
+        assert_eq!(agents.len(), 2);
+
+        let claude = &agents[0];
+        assert_eq!(claude.agent_id, "claude");
+        assert_eq!(claude.entry_count, 3);
+        assert_eq!(
+            claude.first_activity,
+            Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap()
         );
-        storage.save_entry(&entry).unwrap();
+        assert_eq!(
+            claude.last_activity,
+            Utc.with_ymd_and_hms(2024, 1, 9, 12, 0, 0).unwrap()
+        );
+        assert_eq!(claude.file_count, 2);
+        assert_eq!(claude.files, vec!["a.rs", "b.rs"]);
+        assert_eq!(claude.tags, vec!["auth", "perf"]);
 
-        let results = storage.search("nonexistent").unwrap();
-        assert!(results.is_empty());
+        let cursor = &agents[1];
+        assert_eq!(cursor.entry_count, 1);
+        assert_eq!(cursor.first_activity, cursor.last_activity);
+    }
+
+    #[test]
+    fn test_agent_stats_sorted_by_last_activity() {
+        let entries = vec![
+            agent_entry("old", "a.rs", 1, &[]),
+            agent_entry("new", "a.rs", 20, &[]),
+            agent_entry("mid", "a.rs", 10, &[]),
+        ];
+
+        let ids: Vec<_> = agent_stats(&entries)
+            .into_iter()
+            .map(|a| a.agent_id)
+            .collect();
+        assert_eq!(ids, vec!["new", "mid", "old"]);
     }
 
     #[test]
@@ -728,6 +4007,569 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_normalize_agent_id_trims_and_lowercases() {
+        assert_eq!(normalize_agent_id("  Bot "), "bot");
+        assert_eq!(normalize_agent_id("BOT@ci"), "bot@ci");
+    }
+
+    #[test]
+    fn test_normalize_agent_id_collapses_whitespace() {
+        assert_eq!(normalize_agent_id("Claude   Code\t CI"), "claude code ci");
+    }
+
+    #[test]
+    fn test_validate_agent_id() {
+        assert!(validate_agent_id("bot").is_ok());
+        assert!(matches!(
+            validate_agent_id(""),
+            Err(StorageError::InvalidAgentId(_))
+        ));
+        assert!(matches!(
+            validate_agent_id("   "),
+            Err(StorageError::InvalidAgentId(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_agent_ids_config_flag() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        assert!(!storage.normalize_agent_ids().unwrap());
+
+        let config_path = storage.lore_dir().join(CONFIG_FILE);
+        std::fs::write(&config_path, r#"{"normalize_agent_ids": true}"#).unwrap();
+        assert!(storage.normalize_agent_ids().unwrap());
+    }
+
+    #[test]
+    fn test_rename_agent_rewrites_matching_entries() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        for agent in ["Bot ", "Bot ", "other"] {
+            let entry = crate::models::ThoughtObject::new(
+                "test.rs".to_string(),
+                "hash".to_string(),
+                agent.to_string(),
+                "Intent".to_string(),
+                "Reasoning".to_string(),
+            );
+            storage.save_entry(&entry).unwrap();
+        }
+
+        let renamed = storage.rename_agent("Bot ", "bot").unwrap();
+        assert_eq!(renamed, 2);
+
+        let entries = storage.get_all_entries().unwrap();
+        assert_eq!(entries.iter().filter(|e| e.agent_id == "bot").count(), 2);
+        assert_eq!(entries.iter().filter(|e| e.agent_id == "other").count(), 1);
+        assert!(!entries.iter().any(|e| e.agent_id == "Bot "));
+
+        let metas = storage.get_entry_metas().unwrap();
+        assert_eq!(metas.iter().filter(|m| m.agent_id == "bot").count(), 2);
+    }
+
+    #[test]
+    fn test_rename_file_entries_merges_into_new_path() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let start = chrono::Utc::now();
+        let mut ids = Vec::new();
+        for (i, file) in ["old.rs", "new.rs", "old.rs", "other.rs"]
+            .iter()
+            .enumerate()
+        {
+            let mut entry = crate::models::ThoughtObject::new(
+                file.to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                format!("Intent {}", i),
+                "Reasoning".to_string(),
+            );
+            entry.timestamp = start + chrono::Duration::seconds(i as i64);
+            storage.save_entry(&entry).unwrap();
+            ids.push(entry.id);
+        }
+
+        let moved = storage.rename_file_entries("./old.rs", "new.rs").unwrap();
+        assert_eq!(moved, 2);
+
+        let index = storage.load_index().unwrap();
+        assert!(!index.files.contains_key("old.rs"));
+        assert_eq!(index.files["new.rs"], ids[..3].to_vec());
+        assert_eq!(index.files["other.rs"], vec![ids[3].clone()]);
+        assert_eq!(index.entry_count, 4);
+        assert!(index
+            .entries
+            .values()
+            .all(|meta| meta.target_file != "old.rs"));
+
+        assert_eq!(storage.load_entry(&ids[0]).unwrap().target_file, "new.rs");
+        assert_eq!(storage.load_entry(&ids[2]).unwrap().target_file, "new.rs");
+        assert_eq!(
+            storage.load_entry(&ids[0]).unwrap().previous_paths,
+            vec!["old.rs".to_string()]
+        );
+        assert!(storage
+            .load_entry(&ids[1])
+            .unwrap()
+            .previous_paths
+            .is_empty());
+        assert_eq!(
+            storage.moved_to("old.rs").unwrap().as_deref(),
+            Some("new.rs")
+        );
+        assert_eq!(storage.moved_to("new.rs").unwrap(), None);
+        assert_eq!(storage.moved_to("unknown.rs").unwrap(), None);
+
+        // Nothing left to move
+        assert_eq!(storage.rename_file_entries("old.rs", "new.rs").unwrap(), 0);
+
+        // The history survives a rebuild from the entry files
+        storage.rebuild_index().unwrap();
+        assert_eq!(
+            storage.moved_to("old.rs").unwrap().as_deref(),
+            Some("new.rs")
+        );
+    }
+
+    fn save_test_entry(storage: &LoreStorage, intent: &str) -> ThoughtObject {
+        let entry = crate::models::ThoughtObject::new(
+            "test.rs".to_string(),
+            "hash".to_string(),
+            "agent".to_string(),
+            intent.to_string(),
+            "Reasoning".to_string(),
+        );
+        storage.save_entry(&entry).unwrap();
+        entry
+    }
+
+    #[test]
+    fn test_diff_against_disjoint_stores() {
+        let (_ours_dir, ours) = create_test_storage();
+        let (_base_dir, base) = create_test_storage();
+        ours.init(None).unwrap();
+        base.init(None).unwrap();
+
+        let added = save_test_entry(&ours, "Ours");
+        let removed = save_test_entry(&base, "Theirs");
+
+        let diff = ours.diff_against(&base).unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, added.id);
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].id, removed.id);
+        assert!(diff.modified.is_empty());
+    }
+
+    #[test]
+    fn test_diff_against_overlapping_stores() {
+        let (_ours_dir, ours) = create_test_storage();
+        let (_base_dir, base) = create_test_storage();
+        ours.init(None).unwrap();
+        base.init(None).unwrap();
+
+        let shared = save_test_entry(&base, "Shared");
+        ours.save_entry(&shared).unwrap();
+        let added = save_test_entry(&ours, "New");
+
+        let diff = ours.diff_against(&base).unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, added.id);
+        assert!(diff.removed.is_empty());
+        assert!(diff.modified.is_empty());
+
+        // Comparing a store with itself finds nothing
+        assert!(ours.diff_against(&ours).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_against_modified_entry() {
+        let (_ours_dir, ours) = create_test_storage();
+        let (_base_dir, base) = create_test_storage();
+        ours.init(None).unwrap();
+        base.init(None).unwrap();
+
+        let mut entry = save_test_entry(&base, "Original");
+        entry.tags.push("edited".to_string());
+        ours.save_entry(&entry).unwrap();
+
+        let diff = ours.diff_against(&base).unwrap();
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].id, entry.id);
+        assert_eq!(diff.modified[0].tags, vec!["edited".to_string()]);
+    }
+
+    #[test]
+    fn test_audit_log_records_each_mutation() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        assert!(storage.load_audit_log().unwrap().is_empty());
+
+        let entry = save_test_entry(&storage, "Intent");
+        storage.rename_agent("agent", "bot").unwrap();
+        storage.rename_file_entries("test.rs", "moved.rs").unwrap();
+
+        let mut imported = entry.clone();
+        imported.id = "imported-entry".to_string();
+        let export = storage.export(vec![imported]).unwrap();
+        storage.import(&export, false).unwrap();
+
+        let log = storage.load_audit_log().unwrap();
+        let actions: Vec<_> = log.iter().map(|e| e.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::Record,
+                AuditAction::Edit,
+                AuditAction::Edit,
+                AuditAction::Import
+            ]
+        );
+        assert_eq!(log[0].entry_id, entry.id);
+        assert_eq!(log[0].agent_id, "agent");
+        assert_eq!(log[1].agent_id, "bot");
+        assert_eq!(log[2].target_file, "moved.rs");
+        assert_eq!(log[3].entry_id, "imported-entry");
+    }
+
+    #[test]
+    fn test_audit_log_survives_rebuild() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        save_test_entry(&storage, "One");
+        save_test_entry(&storage, "Two");
+        let before = fs::read_to_string(storage.audit_log_path()).unwrap();
+
+        storage.rebuild_index().unwrap();
+        storage.repair(None).unwrap();
+
+        let after = fs::read_to_string(storage.audit_log_path()).unwrap();
+        assert_eq!(before, after);
+        assert_eq!(after.lines().count(), 2);
+    }
+
+    /// Write `entry` where versions before sharding put it, bypassing the index
+    fn write_flat_entry(storage: &LoreStorage, entry: &ThoughtObject) {
+        let content = serde_json::to_string_pretty(entry).unwrap();
+        fs::write(storage.flat_entry_path(&entry.id), content).unwrap();
+    }
+
+    #[test]
+    fn test_mixed_layout_entries_are_read_and_rewritten_sharded() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let sharded = create_test_entry("a.rs");
+        storage.save_entry(&sharded).unwrap();
+        let legacy = create_test_entry("b.rs");
+        write_flat_entry(&storage, &legacy);
+
+        assert!(storage.entry_exists(&legacy.id));
+        assert_eq!(storage.load_entry(&legacy.id).unwrap().target_file, "b.rs");
+        assert_eq!(
+            storage.resolve_entry_id(&legacy.id[..8]).unwrap(),
+            legacy.id
+        );
+        assert_eq!(storage.get_all_entries().unwrap().len(), 2);
+        assert_eq!(storage.count_entry_files().unwrap(), 2);
+        assert_eq!(storage.rebuild_index().unwrap().indexed, 2);
+        assert!(storage.fsck(false).unwrap().is_clean());
+
+        // Updating a legacy entry moves it into its shard
+        let mut updated = storage.load_entry(&legacy.id).unwrap();
+        updated.intent = "Updated".to_string();
+        storage.update_entry(&updated).unwrap();
+        assert!(!storage.flat_entry_path(&legacy.id).exists());
+        assert!(storage.sharded_entry_path(&legacy.id).exists());
+        assert_eq!(storage.count_entry_files().unwrap(), 2);
+
+        storage.prune(&[updated], None).unwrap();
+        assert!(!storage.entry_exists(&legacy.id));
+    }
+
+    #[test]
+    fn test_migrate_entries_moves_flat_files_into_shards() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let a = create_test_entry("a.rs");
+        let b = create_test_entry("b.rs");
+        write_flat_entry(&storage, &a);
+        write_flat_entry(&storage, &b);
+        storage.rebuild_index().unwrap();
+        // Also saved sharded, so the flat copy conflicts
+        storage.save_entry(&b).unwrap();
+        write_flat_entry(&storage, &b);
+
+        let report = storage.migrate_entries().unwrap();
+        assert_eq!(report.moved, 1);
+        assert_eq!(report.conflicts, vec![b.id.clone()]);
+        assert!(storage.sharded_entry_path(&a.id).exists());
+        assert!(!storage.flat_entry_path(&a.id).exists());
+        assert!(storage.flat_entry_path(&b.id).exists());
+
+        // Running it again has nothing left to move
+        assert_eq!(storage.migrate_entries().unwrap().moved, 0);
+        assert_eq!(storage.load_entry(&a.id).unwrap().target_file, "a.rs");
+    }
+
+    /// A store with two valid, indexed entries, for `fsck` to find broken
+    fn fsck_store() -> (TempDir, LoreStorage, ThoughtObject, ThoughtObject) {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let entry = |file: &str| {
+            ThoughtObject::new(
+                file.to_string(),
+                "hash".to_string(),
+                "agent".to_string(),
+                "Intent".to_string(),
+                "Reasoning".to_string(),
+            )
+        };
+        let a = entry("a.rs");
+        let b = entry("b.rs");
+        storage.save_entry(&a).unwrap();
+        storage.save_entry(&b).unwrap();
+        (temp_dir, storage, a, b)
+    }
+
+    #[test]
+    fn test_fsck_clean_store() {
+        let (_temp_dir, storage, _, _) = fsck_store();
+        let report = storage.fsck(false).unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+    }
+
+    #[test]
+    fn test_fsck_dangling_reference_and_fix() {
+        let (_temp_dir, storage, a, _) = fsck_store();
+        std::fs::remove_file(storage.entry_path(&a.id)).unwrap();
+
+        let report = storage.fsck(false).unwrap();
+        assert_eq!(
+            report.dangling,
+            vec![EntryRef {
+                id: a.id.clone(),
+                target_file: "a.rs".to_string()
+            }]
+        );
+        assert_eq!(
+            report.entry_count,
+            Some(CountMismatch {
+                recorded: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(report.problem_count(), 2);
+
+        storage.rebuild_index().unwrap();
+        assert!(storage.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_fsck_unparseable_entry_file_survives_fix() {
+        let (_temp_dir, storage, a, _) = fsck_store();
+        let path = storage.entry_path(&a.id);
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let report = storage.fsck(false).unwrap();
+        assert_eq!(report.unparseable, vec![format!("{}.json", a.id)]);
+        assert!(report.dangling.is_empty());
+
+        // Rebuilding drops it from the index but never deletes the file
+        storage.rebuild_index().unwrap();
+        let report = storage.fsck(false).unwrap();
+        assert_eq!(report.unparseable.len(), 1);
+        assert_eq!(report.problem_count(), 1);
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_fsck_unindexed_and_misfiled_entries() {
+        let (_temp_dir, storage, a, b) = fsck_store();
+        let mut index = storage.load_index().unwrap();
+        // Drop a from the index and list b under the wrong file
+        index.files.remove("a.rs");
+        index.files.remove("b.rs");
+        index.files.insert("c.rs".to_string(), vec![b.id.clone()]);
+        index.entry_count = 1;
+        storage.save_index(&index).unwrap();
+
+        let report = storage.fsck(false).unwrap();
+        let unindexed: Vec<&str> = report.unindexed.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(unindexed.len(), 2);
+        assert!(unindexed.contains(&a.id.as_str()));
+        assert!(unindexed.contains(&b.id.as_str()));
+
+        storage.rebuild_index().unwrap();
+        assert!(storage.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_fsck_duplicate_ids() {
+        let (_temp_dir, storage, a, _) = fsck_store();
+
+        // Listed twice in the index
+        let mut index = storage.load_index().unwrap();
+        index.files.get_mut("a.rs").unwrap().push(a.id.clone());
+        index.entry_count += 1;
+        storage.save_index(&index).unwrap();
+        // And claimed by a second entry file
+        std::fs::copy(
+            storage.entry_path(&a.id),
+            storage.flat_entry_path("copy-of-a"),
+        )
+        .unwrap();
+
+        let report = storage.fsck(false).unwrap();
+        assert_eq!(report.duplicates, vec![a.id.clone()]);
+
+        storage.rebuild_index().unwrap();
+        let report = storage.fsck(false).unwrap();
+        assert_eq!(report.duplicates, vec![a.id.clone()]);
+        assert!(report.dangling.is_empty() && report.entry_count.is_none());
+    }
+
+    #[test]
+    fn test_fsck_unreadable_index() {
+        let (temp_dir, storage, _, _) = fsck_store();
+        std::fs::write(temp_dir.path().join(".lore").join("index.json"), "[").unwrap();
+
+        let report = storage.fsck(false).unwrap();
+        assert!(report.unreadable_index.is_some());
+        assert_eq!(report.unindexed.len(), 2);
+        assert!(report.entry_count.is_none());
+
+        storage.rebuild_index().unwrap();
+        assert!(storage.fsck(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_fsck_check_targets() {
+        let (temp_dir, storage, _, _) = fsck_store();
+        std::fs::write(temp_dir.path().join("a.rs"), "fn a() {}").unwrap();
+
+        assert!(storage.fsck(false).unwrap().is_clean());
+        let report = storage.fsck(true).unwrap();
+        let missing: Vec<&str> = report
+            .missing_targets
+            .iter()
+            .map(|r| r.target_file.as_str())
+            .collect();
+        assert_eq!(missing, vec!["b.rs"]);
+    }
+
+    #[test]
+    fn test_attachments_are_saved_loaded_and_pruned() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        let content = b"bench: 12ms".to_vec();
+        let attachment = Attachment {
+            filename: "bench.txt".to_string(),
+            size: content.len() as u64,
+            sha256: hash_bytes(&content),
+        };
+        let entry = create_test_entry("a.rs").with_attachments(vec![attachment.clone()]);
+        storage
+            .save_attachments(&entry.id, &[(attachment, content.clone())])
+            .unwrap();
+        storage.save_entry(&entry).unwrap();
+
+        assert_eq!(
+            storage.load_attachment(&entry, "bench.txt").unwrap(),
+            content
+        );
+        assert!(matches!(
+            storage.load_attachment(&entry, "other.txt"),
+            Err(StorageError::AttachmentNotFound(..))
+        ));
+        assert!(matches!(
+            storage.attachment_path(&entry.id, "../config.json"),
+            Err(StorageError::InvalidAttachmentName(_))
+        ));
+
+        storage.prune(std::slice::from_ref(&entry), None).unwrap();
+        assert!(!storage.attachment_dir(&entry.id).exists());
+    }
+
+    #[test]
+    fn test_templates_save_load_and_list() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+        assert!(storage.list_templates().unwrap().is_empty());
+
+        storage.save_template("adr", "## Context\n", false).unwrap();
+        storage
+            .save_template("bugfix", "## Cause\n", false)
+            .unwrap();
+        assert_eq!(storage.list_templates().unwrap(), vec!["adr", "bugfix"]);
+        assert_eq!(storage.load_template("adr").unwrap(), "## Context\n");
+
+        assert!(matches!(
+            storage.save_template("adr", "## Decision\n", false),
+            Err(StorageError::TemplateExists(_))
+        ));
+        storage.save_template("adr", "## Decision\n", true).unwrap();
+        assert_eq!(storage.load_template("adr").unwrap(), "## Decision\n");
+
+        assert!(matches!(
+            storage.load_template("missing"),
+            Err(StorageError::TemplateNotFound(_))
+        ));
+        assert!(matches!(
+            storage.save_template("../config", "", false),
+            Err(StorageError::InvalidTemplateName(_))
+        ));
+    }
+
+    #[test]
+    fn test_loreignore_globs_directories_and_negation() {
+        let (temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        // No .loreignore: nothing is ignored
+        assert!(!storage.is_ignored("Cargo.lock"));
+
+        std::fs::write(
+            temp_dir.path().join(".lore").join(".loreignore"),
+            "# Generated\n*.lock\ngenerated/\n*.snap\n!keep.snap\n/dist\n",
+        )
+        .unwrap();
+        let ignore = storage.loreignore().unwrap();
+
+        // Globs
+        assert!(ignore.is_ignored("Cargo.lock"));
+        assert!(ignore.is_ignored("./web/package.lock"));
+        assert!(!ignore.is_ignored("src/lock.rs"));
+
+        // Directories match everything beneath them
+        assert!(ignore.is_ignored("generated/schema.rs"));
+        assert!(ignore.is_ignored("crates/api/generated/types.rs"));
+        assert!(ignore.is_ignored("dist/app.js"));
+        assert!(!ignore.is_ignored("src/dist/app.js"));
+
+        // Negation re-includes a path
+        assert!(ignore.is_ignored("tests/output.snap"));
+        assert!(!ignore.is_ignored("tests/keep.snap"));
+
+        assert!(storage.is_ignored("Cargo.lock"));
+    }
+
+    #[test]
+    fn test_rename_agent_rejects_empty_name() {
+        let (_temp_dir, storage) = create_test_storage();
+        storage.init(None).unwrap();
+
+        let result = storage.rename_agent("bot", " ");
+        assert!(matches!(result, Err(StorageError::InvalidAgentId(_))));
+    }
+
     #[test]
     fn test_find_lore_root_found() {
         let temp_dir = TempDir::new().unwrap();
@@ -761,4 +4603,27 @@ mod tests {
         assert!(root.is_some());
         assert_eq!(root.unwrap(), temp_dir.path());
     }
+
+    #[test]
+    fn test_infer_project_from_nearest_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join("Cargo.toml"), "[workspace]").unwrap();
+        std::fs::create_dir_all(root.join("crates/core/src/parse")).unwrap();
+        std::fs::write(root.join("crates/core/Cargo.toml"), "[package]").unwrap();
+        std::fs::create_dir_all(root.join("web/src")).unwrap();
+        std::fs::write(root.join("web/package.json"), "{}").unwrap();
+
+        assert_eq!(
+            infer_project(root, "crates/core/src/parse/mod.rs").as_deref(),
+            Some("crates/core")
+        );
+        assert_eq!(
+            infer_project(root, "web/src/app.ts").as_deref(),
+            Some("web")
+        );
+        // Only the root manifest applies
+        assert_eq!(infer_project(root, "build.rs"), None);
+        assert_eq!(infer_project(root, "docs/guide.md"), None);
+    }
 }
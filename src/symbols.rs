@@ -0,0 +1,246 @@
+use regex::Regex;
+
+/// Languages whose symbol definitions lore can locate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    JavaScript,
+    Python,
+}
+
+impl Language {
+    /// Guess the language from a file's extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = path.rsplit_once('.')?.1;
+        match extension {
+            "rs" => Some(Language::Rust),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => {
+                Some(Language::JavaScript)
+            }
+            "py" | "pyi" => Some(Language::Python),
+            _ => None,
+        }
+    }
+}
+
+/// Find the 1-based, inclusive line span of the definition of `symbol` in
+/// `content`. Returns `None` if the language isn't supported or no definition
+/// was found. These are line-based heuristics, not parsers: the first matching
+/// definition wins.
+pub fn locate_symbol(path: &str, content: &str, symbol: &str) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    match Language::from_path(path)? {
+        Language::Rust => locate_rust(&lines, symbol),
+        Language::JavaScript => locate_javascript(&lines, symbol),
+        Language::Python => locate_python(&lines, symbol),
+    }
+}
+
+/// Turn a range relative to a symbol's span (line 1 is the definition line)
+/// into absolute file lines
+pub fn offset_range(span: (usize, usize), relative: (usize, usize)) -> (usize, usize) {
+    let (start, _) = span;
+    (start + relative.0 - 1, start + relative.1 - 1)
+}
+
+fn locate_rust(lines: &[&str], symbol: &str) -> Option<(usize, usize)> {
+    let name = regex::escape(symbol);
+    let definition = Regex::new(&format!(
+        r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|default)\s+)*(?:extern\s+"[^"]*"\s+)?(?:fn|struct|enum|union|trait|type|mod|const|static|macro_rules!)\s+{}\b"#,
+        name
+    ))
+    .ok()?;
+    locate_braced(lines, &definition)
+}
+
+fn locate_javascript(lines: &[&str], symbol: &str) -> Option<(usize, usize)> {
+    let name = regex::escape(symbol);
+    let definition = Regex::new(&format!(
+        r"^\s*(?:export\s+)?(?:default\s+)?(?:(?:async\s+)?function\s*\*?\s*{name}\b|(?:abstract\s+)?(?:class|interface|enum|type)\s+{name}\b|(?:const|let|var)\s+{name}\s*[:=]|(?:(?:static|async|get|set|public|private|protected|readonly)\s+)*{name}\s*\([^)]*\)\s*(?::[^{{]+)?\{{)",
+        name = name
+    ))
+    .ok()?;
+    locate_braced(lines, &definition)
+}
+
+fn locate_python(lines: &[&str], symbol: &str) -> Option<(usize, usize)> {
+    let definition = Regex::new(&format!(
+        r"^(\s*)(?:async\s+)?(?:def|class)\s+{}\b",
+        regex::escape(symbol)
+    ))
+    .ok()?;
+
+    let start = lines.iter().position(|line| definition.is_match(line))?;
+    let indent = indent_of(lines[start]);
+
+    // Decorators directly above belong to the definition
+    let mut first = start;
+    while first > 0 && lines[first - 1].trim_start().starts_with('@') {
+        first -= 1;
+    }
+
+    // The signature may span several lines; it ends at the `:` outside brackets
+    let mut depth = 0i32;
+    let mut header_end = start;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let code = strip_comment(line, "#");
+        depth += bracket_delta(code);
+        header_end = i;
+        if depth <= 0 && code.trim_end().ends_with(':') {
+            break;
+        }
+    }
+
+    // The body is everything indented deeper than the definition
+    let mut end = header_end;
+    for (i, line) in lines.iter().enumerate().skip(header_end + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indent_of(line) <= indent {
+            break;
+        }
+        end = i;
+    }
+
+    Some((first + 1, end + 1))
+}
+
+/// Span of a definition in a brace-delimited language: from the matching line
+/// to the brace that closes it, or to the first `;` if no block is opened
+fn locate_braced(lines: &[&str], definition: &Regex) -> Option<(usize, usize)> {
+    let start = lines.iter().position(|line| definition.is_match(line))?;
+
+    let mut braces = 0i32;
+    let mut brackets = 0i32;
+    let mut opened = false;
+
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let mut in_string = false;
+        let mut escaped = false;
+        let code = strip_comment(line, "//");
+
+        for c in code.chars() {
+            if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => in_string = true,
+                '(' | '[' => brackets += 1,
+                ')' | ']' => brackets -= 1,
+                '{' => {
+                    braces += 1;
+                    opened = true;
+                }
+                '}' => {
+                    braces -= 1;
+                    if opened && braces == 0 {
+                        return Some((start + 1, i + 1));
+                    }
+                }
+                ';' if !opened && brackets == 0 => return Some((start + 1, i + 1)),
+                _ => {}
+            }
+        }
+    }
+
+    // Unbalanced or a one-line declaration without a terminator
+    Some((start + 1, if opened { lines.len() } else { start + 1 }))
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Drop a trailing line comment. Doesn't understand strings, which is fine
+/// for the definition and signature lines this is used on.
+fn strip_comment<'a>(line: &'a str, marker: &str) -> &'a str {
+    line.find(marker).map_or(line, |i| &line[..i])
+}
+
+fn bracket_delta(code: &str) -> i32 {
+    code.chars()
+        .map(|c| match c {
+            '(' | '[' | '{' => 1,
+            ')' | ']' | '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_rust_fn() {
+        let content = r#"use std::io;
+
+/// Parse arguments
+pub fn parse_args(args: &[String]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err("no args {".to_string());
+    }
+    Ok(())
+}
+
+fn other() {}
+"#;
+        assert_eq!(
+            locate_symbol("src/main.rs", content, "parse_args"),
+            Some((4, 9))
+        );
+        assert_eq!(
+            locate_symbol("src/main.rs", content, "other"),
+            Some((11, 11))
+        );
+        assert_eq!(locate_symbol("src/main.rs", content, "parse"), None);
+    }
+
+    #[test]
+    fn test_locate_rust_item_without_block() {
+        let content = "pub const LIMIT: [u8; 2] = [1, 2];\nstruct Unit;\n";
+        assert_eq!(locate_symbol("a.rs", content, "LIMIT"), Some((1, 1)));
+        assert_eq!(locate_symbol("a.rs", content, "Unit"), Some((2, 2)));
+    }
+
+    #[test]
+    fn test_locate_python_def() {
+        let content = "import os\n\n@cache\ndef load(\n    path,\n):\n    if path:\n\n        return os.read(path)\n    return None\n\n\ndef other():\n    pass\n";
+        assert_eq!(locate_symbol("app/io.py", content, "load"), Some((3, 10)));
+        assert_eq!(locate_symbol("app/io.py", content, "other"), Some((13, 14)));
+    }
+
+    #[test]
+    fn test_locate_python_method() {
+        let content = "class Auth:\n    def check(self):\n        return True\n\n    def other(self):\n        pass\n";
+        assert_eq!(locate_symbol("auth.py", content, "check"), Some((2, 3)));
+        assert_eq!(locate_symbol("auth.py", content, "Auth"), Some((1, 6)));
+    }
+
+    #[test]
+    fn test_locate_javascript() {
+        let content = "export function parseArgs(argv) {\n  return argv.slice(2);\n}\n\nconst add = (a, b) => a + b;\n\nclass Cli {\n  run(args) {\n    return parseArgs(args);\n  }\n}\n";
+        assert_eq!(locate_symbol("cli.js", content, "parseArgs"), Some((1, 3)));
+        assert_eq!(locate_symbol("cli.ts", content, "add"), Some((5, 5)));
+        assert_eq!(locate_symbol("cli.js", content, "run"), Some((8, 10)));
+        assert_eq!(locate_symbol("cli.js", content, "Cli"), Some((7, 11)));
+    }
+
+    #[test]
+    fn test_unsupported_language() {
+        assert_eq!(locate_symbol("main.go", "func main() {}\n", "main"), None);
+    }
+
+    #[test]
+    fn test_offset_range() {
+        assert_eq!(offset_range((10, 30), (1, 1)), (10, 10));
+        assert_eq!(offset_range((10, 30), (3, 5)), (12, 14));
+    }
+}